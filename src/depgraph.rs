@@ -0,0 +1,174 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The dependency relationships between all DM devices.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    dev_ids::{DmName, DmNameBuf},
+    device::Device,
+    dm::DepEntry,
+    errors::{DmError, DmResult, MalformedReason},
+};
+
+#[cfg(test)]
+#[path = "tests/depgraph.rs"]
+mod tests;
+
+/// The dependency relationships between all DM devices, like
+/// `dmsetup ls --tree`.  Returned by [`DM::dep_tree`].
+///
+/// A device's *children* are the devices its table refers to, which
+/// may or may not be DM devices; its *parents* are the DM devices
+/// whose tables refer to it.  The *roots* are the DM devices at the
+/// top of each stack.
+///
+/// [`DM::dep_tree`]: crate::DM::dep_tree
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DepGraph {
+    nodes: BTreeMap<DmNameBuf, Node>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Node {
+    device: Device,
+    deps: Vec<DepEntry>,
+}
+
+impl DepGraph {
+    /// Add the DM device `name`, whose table refers to `deps`.
+    pub(crate) fn insert(
+        &mut self,
+        name: DmNameBuf,
+        device: Device,
+        deps: Vec<DepEntry>,
+    ) {
+        self.nodes.insert(name, Node { device, deps });
+    }
+
+    /// The names of all DM devices in the graph, in sorted order.
+    pub fn devices(&self) -> impl Iterator<Item = &DmName> {
+        self.nodes.keys().map(|name| &**name)
+    }
+
+    /// The device number of the DM device `name`, or `None` if it is
+    /// not in the graph.
+    pub fn device(&self, name: &DmName) -> Option<Device> {
+        self.nodes.get(name).map(|node| node.device)
+    }
+
+    /// The devices that the table of the DM device `name` refers to,
+    /// or `None` if it is not in the graph.
+    pub fn children(&self, name: &DmName) -> Option<&[DepEntry]> {
+        self.nodes.get(name).map(|node| &node.deps[..])
+    }
+
+    /// The DM devices whose tables refer to the DM device `name`, in
+    /// sorted order.
+    pub fn parents(&self, name: &DmName) -> Vec<&DmName> {
+        self.nodes
+            .iter()
+            .filter(|(_, node)| {
+                node.deps
+                    .iter()
+                    .any(|dep| dep.name.as_deref() == Some(name))
+            })
+            .map(|(parent, _)| &**parent)
+            .collect()
+    }
+
+    /// The DM devices that no other DM device's table refers to, in
+    /// sorted order.
+    pub fn roots(&self) -> Vec<&DmName> {
+        let referenced = self.referenced();
+        self.devices()
+            .filter(|name| !referenced.contains(name))
+            .collect()
+    }
+
+    /// The devices that are not DM devices which the DM device `name`
+    /// ultimately sits on, directly or through other DM devices, in
+    /// sorted order.
+    pub fn leaves(&self, name: &DmName) -> Vec<Device> {
+        let mut leaves = BTreeSet::new();
+        let mut seen = BTreeSet::new();
+        let mut stack = vec![name];
+        while let Some(name) = stack.pop() {
+            if !seen.insert(name) {
+                continue;
+            }
+            for dep in self.children(name).unwrap_or_default() {
+                match &dep.name {
+                    Some(dep_name) => stack.push(dep_name),
+                    None => {
+                        leaves.insert(dep.device);
+                    }
+                }
+            }
+        }
+        leaves.into_iter().collect()
+    }
+
+    /// All DM devices in the graph, ordered so that each device comes
+    /// before every device its table refers to.  This is a safe order
+    /// in which to suspend or remove the devices; reverse it to
+    /// resume them.
+    ///
+    /// Fails with [`MalformedReason::DependencyCycle`] if the devices'
+    /// tables refer to each other in a cycle, which the kernel should
+    /// never allow.
+    pub fn topological_order(&self) -> DmResult<Vec<&DmName>> {
+        let mut parent_counts = self
+            .devices()
+            .map(|name| (name, 0usize))
+            .collect::<BTreeMap<_, _>>();
+        for node in self.nodes.values() {
+            for dep_name in
+                node.deps.iter().filter_map(|dep| dep.name.as_deref())
+            {
+                if let Some(count) = parent_counts.get_mut(dep_name) {
+                    *count += 1;
+                }
+            }
+        }
+
+        let mut ready = self.roots();
+        ready.reverse();
+        let mut order = Vec::with_capacity(self.nodes.len());
+        while let Some(name) = ready.pop() {
+            order.push(name);
+            for dep_name in self
+                .children(name)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|dep| dep.name.as_deref())
+            {
+                if let Some(count) = parent_counts.get_mut(dep_name) {
+                    *count -= 1;
+                    if *count == 0 {
+                        ready.push(dep_name);
+                    }
+                }
+            }
+        }
+
+        if order.len() < self.nodes.len() {
+            return Err(DmError::IoctlResultMalformed(
+                MalformedReason::DependencyCycle,
+            ));
+        }
+        Ok(order)
+    }
+
+    /// The names of the DM devices that some DM device's table
+    /// refers to.
+    fn referenced(&self) -> BTreeSet<&DmName> {
+        self.nodes
+            .values()
+            .flat_map(|node| &node.deps)
+            .filter_map(|dep| dep.name.as_deref())
+            .collect()
+    }
+}
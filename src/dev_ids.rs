@@ -15,9 +15,10 @@
 //! *not* necessarily required to be a well-formed Universally Unique
 //! Identifier.
 
-use core::{borrow::Borrow, fmt, ops::Deref};
+use core::{borrow::Borrow, fmt, ops::Deref, str::FromStr};
 
 use crate::bindings::{DM_NAME_LEN, DM_UUID_LEN};
+use crate::device::Device;
 use crate::errors::{DmError, DmResult};
 
 #[cfg(test)]
@@ -42,7 +43,7 @@ fn check_id(value: &str, limit: usize) -> DmResult<()> {
 
 /// A borrowed string (analogous to [`str`]) that meets the
 /// requirements for a device ID with length limit `LIMIT`.
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[repr(transparent)]
 pub struct DevIdStr<const LIMIT: usize> {
     inner: str,
@@ -50,7 +51,7 @@ pub struct DevIdStr<const LIMIT: usize> {
 
 /// An owned string (analogous to [`String`]) that meets the
 /// requirements for a device ID with length limit `LIMIT`.
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[repr(transparent)]
 pub struct DevIdString<const LIMIT: usize> {
     inner: String,
@@ -74,6 +75,11 @@ pub enum DevId<'a> {
     Name(&'a DmName),
     /// The parameter is the device's devicemapper uuid
     Uuid(&'a DmUuid),
+    /// The parameter is the device's major and minor numbers.  This
+    /// can identify an existing device for most operations, but it
+    /// cannot be used as the new id in
+    /// [`DM::device_rename`][crate::DM::device_rename].
+    Devno(Device),
 }
 
 impl<'a> fmt::Display for DevId<'a> {
@@ -81,6 +87,7 @@ impl<'a> fmt::Display for DevId<'a> {
         match *self {
             DevId::Name(name) => write!(f, "{name}"),
             DevId::Uuid(uuid) => write!(f, "{uuid}"),
+            DevId::Devno(device) => write!(f, "{device}"),
         }
     }
 }
@@ -108,6 +115,30 @@ impl<const LIMIT: usize> DevIdStr<LIMIT> {
     pub fn as_bytes(&self) -> &[u8] {
         self.inner.as_bytes()
     }
+
+    /// Whether the id starts with `prefix`.
+    pub fn starts_with(&self, prefix: &str) -> bool {
+        self.inner.starts_with(prefix)
+    }
+
+    /// Whether the id ends with `suffix`.
+    pub fn ends_with(&self, suffix: &str) -> bool {
+        self.inner.ends_with(suffix)
+    }
+}
+
+impl<const LIMIT: usize> AsRef<str> for DevIdStr<LIMIT> {
+    fn as_ref(&self) -> &str {
+        &self.inner
+    }
+}
+
+impl<'a, const LIMIT: usize> TryFrom<&'a str> for &'a DevIdStr<LIMIT> {
+    type Error = DmError;
+
+    fn try_from(value: &'a str) -> DmResult<Self> {
+        DevIdStr::new(value)
+    }
 }
 
 impl<const LIMIT: usize> ToOwned for DevIdStr<LIMIT> {
@@ -125,6 +156,18 @@ impl<const LIMIT: usize> fmt::Display for DevIdStr<LIMIT> {
     }
 }
 
+impl<const LIMIT: usize> PartialEq<str> for DevIdStr<LIMIT> {
+    fn eq(&self, other: &str) -> bool {
+        self.inner == *other
+    }
+}
+
+impl<const LIMIT: usize> PartialEq<&str> for DevIdStr<LIMIT> {
+    fn eq(&self, other: &&str) -> bool {
+        self.inner == **other
+    }
+}
+
 impl<const LIMIT: usize> DevIdString<LIMIT> {
     /// Construct a new owned identifier.
     pub fn new(value: String) -> DmResult<Self> {
@@ -133,12 +176,34 @@ impl<const LIMIT: usize> DevIdString<LIMIT> {
     }
 }
 
+impl<const LIMIT: usize> TryFrom<String> for DevIdString<LIMIT> {
+    type Error = DmError;
+
+    fn try_from(value: String) -> DmResult<Self> {
+        DevIdString::new(value)
+    }
+}
+
+impl<const LIMIT: usize> FromStr for DevIdString<LIMIT> {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<Self> {
+        DevIdString::new(s.to_string())
+    }
+}
+
 impl<const LIMIT: usize> AsRef<DevIdStr<LIMIT>> for DevIdString<LIMIT> {
     fn as_ref(&self) -> &DevIdStr<LIMIT> {
         self.deref()
     }
 }
 
+impl<const LIMIT: usize> AsRef<str> for DevIdString<LIMIT> {
+    fn as_ref(&self) -> &str {
+        self.deref().as_ref()
+    }
+}
+
 impl<const LIMIT: usize> Borrow<DevIdStr<LIMIT>> for DevIdString<LIMIT> {
     fn borrow(&self) -> &DevIdStr<LIMIT> {
         self.deref()
@@ -152,3 +217,48 @@ impl<const LIMIT: usize> Deref for DevIdString<LIMIT> {
         unsafe { DevIdStr::new_unchecked(&self.inner) }
     }
 }
+
+impl<const LIMIT: usize> PartialEq<str> for DevIdString<LIMIT> {
+    fn eq(&self, other: &str) -> bool {
+        self.inner == *other
+    }
+}
+
+impl<const LIMIT: usize> PartialEq<&str> for DevIdString<LIMIT> {
+    fn eq(&self, other: &&str) -> bool {
+        self.inner == **other
+    }
+}
+
+/// Serialized as a plain string.
+#[cfg(feature = "serde")]
+impl<const LIMIT: usize> serde::Serialize for DevIdStr<LIMIT> {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.inner)
+    }
+}
+
+/// Serialized as a plain string.
+#[cfg(feature = "serde")]
+impl<const LIMIT: usize> serde::Serialize for DevIdString<LIMIT> {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        self.deref().serialize(serializer)
+    }
+}
+
+/// Deserialized from a plain string, which must be a valid device ID.
+#[cfg(feature = "serde")]
+impl<'de, const LIMIT: usize> serde::Deserialize<'de> for DevIdString<LIMIT> {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        DevIdString::new(String::deserialize(deserializer)?)
+            .map_err(serde::de::Error::custom)
+    }
+}
@@ -29,15 +29,58 @@
 //! format, but when encoding a kdev_t from a Device object, we
 //! produce a 32-bit quantity or fail.
 
-use core::fmt;
+use core::{fmt, str::FromStr};
+use std::{
+    fs, io,
+    os::unix::fs::{FileTypeExt, MetadataExt},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use crate::errors::{DmError, DmResult};
 
 #[cfg(test)]
 #[path = "tests/device.rs"]
 mod test;
 
+/// Where the kernel lists the major numbers of device drivers.
+const PROC_DEVICES_PATH: &str = "/proc/devices";
+
+/// The block major number of device-mapper, once it is known.
+static DM_MAJOR: OnceLock<u32> = OnceLock::new();
+
+/// The major number of DM devices, which is assigned dynamically
+/// when device-mapper is loaded.  Returns `None` if device-mapper is
+/// not loaded.  The number is read from `/proc/devices` and cached
+/// once found.
+pub fn dm_major() -> DmResult<Option<u32>> {
+    if let Some(&major) = DM_MAJOR.get() {
+        return Ok(Some(major));
+    }
+    let devices = fs::read_to_string(PROC_DEVICES_PATH)
+        .map_err(|err| DmError::DeviceNode(PROC_DEVICES_PATH.into(), err))?;
+    Ok(parse_proc_devices(&devices)
+        .map(|major| *DM_MAJOR.get_or_init(|| major)))
+}
+
+/// Find the block major number of device-mapper in the contents of
+/// `/proc/devices`, which lists character devices and then block
+/// devices.
+fn parse_proc_devices(devices: &str) -> Option<u32> {
+    devices
+        .lines()
+        .skip_while(|line| line.trim() != "Block devices:")
+        .find_map(|line| {
+            let (major, name) = line.trim_start().split_once(' ')?;
+            (name.trim() == "device-mapper").then_some(())?;
+            major.parse().ok()
+        })
+}
+
 /// A struct representing a block device, identified by major and
-/// minor numbers.
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+/// minor numbers.  Devices are ordered numerically, by major number
+/// and then by minor number.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
 pub struct Device {
     /// Device major number
     pub major: u32,
@@ -52,6 +95,50 @@ impl fmt::Display for Device {
     }
 }
 
+/// Parses the `<major>:<minor>` format produced by `Display`, which is
+/// also the format the kernel uses for devices in table parameters.
+impl FromStr for Device {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<Device> {
+        s.split_once(':')
+            .and_then(|(major, minor)| {
+                Some(Device {
+                    major: major.parse().ok()?,
+                    minor: minor.parse().ok()?,
+                })
+            })
+            .ok_or_else(|| {
+                DmError::InvalidArgument(format!(
+                    "{s:?} is not a device number in <major>:<minor> format"
+                ))
+            })
+    }
+}
+
+/// Serialized in the `<major>:<minor>` format produced by `Display`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Device {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+/// Deserialized from the `<major>:<minor>` format accepted by `FromStr`.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Device {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
 impl Device {
     /// Make a `Device` from a 64-bit extended `kdev_t`.
     /// See module-level documentation for discussion of the format.
@@ -69,6 +156,43 @@ impl Device {
         Device { major, minor }
     }
 
+    /// Make a `Device` from the path of a block device node, e.g.
+    /// `/dev/sda1`.  Symbolic links are followed.  Returns `None` if
+    /// there is nothing at `path`, or if it is not a block device.
+    pub fn from_devnode(path: &Path) -> DmResult<Option<Device>> {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Ok(None)
+            }
+            Err(err) => {
+                return Err(DmError::DeviceNode(path.to_path_buf(), err))
+            }
+        };
+        if !metadata.file_type().is_block_device() {
+            return Ok(None);
+        }
+        // st_rdev uses the C library's extended dev_t format, which is
+        // what from_kdev_t expects.
+        Ok(Some(Device::from_kdev_t(metadata.rdev())))
+    }
+
+    /// Whether this is a DM device, judged by its major number.  DM
+    /// devices do not exist if device-mapper is not loaded, so
+    /// nothing is one.  See [`dm_major`].
+    pub fn is_dm(self) -> DmResult<bool> {
+        Ok(dm_major()? == Some(self.major))
+    }
+
+    /// The conventional path of the device node for a DM device with
+    /// this device number, `/dev/dm-<minor>`.  This is computed from
+    /// the minor number alone; nothing is checked, so the result is
+    /// only meaningful if this is a DM device, and the node may not
+    /// exist.
+    pub fn to_dm_node_path(self) -> PathBuf {
+        PathBuf::from(format!("/dev/dm-{}", self.minor))
+    }
+
     /// Convert self to a `kdev_t` value.  Returns `None` if self
     /// is not representable as a *32-bit* kdev_t.
     pub fn to_kdev_t(self) -> Option<u32> {
@@ -9,7 +9,7 @@ use crate::{
     bindings::dm_ioctl as Struct_dm_ioctl,
     dev_ids::{DmName, DmNameBuf, DmUuid, DmUuidBuf},
     device::Device,
-    errors::{DmError, DmResult},
+    errors::{DmError, DmResult, MalformedReason},
     flags::DmFlags,
     util::str_from_c_str,
 };
@@ -25,7 +25,7 @@ pub struct DeviceInfo {
     #[allow(dead_code)]
     data_start: u32,
 
-    pub(super) target_count: u32,
+    target_count: u32,
 
     open_count: i32,
     flags: DmFlags,
@@ -41,9 +41,7 @@ impl TryFrom<Struct_dm_ioctl> for DeviceInfo {
     fn try_from(ioctl: Struct_dm_ioctl) -> DmResult<Self> {
         let uuid =
             str_from_c_str(&ioctl.uuid as &[c_char]).ok_or_else(|| {
-                DmError::IoctlResultMalformed(
-                    "Devicemapper UUID is not null terminated",
-                )
+                DmError::IoctlResultMalformed(MalformedReason::UuidInvalid)
             })?;
         let uuid = if uuid.is_empty() {
             None
@@ -52,9 +50,7 @@ impl TryFrom<Struct_dm_ioctl> for DeviceInfo {
         };
         let name =
             str_from_c_str(&ioctl.name as &[c_char]).ok_or_else(|| {
-                DmError::IoctlResultMalformed(
-                    "Devicemapper name is not null terminated",
-                )
+                DmError::IoctlResultMalformed(MalformedReason::NameInvalid)
             })?;
         let name = if name.is_empty() {
             None
@@ -94,10 +90,23 @@ impl DeviceInfo {
     }
 
     /// The number of times the device is currently open.
+    ///
+    /// Only meaningful in the results of operations that report a
+    /// device's status, such as [`DM::device_info`]; other
+    /// operations may leave it zero.
+    ///
+    /// [`DM::device_info`]: crate::DM::device_info
     pub fn open_count(&self) -> i32 {
         self.open_count
     }
 
+    /// The number of targets in the device's table: the active
+    /// table, unless the inactive one was asked about with
+    /// `DM_QUERY_INACTIVE_TABLE`.
+    pub fn target_count(&self) -> u32 {
+        self.target_count
+    }
+
     /// The last event number for the device.
     pub fn event_nr(&self) -> u32 {
         self.event_nr
@@ -122,4 +131,31 @@ impl DeviceInfo {
     pub fn flags(&self) -> DmFlags {
         self.flags
     }
+
+    /// Whether the device is suspended (`DM_SUSPEND`).
+    pub fn is_suspended(&self) -> bool {
+        self.flags.contains(DmFlags::DM_SUSPEND)
+    }
+
+    /// Whether the device is read-only (`DM_READONLY`).
+    pub fn is_read_only(&self) -> bool {
+        self.flags.contains(DmFlags::DM_READONLY)
+    }
+
+    /// Whether the device has an active table (`DM_ACTIVE_PRESENT`).
+    pub fn is_active_table_present(&self) -> bool {
+        self.flags.contains(DmFlags::DM_ACTIVE_PRESENT)
+    }
+
+    /// Whether the device has an inactive table
+    /// (`DM_INACTIVE_PRESENT`).
+    pub fn is_inactive_table_present(&self) -> bool {
+        self.flags.contains(DmFlags::DM_INACTIVE_PRESENT)
+    }
+
+    /// Whether the device is scheduled to be removed once it is no
+    /// longer in use (`DM_DEFERRED_REMOVE`).
+    pub fn is_deferred_remove_scheduled(&self) -> bool {
+        self.flags.contains(DmFlags::DM_DEFERRED_REMOVE)
+    }
 }
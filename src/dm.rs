@@ -2,15 +2,32 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use core::{cmp, mem::size_of, slice, str};
+use core::{
+    cmp, iter,
+    mem::{self, size_of},
+    ptr, slice, str,
+};
 
 use std::{
-    fs::File,
-    io::{Cursor, Read, Write},
-    os::unix::io::{AsRawFd, RawFd},
+    collections::HashMap,
+    ffi::CString,
+    fs::{self, File},
+    io::{self, Cursor, Read, Write},
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{self as unix_fs, FileTypeExt, MetadataExt},
+        io::{AsRawFd, FromRawFd, RawFd},
+    },
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock, PoisonError},
+    thread,
+    time::{Duration, Instant},
 };
 
-use nix::libc::ioctl as nix_ioctl;
+use nix::{
+    errno::Errno,
+    libc::{self, ioctl as nix_ioctl},
+};
 use semver::Version;
 
 use crate::{
@@ -19,29 +36,275 @@ use crate::{
         dm_target_deps as Struct_dm_target_deps,
         dm_target_msg as Struct_dm_target_msg,
         dm_target_spec as Struct_dm_target_spec,
-        dm_target_versions as Struct_dm_target_versions,
+        dm_target_versions as Struct_dm_target_versions, DM_NAME_LEN,
     },
-    dev_ids::{DevId, DmName, DmNameBuf, DmUuid},
+    depgraph::DepGraph,
+    dev_ids::{DevId, DmName, DmNameBuf, DmUuid, DmUuidBuf},
     device::Device,
     deviceinfo::DeviceInfo,
-    errors::{DmError, DmResult},
-    flags::DmFlags,
+    errors::{
+        BatchError, BatchOutcome, DmError, DmResult, MalformedReason,
+        ReloadStep,
+    },
+    flags::{DmFlags, DmNameListFlags},
     ioctl_cmds::{ioctl_to_version, DmIoctlCmd, DM_IOCTL_GROUP},
+    options::{
+        BatchMode, DmOpenOptions, MapperNode, ReloadOptions,
+        RemoveAllConfirmation, SuspendOptions,
+    },
+    stats::{
+        check_program_id, parse_print_output, RegionBounds, StatsCounters,
+        StatsRegion, StatsStep,
+    },
+    table::{check_table_layout, TargetLine, TargetTable, TargetTypeBuf},
     util::{
-        align_to, c_struct_from_slice, mut_slice_from_c_str,
-        slice_from_c_struct, str_from_byte_slice, str_from_c_str,
+        align_to, mut_slice_from_c_str, slice_from_c_struct,
+        str_from_byte_slice, str_from_c_str, zero_bytes, zero_string,
     },
 };
 
+#[cfg(test)]
+#[path = "tests/dm.rs"]
+mod tests;
+
 /// Control path for user space to pass IOCTL to kernel DM
-const DM_CTL_PATH: &str = "/dev/mapper/control";
+pub(crate) const DM_CTL_PATH: &str = "/dev/mapper/control";
+
+/// Where sysfs reports the device number of the control node.
+const DM_CTL_SYSFS_PATH: &str = "/sys/class/misc/device-mapper/dev";
+
+/// The major device number of all misc devices, including the
+/// control node.
+const MISC_MAJOR: u32 = 10;
+
+/// Directory containing the conventional symlinks to DM devices,
+/// named after the devices.
+const DM_DEV_DIR: &str = "/dev/mapper";
 
 /// Start with a large buffer to make BUFFER_FULL rare. Libdm does this too.
 const MIN_BUF_SIZE: usize = 16 * 1024;
 
+/// How often [`DM::device_remove_deferred`] checks whether a device
+/// has gone away.
+const REMOVAL_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The result of [`DM::device_remove_deferred`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RemovalOutcome {
+    /// The device was not in use and was removed immediately.
+    Removed,
+    /// The device was in use, so its removal was deferred, and it
+    /// was subsequently removed.
+    DeferredRemoved,
+    /// The device was in use, so its removal was deferred, and it
+    /// still existed when we stopped waiting.
+    Pending,
+}
+
+/// The status of one target, exactly as reported by the kernel.
+/// Returned by [`DM::table_status_raw`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RawTargetStatus {
+    /// The first sector of the device covered by this target.
+    pub sector_start: u64,
+    /// The number of sectors covered by this target.
+    pub length: u64,
+    /// The target type, e.g. "linear".
+    pub target_type: String,
+    /// The entire params region following the target's
+    /// `dm_target_spec`.  This normally holds a NUL-terminated string,
+    /// followed by padding up to the next record.
+    pub params: Vec<u8>,
+}
+
+/// Everything the kernel reports about one device in a device list.
+/// Returned by [`DM::list_devices_extended`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DeviceListEntry {
+    /// The device's name.
+    pub name: DmNameBuf,
+    /// The device's major and minor numbers.
+    pub device: Device,
+    /// The device's last event_nr.  Reported by DM version 4.37 and
+    /// later.
+    pub event_nr: Option<u32>,
+    /// The flags from the extended portion of the record.  Reported
+    /// by DM version 4.45 and later; `None` means the kernel does
+    /// not emit them.
+    pub flags: Option<DmNameListFlags>,
+    /// The device's uuid, if the flags say it has one.
+    pub uuid: Option<DmUuidBuf>,
+}
+
+/// An iterator over the result of `DM_LIST_DEVICES`, yielding the
+/// same tuples as [`DM::list_devices`].  Returned by
+/// [`DM::iter_devices`].
+///
+/// Each record is decoded when the iterator reaches it.  If a record
+/// is malformed, the iterator yields the error and then ends.
+#[derive(Debug)]
+pub struct DeviceIter {
+    buf: Vec<u8>,
+    /// The offset in `buf` of the next record, or `None` once the
+    /// last record, or an error, has been yielded.
+    offset: Option<usize>,
+    /// Whether the kernel provided event numbers.
+    event_nr_set: bool,
+    /// Whether the kernel provided flags in the extended portion of
+    /// each record.
+    flags_set: bool,
+}
+
+impl DeviceIter {
+    /// Iterate over `buf`, the result of `DM_LIST_DEVICES`.
+    /// `event_nr_set` and `flags_set` say whether the kernel will
+    /// have provided event numbers and flags in the extended portion
+    /// of each record.
+    fn new(buf: Vec<u8>, event_nr_set: bool, flags_set: bool) -> DeviceIter {
+        DeviceIter {
+            offset: if buf.is_empty() { None } else { Some(0) },
+            buf,
+            event_nr_set,
+            flags_set,
+        }
+    }
+
+    /// Decode every remaining record.
+    fn collect_entries(mut self) -> DmResult<Vec<DeviceListEntry>> {
+        iter::from_fn(|| self.next_entry()).collect()
+    }
+
+    /// Decode the next record, and advance past it.
+    fn next_entry(&mut self) -> Option<DmResult<DeviceListEntry>> {
+        let offset = self.offset.take()?;
+        let (entry, next) = match self.parse_record(offset) {
+            Ok(parsed) => parsed,
+            Err(err) => return Some(Err(err)),
+        };
+        if next != 0 {
+            self.offset = Some(offset + next as usize);
+        }
+        Some(Ok(entry))
+    }
+
+    /// Decode the record at `offset`, returning it and its `next`
+    /// field.
+    fn parse_record(&self, offset: usize) -> DmResult<(DeviceListEntry, u32)> {
+        let name_offset = {
+            let template = Struct_dm_name_list::default();
+            // SAFETY: both pointers are derived from `template`.
+            unsafe {
+                (template.name.as_ptr() as *const u8)
+                    .offset_from(&template as *const _ as *const u8)
+            }
+        } as usize;
+
+        let result = self.buf.get(offset..).ok_or(
+            DmError::IoctlResultMalformed(MalformedReason::TruncatedRecord),
+        )?;
+        let dev = read_u64(result, 0)?;
+        let next = read_u32(result, size_of::<u64>())?;
+
+        let dm_name = result
+            .get(name_offset..)
+            .and_then(str_from_byte_slice)
+            .map(|s| s.to_owned())
+            .ok_or_else(|| {
+                DmError::IoctlResultMalformed(MalformedReason::NameInvalid)
+            })?;
+
+        // Get each device's event number after its name, if the kernel
+        // DM version supports it.
+        // Should match offset calc in kernel's
+        // drivers/md/dm-ioctl.c:list_devices
+        // `end` tracks the end of the data read from this record.
+        let mut end = name_offset + dm_name.len() + 1;
+        let ext_offset = align_to(end, size_of::<u64>());
+        let event_nr = if self.event_nr_set {
+            end = ext_offset + size_of::<u32>();
+            Some(read_u32(result, ext_offset)?)
+        } else {
+            None
+        };
+
+        // The extended record continues with a flags word,
+        // and then, if the flags say so, the uuid.
+        let flags = if self.flags_set {
+            end = ext_offset + 2 * size_of::<u32>();
+            Some(DmNameListFlags::from_bits_truncate(read_u32(
+                result,
+                ext_offset + size_of::<u32>(),
+            )?))
+        } else {
+            None
+        };
+        let uuid = match flags {
+            Some(flags) if flags.contains(DmNameListFlags::HAS_UUID) => {
+                let uuid_offset = ext_offset + 2 * size_of::<u32>();
+                let uuid = result
+                    .get(uuid_offset..)
+                    .and_then(str_from_byte_slice)
+                    .ok_or_else(|| {
+                        DmError::IoctlResultMalformed(
+                            MalformedReason::UuidInvalid,
+                        )
+                    })?;
+                end = uuid_offset + uuid.len() + 1;
+                Some(DmUuidBuf::new(uuid.to_owned())?)
+            }
+            _ => None,
+        };
+
+        // `next` is relative to this record, and zero for the last
+        // one.  Otherwise it must point past the data just read, and
+        // leave room for another record in the buffer.
+        if next != 0 && !(end..result.len()).contains(&(next as usize)) {
+            return Err(DmError::IoctlResultMalformed(
+                MalformedReason::BadNextOffset,
+            ));
+        }
+
+        let entry = DeviceListEntry {
+            name: DmNameBuf::new(dm_name)?,
+            device: Device::from_kdev_t(dev),
+            event_nr,
+            flags,
+            uuid,
+        };
+        Ok((entry, next))
+    }
+}
+
+impl Iterator for DeviceIter {
+    type Item = DmResult<(DmNameBuf, Device, Option<u32>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry().map(|entry| {
+            entry.map(|entry| (entry.name, entry.device, entry.event_nr))
+        })
+    }
+}
+
+/// One device referenced by a device's table.  Returned by
+/// [`DM::table_deps_resolved`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DepEntry {
+    /// The device's major and minor numbers.
+    pub device: Device,
+    /// The device's name, if it is a DM device, or `None` if it is
+    /// something else, such as a disk or a loop device.
+    pub name: Option<DmNameBuf>,
+}
+
 /// Context needed for communicating with devicemapper.
 pub struct DM {
     file: File,
+    version: OnceLock<Version>,
+    check_kernel_version: bool,
+    target_versions: OnceLock<Vec<(String, u32, u32, u32)>>,
+    /// The buffer for ioctl requests and responses, kept between
+    /// calls to avoid reallocating it.
+    buffer: Mutex<Vec<u8>>,
 }
 
 impl DmFlags {
@@ -63,6 +326,7 @@ impl DmFlags {
             match id {
                 DevId::Name(name) => DM::hdr_set_name(&mut hdr, name)?,
                 DevId::Uuid(uuid) => DM::hdr_set_uuid(&mut hdr, uuid)?,
+                DevId::Devno(device) => DM::hdr_set_dev(&mut hdr, *device)?,
             };
         };
 
@@ -73,8 +337,52 @@ impl DmFlags {
 impl DM {
     /// Create a new context for communicating with DM.
     pub fn new() -> DmResult<DM> {
+        DM::open_with(&DmOpenOptions::default())
+    }
+
+    /// Create a new context for communicating with DM, opening the
+    /// control node as specified by `options`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dm_ioctl::{DmOpenOptions, DM};
+    ///
+    /// let dm = DM::open_with(
+    ///     DmOpenOptions::default()
+    ///         .path("/run/sandbox/dev/mapper/control")
+    ///         .nonblock(true),
+    /// )
+    /// .unwrap();
+    /// ```
+    pub fn open_with(options: &DmOpenOptions<'_>) -> DmResult<DM> {
+        let mut flags = libc::O_RDONLY;
+        if options.cloexec {
+            flags |= libc::O_CLOEXEC;
+        }
+        if options.nonblock {
+            flags |= libc::O_NONBLOCK;
+        }
+        let dir = options.dir.map_or(libc::AT_FDCWD, |dir| dir.as_raw_fd());
+
+        let file = match open_at(dir, &options.path, flags) {
+            Err(err)
+                if err.kind() == io::ErrorKind::NotFound
+                    && options.create_control =>
+            {
+                create_control_node(dir, &options.path)?;
+                open_at(dir, &options.path, flags)
+            }
+            res => res,
+        }
+        .map_err(DmError::ContextInit)?;
+
         Ok(DM {
-            file: File::open(DM_CTL_PATH).map_err(DmError::ContextInit)?,
+            file,
+            version: OnceLock::new(),
+            check_kernel_version: options.check_kernel_version,
+            target_versions: OnceLock::new(),
+            buffer: Mutex::new(Vec::new()),
         })
     }
 
@@ -86,6 +394,19 @@ impl DM {
         Ok(())
     }
 
+    fn hdr_set_dev(hdr: &mut Struct_dm_ioctl, device: Device) -> DmResult<()> {
+        // The kernel only looks at the low 32 bits of hdr.dev.
+        hdr.dev = device
+            .to_kdev_t()
+            .ok_or_else(|| {
+                DmError::InvalidArgument(format!(
+                    "device number {device} is out of range"
+                ))
+            })?
+            .into();
+        Ok(())
+    }
+
     fn hdr_set_uuid(hdr: &mut Struct_dm_ioctl, uuid: &DmUuid) -> DmResult<()> {
         let _ = uuid
             .as_bytes()
@@ -94,7 +415,8 @@ impl DM {
         Ok(())
     }
 
-    /// Get the file within the DM context, likely for polling purposes.
+    /// Get the file within the DM context, likely for polling
+    /// purposes; see [`Self::poll_event`].
     pub fn file(&self) -> &File {
         &self.file
     }
@@ -106,6 +428,34 @@ impl DM {
         ioctl: DmIoctlCmd,
         hdr: &mut Struct_dm_ioctl,
         in_data: Option<&[u8]>,
+    ) -> DmResult<(DeviceInfo, Vec<u8>)> {
+        // The buffer is taken out of the mutex rather than locked for
+        // the duration of the ioctl, so that a concurrent call from
+        // another thread, or a blocking DM_DEV_WAIT, does not hold up
+        // this one.  Such a call uses a fresh buffer instead.
+        let mut buffer = self.take_buffer();
+        let result = self.do_ioctl_with(&mut buffer, ioctl, hdr, in_data);
+        if hdr.flags & DmFlags::DM_SECURE_DATA.bits() != 0 {
+            zero_bytes(&mut buffer);
+        }
+        *self.buffer.lock().unwrap_or_else(PoisonError::into_inner) = buffer;
+        result
+    }
+
+    /// Take the saved ioctl buffer, leaving an empty one in its place.
+    fn take_buffer(&self) -> Vec<u8> {
+        mem::take(
+            &mut *self.buffer.lock().unwrap_or_else(PoisonError::into_inner),
+        )
+    }
+
+    /// Perform `ioctl`, using `buffer` for the request and response.
+    fn do_ioctl_with(
+        &self,
+        buffer: &mut Vec<u8>,
+        ioctl: DmIoctlCmd,
+        hdr: &mut Struct_dm_ioctl,
+        in_data: Option<&[u8]>,
     ) -> DmResult<(DeviceInfo, Vec<u8>)> {
         let op = request_code_readwrite!(
             DM_IOCTL_GROUP,
@@ -114,6 +464,18 @@ impl DM {
         );
 
         let ioctl_version = ioctl_to_version(ioctl);
+        if self.check_kernel_version && ioctl_version > (4, 0, 0) {
+            // Every version of the interface supports DM_VERSION, so
+            // this does not recurse.
+            let actual = self.interface_version()?;
+            if actual < ioctl_version {
+                return Err(DmError::UnsupportedKernel {
+                    cmd: ioctl,
+                    required: ioctl_version,
+                    actual,
+                });
+            }
+        }
         hdr.version[0] = ioctl_version.0;
         hdr.version[1] = ioctl_version.1;
         hdr.version[2] = ioctl_version.2;
@@ -123,7 +485,8 @@ impl DM {
             size_of::<Struct_dm_ioctl>() + in_data.map_or(0, |x| x.len()),
         );
 
-        let mut buffer: Vec<u8> = Vec::with_capacity(data_size);
+        buffer.clear();
+        buffer.reserve(data_size);
         let mut buffer_hdr;
         loop {
             hdr.data_size = buffer.capacity() as u32;
@@ -172,6 +535,12 @@ impl DM {
             if len == u32::MAX as usize {
                 return Err(DmError::IoctlResultTooLarge);
             }
+            // Growing the buffer may move it, leaving its contents
+            // behind in freed memory.  It is rebuilt on the next
+            // iteration in any case.
+            if hdr.flags & DmFlags::DM_SECURE_DATA.bits() != 0 {
+                zero_bytes(buffer);
+            }
             buffer.resize((len as u32).saturating_mul(2) as usize, 0);
         }
 
@@ -185,39 +554,63 @@ impl DM {
 
     /// Devicemapper version information: Major, Minor, and patchlevel versions.
     pub fn version(&self) -> DmResult<(u32, u32, u32)> {
+        Ok(version_tuple(&self.fetch_version()?))
+    }
+
+    /// Devicemapper version information, as for [`Self::version`],
+    /// but only retrieved from the kernel the first time it is
+    /// requested from this context; see [`Self::cached_version`].
+    /// This is the version the kernel reports, not the version this
+    /// crate requests in each ioctl.
+    pub fn interface_version(&self) -> DmResult<(u32, u32, u32)> {
+        Ok(version_tuple(self.cached_version()?))
+    }
+
+    /// Devicemapper version information, as for [`Self::version`],
+    /// but only retrieved from the kernel the first time it is
+    /// requested from this context.
+    pub fn cached_version(&self) -> DmResult<&Version> {
+        if let Some(version) = self.version.get() {
+            return Ok(version);
+        }
+        let version = self.fetch_version()?;
+        Ok(self.version.get_or_init(|| version))
+    }
+
+    /// Retrieve devicemapper version information from the kernel
+    /// again, and update the value returned by
+    /// [`Self::cached_version`].  This is only necessary if the
+    /// device-mapper module may have been replaced since this context
+    /// was created.
+    pub fn refresh_version(&mut self) -> DmResult<&Version> {
+        self.version.take();
+        self.cached_version()
+    }
+
+    fn fetch_version(&self) -> DmResult<Version> {
         let mut hdr =
             DmFlags::default().to_ioctl_hdr(None, DmFlags::empty())?;
 
         let (hdr_out, _) =
             self.do_ioctl(DmIoctlCmd::DM_VERSION, &mut hdr, None)?;
 
-        Ok((
-            hdr_out
-                .version()
-                .major
-                .try_into()
-                .expect("dm_ioctl struct field is u32"),
-            hdr_out
-                .version()
-                .minor
-                .try_into()
-                .expect("dm_ioctl struct field is u32"),
-            hdr_out
-                .version()
-                .patch
-                .try_into()
-                .expect("dm_ioctl struct field is u32"),
-        ))
+        Ok(hdr_out.version().clone())
     }
 
-    /// Remove all DM devices and tables. Use discouraged other than
-    /// for debugging.
+    /// Remove all DM devices and tables, *including devices that
+    /// were not created by this program*.  Use discouraged other
+    /// than for debugging; [`Self::remove_all_matching`] is almost
+    /// always what you want instead.
     ///
     /// If `DM_DEFERRED_REMOVE` is set, the request will succeed for
     /// in-use devices, and they will be removed when released.
     ///
     /// Valid flags: `DM_DEFERRED_REMOVE`
-    pub fn remove_all(&self, flags: DmFlags) -> DmResult<()> {
+    pub fn remove_all(
+        &self,
+        _confirm: RemoveAllConfirmation,
+        flags: DmFlags,
+    ) -> DmResult<()> {
         let mut hdr = flags.to_ioctl_hdr(None, DmFlags::DM_DEFERRED_REMOVE)?;
 
         self.do_ioctl(DmIoctlCmd::DM_REMOVE_ALL, &mut hdr, None)?;
@@ -225,87 +618,277 @@ impl DM {
         Ok(())
     }
 
+    /// Remove all DM devices for which `predicate`, called with each
+    /// device's name and uuid, returns true.  Returns the names of
+    /// the devices that were removed.
+    ///
+    /// Like the kernel's implementation of [`Self::remove_all`],
+    /// this makes repeated passes over the matching devices for as
+    /// long as progress is being made, so that a device that is
+    /// in use by another matching device is removed after it.  If
+    /// some matching devices still cannot be removed, the error
+    /// from the last failed removal is returned.  Devices that
+    /// disappear before they can be removed are not treated as
+    /// errors.
+    ///
+    /// If `DM_DEFERRED_REMOVE` is set, removal of in-use devices
+    /// will succeed, and they will be removed when released.
+    ///
+    /// Valid flags: `DM_DEFERRED_REMOVE`
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dm_ioctl::{DM, DmFlags};
+    /// let dm = DM::new().unwrap();
+    ///
+    /// let removed = dm
+    ///     .remove_all_matching(
+    ///         |name, _| name.as_bytes().starts_with(b"myapp-"),
+    ///         DmFlags::default(),
+    ///     )
+    ///     .unwrap();
+    /// ```
+    pub fn remove_all_matching(
+        &self,
+        predicate: impl Fn(&DmName, Option<&DmUuid>) -> bool,
+        flags: DmFlags,
+    ) -> DmResult<Vec<DmNameBuf>> {
+        let uuids_listed = self.interface_version()? >= (4, 45, 0);
+
+        let mut pending = Vec::new();
+        for (name, _, _, uuid) in self.list_devices_with_uuids()? {
+            let uuid = if uuids_listed {
+                uuid
+            } else {
+                self.device_info(&DevId::Name(&name))?
+                    .uuid()
+                    .map(|uuid| uuid.to_owned())
+            };
+            if predicate(&name, uuid.as_deref()) {
+                pending.push(name);
+            }
+        }
+
+        let mut removed = Vec::new();
+        loop {
+            let mut failed = Vec::new();
+            let mut last_err = None;
+            let pending_count = pending.len();
+            for name in pending {
+                match self.device_remove(&DevId::Name(&name), flags) {
+                    Ok(_) => removed.push(name),
+                    Err(DmError::Ioctl(_, _, _, Errno::ENXIO)) => (),
+                    Err(err) => {
+                        failed.push(name);
+                        last_err = Some(err);
+                    }
+                }
+            }
+
+            match last_err {
+                None => return Ok(removed),
+                Some(err) if failed.len() == pending_count => return Err(err),
+                Some(_) => pending = failed,
+            }
+        }
+    }
+
+    /// Remove the DM devices `names`, first replacing each one's
+    /// table with an `error` target of the same length, so that any
+    /// I/O still sent to it fails rather than reaching the devices
+    /// beneath it.  This is what `dmsetup remove --force` does.
+    ///
+    /// The devices are handled in dependency order, those on top
+    /// first.  For each one, the `error` table is swapped in as
+    /// [`Self::wipe_table`] does, suspending the device, and then the
+    /// device is removed.  A device with no active table is just
+    /// removed.  A failure at any step is recorded against that
+    /// device, and the rest of the devices are still attempted.
+    /// Returns each device's name with its result, in the order they
+    /// were handled.  An error is returned only if the dependencies
+    /// between devices cannot be retrieved.
+    ///
+    /// A device that could not then be removed is left alone, with
+    /// its table intact, and [`DmError::InvalidArgument`] is recorded
+    /// against it: that is, a device used by another DM device that
+    /// is not in `names`, or one that is still open, for instance
+    /// because a device in `names` that uses it failed to be removed.
+    /// With `DM_DEFERRED_REMOVE`, such devices are fenced anyway, and
+    /// removed once they are closed.
+    ///
+    /// Valid flags: `DM_DEFERRED_REMOVE`
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dm_ioctl::{DM, DmFlags, DmName};
+    /// let dm = DM::new().unwrap();
+    ///
+    /// let names = [
+    ///     DmName::new("example-top").unwrap(),
+    ///     DmName::new("example-bottom").unwrap(),
+    /// ];
+    /// for (name, result) in
+    ///     dm.remove_devices(&names, DmFlags::default()).unwrap()
+    /// {
+    ///     if let Err(err) = result {
+    ///         eprintln!("failed to remove {name:?}: {err}");
+    ///     }
+    /// }
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn remove_devices(
+        &self,
+        names: &[&DmName],
+        flags: DmFlags,
+    ) -> DmResult<Vec<(DmNameBuf, DmResult<()>)>> {
+        let graph = self.dep_tree()?;
+        let mut ordered = graph
+            .topological_order()?
+            .into_iter()
+            .filter(|name| names.contains(name))
+            .collect::<Vec<_>>();
+        // Devices that do not exist are attempted last, so that each
+        // gets an error of its own.
+        for name in names {
+            if !ordered.contains(name) {
+                ordered.push(name);
+            }
+        }
+
+        Ok(ordered
+            .into_iter()
+            .map(|name| {
+                let result = self.fence_and_remove(&graph, names, name, flags);
+                (name.to_owned(), result)
+            })
+            .collect())
+    }
+
+    /// Replace the table of the DM device `name` with an `error`
+    /// target, and remove it, unless it is still in use.  See
+    /// [`Self::remove_devices`].
+    fn fence_and_remove(
+        &self,
+        graph: &DepGraph,
+        names: &[&DmName],
+        name: &DmName,
+        flags: DmFlags,
+    ) -> DmResult<()> {
+        let id = DevId::Name(name);
+        let info = self.device_info(&id)?;
+        if !flags.contains(DmFlags::DM_DEFERRED_REMOVE) {
+            let parents = graph.parents(name);
+            if let Some(holder) = parents.iter().find(|p| !names.contains(p)) {
+                return Err(DmError::InvalidArgument(format!(
+                    "device {name} is used by {holder}, which is not being \
+                     removed"
+                )));
+            }
+            if info.open_count() > 0 {
+                return Err(DmError::InvalidArgument(format!(
+                    "device {name} is open"
+                )));
+            }
+        }
+        if info.is_active_table_present() {
+            let opts = ReloadOptions {
+                suspend: true,
+                ..Default::default()
+            };
+            self.wipe_table(&id, opts)?;
+        }
+        self.device_remove(&id, flags)?;
+        Ok(())
+    }
+
     /// Returns a list of tuples containing DM device names, a Device, which
     /// holds their major and minor device numbers, and on kernels that
     /// support it, each device's last event_nr.
     pub fn list_devices(
         &self,
     ) -> DmResult<Vec<(DmNameBuf, Device, Option<u32>)>> {
-        let mut hdr =
-            DmFlags::default().to_ioctl_hdr(None, DmFlags::empty())?;
-        let (hdr_out, data_out) =
-            self.do_ioctl(DmIoctlCmd::DM_LIST_DEVICES, &mut hdr, None)?;
-
-        let event_nr_set = hdr_out.version() >= &Version::new(4, 37, 0);
-
-        let mut devs = Vec::new();
-        if !data_out.is_empty() {
-            let mut result = &data_out[..];
+        Ok(self
+            .do_list_devices(DmFlags::default())?
+            .into_iter()
+            .map(|entry| (entry.name, entry.device, entry.event_nr))
+            .collect())
+    }
 
-            loop {
-                let device = c_struct_from_slice::<Struct_dm_name_list>(result)
-                    .ok_or_else(|| {
-                        DmError::IoctlResultMalformed(
-                            "Received null pointer from kernel",
-                        )
-                    })?;
-                let name_offset = unsafe {
-                    (device.name.as_ptr() as *const u8)
-                        .offset_from(device as *const _ as *const u8)
-                } as usize;
+    /// Like [`Self::list_devices`], but also returns each device's
+    /// uuid, if it has one.
+    ///
+    /// The uuids are retrieved with the same ioctl as the rest of
+    /// the list, which requires DM version 4.45 or later.  On older
+    /// kernels the uuid will always be `None`; use
+    /// [`Self::device_info`] to retrieve it instead.
+    #[allow(clippy::type_complexity)]
+    pub fn list_devices_with_uuids(
+        &self,
+    ) -> DmResult<Vec<(DmNameBuf, Device, Option<u32>, Option<DmUuidBuf>)>>
+    {
+        Ok(self
+            .list_devices_extended()?
+            .into_iter()
+            .map(|entry| (entry.name, entry.device, entry.event_nr, entry.uuid))
+            .collect())
+    }
 
-                let dm_name = str_from_byte_slice(&result[name_offset..])
-                    .map(|s| s.to_owned())
-                    .ok_or_else(|| {
-                        DmError::IoctlResultMalformed(
-                            "Devicemapper name is not valid UTF8",
-                        )
-                    })?;
+    /// Like [`Self::list_devices_with_uuids`], but returns everything
+    /// the kernel reports about each device, including the flags
+    /// from the extended portion of each record.  See
+    /// [`DeviceListEntry`] for which kernels report what.
+    pub fn list_devices_extended(&self) -> DmResult<Vec<DeviceListEntry>> {
+        self.do_list_devices(DmFlags::DM_UUID)
+    }
 
-                // Get each device's event number after its name, if the kernel
-                // DM version supports it.
-                // Should match offset calc in kernel's
-                // drivers/md/dm-ioctl.c:list_devices
-                let event_nr = if event_nr_set {
-                    // offsetof "name" in Struct_dm_name_list.
-                    let offset = align_to(
-                        name_offset + dm_name.len() + 1,
-                        size_of::<u64>(),
-                    );
-                    let nr = u32::from_ne_bytes(
-                        result[offset..offset + size_of::<u32>()]
-                            .try_into()
-                            .map_err(|_| {
-                                DmError::IoctlResultMalformed(
-                                    "Incorrectly sized slice for u32",
-                                )
-                            })?,
-                    );
-
-                    Some(nr)
-                } else {
-                    None
-                };
+    /// Like [`Self::list_devices`], but decodes each device only as
+    /// the returned iterator reaches it, so that a caller looking for
+    /// one device can stop early.  The list is still retrieved from
+    /// the kernel all at once.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dm_ioctl::DM;
+    ///
+    /// let dm = DM::new().unwrap();
+    /// let first_crypt = dm
+    ///     .iter_devices()
+    ///     .unwrap()
+    ///     .find(|dev| {
+    ///         dev.as_ref().map_or(true, |dev| dev.0.starts_with("crypt-"))
+    ///     });
+    /// ```
+    pub fn iter_devices(&self) -> DmResult<DeviceIter> {
+        self.do_iter_devices(DmFlags::default())
+    }
 
-                devs.push((
-                    DmNameBuf::new(dm_name)?,
-                    Device::from_kdev_t(device.dev),
-                    event_nr,
-                ));
+    fn do_list_devices(
+        &self,
+        flags: DmFlags,
+    ) -> DmResult<Vec<DeviceListEntry>> {
+        self.do_iter_devices(flags)?.collect_entries()
+    }
 
-                if device.next == 0 {
-                    break;
-                }
+    fn do_iter_devices(&self, flags: DmFlags) -> DmResult<DeviceIter> {
+        let version = self.interface_version()?;
+        let event_nr_set = version >= (4, 37, 0);
+        let flags_set = version >= (4, 45, 0);
 
-                result = &result[device.next as usize..];
-            }
-        }
+        let mut hdr = flags.to_ioctl_hdr(None, DmFlags::DM_UUID)?;
+        let (_, data_out) =
+            self.do_ioctl(DmIoctlCmd::DM_LIST_DEVICES, &mut hdr, None)?;
 
-        Ok(devs)
+        Ok(DeviceIter::new(data_out, event_nr_set, flags_set))
     }
 
     /// Create a DM device. It starts out in a "suspended" state.
     ///
+    /// `flags` may be given either as [`DmFlags`] or as
+    /// [`DmOptions`][crate::DmOptions].
+    ///
     /// Valid flags: `DM_READONLY`, `DM_PERSISTENT_DEV`
     ///
     /// # Example
@@ -323,8 +906,35 @@ impl DM {
         &self,
         name: &DmName,
         uuid: Option<&DmUuid>,
-        flags: DmFlags,
+        flags: impl Into<DmFlags>,
     ) -> DmResult<DeviceInfo> {
+        let mut hdr = Self::device_create_request(name, uuid, flags.into())?;
+
+        self.do_ioctl(DmIoctlCmd::DM_DEV_CREATE, &mut hdr, None)
+            .map(|(hdr, _)| hdr)
+    }
+
+    /// Check that [`Self::device_create`] could send a request to
+    /// create a device, without sending it, and so without needing a
+    /// DM context or privileges.  Returns the error that
+    /// `device_create` would return before reaching the kernel, if
+    /// any.
+    ///
+    /// Valid flags: `DM_READONLY`, `DM_PERSISTENT_DEV`
+    pub fn validate_device_create(
+        name: &DmName,
+        uuid: Option<&DmUuid>,
+        flags: impl Into<DmFlags>,
+    ) -> DmResult<()> {
+        Self::device_create_request(name, uuid, flags.into()).map(|_| ())
+    }
+
+    /// Build the header of a `DM_DEV_CREATE` request.
+    fn device_create_request(
+        name: &DmName,
+        uuid: Option<&DmUuid>,
+        flags: DmFlags,
+    ) -> DmResult<Struct_dm_ioctl> {
         let mut hdr = flags.to_ioctl_hdr(
             None,
             DmFlags::DM_READONLY | DmFlags::DM_PERSISTENT_DEV,
@@ -334,36 +944,229 @@ impl DM {
         if let Some(uuid) = uuid {
             Self::hdr_set_uuid(&mut hdr, uuid)?;
         }
+        Ok(hdr)
+    }
+
+    /// Create a DM device with the minor device number `minor`, so
+    /// that it appears as `/dev/dm-<minor>`.  Otherwise the same as
+    /// [`Self::device_create`]; `DM_PERSISTENT_DEV` is set
+    /// automatically.
+    ///
+    /// The kernel only allows 20-bit minor numbers; a larger `minor`
+    /// is rejected with [`DmError::InvalidArgument`] without issuing
+    /// the ioctl.  If another device already has the minor number,
+    /// the kernel fails the request with `EBUSY`.
+    ///
+    /// Valid flags: `DM_READONLY`
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dm_ioctl::{DM, DmFlags, DmName};
+    /// use nix::errno::Errno;
+    ///
+    /// let dm = DM::new().unwrap();
+    ///
+    /// let name = DmName::new("example-dev").expect("is valid DM name");
+    /// match dm.device_create_persistent(name, None, 100, DmFlags::default())
+    /// {
+    ///     Ok(info) => assert_eq!(info.device().minor, 100),
+    ///     Err(err) if err.errno() == Some(Errno::EBUSY) => {
+    ///         println!("minor 100 is already in use")
+    ///     }
+    ///     Err(err) => panic!("{err}"),
+    /// }
+    /// ```
+    pub fn device_create_persistent(
+        &self,
+        name: &DmName,
+        uuid: Option<&DmUuid>,
+        minor: u32,
+        flags: impl Into<DmFlags>,
+    ) -> DmResult<DeviceInfo> {
+        let mut hdr = Self::device_create_request(
+            name,
+            uuid,
+            flags.into() | DmFlags::DM_PERSISTENT_DEV,
+        )?;
+        // The kernel ignores the major number, always using
+        // device-mapper's own.
+        Self::hdr_set_dev(&mut hdr, Device { major: 0, minor })?;
 
         self.do_ioctl(DmIoctlCmd::DM_DEV_CREATE, &mut hdr, None)
             .map(|(hdr, _)| hdr)
     }
 
+    /// Create a DM device, load `targets` into its inactive table
+    /// slot, and resume it, making the table active.  Returns the
+    /// `DeviceInfo` from the final resume.
+    ///
+    /// If loading the table or resuming the device fails, the device
+    /// is removed again (on a best-effort basis) and the error from
+    /// the step that failed is returned.
+    ///
+    /// `flags` is passed to both [`Self::device_create`] and
+    /// [`Self::table_load`].  It may be given either as [`DmFlags`]
+    /// or as [`DmOptions`][crate::DmOptions].
+    ///
+    /// Valid flags: `DM_READONLY`, `DM_PERSISTENT_DEV`, `DM_SECURE_DATA`
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dm_ioctl::{DM, DmFlags, DmName, TargetLine};
+    ///
+    /// let dm = DM::new().unwrap();
+    ///
+    /// let table = vec![TargetLine::new(0, 32768, "zero", "").unwrap()];
+    /// let name = DmName::new("example-dev").expect("is valid DM name");
+    /// let info = dm
+    ///     .device_create_with_table(name, None, &table, DmFlags::default())
+    ///     .unwrap();
+    /// ```
+    pub fn device_create_with_table(
+        &self,
+        name: &DmName,
+        uuid: Option<&DmUuid>,
+        targets: &[TargetLine],
+        flags: impl Into<DmFlags>,
+    ) -> DmResult<DeviceInfo> {
+        let flags = flags.into();
+        self.device_create(name, uuid, flags)?;
+
+        let id = DevId::Name(name);
+        self.table_load(&id, targets, flags)
+            .and_then(|_| self.resume(&id))
+            .map_err(|err| {
+                let _ = self.device_remove(&id, DmFlags::default());
+                err
+            })
+    }
+
     /// Remove a DM device and its mapping tables.
     ///
     /// If `DM_DEFERRED_REMOVE` is set, the request for an in-use
     /// devices will succeed, and it will be removed when no longer
     /// used.
     ///
+    /// `flags` may be given either as [`DmFlags`] or as
+    /// [`DmOptions`][crate::DmOptions].
+    ///
     /// Valid flags: `DM_DEFERRED_REMOVE`
     pub fn device_remove(
         &self,
         id: &DevId<'_>,
-        flags: DmFlags,
+        flags: impl Into<DmFlags>,
     ) -> DmResult<DeviceInfo> {
-        let mut hdr =
-            flags.to_ioctl_hdr(Some(id), DmFlags::DM_DEFERRED_REMOVE)?;
+        let mut hdr = flags
+            .into()
+            .to_ioctl_hdr(Some(id), DmFlags::DM_DEFERRED_REMOVE)?;
         self.do_ioctl(DmIoctlCmd::DM_DEV_REMOVE, &mut hdr, None)
             .map(|(hdr, _)| hdr)
     }
 
+    /// Remove a DM device with `DM_DEFERRED_REMOVE` set, and
+    /// optionally wait for a deferred removal to complete.
+    ///
+    /// If the device is not in use it is removed immediately.
+    /// Otherwise it is scheduled for removal when it is no longer in
+    /// use, and if `wait` is not `None`, this method polls the device
+    /// until it disappears or `wait` has elapsed, whichever comes
+    /// first.  A device that disappears before the first poll counts
+    /// as having been removed.
+    pub fn device_remove_deferred(
+        &self,
+        id: &DevId<'_>,
+        wait: Option<Duration>,
+    ) -> DmResult<RemovalOutcome> {
+        let info = self.device_remove(id, DmFlags::DM_DEFERRED_REMOVE)?;
+        if !info.is_deferred_remove_scheduled() {
+            return Ok(RemovalOutcome::Removed);
+        }
+
+        let Some(wait) = wait else {
+            return Ok(RemovalOutcome::Pending);
+        };
+        let deadline = Instant::now() + wait;
+        loop {
+            match self.device_info(id) {
+                Err(DmError::Ioctl(_, _, _, Errno::ENXIO)) => {
+                    return Ok(RemovalOutcome::DeferredRemoved)
+                }
+                Err(err) => return Err(err),
+                Ok(_) => (),
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(RemovalOutcome::Pending);
+            }
+            thread::sleep(cmp::min(deadline - now, REMOVAL_POLL_INTERVAL));
+        }
+    }
+
+    /// Remove a DM device as [`Self::device_remove`] does, retrying
+    /// if the kernel reports that the device is busy (`EBUSY`).
+    ///
+    /// Something else, such as udev or `blkid`, often holds a device
+    /// open briefly just after it is created or changed; removing it
+    /// during that window fails.  This makes up to `attempts` tries in
+    /// all, sleeping for `delay` between them, and returns the error
+    /// from the last one if none succeeds.  An `attempts` of zero is
+    /// treated as one.  Errors other than `EBUSY` are returned at
+    /// once.
+    ///
+    /// Valid flags: `DM_DEFERRED_REMOVE`
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// use dm_ioctl::{DevId, DM, DmFlags, DmName};
+    ///
+    /// let dm = DM::new().unwrap();
+    ///
+    /// let name = DmName::new("example-dev").expect("is valid DM name");
+    /// dm.device_remove_retry(
+    ///     &DevId::Name(name),
+    ///     DmFlags::default(),
+    ///     5,
+    ///     Duration::from_millis(100),
+    /// )
+    /// .unwrap();
+    /// ```
+    pub fn device_remove_retry(
+        &self,
+        id: &DevId<'_>,
+        flags: impl Into<DmFlags>,
+        attempts: u32,
+        delay: Duration,
+    ) -> DmResult<DeviceInfo> {
+        let flags = flags.into();
+        let mut remaining = attempts.max(1);
+        loop {
+            remaining -= 1;
+            match self.device_remove(id, flags) {
+                Err(err)
+                    if remaining > 0 && err.errno() == Some(Errno::EBUSY) =>
+                {
+                    thread::sleep(delay)
+                }
+                result => return result,
+            }
+        }
+    }
+
     /// Change a DM device's name OR set the device's uuid for the first time.
     ///
     /// Prerequisite: if `new == DevId::Name(new_name)`, `old_name != new_name`
     /// Prerequisite: if `new == DevId::Uuid(uuid)`, device's current uuid
     /// must be `""`.
+    /// Prerequisite: `new` is not `DevId::Devno`.
     /// Note: Possibly surprisingly, returned `DeviceInfo`'s uuid or name field
-    /// contains the previous value, not the newly set value.
+    /// contains the previous value, not the newly set value.  Use
+    /// [`Self::device_rename_refreshed`] to get the new values.
     pub fn device_rename(
         &self,
         old_name: &DmName,
@@ -372,6 +1175,11 @@ impl DM {
         let (flags, id_in) = match *new {
             DevId::Name(name) => (DmFlags::default(), name.as_bytes()),
             DevId::Uuid(uuid) => (DmFlags::DM_UUID, uuid.as_bytes()),
+            DevId::Devno(_) => {
+                return Err(DmError::InvalidArgument(
+                    "a device cannot be renamed to a device number".into(),
+                ))
+            }
         };
 
         let data_in = [id_in, b"\0"].concat();
@@ -383,9 +1191,30 @@ impl DM {
             .map(|(hdr, _)| hdr)
     }
 
+    /// Change a DM device's name or set its uuid, as
+    /// [`Self::device_rename`] does, and then retrieve the device's
+    /// DeviceInfo, so that its name and uuid are the newly set values.
+    ///
+    /// This takes an extra ioctl.  If the rename succeeds but
+    /// retrieving the DeviceInfo fails, returns
+    /// [`DmError::RenameStatus`]; the device has been renamed
+    /// nonetheless.
+    pub fn device_rename_refreshed(
+        &self,
+        old_name: &DmName,
+        new: &DevId<'_>,
+    ) -> DmResult<DeviceInfo> {
+        self.device_rename(old_name, new)?;
+        self.device_info(new)
+            .map_err(|err| DmError::RenameStatus(Box::new(err)))
+    }
+
     /// Suspend or resume a DM device, depending on if `DM_SUSPEND` flag
     /// is set or not.
     ///
+    /// [`Self::suspend`] and [`Self::resume`] are clearer ways of
+    /// doing the same thing, and should be preferred in new code.
+    ///
     /// Resuming a DM device moves a table loaded into the "inactive"
     /// slot by [`Self::table_load`] into the "active" slot.
     ///
@@ -394,6 +1223,9 @@ impl DM {
     /// flags is given. Additional I/O to a suspended device will be
     /// held until it is resumed.
     ///
+    /// `flags` may be given either as [`DmFlags`] or as
+    /// [`DmOptions`][crate::DmOptions].
+    ///
     /// Valid flags: `DM_SUSPEND`, `DM_NOFLUSH`, `DM_SKIP_LOCKFS`
     ///
     /// # Example
@@ -409,9 +1241,9 @@ impl DM {
     pub fn device_suspend(
         &self,
         id: &DevId<'_>,
-        flags: DmFlags,
+        flags: impl Into<DmFlags>,
     ) -> DmResult<DeviceInfo> {
-        let mut hdr = flags.to_ioctl_hdr(
+        let mut hdr = flags.into().to_ioctl_hdr(
             Some(id),
             DmFlags::DM_SUSPEND | DmFlags::DM_NOFLUSH | DmFlags::DM_SKIP_LOCKFS,
         )?;
@@ -420,6 +1252,131 @@ impl DM {
             .map(|(hdr, _)| hdr)
     }
 
+    /// Suspend a DM device.
+    ///
+    /// Will block until pending I/O is completed unless
+    /// `opts.noflush` is set.  Will freeze any filesystem backed by
+    /// the device unless `opts.skip_lockfs` is set.  Additional I/O
+    /// to a suspended device will be held until it is resumed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dm_ioctl::{DM, DevId, DmName, SuspendOptions};
+    /// let dm = DM::new().unwrap();
+    ///
+    /// let name = DmName::new("example-dev").expect("is valid DM name");
+    /// let id = DevId::Name(name);
+    /// let opts = SuspendOptions {
+    ///     noflush: true,
+    ///     ..Default::default()
+    /// };
+    /// dm.suspend(&id, opts).unwrap();
+    /// ```
+    pub fn suspend(
+        &self,
+        id: &DevId<'_>,
+        opts: SuspendOptions,
+    ) -> DmResult<DeviceInfo> {
+        self.device_suspend(id, opts.to_flags())
+    }
+
+    /// Resume a DM device.
+    ///
+    /// If a table has been loaded into the device's "inactive" slot
+    /// by [`Self::table_load`], it is moved into the "active" slot,
+    /// replacing (and destroying) the previously active table.
+    ///
+    /// It is not an error to resume a device that is not suspended.
+    pub fn resume(&self, id: &DevId<'_>) -> DmResult<DeviceInfo> {
+        self.device_suspend(id, DmFlags::empty())
+    }
+
+    /// Suspend several DM devices, in the order given, as
+    /// [`Self::suspend`] does.  When devices are stacked, list the
+    /// ones on top first.
+    ///
+    /// Returns the DeviceInfo for each device.  If suspending any
+    /// device fails, returns a [`BatchError`] recording which devices
+    /// were suspended, which failed, and, if `mode` is
+    /// [`BatchMode::StopOnError`], which were not attempted.  The
+    /// devices that were suspended are left suspended.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dm_ioctl::{BatchMode, DM, DevId, DmName, SuspendOptions};
+    /// let dm = DM::new().unwrap();
+    ///
+    /// let top = DmName::new("example-top").expect("is valid DM name");
+    /// let bottom = DmName::new("example-bottom").expect("is valid DM name");
+    /// let ids = [DevId::Name(top), DevId::Name(bottom)];
+    /// dm.suspend_all(&ids, SuspendOptions::default(), BatchMode::StopOnError)
+    ///     .unwrap();
+    /// // ...
+    /// let ids = [DevId::Name(bottom), DevId::Name(top)];
+    /// dm.resume_all(&ids, BatchMode::StopOnError).unwrap();
+    /// ```
+    pub fn suspend_all(
+        &self,
+        ids: &[DevId<'_>],
+        opts: SuspendOptions,
+        mode: BatchMode,
+    ) -> Result<Vec<DeviceInfo>, BatchError> {
+        DM::batch(ids, mode, |id| self.suspend(id, opts))
+    }
+
+    /// Resume several DM devices, in the order given, as
+    /// [`Self::resume`] does.  When devices are stacked, list the
+    /// ones on the bottom first.
+    ///
+    /// Returns the DeviceInfo for each device.  If resuming any
+    /// device fails, returns a [`BatchError`] recording which devices
+    /// were resumed, which failed, and, if `mode` is
+    /// [`BatchMode::StopOnError`], which were not attempted.
+    pub fn resume_all(
+        &self,
+        ids: &[DevId<'_>],
+        mode: BatchMode,
+    ) -> Result<Vec<DeviceInfo>, BatchError> {
+        DM::batch(ids, mode, |id| self.resume(id))
+    }
+
+    /// Apply `op` to each of `ids` for [`Self::suspend_all`] and
+    /// [`Self::resume_all`].
+    fn batch(
+        ids: &[DevId<'_>],
+        mode: BatchMode,
+        op: impl Fn(&DevId<'_>) -> DmResult<DeviceInfo>,
+    ) -> Result<Vec<DeviceInfo>, BatchError> {
+        let mut outcomes = Vec::with_capacity(ids.len());
+        let mut failed = false;
+        for id in ids {
+            if failed && mode == BatchMode::StopOnError {
+                outcomes.push(BatchOutcome::Skipped);
+                continue;
+            }
+            outcomes.push(match op(id) {
+                Ok(info) => BatchOutcome::Succeeded(info),
+                Err(err) => {
+                    failed = true;
+                    BatchOutcome::Failed(err)
+                }
+            });
+        }
+
+        if failed {
+            return Err(BatchError { outcomes });
+        }
+        Ok(outcomes
+            .into_iter()
+            .filter_map(|outcome| match outcome {
+                BatchOutcome::Succeeded(info) => Some(info),
+                _ => None,
+            })
+            .collect())
+    }
+
     /// Get DeviceInfo for a device. This is also returned by other
     /// methods, but if just the DeviceInfo is desired then this just
     /// gets it.
@@ -431,50 +1388,181 @@ impl DM {
             .map(|(hdr, _)| hdr)
     }
 
+    /// Whether a device has an active table (`DM_ACTIVE_PRESENT`).
+    pub fn has_active_table(&self, id: &DevId<'_>) -> DmResult<bool> {
+        self.device_info(id)
+            .map(|info| info.is_active_table_present())
+    }
+
+    /// Whether a device has an inactive table, loaded but not yet
+    /// made active by resuming the device (`DM_INACTIVE_PRESENT`).
+    pub fn has_inactive_table(&self, id: &DevId<'_>) -> DmResult<bool> {
+        self.device_info(id)
+            .map(|info| info.is_inactive_table_present())
+    }
+
+    /// Get the conventional paths of the device nodes for a device:
+    /// `/dev/dm-<minor>`, and `/dev/mapper/<name>` if the device has
+    /// a name.
+    ///
+    /// These paths are computed from the device's DeviceInfo; this
+    /// function does not check that anything exists at either path.
+    /// The `/dev/mapper` symlinks in particular are normally created
+    /// by udev, and may not appear immediately.
+    pub fn device_node_paths(
+        &self,
+        id: &DevId<'_>,
+    ) -> DmResult<(PathBuf, Option<PathBuf>)> {
+        let info = self.device_info(id)?;
+        let dm_path = info.device().to_dm_node_path();
+        let mapper_path = info
+            .name()
+            .map(|name| Path::new(DM_DEV_DIR).join(name.as_ref()));
+        Ok((dm_path, mapper_path))
+    }
+
+    /// Create the node `/dev/mapper/<name>` for the device described
+    /// by `info`, which must have a name, and return its path.  This
+    /// is what `dmsetup mknodes` does, for systems where udev is not
+    /// running to do it.
+    ///
+    /// The `/dev/mapper` directory is created if it does not exist.
+    /// If there is already a node at the path, it is left alone if it
+    /// is the kind requested and refers to the right device, and is
+    /// replaced otherwise.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dm_ioctl::{DM, DmFlags, DmName, MapperNode};
+    ///
+    /// let dm = DM::new().unwrap();
+    ///
+    /// let name = DmName::new("example-dev").expect("is valid DM name");
+    /// let info = dm.device_create(name, None, DmFlags::default()).unwrap();
+    /// let path = dm.mknod(&info, MapperNode::BlockDevice).unwrap();
+    /// assert_eq!(path.to_str(), Some("/dev/mapper/example-dev"));
+    /// ```
+    pub fn mknod(
+        &self,
+        info: &DeviceInfo,
+        kind: MapperNode,
+    ) -> DmResult<PathBuf> {
+        let name = info.name().ok_or_else(|| {
+            DmError::InvalidArgument("device has no name".to_string())
+        })?;
+        make_mapper_node(Path::new(DM_DEV_DIR), name, info.device(), kind)
+    }
+
+    /// Remove the node `/dev/mapper/<name>`, if there is one, as
+    /// `dmsetup mknodes` does for devices that no longer exist.
+    pub fn rmnod(&self, name: &DmName) -> DmResult<()> {
+        remove_mapper_node(Path::new(DM_DEV_DIR), name)
+    }
+
     /// Wait for a device to report an event.
     ///
     /// Once an event occurs, this function behaves just like
     /// [`Self::table_status`], see that function for more details.
     ///
+    /// This is [`Self::device_wait_for`] with `last_seen` set to 0,
+    /// so it returns immediately if the device has ever reported an
+    /// event.
+    ///
     /// This interface is not very friendly to monitoring multiple devices.
     /// Events are also exported via uevents, that method may be preferable.
-    #[allow(clippy::type_complexity)]
     pub fn device_wait(
         &self,
         id: &DevId<'_>,
         flags: DmFlags,
-    ) -> DmResult<(DeviceInfo, Vec<(u64, u64, String, String)>)> {
+    ) -> DmResult<(DeviceInfo, Vec<TargetLine>)> {
+        self.device_wait_for(id, 0, flags)
+    }
+
+    /// Wait until a device's event number differs from `last_seen`,
+    /// which should be the [`DeviceInfo::event_nr`] from an earlier
+    /// call.  Returns immediately if it already does.
+    ///
+    /// Once an event occurs, this function behaves just like
+    /// [`Self::table_status`], see that function for more details.
+    ///
+    /// Valid flags: DM_QUERY_INACTIVE_TABLE
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dm_ioctl::{DM, DevId, DmFlags, DmName};
+    /// let dm = DM::new().unwrap();
+    ///
+    /// let name = DmName::new("example-dev").expect("is valid DM name");
+    /// let id = DevId::Name(name);
+    /// let mut last_seen = dm.device_info(&id).unwrap().event_nr();
+    /// loop {
+    ///     let (info, status) =
+    ///         dm.device_wait_for(&id, last_seen, DmFlags::default()).unwrap();
+    ///     last_seen = info.event_nr();
+    ///     println!("event {last_seen}: {status:?}");
+    /// }
+    /// ```
+    pub fn device_wait_for(
+        &self,
+        id: &DevId<'_>,
+        last_seen: u32,
+        flags: DmFlags,
+    ) -> DmResult<(DeviceInfo, Vec<TargetLine>)> {
         let mut hdr =
             flags.to_ioctl_hdr(Some(id), DmFlags::DM_QUERY_INACTIVE_TABLE)?;
+        hdr.event_nr = last_seen;
 
         let (hdr_out, data_out) =
             self.do_ioctl(DmIoctlCmd::DM_DEV_WAIT, &mut hdr, None)?;
 
-        let status = DM::parse_table_status(hdr.target_count, &data_out)?;
+        if !queried_table_present(&hdr_out, flags) {
+            return Ok((hdr_out, vec![]));
+        }
+        let mut status = Vec::new();
+        DM::parse_table_status(
+            hdr_out.target_count(),
+            &data_out,
+            flags,
+            &mut status,
+        )?;
 
         Ok((hdr_out, status))
     }
 
     /// Load targets for a device into its inactive table slot.
     ///
-    /// `targets` is an array of `(sector_start, sector_length, type, params)`.
+    /// `targets` is an array of [`TargetLine`]s; see also
+    /// [`TargetTable`].
+    ///
+    /// The returned `DeviceInfo` describes the device after the load,
+    /// so [`DeviceInfo::is_inactive_table_present`] is true, and
+    /// [`DeviceInfo::is_active_table_present`] still reports whether
+    /// there was already an active table.
+    ///
+    /// `flags` may be given either as [`DmFlags`] or as
+    /// [`DmOptions`][crate::DmOptions].
     ///
-    /// `flags` Valid flags: `DM_READ_ONLY`, `DM_SECURE_DATA`
+    /// Valid flags: `DM_READONLY`, `DM_SECURE_DATA`
+    ///
+    /// If `DM_SECURE_DATA` is set, the buffers this library uses for
+    /// the request are zeroed once the kernel has handled it, or once
+    /// the request has failed, as the kernel does with its own.
+    /// [`Self::table_load_typed`] sets it automatically for tables
+    /// that likely contain key material.
     ///
     /// # Example
     ///
     /// ```no_run
-    /// use dm_ioctl::{DM, DevId, DmName, DmFlags};
+    /// use dm_ioctl::{DM, DevId, DmName, DmFlags, TargetLine};
     /// let dm = DM::new().unwrap();
     ///
     /// // Create a 16MiB device (32768 512-byte sectors) that maps to /dev/sdb1
     /// // starting 1MiB into sdb1
-    /// let table = vec![(
-    ///     0,
-    ///     32768,
-    ///     "linear".into(),
-    ///     "/dev/sdb1 2048".into()
-    /// )];
+    /// let table = vec![
+    ///     TargetLine::new(0, 32768, "linear", "/dev/sdb1 2048").unwrap(),
+    /// ];
     ///
     /// let name = DmName::new("example-dev").expect("is valid DM name");
     /// let id = DevId::Name(name);
@@ -483,63 +1571,329 @@ impl DM {
     pub fn table_load(
         &self,
         id: &DevId<'_>,
-        targets: &[(u64, u64, String, String)],
-        flags: DmFlags,
+        targets: &[TargetLine],
+        flags: impl Into<DmFlags>,
     ) -> DmResult<DeviceInfo> {
-        let mut cursor = Cursor::new(Vec::new());
+        let (mut hdr, mut data_in) =
+            Self::table_load_request(id, targets, flags.into())?;
+
+        let result = self
+            .do_ioctl(DmIoctlCmd::DM_TABLE_LOAD, &mut hdr, Some(&data_in))
+            .map(|(hdr, _)| hdr);
+        // Whatever happens, the copy of the parameters must be wiped.
+        if hdr.flags & DmFlags::DM_SECURE_DATA.bits() != 0 {
+            zero_bytes(&mut data_in);
+        }
+        result
+    }
+
+    /// Check that [`Self::table_load_checked`] could send a request
+    /// to load `targets`, without sending it, and so without needing
+    /// a DM context or privileges.  Returns the error that
+    /// `table_load_checked` would return before reaching the kernel,
+    /// if any; the kernel may still reject the table, for instance if
+    /// a target's parameters are invalid or a device does not exist.
+    ///
+    /// Valid flags: `DM_READONLY`, `DM_SECURE_DATA`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dm_ioctl::{DevId, DmError, DmFlags, DmName, TargetLine, DM};
+    ///
+    /// let id = DevId::Name(DmName::new("example-dev").unwrap());
+    /// let table = vec![
+    ///     TargetLine::new(0, 2048, "linear", "8:16 0").unwrap(),
+    ///     TargetLine::new(2048, 2048, "linear", "8:32 0").unwrap(),
+    /// ];
+    /// DM::validate_table_load(&id, &table, DmFlags::default()).unwrap();
+    ///
+    /// assert!(matches!(
+    ///     DM::validate_table_load(&id, &table[1..], DmFlags::default()),
+    ///     Err(DmError::InvalidArgument(_))
+    /// ));
+    /// ```
+    pub fn validate_table_load(
+        id: &DevId<'_>,
+        targets: &[TargetLine],
+        flags: impl Into<DmFlags>,
+    ) -> DmResult<()> {
+        check_table_layout(targets)?;
+        let (hdr, mut data_in) =
+            Self::table_load_request(id, targets, flags.into())?;
+        if hdr.flags & DmFlags::DM_SECURE_DATA.bits() != 0 {
+            zero_bytes(&mut data_in);
+        }
+        Ok(())
+    }
+
+    /// Build the header and data of a `DM_TABLE_LOAD` request loading
+    /// `targets` into the device `id`.  The data holds a copy of each
+    /// target's parameters, which the caller must wipe if
+    /// `DM_SECURE_DATA` is set; it is wiped here if building fails.
+    fn table_load_request(
+        id: &DevId<'_>,
+        targets: &[TargetLine],
+        flags: DmFlags,
+    ) -> DmResult<(Struct_dm_ioctl, Vec<u8>)> {
+        let mut hdr = flags.to_ioctl_hdr(
+            Some(id),
+            DmFlags::DM_READONLY | DmFlags::DM_SECURE_DATA,
+        )?;
+
+        // io_ioctl() will set hdr.data_size but we must set target_count
+        hdr.target_count = targets.len() as u32;
+
+        // Size of the largest single member of dm_target_spec
+        let align_to_size = size_of::<u64>();
 
-        // Construct targets first, since we need to know how many & size
-        // before initializing the header.
-        for (sector_start, length, target_type, params) in targets {
+        // Allocate the whole buffer up front, so that it is never
+        // moved and can be reliably zeroed if it holds secure data.
+        let data_size = targets
+            .iter()
+            .map(|line| {
+                size_of::<Struct_dm_target_spec>()
+                    + align_to(line.params.len() + 1usize, align_to_size)
+            })
+            .sum();
+        let mut cursor = Cursor::new(Vec::with_capacity(data_size));
+
+        // Flatten targets into a buf
+        let built = targets.iter().try_for_each(|line| {
+            let params = &line.params;
             let mut targ = Struct_dm_target_spec {
-                sector_start: *sector_start,
-                length: *length,
+                sector_start: line.start,
+                length: line.length,
                 status: 0,
                 ..Default::default()
             };
 
+            // TargetTypeBuf guarantees that the target type fits.
             let dst = mut_slice_from_c_str(&mut targ.target_type);
-            assert!(
-                target_type.len() <= dst.len(),
-                "TargetType max length = targ.target_type.len()"
-            );
-            let _ = target_type
-                .as_bytes()
-                .read(dst)
-                .map_err(DmError::RequestConstruction)?;
-
-            // Size of the largest single member of dm_target_spec
-            let align_to_size = size_of::<u64>();
+            let _ = line.target_type.as_bytes().read(dst)?;
+
             let aligned_len = align_to(params.len() + 1usize, align_to_size);
             targ.next =
                 (size_of::<Struct_dm_target_spec>() + aligned_len) as u32;
 
-            cursor
-                .write_all(slice_from_c_struct(&targ))
-                .map_err(DmError::RequestConstruction)?;
-            cursor
-                .write_all(params.as_bytes())
-                .map_err(DmError::RequestConstruction)?;
+            cursor.write_all(slice_from_c_struct(&targ))?;
+            cursor.write_all(params.as_bytes())?;
 
             let padding = aligned_len - params.len();
-            cursor
-                .write_all(vec![0; padding].as_slice())
-                .map_err(DmError::RequestConstruction)?;
+            cursor.write_all(vec![0; padding].as_slice())
+        });
+        let mut data_in = cursor.into_inner();
+
+        match built {
+            Ok(()) => Ok((hdr, data_in)),
+            Err(err) => {
+                if hdr.flags & DmFlags::DM_SECURE_DATA.bits() != 0 {
+                    zero_bytes(&mut data_in);
+                }
+                Err(DmError::RequestConstruction(err))
+            }
         }
+    }
 
-        let mut hdr = flags.to_ioctl_hdr(
-            Some(id),
-            DmFlags::DM_READONLY | DmFlags::DM_SECURE_DATA,
-        )?;
+    /// Load targets into a device's inactive table slot, as
+    /// [`Self::table_load`] does, after checking that they are laid
+    /// out correctly: the first target must start at sector 0, each
+    /// of the others must start where the previous one ends, and
+    /// every target must be at least one sector long and have a
+    /// valid target type.  If not, the first problem found is
+    /// returned as [`DmError::InvalidArgument`], and nothing is
+    /// loaded.
+    ///
+    /// A table built with [`TargetTable::push`], which rejects empty
+    /// targets, is always laid out correctly.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dm_ioctl::{DM, DevId, DmError, DmFlags, DmName, TargetLine};
+    /// let dm = DM::new().unwrap();
+    ///
+    /// let table = vec![
+    ///     TargetLine::new(0, 2048, "linear", "8:16 0").unwrap(),
+    ///     TargetLine::new(2047, 2048, "linear", "8:32 0").unwrap(),
+    /// ];
+    ///
+    /// let name = DmName::new("example-dev").expect("is valid DM name");
+    /// let id = DevId::Name(name);
+    /// assert!(matches!(
+    ///     dm.table_load_checked(&id, &table, DmFlags::default()),
+    ///     Err(DmError::InvalidArgument(_))
+    /// ));
+    /// ```
+    pub fn table_load_checked(
+        &self,
+        id: &DevId<'_>,
+        targets: &[TargetLine],
+        flags: impl Into<DmFlags>,
+    ) -> DmResult<DeviceInfo> {
+        check_table_layout(targets)?;
+        self.table_load(id, targets, flags)
+    }
 
-        // io_ioctl() will set hdr.data_size but we must set target_count
-        hdr.target_count = targets.len() as u32;
+    /// Load a [`TargetTable`] into a device's inactive table slot, as
+    /// [`Self::table_load`] does.
+    ///
+    /// If [`TargetTable::contains_sensitive_params`] is true,
+    /// `DM_SECURE_DATA` is set even if `flags` does not include it,
+    /// so that the kernel wipes its copies of the table's parameters.
+    pub fn table_load_typed(
+        &self,
+        id: &DevId<'_>,
+        table: &TargetTable,
+        flags: impl Into<DmFlags>,
+    ) -> DmResult<DeviceInfo> {
+        let mut flags = flags.into();
+        if table.contains_sensitive_params() {
+            flags |= DmFlags::DM_SECURE_DATA;
+        }
+        self.table_load(id, table.targets(), flags)
+    }
 
-        // Flatten targets into a buf
-        let data_in = cursor.into_inner();
+    /// Replace the active table of a device with `targets`: load
+    /// the new table into the inactive slot, optionally suspend the
+    /// device, and then resume it.  This is equivalent to `dmsetup
+    /// reload` followed by `dmsetup resume`.
+    ///
+    /// If any step after the load fails, the inactive slot is
+    /// cleared again, and if this method suspended the device it
+    /// resumes it with its old table, both on a best-effort basis.
+    /// The error returned is [`DmError::TableReload`], recording
+    /// which step failed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dm_ioctl::{DM, DevId, DmName, ReloadOptions, TargetLine};
+    /// let dm = DM::new().unwrap();
+    ///
+    /// let table =
+    ///     vec![TargetLine::new(0, 32768, "linear", "8:17 2048").unwrap()];
+    /// let name = DmName::new("example-dev").expect("is valid DM name");
+    /// let opts = ReloadOptions {
+    ///     suspend: true,
+    ///     noflush: true,
+    ///     ..Default::default()
+    /// };
+    /// dm.table_reload(&DevId::Name(name), &table, opts).unwrap();
+    /// ```
+    pub fn table_reload(
+        &self,
+        id: &DevId<'_>,
+        targets: &[TargetLine],
+        opts: ReloadOptions,
+    ) -> DmResult<DeviceInfo> {
+        let mut load_flags = DmFlags::empty();
+        load_flags.set(DmFlags::DM_READONLY, opts.read_only);
+        load_flags.set(DmFlags::DM_SECURE_DATA, opts.secure_data);
+        self.table_load(id, targets, load_flags).map_err(|err| {
+            DmError::TableReload(ReloadStep::Load, Box::new(err))
+        })?;
+
+        if opts.suspend {
+            let suspend_opts = SuspendOptions {
+                noflush: opts.noflush,
+                ..Default::default()
+            };
+            if let Err(err) = self.suspend(id, suspend_opts) {
+                let _ = self.table_clear(id);
+                return Err(DmError::TableReload(
+                    ReloadStep::Suspend,
+                    Box::new(err),
+                ));
+            }
+        }
 
-        self.do_ioctl(DmIoctlCmd::DM_TABLE_LOAD, &mut hdr, Some(&data_in))
-            .map(|(hdr, _)| hdr)
+        self.resume(id).map_err(|err| {
+            let _ = self.table_clear(id);
+            if opts.suspend {
+                let _ = self.resume(id);
+            }
+            DmError::TableReload(ReloadStep::Resume, Box::new(err))
+        })
+    }
+
+    /// Replace the active table of a device with a single `error`
+    /// target `length` sectors long, which fails all I/O, as
+    /// [`Self::table_reload`] does.  This is the usual way to fence
+    /// off a device whose backing storage has failed, or which is
+    /// about to be removed.  See [`Self::wipe_table`] to keep the
+    /// device's current length.  Fails with
+    /// [`DmError::InvalidArgument`] if `length` is 0.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dm_ioctl::{DM, DevId, DmName, ReloadOptions};
+    /// let dm = DM::new().unwrap();
+    ///
+    /// let name = DmName::new("example-dev").expect("is valid DM name");
+    /// let id = DevId::Name(name);
+    /// dm.device_set_error_table(&id, 32768, ReloadOptions::default())
+    ///     .unwrap();
+    /// ```
+    pub fn device_set_error_table(
+        &self,
+        id: &DevId<'_>,
+        length: u64,
+        opts: ReloadOptions,
+    ) -> DmResult<DeviceInfo> {
+        self.table_reload(id, TargetTable::error_table(length)?.targets(), opts)
+    }
+
+    /// Replace the active table of a device with a single `error`
+    /// target covering the whole device, like `dmsetup wipe_table`,
+    /// so that nothing can be read from or written to whatever the
+    /// device was mapped to.  The length of the device is taken from
+    /// its active table, which it must have; otherwise
+    /// [`DmError::InvalidArgument`] is returned.
+    ///
+    /// This is typically done when the backing storage has already
+    /// gone away, in which case set `opts.suspend` and `opts.noflush`
+    /// so that the device is suspended without waiting for I/O to
+    /// storage that will never complete it.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dm_ioctl::{DM, DevId, DmName, ReloadOptions};
+    /// let dm = DM::new().unwrap();
+    ///
+    /// let name = DmName::new("example-dev").expect("is valid DM name");
+    /// let opts = ReloadOptions {
+    ///     suspend: true,
+    ///     noflush: true,
+    ///     ..Default::default()
+    /// };
+    /// dm.wipe_table(&DevId::Name(name), opts).unwrap();
+    /// ```
+    pub fn wipe_table(
+        &self,
+        id: &DevId<'_>,
+        opts: ReloadOptions,
+    ) -> DmResult<DeviceInfo> {
+        // Only the extent of each target is needed, so ask for the
+        // INFO status rather than the table, which could hold keys.
+        let (info, targets) = self.table_status(id, DmFlags::empty())?;
+        if !info.is_active_table_present() || targets.is_empty() {
+            return Err(DmError::InvalidArgument(format!(
+                "device {id} has no active table"
+            )));
+        }
+        let mut length = 0;
+        for line in &targets {
+            let end = line.start.checked_add(line.length).ok_or(
+                DmError::IoctlResultMalformed(
+                    MalformedReason::TargetExtentOverflow,
+                ),
+            )?;
+            length = length.max(end);
+        }
+        self.device_set_error_table(id, length, opts)
     }
 
     /// Clear the "inactive" table for a device.
@@ -557,6 +1911,12 @@ impl DM {
     /// If DM_QUERY_INACTIVE_TABLE is set, instead return for the
     /// inactive table.
     ///
+    /// If the device has no table of the kind requested, the Vec is
+    /// empty.
+    ///
+    /// See [`Self::table_deps_resolved`] to also get the names of the
+    /// referenced devices that are DM devices.
+    ///
     /// Valid flags: DM_QUERY_INACTIVE_TABLE
     pub fn table_deps(
         &self,
@@ -566,10 +1926,10 @@ impl DM {
         let mut hdr =
             flags.to_ioctl_hdr(Some(id), DmFlags::DM_QUERY_INACTIVE_TABLE)?;
 
-        let (_, data_out) =
+        let (hdr_out, data_out) =
             self.do_ioctl(DmIoctlCmd::DM_TABLE_DEPS, &mut hdr, None)?;
 
-        if data_out.is_empty() {
+        if data_out.is_empty() || !queried_table_present(&hdr_out, flags) {
             Ok(vec![])
         } else {
             let result = &data_out[..];
@@ -588,61 +1948,178 @@ impl DM {
         }
     }
 
+    /// Like [`Self::table_deps`], but also report which of the
+    /// devices are DM devices, and their names.  The names are found
+    /// with a single [`Self::list_devices`] call, so a DM device that
+    /// is created or removed concurrently may be missed.
+    pub fn table_deps_resolved(
+        &self,
+        id: &DevId<'_>,
+        flags: DmFlags,
+    ) -> DmResult<Vec<DepEntry>> {
+        let deps = self.table_deps(id, flags)?;
+        if deps.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let names = self
+            .list_devices()?
+            .into_iter()
+            .map(|(name, device, _)| (device, name))
+            .collect::<HashMap<_, _>>();
+        Ok(deps
+            .into_iter()
+            .map(|device| DepEntry {
+                device,
+                name: names.get(&device).cloned(),
+            })
+            .collect())
+    }
+
+    /// Build the graph of dependencies between all DM devices, like
+    /// `dmsetup ls --tree`.  This lists the devices once, then
+    /// retrieves the dependencies of each one's active table.  A
+    /// device removed in the meantime is left out.
+    pub fn dep_tree(&self) -> DmResult<DepGraph> {
+        let devices = self.list_devices()?;
+        let names = devices
+            .iter()
+            .map(|(name, device, _)| (*device, name))
+            .collect::<HashMap<_, _>>();
+
+        let mut graph = DepGraph::default();
+        for (name, device, _) in &devices {
+            let deps =
+                match self.table_deps(&DevId::Name(name), DmFlags::default()) {
+                    Err(DmError::Ioctl(_, _, _, Errno::ENXIO)) => continue,
+                    res => res?,
+                };
+            let deps = deps
+                .into_iter()
+                .map(|device| DepEntry {
+                    device,
+                    name: names.get(&device).map(|name| (*name).clone()),
+                })
+                .collect();
+            graph.insert(name.clone(), *device, deps);
+        }
+        Ok(graph)
+    }
+
     /// Parse a device's table. The table value is in buf, count indicates the
     /// expected number of lines.
     /// Trims trailing white space off final entry on each line. This
     /// canonicalization makes checking identity of tables easier.
     /// Postcondition: The length of the next to last entry in any tuple is
     /// no more than 16 characters.
+    ///
+    /// The lines are appended to `targets`.  Each params string is
+    /// copied straight out of `buf`, so no other copy of it is made;
+    /// if `DM_SECURE_DATA` is set in `flags` and parsing fails, the
+    /// params of the lines already appended are wiped.
     fn parse_table_status(
         count: u32,
         buf: &[u8],
-    ) -> DmResult<Vec<(u64, u64, String, String)>> {
-        let mut targets = Vec::new();
-        if !buf.is_empty() {
-            let mut next_off = 0;
-
-            for _ in 0..count {
-                let result = &buf[next_off..];
-                let targ = unsafe {
-                    &*(result.as_ptr() as *const Struct_dm_target_spec)
-                };
-
-                let target_type = str_from_c_str(&targ.target_type)
-                    .ok_or_else(|| {
-                        DmError::IoctlResultMalformed(
-                            "Could not convert target type to a String",
-                        )
-                    })?
-                    .to_string();
+        flags: DmFlags,
+        targets: &mut Vec<TargetLine>,
+    ) -> DmResult<()> {
+        let result = DM::walk_table_status(count, buf, |targ, params| {
+            let target_type = str_from_c_str(&targ.target_type)
+                .and_then(|ty| TargetTypeBuf::new(ty.to_string()).ok())
+                .ok_or(DmError::IoctlResultMalformed(
+                    MalformedReason::TargetTypeInvalid,
+                ))?;
+            let params = str_from_byte_slice(params).ok_or(
+                DmError::IoctlResultMalformed(
+                    MalformedReason::TargetParamsInvalid,
+                ),
+            )?;
+            targets.push(TargetLine {
+                start: targ.sector_start,
+                length: targ.length,
+                target_type,
+                params: params.to_string(),
+            });
+            Ok(())
+        });
+        if result.is_err() && flags.contains(DmFlags::DM_SECURE_DATA) {
+            for line in targets.iter_mut() {
+                zero_string(&mut line.params);
+            }
+        }
+        result
+    }
 
-                let params = str_from_byte_slice(
-                    &result[size_of::<Struct_dm_target_spec>()..],
-                )
-                .ok_or_else(|| {
-                    DmError::IoctlResultMalformed(
-                        "Invalid DM target parameters returned from kernel",
-                    )
-                })?
+    fn parse_table_status_raw(
+        count: u32,
+        buf: &[u8],
+    ) -> DmResult<Vec<RawTargetStatus>> {
+        let mut targets = Vec::new();
+        DM::walk_table_status(count, buf, |targ, params| {
+            let target_type = str_from_c_str(&targ.target_type)
+                .ok_or(DmError::IoctlResultMalformed(
+                    MalformedReason::TargetTypeInvalid,
+                ))?
                 .to_string();
+            targets.push(RawTargetStatus {
+                sector_start: targ.sector_start,
+                length: targ.length,
+                target_type,
+                params: params.to_vec(),
+            });
+            Ok(())
+        })?;
+        Ok(targets)
+    }
 
-                targets.push((
-                    targ.sector_start,
-                    targ.length,
-                    target_type,
-                    params,
+    /// Call `f` with the `dm_target_spec` of each of the `count`
+    /// records in `buf`, and the params region that follows it.
+    fn walk_table_status(
+        count: u32,
+        buf: &[u8],
+        mut f: impl FnMut(&Struct_dm_target_spec, &[u8]) -> DmResult<()>,
+    ) -> DmResult<()> {
+        let spec_size = size_of::<Struct_dm_target_spec>();
+        if buf.is_empty() {
+            return Ok(());
+        }
+        let mut next_off = 0;
+
+        for _ in 0..count {
+            let result = buf
+                .get(next_off..)
+                .filter(|result| result.len() >= spec_size)
+                .ok_or(DmError::IoctlResultMalformed(
+                    MalformedReason::TruncatedRecord,
+                ))?;
+            // SAFETY: result is long enough to hold a dm_target_spec,
+            // and read_unaligned does not require it to be aligned.
+            let targ: Struct_dm_target_spec =
+                unsafe { ptr::read_unaligned(result.as_ptr().cast()) };
+
+            // The kernel pads each record to an 8-byte boundary when
+            // computing `next`, but does not include the padding after
+            // the last record in the buffer.  So `next` may point up
+            // to that padding past the end.
+            let next = targ.next as usize;
+            if next < next_off + spec_size
+                || next > align_to(buf.len(), size_of::<u64>())
+            {
+                return Err(DmError::IoctlResultMalformed(
+                    MalformedReason::BadNextOffset,
                 ));
-
-                next_off = targ.next as usize;
             }
+            f(&targ, &buf[next_off + spec_size..cmp::min(next, buf.len())])?;
+
+            next_off = next;
         }
-        Ok(targets)
+        Ok(())
     }
 
     /// Return the status of all targets for a device's "active"
     /// table.
     ///
-    /// Returns DeviceInfo and a Vec of (sector_start, sector_length, type, params).
+    /// Returns DeviceInfo and a Vec of [`TargetLine`]s.
     ///
     /// If DM_STATUS_TABLE flag is set, returns the current table value. Otherwise
     /// returns target-specific status information.
@@ -653,7 +2130,23 @@ impl DM {
     /// If DM_QUERY_INACTIVE_TABLE is set, instead return the status of the
     /// inactive table.
     ///
-    /// Valid flags: DM_NOFLUSH, DM_STATUS_TABLE, DM_QUERY_INACTIVE_TABLE
+    /// If the device has no table of the kind requested, as reported
+    /// by [`DeviceInfo::is_active_table_present`] or
+    /// [`DeviceInfo::is_inactive_table_present`], the Vec is empty.
+    ///
+    /// If DM_SECURE_DATA is set, as it should be when retrieving the
+    /// table of a device whose parameters include key material, the
+    /// kernel and this library wipe their buffers after use.  The
+    /// returned params are the caller's responsibility.
+    ///
+    /// If DM_IMA_MEASUREMENT is set, the params of each target are
+    /// instead what the IMA subsystem would measure for it, taking
+    /// precedence over DM_STATUS_TABLE.  This requires DM version
+    /// 4.45 or later, since older kernels ignore the flag; see
+    /// [`Self::ima_measurement`].
+    ///
+    /// Valid flags: DM_NOFLUSH, DM_STATUS_TABLE, DM_QUERY_INACTIVE_TABLE,
+    /// DM_SECURE_DATA, DM_IMA_MEASUREMENT
     ///
     /// # Example
     ///
@@ -667,27 +2160,131 @@ impl DM {
     ///                           DmFlags::DM_STATUS_TABLE).unwrap();
     /// println!("{:?} {:?}", res.0.name(), res.1);
     /// ```
-    #[allow(clippy::type_complexity)]
     pub fn table_status(
         &self,
         id: &DevId<'_>,
         flags: DmFlags,
-    ) -> DmResult<(DeviceInfo, Vec<(u64, u64, String, String)>)> {
+    ) -> DmResult<(DeviceInfo, Vec<TargetLine>)> {
+        let mut hdr = flags.to_ioctl_hdr(
+            Some(id),
+            DmFlags::DM_NOFLUSH
+                | DmFlags::DM_STATUS_TABLE
+                | DmFlags::DM_QUERY_INACTIVE_TABLE
+                | DmFlags::DM_SECURE_DATA
+                | DmFlags::DM_IMA_MEASUREMENT,
+        )?;
+        self.check_ima_measurement(flags)?;
+
+        let (hdr_out, mut data_out) =
+            self.do_ioctl(DmIoctlCmd::DM_TABLE_STATUS, &mut hdr, None)?;
+
+        let status = DM::table_status_reply(&hdr_out, &mut data_out, flags)?;
+        Ok((hdr_out, status))
+    }
+
+    /// Parse the reply to a `DM_TABLE_STATUS` request made with
+    /// `flags`, returning no targets if the table asked about is not
+    /// present, whatever the kernel put in the buffer.  If
+    /// `DM_SECURE_DATA` is set, `data_out` is wiped in any case.
+    fn table_status_reply(
+        hdr_out: &DeviceInfo,
+        data_out: &mut [u8],
+        flags: DmFlags,
+    ) -> DmResult<Vec<TargetLine>> {
+        let mut status = Vec::new();
+        let result = if queried_table_present(hdr_out, flags) {
+            DM::parse_table_status(
+                hdr_out.target_count(),
+                data_out,
+                flags,
+                &mut status,
+            )
+        } else {
+            Ok(())
+        };
+        if flags.contains(DmFlags::DM_SECURE_DATA) {
+            zero_bytes(data_out);
+        }
+        result.map(|_| status)
+    }
+
+    /// Return the status of all targets for a device's "active"
+    /// table, without interpreting the params of each target.
+    ///
+    /// This is the same as [`Self::table_status`], except that the
+    /// params region of each target is returned exactly as the
+    /// kernel provided it; see [`RawTargetStatus`].
+    ///
+    /// Valid flags: DM_NOFLUSH, DM_STATUS_TABLE, DM_QUERY_INACTIVE_TABLE,
+    /// DM_IMA_MEASUREMENT
+    pub fn table_status_raw(
+        &self,
+        id: &DevId<'_>,
+        flags: DmFlags,
+    ) -> DmResult<(DeviceInfo, Vec<RawTargetStatus>)> {
         let mut hdr = flags.to_ioctl_hdr(
             Some(id),
             DmFlags::DM_NOFLUSH
                 | DmFlags::DM_STATUS_TABLE
-                | DmFlags::DM_QUERY_INACTIVE_TABLE,
+                | DmFlags::DM_QUERY_INACTIVE_TABLE
+                | DmFlags::DM_IMA_MEASUREMENT,
         )?;
+        self.check_ima_measurement(flags)?;
 
         let (hdr_out, data_out) =
             self.do_ioctl(DmIoctlCmd::DM_TABLE_STATUS, &mut hdr, None)?;
 
-        let status = DM::parse_table_status(hdr_out.target_count, &data_out)?;
+        if !queried_table_present(&hdr_out, flags) {
+            return Ok((hdr_out, vec![]));
+        }
+        let status =
+            DM::parse_table_status_raw(hdr_out.target_count(), &data_out)?;
 
         Ok((hdr_out, status))
     }
 
+    /// What the IMA subsystem would measure for the device's active
+    /// table: the concatenation, in table order, of what each target
+    /// reports for DM_IMA_MEASUREMENT, each ending in `;`.  Targets
+    /// that do not support IMA contribute nothing.  Use
+    /// [`Self::table_status`] with DM_IMA_MEASUREMENT to get each
+    /// target's string separately.
+    ///
+    /// Requires DM version 4.45 or later.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dm_ioctl::{DM, DevId, DmName};
+    /// let dm = DM::new().unwrap();
+    ///
+    /// let name = DmName::new("example-dev").expect("is valid DM name");
+    /// println!("{}", dm.ima_measurement(&DevId::Name(name)).unwrap());
+    /// ```
+    pub fn ima_measurement(&self, id: &DevId<'_>) -> DmResult<String> {
+        let (_, targets) =
+            self.table_status(id, DmFlags::DM_IMA_MEASUREMENT)?;
+        Ok(targets.into_iter().map(|target| target.params).collect())
+    }
+
+    /// Returns an error if `flags` asks for IMA measurements and the
+    /// kernel is too old to provide them, rather than letting it
+    /// return ordinary status in their place.
+    fn check_ima_measurement(&self, flags: DmFlags) -> DmResult<()> {
+        const IMA_VERSION: (u32, u32, u32) = (4, 45, 0);
+        if flags.contains(DmFlags::DM_IMA_MEASUREMENT) {
+            let version = self.interface_version()?;
+            if version < IMA_VERSION {
+                return Err(DmError::UnsupportedKernel {
+                    cmd: DmIoctlCmd::DM_TABLE_STATUS,
+                    required: IMA_VERSION,
+                    actual: version,
+                });
+            }
+        }
+        Ok(())
+    }
+
     /// Returns a list of each loaded target type with its name, and
     /// version broken into major, minor, and patchlevel.
     pub fn list_versions(&self) -> DmResult<Vec<(String, u32, u32, u32)>> {
@@ -697,51 +2294,192 @@ impl DM {
         let (_, data_out) =
             self.do_ioctl(DmIoctlCmd::DM_LIST_VERSIONS, &mut hdr, None)?;
 
+        DM::parse_target_versions(&data_out)
+    }
+
+    /// Parse the result of `DM_LIST_VERSIONS` or
+    /// `DM_GET_TARGET_VERSION`.
+    fn parse_target_versions(
+        buf: &[u8],
+    ) -> DmResult<Vec<(String, u32, u32, u32)>> {
+        let name_offset = size_of::<Struct_dm_target_versions>();
+
         let mut targets = Vec::new();
-        if !data_out.is_empty() {
-            let mut result = &data_out[..];
+        if !buf.is_empty() {
+            let mut result = buf;
 
             loop {
-                let tver = unsafe {
-                    &*(result.as_ptr() as *const Struct_dm_target_versions)
-                };
+                let next = read_u32(result, 0)?;
+                let mut version = [0; 3];
+                for (i, part) in version.iter_mut().enumerate() {
+                    *part = read_u32(result, (i + 1) * size_of::<u32>())?;
+                }
 
-                let name = str_from_byte_slice(
-                    &result[size_of::<Struct_dm_target_versions>()..],
-                )
-                .ok_or_else(|| {
-                    DmError::IoctlResultMalformed(
-                        "Invalid DM target name returned from kernel",
-                    )
-                })?
-                .to_string();
-                targets.push((
-                    name,
-                    tver.version[0],
-                    tver.version[1],
-                    tver.version[2],
-                ));
+                let name = result
+                    .get(name_offset..)
+                    .and_then(str_from_byte_slice)
+                    .ok_or_else(|| {
+                        DmError::IoctlResultMalformed(
+                            MalformedReason::TargetTypeInvalid,
+                        )
+                    })?
+                    .to_string();
+                targets.push((name, version[0], version[1], version[2]));
 
-                if tver.next == 0 {
+                if next == 0 {
                     break;
                 }
 
-                result = &result[tver.next as usize..];
+                result = result.get(next as usize..).ok_or(
+                    DmError::IoctlResultMalformed(
+                        MalformedReason::TruncatedRecord,
+                    ),
+                )?;
             }
         }
 
         Ok(targets)
     }
 
+    /// The version of the target type `name`, or `None` if the
+    /// kernel does not support it.
+    ///
+    /// This consults a list of the loaded target types, which is
+    /// retrieved from the kernel the first time it is needed and
+    /// then cached; see [`Self::refresh_target_versions`].  If
+    /// `name` is not in the list, and the kernel supports it (DM
+    /// version 4.41 or later), the kernel is asked about `name`
+    /// specifically, which loads the module implementing it if
+    /// necessary.
+    pub fn target_version(
+        &self,
+        name: &str,
+    ) -> DmResult<Option<(u32, u32, u32)>> {
+        let targets = match self.target_versions.get() {
+            Some(targets) => targets,
+            None => {
+                let targets = self.list_versions()?;
+                self.target_versions.get_or_init(|| targets)
+            }
+        };
+        if let Some((_, major, minor, patch)) =
+            targets.iter().find(|target| target.0 == name)
+        {
+            return Ok(Some((*major, *minor, *patch)));
+        }
+
+        if self.interface_version()? < (4, 41, 0)
+            || name.len() >= DM_NAME_LEN
+            || name.contains('\0')
+        {
+            return Ok(None);
+        }
+
+        let mut hdr =
+            DmFlags::default().to_ioctl_hdr(None, DmFlags::empty())?;
+        let _ = name
+            .as_bytes()
+            .read(mut_slice_from_c_str(&mut hdr.name))
+            .map_err(DmError::RequestConstruction)?;
+
+        match self.do_ioctl(DmIoctlCmd::DM_GET_TARGET_VERSION, &mut hdr, None) {
+            Ok((_, data_out)) => Ok(DM::parse_target_versions(&data_out)?
+                .into_iter()
+                .next()
+                .map(|(_, major, minor, patch)| (major, minor, patch))),
+            Err(DmError::Ioctl(_, _, _, Errno::EINVAL)) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Whether the kernel supports the target type `name`.  See
+    /// [`Self::target_version`].
+    pub fn has_target(&self, name: &str) -> DmResult<bool> {
+        Ok(self.target_version(name)?.is_some())
+    }
+
+    /// Returns an error unless the kernel supports the target type
+    /// `name` at version `min` or later.  See
+    /// [`Self::target_version`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dm_ioctl::DM;
+    /// let dm = DM::new().unwrap();
+    ///
+    /// dm.require_target_version("thin-pool", (1, 14, 0)).unwrap();
+    /// ```
+    pub fn require_target_version(
+        &self,
+        name: &str,
+        min: (u32, u32, u32),
+    ) -> DmResult<()> {
+        match self.target_version(name)? {
+            Some(version) if version >= min => Ok(()),
+            version => {
+                Err(DmError::TargetUnavailable(name.to_string(), min, version))
+            }
+        }
+    }
+
+    /// Discard the list of loaded target types cached by
+    /// [`Self::target_version`], so that it is retrieved again the
+    /// next time it is needed, e.g. after loading a module.
+    pub fn refresh_target_versions(&mut self) {
+        self.target_versions.take();
+    }
+
     /// Send a message to the device specified by id and the sector
     /// specified by sector. If sending to the whole device, set sector to
     /// None.
+    ///
+    /// The reply, if any, must be valid UTF-8; use
+    /// [`Self::target_msg_raw`] for targets whose replies may not be.
     pub fn target_msg(
         &self,
         id: &DevId<'_>,
         sector: Option<u64>,
         msg: &str,
     ) -> DmResult<(DeviceInfo, Option<String>)> {
+        let (hdr_out, data_out) =
+            self.target_msg_raw(id, sector, msg.as_bytes())?;
+
+        let output = data_out
+            .map(|mut data| {
+                if data.last() == Some(&b'\0') {
+                    data.pop();
+                }
+                String::from_utf8(data).map_err(|_| {
+                    DmError::IoctlResultMalformed(
+                        MalformedReason::MessageNotUtf8,
+                    )
+                })
+            })
+            .transpose()?;
+        Ok((hdr_out, output))
+    }
+
+    /// Send a message to the device specified by id and the sector
+    /// specified by sector, as with [`Self::target_msg`], but
+    /// without assuming that either the message or the reply is text.
+    /// The message is NUL-terminated when sent, so it may not itself
+    /// contain any NUL bytes.
+    ///
+    /// The reply, if any, is returned exactly as the kernel provided
+    /// it, which normally includes a trailing NUL.
+    pub fn target_msg_raw(
+        &self,
+        id: &DevId<'_>,
+        sector: Option<u64>,
+        msg: &[u8],
+    ) -> DmResult<(DeviceInfo, Option<Vec<u8>>)> {
+        if msg.contains(&b'\0') {
+            return Err(DmError::InvalidArgument(
+                "target message contains a NUL byte".to_string(),
+            ));
+        }
+
         let mut hdr =
             DmFlags::default().to_ioctl_hdr(Some(id), DmFlags::empty())?;
 
@@ -755,32 +2493,116 @@ impl DM {
                 .to_vec()
         };
 
-        data_in.extend(msg.as_bytes());
+        data_in.extend(msg);
         data_in.push(b'\0');
 
         let (hdr_out, data_out) =
             self.do_ioctl(DmIoctlCmd::DM_TARGET_MSG, &mut hdr, Some(&data_in))?;
 
-        let output =
-            if (hdr_out.flags().bits() & DmFlags::DM_DATA_OUT.bits()) > 0 {
-                Some(
-                    str::from_utf8(&data_out[..data_out.len() - 1])
-                        .map(|res| res.to_string())
-                        .map_err(|_| {
-                            DmError::IoctlResultMalformed(
-                                "Message result was not UTF-8",
-                            )
-                        })?,
-                )
-            } else {
-                None
-            };
+        let output = if hdr_out.flags().contains(DmFlags::DM_DATA_OUT) {
+            Some(data_out)
+        } else {
+            None
+        };
         Ok((hdr_out, output))
     }
 
+    /// Start collecting I/O statistics for the sectors `range` of the
+    /// device, given as its first sector and length, or for the whole
+    /// device if `range` is `None`, counted separately for each area
+    /// of the region as given by `step`.  `program_id`, if given,
+    /// marks the region as belonging to a program, so that it can be
+    /// listed separately; it may not be empty, contain whitespace, or
+    /// be a number.
+    ///
+    /// Returns the id of the new region.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dm_ioctl::{stats::StatsStep, DevId, DmName, DM};
+    /// let dm = DM::new().unwrap();
+    /// let id = DevId::Name(DmName::new("example-dev").unwrap());
+    ///
+    /// let region_id = dm
+    ///     .stats_create(&id, None, StatsStep::Areas(1), Some("example"))
+    ///     .unwrap();
+    /// let regions = dm.stats_list(&id, Some("example")).unwrap();
+    /// assert_eq!(regions[0].region_id, region_id);
+    /// dm.stats_delete(&id, region_id).unwrap();
+    /// ```
+    pub fn stats_create(
+        &self,
+        id: &DevId<'_>,
+        range: Option<(u64, u64)>,
+        step: StatsStep,
+        program_id: Option<&str>,
+    ) -> DmResult<u64> {
+        let mut msg = match range {
+            Some((start, len)) => format!("@stats_create {start}+{len}"),
+            None => "@stats_create -".to_string(),
+        };
+        msg.push_str(&format!(" {step}"));
+        if let Some(program_id) = program_id {
+            check_program_id(program_id)?;
+            msg.push_str(&format!(" {program_id}"));
+        }
+
+        let (_, reply) = self.target_msg(id, None, &msg)?;
+        reply
+            .as_deref()
+            .map(str::trim)
+            .and_then(|reply| reply.parse().ok())
+            .ok_or(DmError::IoctlResultMalformed(
+                MalformedReason::MessageReplyInvalid,
+            ))
+    }
+
+    /// List the statistics regions of the device, or only those
+    /// created with `program_id` if it is given.
+    pub fn stats_list(
+        &self,
+        id: &DevId<'_>,
+        program_id: Option<&str>,
+    ) -> DmResult<Vec<StatsRegion>> {
+        let msg = match program_id {
+            Some(program_id) => {
+                check_program_id(program_id)?;
+                format!("@stats_list {program_id}")
+            }
+            None => "@stats_list".to_string(),
+        };
+        let (_, reply) = self.target_msg(id, None, &msg)?;
+        reply
+            .unwrap_or_default()
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::parse)
+            .collect()
+    }
+
+    /// The statistics counted for each area of the region `region_id`
+    /// of the device, with the sectors that each area covers.
+    pub fn stats_print(
+        &self,
+        id: &DevId<'_>,
+        region_id: u64,
+    ) -> DmResult<Vec<(RegionBounds, StatsCounters)>> {
+        let (_, reply) =
+            self.target_msg(id, None, &format!("@stats_print {region_id}"))?;
+        parse_print_output(&reply.unwrap_or_default())
+    }
+
+    /// Stop collecting statistics for the region `region_id` of the
+    /// device, and discard them.
+    pub fn stats_delete(&self, id: &DevId<'_>, region_id: u64) -> DmResult<()> {
+        self.target_msg(id, None, &format!("@stats_delete {region_id}"))?;
+        Ok(())
+    }
+
     /// If DM is being used to poll for events, once it indicates readiness it
     /// will continue to do so until we rearm it, which is what this method
-    /// does.
+    /// does.  See [`Self::poll_event`].
     pub fn arm_poll(&self) -> DmResult<DeviceInfo> {
         let mut hdr =
             DmFlags::default().to_ioctl_hdr(None, DmFlags::empty())?;
@@ -788,6 +2610,329 @@ impl DM {
         self.do_ioctl(DmIoctlCmd::DM_DEV_ARM_POLL, &mut hdr, None)
             .map(|(hdr, _)| hdr)
     }
+
+    /// Wait up to `timeout`, or indefinitely if `timeout` is `None`,
+    /// for any DM device to report an event.  Returns whether an
+    /// event was reported.  The control node's file descriptor can
+    /// also be polled directly, as part of a larger event loop.
+    ///
+    /// The descriptor is ready whenever an event has been reported
+    /// since the last [`Self::arm_poll`].  To watch for events:
+    ///
+    /// 1. Call `arm_poll`.
+    /// 2. Call `poll_event`, or poll the descriptor yourself.
+    /// 3. Once it is ready, call `arm_poll` again, *before* finding
+    ///    out which devices reported events (e.g. by comparing their
+    ///    event numbers from [`Self::list_devices_extended`] with the
+    ///    ones previously seen).  Rearming afterward would lose any
+    ///    event reported in between.
+    /// 4. Go back to step 2.
+    pub fn poll_event(&self, timeout: Option<Duration>) -> DmResult<bool> {
+        let deadline = timeout.and_then(|t| Instant::now().checked_add(t));
+        loop {
+            let timeout_ms = deadline.map_or(-1, |deadline| {
+                let remaining =
+                    deadline.saturating_duration_since(Instant::now());
+                let ms = (remaining.as_nanos() + 999_999) / 1_000_000;
+                cmp::min(ms, i32::MAX as u128) as i32
+            });
+            let mut pollfd = libc::pollfd {
+                fd: self.file.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            // SAFETY: pollfd is a single valid pollfd structure.
+            match unsafe { libc::poll(&mut pollfd, 1, timeout_ms) } {
+                -1 => {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(DmError::Poll(err));
+                    }
+                }
+                0 => return Ok(false),
+                _ => return Ok(pollfd.revents & libc::POLLIN != 0),
+            }
+        }
+    }
+
+    /// Issue the device-mapper ioctl `cmd` directly, for operations
+    /// this crate does not otherwise support.  Returns the DeviceInfo
+    /// from the header the kernel returned, and the data following
+    /// it.
+    ///
+    /// The header is filled in from `id`, if given, and `flags`,
+    /// which are passed to the kernel as they are, without the
+    /// masking that other methods apply.  `in_data` is appended to
+    /// the header verbatim; it must be laid out as the kernel expects
+    /// for `cmd`, including any alignment padding.  The header's
+    /// `event_nr` is always zero.
+    ///
+    /// As with every other method, the ioctl is refused with
+    /// [`DmError::UnsupportedKernel`] if the kernel is too old for
+    /// `cmd`, unless that check was disabled with
+    /// [`DmOpenOptions::check_kernel_version`].
+    ///
+    /// This is an escape hatch: prefer a typed method wherever one
+    /// exists.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use dm_ioctl::{DM, DevId, DmFlags, DmIoctlCmd, DmName};
+    ///
+    /// let dm = DM::new().unwrap();
+    ///
+    /// let name = DmName::new("example-dev").expect("is valid DM name");
+    /// let (info, _) = dm
+    ///     .ioctl_raw(
+    ///         DmIoctlCmd::DM_DEV_STATUS,
+    ///         Some(&DevId::Name(name)),
+    ///         DmFlags::empty(),
+    ///         None,
+    ///     )
+    ///     .unwrap();
+    /// ```
+    pub fn ioctl_raw(
+        &self,
+        cmd: DmIoctlCmd,
+        id: Option<&DevId<'_>>,
+        flags: DmFlags,
+        in_data: Option<&[u8]>,
+    ) -> DmResult<(DeviceInfo, Vec<u8>)> {
+        let mut hdr = flags.to_ioctl_hdr(id, flags)?;
+        self.do_ioctl(cmd, &mut hdr, in_data)
+    }
+}
+
+/// Whether the table that a request with `flags` asked about, active
+/// or inactive, is present according to `info`.  If it is not, the
+/// kernel's response says nothing about it.
+fn queried_table_present(info: &DeviceInfo, flags: DmFlags) -> bool {
+    if flags.contains(DmFlags::DM_QUERY_INACTIVE_TABLE) {
+        info.is_inactive_table_present()
+    } else {
+        info.is_active_table_present()
+    }
+}
+
+/// Convert the version reported by a `dm_ioctl` header back to the
+/// fields of that header.
+fn version_tuple(version: &Version) -> (u32, u32, u32) {
+    (
+        version
+            .major
+            .try_into()
+            .expect("dm_ioctl struct field is u32"),
+        version
+            .minor
+            .try_into()
+            .expect("dm_ioctl struct field is u32"),
+        version
+            .patch
+            .try_into()
+            .expect("dm_ioctl struct field is u32"),
+    )
+}
+
+/// Convert `path` to a C string for use with `*at` system calls.
+fn path_to_cstring(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+}
+
+/// Open `path` relative to the directory `dir`, with `openat(2)`.
+fn open_at(dir: RawFd, path: &Path, flags: i32) -> io::Result<File> {
+    let path = path_to_cstring(path)?;
+    // SAFETY: path is a valid C string, and dir is either AT_FDCWD
+    // or a file descriptor borrowed for the duration of the call.
+    let fd = unsafe { libc::openat(dir, path.as_ptr(), flags) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: openat just returned fd, so nothing else owns it.
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+/// Create the device-mapper control node at `path`, relative to the
+/// directory `dir`, along with its parent directory if necessary, the
+/// way libdevmapper does.
+fn create_control_node(dir: RawFd, path: &Path) -> DmResult<()> {
+    let dev = control_device_number()?;
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        let res = path_to_cstring(parent).and_then(|c_parent| {
+            // SAFETY: c_parent is a valid C string.
+            if unsafe { libc::mkdirat(dir, c_parent.as_ptr(), 0o755) } < 0 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(())
+            }
+        });
+        match res {
+            Err(err) if err.kind() != io::ErrorKind::AlreadyExists => {
+                return Err(DmError::ControlNodeCreation(
+                    parent.to_path_buf(),
+                    err,
+                ))
+            }
+            _ => (),
+        }
+    }
+
+    path_to_cstring(path)
+        .and_then(|c_path| {
+            let devno = libc::makedev(dev.major, dev.minor);
+            // SAFETY: c_path is a valid C string.
+            let res = unsafe {
+                libc::mknodat(
+                    dir,
+                    c_path.as_ptr(),
+                    libc::S_IFCHR | 0o600,
+                    devno,
+                )
+            };
+            if res < 0 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(())
+            }
+        })
+        .map_err(|err| DmError::ControlNodeCreation(path.to_path_buf(), err))
+}
+
+/// The path of the node for the device `name` in `dir`, refusing
+/// the name of the control node.
+fn mapper_node_path(dir: &Path, name: &DmName) -> DmResult<PathBuf> {
+    let path = dir.join(name.as_ref());
+    if path == Path::new(DM_CTL_PATH) {
+        return Err(DmError::InvalidArgument(format!(
+            "{} is the control node",
+            path.display()
+        )));
+    }
+    Ok(path)
+}
+
+/// Create a node of kind `kind` for `device` at `dir/name`, along
+/// with `dir` if necessary, replacing whatever was there unless it
+/// already matches.
+fn make_mapper_node(
+    dir: &Path,
+    name: &DmName,
+    device: Device,
+    kind: MapperNode,
+) -> DmResult<PathBuf> {
+    let path = mapper_node_path(dir, name)?;
+    let target = PathBuf::from(format!("../dm-{}", device.minor));
+
+    fs::create_dir_all(dir)
+        .map_err(|err| DmError::DeviceNode(dir.to_path_buf(), err))?;
+
+    match fs::symlink_metadata(&path) {
+        Ok(metadata) => {
+            let matches = match kind {
+                MapperNode::Symlink => {
+                    metadata.file_type().is_symlink()
+                        && fs::read_link(&path).ok().as_ref() == Some(&target)
+                }
+                MapperNode::BlockDevice => {
+                    metadata.file_type().is_block_device()
+                        && Device::from_kdev_t(metadata.rdev()) == device
+                }
+            };
+            if matches {
+                return Ok(path);
+            }
+            fs::remove_file(&path)
+                .map_err(|err| DmError::DeviceNode(path.clone(), err))?;
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+        Err(err) => return Err(DmError::DeviceNode(path, err)),
+    }
+
+    let res = match kind {
+        MapperNode::Symlink => unix_fs::symlink(&target, &path),
+        MapperNode::BlockDevice => path_to_cstring(&path).and_then(|c_path| {
+            let devno = libc::makedev(device.major, device.minor);
+            // SAFETY: c_path is a valid C string.
+            let res = unsafe {
+                libc::mknod(c_path.as_ptr(), libc::S_IFBLK | 0o600, devno)
+            };
+            if res < 0 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(())
+            }
+        }),
+    };
+    res.map_err(|err| DmError::DeviceNode(path.clone(), err))?;
+    Ok(path)
+}
+
+/// Remove the node for the device `name` in `dir`, if there is one.
+fn remove_mapper_node(dir: &Path, name: &DmName) -> DmResult<()> {
+    let path = mapper_node_path(dir, name)?;
+    match fs::remove_file(&path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => {
+            Err(DmError::DeviceNode(path, err))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Determine the device number of the device-mapper control node,
+/// from sysfs if possible, or else from `/proc/misc`.
+fn control_device_number() -> DmResult<Device> {
+    if let Ok(dev) = fs::read_to_string(DM_CTL_SYSFS_PATH) {
+        return dev.trim().parse().map_err(|_| {
+            DmError::NoControlDevice(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{DM_CTL_SYSFS_PATH} does not hold a device number"),
+            ))
+        });
+    }
+
+    let misc =
+        fs::read_to_string("/proc/misc").map_err(DmError::NoControlDevice)?;
+    parse_proc_misc(&misc).ok_or_else(|| {
+        DmError::NoControlDevice(io::Error::new(
+            io::ErrorKind::NotFound,
+            "device-mapper is not a registered misc device",
+        ))
+    })
+}
+
+/// Find the device-mapper control device in the contents of
+/// `/proc/misc`, which lists the minor numbers of misc devices.
+fn parse_proc_misc(misc: &str) -> Option<Device> {
+    misc.lines().find_map(|line| {
+        let (minor, name) = line.trim_start().split_once(' ')?;
+        (name.trim() == "device-mapper").then_some(())?;
+        Some(Device {
+            major: MISC_MAJOR,
+            minor: minor.parse().ok()?,
+        })
+    })
+}
+
+/// Read a native-endian `u32` from `buf` at `offset`.
+fn read_u32(buf: &[u8], offset: usize) -> DmResult<u32> {
+    buf.get(offset..offset + size_of::<u32>())
+        .and_then(|slc| slc.try_into().ok())
+        .map(u32::from_ne_bytes)
+        .ok_or(DmError::IoctlResultMalformed(
+            MalformedReason::SliceSizeMismatch,
+        ))
+}
+
+/// Read a native-endian `u64` from `buf` at `offset`.
+fn read_u64(buf: &[u8], offset: usize) -> DmResult<u64> {
+    buf.get(offset..offset + size_of::<u64>())
+        .and_then(|slc| slc.try_into().ok())
+        .map(u64::from_ne_bytes)
+        .ok_or(DmError::IoctlResultMalformed(
+            MalformedReason::SliceSizeMismatch,
+        ))
 }
 
 impl AsRawFd for DM {
@@ -5,19 +5,39 @@
 //! The error types used by all public interfaces.
 
 use core::fmt;
-use std::io;
+use std::{io, path::PathBuf};
 
 use crate::deviceinfo::DeviceInfo;
 use crate::ioctl_cmds::DmIoctlCmd;
 
+#[cfg(test)]
+#[path = "tests/errors.rs"]
+mod tests;
+
 #[derive(Debug)]
 #[non_exhaustive]
 /// Represents any kind of failure produced by this crate.
+///
+/// Errors compare equal if they are the same variant with equal
+/// contents, except that `io::Error`s are compared by kind only, and
+/// the request and response packets recorded by [`Self::Ioctl`] are
+/// ignored.
 pub enum DmError {
     /// Unable to create a DM context due to a system-level error,
     /// e.g. not allowed to open `/dev/mapper/control`.
     ContextInit(io::Error),
 
+    /// Unable to create the device-mapper control node, or its
+    /// parent directory, at the recorded path.  Typically this means
+    /// permission to create device nodes was denied.
+    ControlNodeCreation(PathBuf, io::Error),
+
+    /// Unable to determine the device number of the device-mapper
+    /// control node, which is needed to create it.  Typically this
+    /// means the kernel does not support device-mapper, e.g. because
+    /// the dm-mod module is not loaded.
+    NoControlDevice(io::Error),
+
     /// The empty string was provided as a device ID argument.
     DeviceIdEmpty,
 
@@ -29,6 +49,15 @@ pub enum DmError {
     /// in device IDs.
     DeviceIdHasBadChars,
 
+    /// Unable to inspect, create, or remove a device node, or to read
+    /// the kernel's list of devices, due to a system-level error.
+    /// Records the path of the file and the error.
+    DeviceNode(PathBuf, io::Error),
+
+    /// An argument to a function was invalid in some way that the
+    /// type system could not prevent; the string provides details.
+    InvalidArgument(String),
+
     /// A DM ioctl operation returned a system-level error.  Records
     /// the opcode, the system error code, and, if possible, decoded
     /// versions of the request and response packets, to facilitate
@@ -41,8 +70,8 @@ pub enum DmError {
     ),
 
     /// The kernel's response to a DM operation was malformed in
-    /// some way; the string provides details.
-    IoctlResultMalformed(&'static str),
+    /// some way; the [`MalformedReason`] says how.
+    IoctlResultMalformed(MalformedReason),
 
     /// The kernel's response to a DM operation is impossibly large;
     /// so large that the `data_size` field of the `dm_ioctl` header
@@ -51,9 +80,182 @@ pub enum DmError {
     /// that large.
     IoctlResultTooLarge,
 
+    /// Waiting for an event on the control node failed due to a
+    /// system-level error.
+    Poll(io::Error),
+
     /// We were unable to construct a DM request packet due to a
     /// system-level error.
     RequestConstruction(io::Error),
+
+    /// The kernel's device-mapper interface is too old to support a
+    /// DM operation, so it was not attempted.
+    /// See [`DmOpenOptions::check_kernel_version`].
+    ///
+    /// [`DmOpenOptions::check_kernel_version`]: crate::DmOpenOptions::check_kernel_version
+    UnsupportedKernel {
+        /// The opcode of the operation
+        cmd: DmIoctlCmd,
+        /// The interface version the operation requires
+        required: (u32, u32, u32),
+        /// The kernel's interface version
+        actual: (u32, u32, u32),
+    },
+
+    /// A target type is not supported by the kernel at the required
+    /// version.  Records the name of the target type, the required
+    /// version, and the kernel's version of it, if any.
+    TargetUnavailable(String, (u32, u32, u32), Option<(u32, u32, u32)>),
+
+    /// [`DM::device_rename_refreshed`] renamed a device, but then
+    /// failed to retrieve its DeviceInfo.  Records the error from
+    /// retrieving the DeviceInfo.  The device keeps its new name or
+    /// uuid.
+    ///
+    /// [`DM::device_rename_refreshed`]: crate::DM::device_rename_refreshed
+    RenameStatus(Box<DmError>),
+
+    /// [`DM::table_reload`][crate::DM::table_reload] failed.  Records
+    /// the step that failed and the error from that step.
+    TableReload(ReloadStep, Box<DmError>),
+}
+
+/// The error returned by [`DM::suspend_all`][crate::DM::suspend_all]
+/// and [`DM::resume_all`][crate::DM::resume_all] when the operation
+/// failed for at least one device.  Records what happened to each
+/// device, in the order the devices were given, so that the caller
+/// can undo the operation for the devices where it succeeded.
+#[derive(Debug)]
+pub struct BatchError {
+    /// What happened to each device.
+    pub outcomes: Vec<BatchOutcome>,
+}
+
+/// What happened to one device in a batch operation; see
+/// [`BatchError`].
+#[derive(Debug)]
+pub enum BatchOutcome {
+    /// The operation succeeded; records the resulting DeviceInfo.
+    Succeeded(DeviceInfo),
+    /// The operation failed.
+    Failed(DmError),
+    /// The operation was not attempted, because it failed for an
+    /// earlier device and the batch was set to stop on error.
+    Skipped,
+}
+
+impl BatchError {
+    /// The first error in the batch.
+    pub fn first_error(&self) -> Option<&DmError> {
+        self.outcomes.iter().find_map(|outcome| match outcome {
+            BatchOutcome::Failed(err) => Some(err),
+            _ => None,
+        })
+    }
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failed = self
+            .outcomes
+            .iter()
+            .filter(|outcome| matches!(outcome, BatchOutcome::Failed(_)))
+            .count();
+        write!(
+            f,
+            "batch operation failed for {failed} of {} devices",
+            self.outcomes.len()
+        )?;
+        if let Some(err) = self.first_error() {
+            write!(f, ": {err}")?;
+        }
+        Ok(())
+    }
+}
+
+impl core::error::Error for BatchError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        self.first_error().map(|err| err as _)
+    }
+}
+
+/// The ways in which the kernel's response to a DM operation can be
+/// malformed, for [`DmError::IoctlResultMalformed`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum MalformedReason {
+    /// A device name is not a NUL-terminated UTF-8 string.
+    NameInvalid,
+    /// A device uuid is not a NUL-terminated UTF-8 string.
+    UuidInvalid,
+    /// A target type name is not a NUL-terminated UTF-8 string.
+    TargetTypeInvalid,
+    /// A target's status or table parameters are not a
+    /// NUL-terminated UTF-8 string.
+    TargetParamsInvalid,
+    /// The reply to a target message is not UTF-8.
+    MessageNotUtf8,
+    /// A record in a list extends past the end of the buffer.
+    TruncatedRecord,
+    /// A record in a list gives an impossible offset for the next
+    /// record.
+    BadNextOffset,
+    /// A buffer is too short to hold a fixed-size field.
+    SliceSizeMismatch,
+    /// The tables of some DM devices refer to each other in a
+    /// cycle.
+    DependencyCycle,
+    /// The reply to a target message does not have the form expected
+    /// for the message sent.
+    MessageReplyInvalid,
+    /// A target's start sector plus its length does not fit in a
+    /// u64.
+    TargetExtentOverflow,
+}
+
+impl fmt::Display for MalformedReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::NameInvalid => "device name is not a valid C string",
+            Self::UuidInvalid => "device uuid is not a valid C string",
+            Self::TargetTypeInvalid => "target type is not a valid C string",
+            Self::TargetParamsInvalid => {
+                "target parameters are not a valid C string"
+            }
+            Self::MessageNotUtf8 => "message result is not valid UTF-8",
+            Self::TruncatedRecord => "record extends past end of buffer",
+            Self::BadNextOffset => "record has invalid next offset",
+            Self::SliceSizeMismatch => "buffer too short for field",
+            Self::DependencyCycle => "device dependencies form a cycle",
+            Self::MessageReplyInvalid => "message result has unexpected form",
+            Self::TargetExtentOverflow => "target extends past last sector",
+        })
+    }
+}
+
+impl core::error::Error for MalformedReason {}
+
+/// The steps of [`DM::table_reload`][crate::DM::table_reload], for
+/// reporting which one failed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ReloadStep {
+    /// Loading the new table into the inactive slot.
+    Load,
+    /// Suspending the device before swapping tables.
+    Suspend,
+    /// Resuming the device, which makes the new table active.
+    Resume,
+}
+
+impl fmt::Display for ReloadStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Load => "load",
+            Self::Suspend => "suspend",
+            Self::Resume => "resume",
+        })
+    }
 }
 
 impl fmt::Display for DmError {
@@ -62,6 +264,15 @@ impl fmt::Display for DmError {
             Self::ContextInit(err) => {
                 write!(f, "unable to initialize DM context: {err}")
             }
+            Self::ControlNodeCreation(path, err) => write!(
+                f,
+                "unable to create DM control node {}: {err}",
+                path.display()
+            ),
+            Self::NoControlDevice(err) => write!(
+                f,
+                "unable to find DM control device (no kernel support?): {err}"
+            ),
             Self::DeviceIdEmpty => {
                 write!(f, "device ID cannot be the empty string")
             },
@@ -71,21 +282,76 @@ impl fmt::Display for DmError {
             Self::DeviceIdHasBadChars => {
                 write!(f, "device ID contains NULs or non-ASCII chars")
             }
+            Self::DeviceNode(path, err) => {
+                write!(f, "unable to access {}: {err}", path.display())
+            }
+            Self::InvalidArgument(detail) => {
+                write!(f, "invalid argument: {detail}")
+            }
             Self::Ioctl(op, hdr_in, hdr_out, err) => write!(
                 f,
                 "DM operation {op:?} failed: input header: {hdr_in:?}, header result: {hdr_out:?}, error: {err}"
             ),
-            Self::IoctlResultMalformed(detail) => write!(
+            Self::IoctlResultMalformed(reason) => write!(
                 f,
-                "ioctl result packet is malformed (kernel bug?): {detail}"
+                "ioctl result packet is malformed (kernel bug?): {reason}"
             ),
             Self::IoctlResultTooLarge => write!(
                 f,
                 "ioctl result packet is impossibly large (probable bug)",
             ),
+            Self::Poll(err) => {
+                write!(f, "unable to wait for DM events: {err}")
+            }
             Self::RequestConstruction(err) => {
                 write!(f, "unable to construct ioctl request packet: {err}")
             }
+            Self::UnsupportedKernel {
+                cmd,
+                required: (a, b, c),
+                actual: (x, y, z),
+            } => write!(
+                f,
+                "DM operation {cmd:?} requires interface version {a}.{b}.{c}, \
+                 but the kernel only supports {x}.{y}.{z}"
+            ),
+            Self::TargetUnavailable(name, min, version) => {
+                let (a, b, c) = min;
+                write!(f, "DM target {name} version {a}.{b}.{c} is required")?;
+                match version {
+                    Some((a, b, c)) => {
+                        write!(f, ", but only {a}.{b}.{c} is available")
+                    }
+                    None => write!(f, ", but it is not available"),
+                }
+            }
+            Self::RenameStatus(err) => write!(
+                f,
+                "device was renamed, but retrieving its status failed: {err}"
+            ),
+            Self::TableReload(step, err) => {
+                write!(f, "table reload failed at {step} step: {err}")
+            }
+        }
+    }
+}
+
+impl DmError {
+    /// The system error code, if this is a [`DmError::Ioctl`]
+    /// error.
+    pub fn errno(&self) -> Option<nix::errno::Errno> {
+        match self {
+            Self::Ioctl(_, _, _, err) => Some(*err),
+            _ => None,
+        }
+    }
+
+    /// The opcode of the DM operation that failed, if this is a
+    /// [`DmError::Ioctl`] error.
+    pub fn ioctl_cmd(&self) -> Option<DmIoctlCmd> {
+        match self {
+            Self::Ioctl(op, _, _, _) => Some(*op),
+            _ => None,
         }
     }
 }
@@ -94,12 +360,73 @@ impl core::error::Error for DmError {
     fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
         match self {
             Self::ContextInit(err) => Some(err),
+            Self::ControlNodeCreation(_, err) => Some(err),
+            Self::NoControlDevice(err) => Some(err),
+            Self::DeviceNode(_, err) => Some(err),
             Self::Ioctl(_, _, _, err) => Some(err),
+            Self::IoctlResultMalformed(reason) => Some(reason),
+            Self::Poll(err) => Some(err),
             Self::RequestConstruction(err) => Some(err),
+            Self::RenameStatus(err) => Some(err),
+            Self::TableReload(_, err) => Some(err),
             _ => None,
         }
     }
 }
 
+impl PartialEq for DmError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::ContextInit(a), Self::ContextInit(b))
+            | (Self::NoControlDevice(a), Self::NoControlDevice(b))
+            | (Self::Poll(a), Self::Poll(b))
+            | (Self::RequestConstruction(a), Self::RequestConstruction(b)) => {
+                a.kind() == b.kind()
+            }
+            (
+                Self::ControlNodeCreation(a_path, a),
+                Self::ControlNodeCreation(b_path, b),
+            )
+            | (Self::DeviceNode(a_path, a), Self::DeviceNode(b_path, b)) => {
+                a_path == b_path && a.kind() == b.kind()
+            }
+            (Self::DeviceIdEmpty, Self::DeviceIdEmpty)
+            | (Self::DeviceIdHasBadChars, Self::DeviceIdHasBadChars)
+            | (Self::IoctlResultTooLarge, Self::IoctlResultTooLarge) => true,
+            (Self::DeviceIdTooLong(a0, a1), Self::DeviceIdTooLong(b0, b1)) => {
+                (a0, a1) == (b0, b1)
+            }
+            (Self::InvalidArgument(a), Self::InvalidArgument(b)) => a == b,
+            (Self::Ioctl(a_cmd, _, _, a), Self::Ioctl(b_cmd, _, _, b)) => {
+                a_cmd == b_cmd && a == b
+            }
+            (Self::IoctlResultMalformed(a), Self::IoctlResultMalformed(b)) => {
+                a == b
+            }
+            (
+                Self::UnsupportedKernel {
+                    cmd: a_cmd,
+                    required: a_req,
+                    actual: a,
+                },
+                Self::UnsupportedKernel {
+                    cmd: b_cmd,
+                    required: b_req,
+                    actual: b,
+                },
+            ) => (a_cmd, a_req, a) == (b_cmd, b_req, b),
+            (
+                Self::TargetUnavailable(a_name, a_req, a),
+                Self::TargetUnavailable(b_name, b_req, b),
+            ) => (a_name, a_req, a) == (b_name, b_req, b),
+            (Self::RenameStatus(a), Self::RenameStatus(b)) => a == b,
+            (Self::TableReload(a_step, a), Self::TableReload(b_step, b)) => {
+                a_step == b_step && a == b
+            }
+            _ => false,
+        }
+    }
+}
+
 /// Result specialization for DM functions.
 pub type DmResult<S> = Result<S, DmError>;
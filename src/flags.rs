@@ -74,7 +74,8 @@ bitflags! {
 
         /// In: Wipe all internal buffers before returning.
         /// Use when sending or requesting sensitive data, such as
-        /// encryption keys.
+        /// encryption keys.  The [`DM`][crate::DM] methods that accept
+        /// this flag also wipe the buffers they use themselves.
         const DM_SECURE_DATA          = 1 << 15;
 
         /// Out: True if a message generated output data.
@@ -97,8 +98,10 @@ bitflags! {
         const DM_IMA_MEASUREMENT      = 1 << 19;
     }
 
-    /// Flags in `struct dm_name_list`'s extended portion.  We don't
-    /// currently decode the extended portion but we may in the future.
+    /// Flags in `struct dm_name_list`'s extended portion, as reported
+    /// by [`DM::list_devices_extended`].
+    ///
+    /// [`DM::list_devices_extended`]: crate::DM::list_devices_extended
     #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
     pub struct DmNameListFlags: u32 {
         /// This extended name record includes a UUID.
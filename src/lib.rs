@@ -27,11 +27,9 @@
 //!
 //! Before they can be used, DM devices must be created using
 //! `DM::device_create()`, have a mapping table loaded using
-//! `DM::table_load()`, and then activated with
-//! `DM::device_suspend()`. (This function is used for both suspending
-//! and activating a device.) Once activated, they can be used as a
-//! regular block device, including having other DM devices map to
-//! them.
+//! `DM::table_load()`, and then activated with `DM::resume()`.
+//! Once activated, they can be used as a regular block device,
+//! including having other DM devices map to them.
 //!
 //! Devices have "active" and "inactive" mapping tables. See function
 //! descriptions for which table they affect.
@@ -51,7 +49,7 @@
 //! 2. Call `DM::list_devices()` and track the `event_nr`s for any DM devices
 //!    of interest.
 //! 3. `poll()` on the `DM`'s file descriptor, obtained by calling
-//!    `DM::file().as_raw_fd()`.
+//!    `DM::file().as_raw_fd()`, or call `DM::poll_event()`.
 //! 4. If the fd indicates activity, first clear the event by calling
 //!    `DM::arm_poll()`.  This must be done before event processing to ensure
 //!    events are not missed.
@@ -61,6 +59,11 @@
 //!    device. Handle the event(s). Update the list of last-seen `event_nr`s.
 //! 6. Optionally loop and re-invoke `poll()` on the fd to wait for more
 //!    events.
+//!
+//! # Optional Features
+//!
+//! * `serde`: implement `Serialize` and `Deserialize` for [`Device`],
+//!   [`DmNameBuf`], and [`DmUuidBuf`], as strings.
 
 #![allow(clippy::doc_markdown)]
 #![warn(missing_docs)]
@@ -83,8 +86,11 @@ mod test;
 
 // Modules that define public interfaces
 
+mod depgraph;
+pub use depgraph::DepGraph;
+
 mod device;
-pub use device::Device;
+pub use device::{dm_major, Device};
 
 mod deviceinfo;
 pub use deviceinfo::DeviceInfo;
@@ -93,7 +99,9 @@ mod dev_ids;
 pub use dev_ids::{DevId, DmName, DmNameBuf, DmUuid, DmUuidBuf};
 
 mod dm;
-pub use dm::DM;
+pub use dm::{
+    DepEntry, DeviceIter, DeviceListEntry, RawTargetStatus, RemovalOutcome, DM,
+};
 
 mod flags;
 pub use flags::{DmFlags, DmNameListFlags};
@@ -101,9 +109,22 @@ pub use flags::{DmFlags, DmNameListFlags};
 mod ioctl_cmds;
 pub use ioctl_cmds::DmIoctlCmd;
 
+mod options;
+pub use options::{
+    BatchMode, DmOpenOptions, DmOptions, MapperNode, ReloadOptions,
+    RemoveAllConfirmation, SuspendOptions,
+};
+
+mod table;
+pub use table::{TargetLine, TargetTable, TargetType, TargetTypeBuf};
+
 pub mod errors;
 pub use errors::{DmError, DmResult};
 
+pub mod stats;
+
+pub mod targets;
+
 /// The version number of this crate, which is equal to the API version
 /// number of the newest device-mapper API that it understands.
 pub const VERSION: semver::Version = semver::Version {
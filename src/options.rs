@@ -0,0 +1,277 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Option types for [`DM`][crate::DM] methods that would otherwise
+//! require the caller to assemble the right combination of
+//! [`DmFlags`] by hand.
+
+use std::{
+    os::unix::io::BorrowedFd,
+    path::{Path, PathBuf},
+};
+
+use crate::{dm::DM_CTL_PATH, flags::DmFlags};
+
+#[cfg(test)]
+#[path = "tests/options.rs"]
+mod tests;
+
+/// Options for [`DM::open_with`][crate::DM::open_with], which
+/// control how the device-mapper control node is opened.  The
+/// defaults match [`DM::new`][crate::DM::new].
+#[derive(Clone, Debug)]
+pub struct DmOpenOptions<'a> {
+    pub(crate) path: PathBuf,
+    pub(crate) dir: Option<BorrowedFd<'a>>,
+    pub(crate) cloexec: bool,
+    pub(crate) nonblock: bool,
+    pub(crate) create_control: bool,
+    pub(crate) check_kernel_version: bool,
+}
+
+impl Default for DmOpenOptions<'_> {
+    fn default() -> Self {
+        DmOpenOptions {
+            path: PathBuf::from(DM_CTL_PATH),
+            dir: None,
+            cloexec: true,
+            nonblock: false,
+            create_control: false,
+            check_kernel_version: true,
+        }
+    }
+}
+
+impl<'a> DmOpenOptions<'a> {
+    /// Open the control node at `path` instead of
+    /// `/dev/mapper/control`.  If `path` is relative, it is resolved
+    /// relative to the directory set with [`Self::dir`], or else the
+    /// current directory.
+    pub fn path(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.path = path.as_ref().to_path_buf();
+        self
+    }
+
+    /// Resolve a relative path set with [`Self::path`] relative to
+    /// the open directory `dir`, as `openat(2)` does.  For instance,
+    /// a directory file descriptor for another mount namespace's
+    /// `/dev` can be combined with the path `mapper/control`.
+    pub fn dir(&mut self, dir: BorrowedFd<'a>) -> &mut Self {
+        self.dir = Some(dir);
+        self
+    }
+
+    /// Whether to open the control node with `O_CLOEXEC`.  Defaults
+    /// to true.
+    pub fn cloexec(&mut self, cloexec: bool) -> &mut Self {
+        self.cloexec = cloexec;
+        self
+    }
+
+    /// Whether to open the control node with `O_NONBLOCK`.  Defaults
+    /// to false.
+    pub fn nonblock(&mut self, nonblock: bool) -> &mut Self {
+        self.nonblock = nonblock;
+        self
+    }
+
+    /// Whether to create the control node, and its parent directory,
+    /// if it does not exist, as libdevmapper does.  This is useful in
+    /// minimal environments where device-mapper is available but
+    /// nothing has created the node.  Defaults to false.
+    ///
+    /// The node's device number is read from sysfs, or failing that
+    /// from `/proc/misc`; if neither reports it,
+    /// [`DmError::NoControlDevice`] is returned.  If the node or its
+    /// directory cannot be created, [`DmError::ControlNodeCreation`]
+    /// is returned.
+    ///
+    /// [`DmError::NoControlDevice`]: crate::DmError::NoControlDevice
+    /// [`DmError::ControlNodeCreation`]: crate::DmError::ControlNodeCreation
+    pub fn create_control(&mut self, create_control: bool) -> &mut Self {
+        self.create_control = create_control;
+        self
+    }
+
+    /// Whether to check that the kernel's device-mapper interface
+    /// version is new enough for each operation before issuing it,
+    /// returning [`DmError::UnsupportedKernel`] if it is not.
+    /// Defaults to true.  Disable this for kernels that report an old
+    /// version but have had newer operations backported.
+    ///
+    /// [`DmError::UnsupportedKernel`]: crate::DmError::UnsupportedKernel
+    pub fn check_kernel_version(&mut self, check: bool) -> &mut Self {
+        self.check_kernel_version = check;
+        self
+    }
+}
+
+/// Options for the [`DM`][crate::DM] methods that accept either
+/// [`DmFlags`] or `DmOptions`: [`device_create`],
+/// [`device_create_with_table`], [`device_remove`], [`table_load`],
+/// and [`device_suspend`].
+///
+/// Unlike a bare `DmFlags`, `DmOptions` can only express flags that
+/// a caller can meaningfully set; output-only flags, and flags that
+/// other methods set themselves, are not available.  Each method
+/// still ignores the options that are irrelevant to it.
+///
+/// There is deliberately no way to set a udev cookie: this crate
+/// does not interact with udev.
+///
+/// # Example
+///
+/// ```no_run
+/// use dm_ioctl::{DM, DmName, DmOptions};
+/// let dm = DM::new().unwrap();
+///
+/// let name = DmName::new("example-dev").expect("is valid DM name");
+/// let options = DmOptions::default().read_only(true);
+/// dm.device_create(name, None, options).unwrap();
+/// ```
+///
+/// [`device_create`]: crate::DM::device_create
+/// [`device_create_with_table`]: crate::DM::device_create_with_table
+/// [`device_remove`]: crate::DM::device_remove
+/// [`table_load`]: crate::DM::table_load
+/// [`device_suspend`]: crate::DM::device_suspend
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DmOptions {
+    flags: DmFlags,
+}
+
+impl DmOptions {
+    /// Make the device, or the table being loaded, read-only
+    /// (`DM_READONLY`).
+    pub fn read_only(mut self, value: bool) -> Self {
+        self.flags.set(DmFlags::DM_READONLY, value);
+        self
+    }
+
+    /// If the device is in use, schedule it for removal once it is
+    /// no longer used, rather than failing (`DM_DEFERRED_REMOVE`).
+    pub fn deferred_remove(mut self, value: bool) -> Self {
+        self.flags.set(DmFlags::DM_DEFERRED_REMOVE, value);
+        self
+    }
+
+    /// Suspend the device, rather than resuming it (`DM_SUSPEND`).
+    pub fn suspend(mut self, value: bool) -> Self {
+        self.flags.set(DmFlags::DM_SUSPEND, value);
+        self
+    }
+
+    /// When suspending, do not flush queued I/O first
+    /// (`DM_NOFLUSH`).
+    pub fn noflush(mut self, value: bool) -> Self {
+        self.flags.set(DmFlags::DM_NOFLUSH, value);
+        self
+    }
+
+    /// When suspending, do not attempt to freeze any filesystem
+    /// backed by the device (`DM_SKIP_LOCKFS`).
+    pub fn skip_lockfs(mut self, value: bool) -> Self {
+        self.flags.set(DmFlags::DM_SKIP_LOCKFS, value);
+        self
+    }
+
+    /// Have the kernel wipe its buffers after handling the request,
+    /// because it contains sensitive data such as an encryption key
+    /// (`DM_SECURE_DATA`).
+    pub fn secure_data(mut self, value: bool) -> Self {
+        self.flags.set(DmFlags::DM_SECURE_DATA, value);
+        self
+    }
+
+    /// The flags these options correspond to.
+    pub fn flags(self) -> DmFlags {
+        self.flags
+    }
+}
+
+impl From<DmOptions> for DmFlags {
+    fn from(options: DmOptions) -> DmFlags {
+        options.flags
+    }
+}
+
+/// Options for [`DM::suspend`][crate::DM::suspend].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SuspendOptions {
+    /// Do not flush queued I/O before suspending
+    /// (`DM_NOFLUSH`).
+    pub noflush: bool,
+    /// Do not attempt to freeze any filesystem backed by the device
+    /// (`DM_SKIP_LOCKFS`).
+    pub skip_lockfs: bool,
+}
+
+impl SuspendOptions {
+    /// The flags to pass to `DM_DEV_SUSPEND` to suspend a device
+    /// with these options.
+    pub(crate) fn to_flags(self) -> DmFlags {
+        let mut flags = DmFlags::DM_SUSPEND;
+        flags.set(DmFlags::DM_NOFLUSH, self.noflush);
+        flags.set(DmFlags::DM_SKIP_LOCKFS, self.skip_lockfs);
+        flags
+    }
+}
+
+/// What [`DM::suspend_all`][crate::DM::suspend_all] and
+/// [`DM::resume_all`][crate::DM::resume_all] do when the operation
+/// fails for one of the devices.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum BatchMode {
+    /// Leave the remaining devices alone.
+    #[default]
+    StopOnError,
+    /// Carry on with the remaining devices.
+    ContinueOnError,
+}
+
+/// What kind of node [`DM::mknod`][crate::DM::mknod] creates under
+/// `/dev/mapper`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum MapperNode {
+    /// A symbolic link to `../dm-<minor>`, as udev creates.  This
+    /// relies on something else, such as devtmpfs, providing
+    /// `/dev/dm-<minor>`.
+    #[default]
+    Symlink,
+    /// A block device node, as `dmsetup mknodes` creates.
+    BlockDevice,
+}
+
+/// Options for [`DM::table_reload`][crate::DM::table_reload].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ReloadOptions {
+    /// Explicitly suspend the device after loading the new table
+    /// and before resuming it.  (Resuming a device with a new table
+    /// suspends it internally in any case; this allows the suspend
+    /// to be made with `noflush`.)
+    pub suspend: bool,
+    /// If `suspend` is set, do not flush queued I/O before
+    /// suspending (`DM_NOFLUSH`).
+    pub noflush: bool,
+    /// Load the new table read-only (`DM_READONLY`).
+    pub read_only: bool,
+    /// The new table contains sensitive data such as an encryption
+    /// key, so have the kernel and this library wipe their buffers
+    /// after loading it (`DM_SECURE_DATA`).
+    pub secure_data: bool,
+}
+
+/// Required argument to [`DM::remove_all`][crate::DM::remove_all],
+/// to make it harder to call by accident.
+///
+/// `remove_all` removes *every* device-mapper device on the system,
+/// including ones that the calling program did not create, such as
+/// LVM volumes holding mounted filesystems.  Consider using
+/// [`DM::remove_all_matching`][crate::DM::remove_all_matching]
+/// instead.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RemoveAllConfirmation {
+    /// Yes, really remove every device-mapper device on the system.
+    YesReallyRemoveEverything,
+}
@@ -0,0 +1,263 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Types for DM statistics, which count I/O to regions of a DM
+//! device, divided into areas.  Statistics are managed with messages
+//! to the device; see [`DM::stats_create`], [`DM::stats_list`],
+//! [`DM::stats_print`], and [`DM::stats_delete`].
+//!
+//! [`DM::stats_create`]: crate::DM::stats_create
+//! [`DM::stats_list`]: crate::DM::stats_list
+//! [`DM::stats_print`]: crate::DM::stats_print
+//! [`DM::stats_delete`]: crate::DM::stats_delete
+
+use core::{fmt, str::FromStr};
+
+use crate::errors::{DmError, DmResult, MalformedReason};
+
+#[cfg(test)]
+#[path = "tests/stats.rs"]
+mod tests;
+
+/// How a statistics region is divided into areas, each of which is
+/// counted separately.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StatsStep {
+    /// Areas of this many sectors, the last of which may be shorter
+    AreaSize(u64),
+    /// This many areas of equal size (`/<areas>`)
+    Areas(u64),
+}
+
+impl fmt::Display for StatsStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StatsStep::AreaSize(sectors) => write!(f, "{sectors}"),
+            StatsStep::Areas(areas) => write!(f, "/{areas}"),
+        }
+    }
+}
+
+/// A statistics region of a device, as listed by
+/// [`DM::stats_list`][crate::DM::stats_list].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StatsRegion {
+    /// The region's id
+    pub region_id: u64,
+    /// The first sector of the region
+    pub start: u64,
+    /// The number of sectors in the region
+    pub length: u64,
+    /// The number of sectors in each area of the region
+    pub step: u64,
+    /// The id of the program that created the region, or `-`
+    pub program_id: String,
+    /// Data that the program stored with the region, or `-`
+    pub aux_data: String,
+    /// Whether the region counts time in nanoseconds rather than
+    /// milliseconds
+    pub precise_timestamps: bool,
+    /// The boundaries of the latency histogram, if the region has
+    /// one
+    pub histogram: Vec<u64>,
+}
+
+/// Parses one line of the reply to `@stats_list`:
+/// `<region_id>: <start>+<length> <step> <program_id> <aux_data>
+/// [precise_timestamps] [histogram:<boundary>,...]`.
+impl FromStr for StatsRegion {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<StatsRegion> {
+        let bad = || {
+            DmError::IoctlResultMalformed(MalformedReason::MessageReplyInvalid)
+        };
+
+        let fields = s.split_whitespace().collect::<Vec<_>>();
+        let [region_id, range, step, program_id, aux_data, ref rest @ ..] =
+            fields[..]
+        else {
+            return Err(bad());
+        };
+        let RegionBounds { start, length } = range.parse()?;
+
+        let mut precise_timestamps = false;
+        let mut histogram = Vec::new();
+        for option in rest {
+            match option.split_once(':') {
+                None if *option == "precise_timestamps" => {
+                    precise_timestamps = true;
+                }
+                Some(("histogram", boundaries)) => {
+                    histogram = boundaries
+                        .split(',')
+                        .map(parse_counter)
+                        .collect::<DmResult<_>>()?;
+                }
+                _ => return Err(bad()),
+            }
+        }
+
+        Ok(StatsRegion {
+            region_id: parse_counter(
+                region_id.strip_suffix(':').ok_or_else(bad)?,
+            )?,
+            start,
+            length,
+            step: parse_counter(step)?,
+            program_id: program_id.to_string(),
+            aux_data: aux_data.to_string(),
+            precise_timestamps,
+            histogram,
+        })
+    }
+}
+
+/// The sectors counted by one area of a statistics region, as
+/// reported by [`DM::stats_print`][crate::DM::stats_print].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RegionBounds {
+    /// The first sector of the area
+    pub start: u64,
+    /// The number of sectors in the area
+    pub length: u64,
+}
+
+/// Parses `<start>+<length>`.
+impl FromStr for RegionBounds {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<RegionBounds> {
+        let (start, length) = s.split_once('+').ok_or(
+            DmError::IoctlResultMalformed(MalformedReason::MessageReplyInvalid),
+        )?;
+        Ok(RegionBounds {
+            start: parse_counter(start)?,
+            length: parse_counter(length)?,
+        })
+    }
+}
+
+/// The I/O counted in one area of a statistics region.  The first
+/// eleven counters have the same meaning as the fields of
+/// `/proc/diskstats`.  Times are in milliseconds, or in nanoseconds
+/// if the region was created with precise timestamps.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct StatsCounters {
+    /// The number of reads completed
+    pub reads: u64,
+    /// The number of reads merged with adjacent reads
+    pub read_merges: u64,
+    /// The number of sectors read
+    pub read_sectors: u64,
+    /// The time spent on reads
+    pub read_time: u64,
+    /// The number of writes completed
+    pub writes: u64,
+    /// The number of writes merged with adjacent writes
+    pub write_merges: u64,
+    /// The number of sectors written
+    pub write_sectors: u64,
+    /// The time spent on writes
+    pub write_time: u64,
+    /// The number of I/Os in progress
+    pub in_flight: u64,
+    /// The time during which I/O was in progress (`io_ticks`)
+    pub io_ticks: u64,
+    /// The time spent on I/O, weighted by the number of I/Os in
+    /// progress (`time_in_queue`)
+    pub time_in_queue: u64,
+    /// The time during which reads were in progress
+    pub read_ticks: u64,
+    /// The time during which writes were in progress
+    pub write_ticks: u64,
+    /// The number of I/Os whose latency fell before, between and
+    /// after the region's histogram boundaries, if the region has a
+    /// histogram
+    pub histogram: Vec<u64>,
+}
+
+impl StatsCounters {
+    /// Parses the counters of one line of the reply to
+    /// `@stats_print`, that is, the fields that follow
+    /// `<start>+<length>`.
+    pub fn parse_line(line: &str) -> DmResult<StatsCounters> {
+        let bad = || {
+            DmError::IoctlResultMalformed(MalformedReason::MessageReplyInvalid)
+        };
+        let mut fields = line.split_whitespace();
+        let mut next = || fields.next().ok_or_else(bad).and_then(parse_counter);
+        let mut counters = StatsCounters {
+            reads: next()?,
+            read_merges: next()?,
+            read_sectors: next()?,
+            read_time: next()?,
+            writes: next()?,
+            write_merges: next()?,
+            write_sectors: next()?,
+            write_time: next()?,
+            in_flight: next()?,
+            io_ticks: next()?,
+            time_in_queue: next()?,
+            read_ticks: next()?,
+            write_ticks: next()?,
+            histogram: Vec::new(),
+        };
+        if let Some(histogram) = fields.next() {
+            counters.histogram = histogram
+                .split(':')
+                .map(parse_counter)
+                .collect::<DmResult<_>>()?;
+        }
+        if fields.next().is_some() {
+            return Err(bad());
+        }
+        Ok(counters)
+    }
+}
+
+/// Parses the reply to `@stats_print`, with one line of the form
+/// `<start>+<length> <counter> ...` for each area of the region.
+pub fn parse_print_output(
+    output: &str,
+) -> DmResult<Vec<(RegionBounds, StatsCounters)>> {
+    output
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (bounds, counters) = line.trim_start().split_once(' ').ok_or(
+                DmError::IoctlResultMalformed(
+                    MalformedReason::MessageReplyInvalid,
+                ),
+            )?;
+            Ok((bounds.parse()?, StatsCounters::parse_line(counters)?))
+        })
+        .collect()
+}
+
+/// Parses one number in a statistics message reply.
+fn parse_counter(field: &str) -> DmResult<u64> {
+    field.parse().map_err(|_| {
+        DmError::IoctlResultMalformed(MalformedReason::MessageReplyInvalid)
+    })
+}
+
+/// Checks that `program_id` can be sent as a single argument of a
+/// statistics message.  It also may not be a number, which the kernel
+/// would take as the count of optional arguments that precedes the
+/// program id in `@stats_create`.
+pub(crate) fn check_program_id(program_id: &str) -> DmResult<()> {
+    if program_id.is_empty() || program_id.contains(char::is_whitespace) {
+        return Err(DmError::InvalidArgument(format!(
+            "stats program id {program_id:?} is empty or contains whitespace"
+        )));
+    }
+    let digits = program_id.strip_prefix(['+', '-']).unwrap_or(program_id);
+    if !digits.is_empty() && digits.bytes().all(|c| c.is_ascii_digit()) {
+        return Err(DmError::InvalidArgument(format!(
+            "stats program id {program_id:?} is a number"
+        )));
+    }
+    Ok(())
+}
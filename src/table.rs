@@ -0,0 +1,432 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Construction of device-mapper tables.
+
+use core::{fmt, iter::FromIterator, mem, slice};
+use std::vec;
+
+use crate::{
+    bindings::DM_MAX_TYPE_NAME,
+    dev_ids::{DevIdStr, DevIdString},
+    errors::{DmError, DmResult},
+    targets::{ErrorTargetParams, TargetParams, ZeroTargetParams},
+    util::zero_string,
+};
+
+#[cfg(test)]
+#[path = "tests/table.rs"]
+mod tests;
+
+/// A borrowed target type name, such as `linear`.  Target type names
+/// are subject to the same rules as device IDs, with a length limit
+/// of 15 bytes (`DM_MAX_TYPE_NAME` less the C-string terminator).
+pub type TargetType = DevIdStr<DM_MAX_TYPE_NAME>;
+/// An owned target type name.  See [`TargetType`].
+pub type TargetTypeBuf = DevIdString<DM_MAX_TYPE_NAME>;
+
+/// One line of a device-mapper table: a target, and the range of
+/// sectors of the DM device that it maps.
+///
+/// `Display` produces the line in the format used by `dmsetup
+/// table`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TargetLine {
+    /// First sector of the DM device mapped by this line
+    pub start: u64,
+    /// Number of sectors mapped by this line
+    pub length: u64,
+    /// The type of the target
+    pub target_type: TargetTypeBuf,
+    /// The target's parameters, or, for lines returned by
+    /// [`DM::table_status`] without `DM_STATUS_TABLE`, its status
+    ///
+    /// [`DM::table_status`]: crate::DM::table_status
+    pub params: String,
+}
+
+impl TargetLine {
+    /// Make a line for a target of type `target_type`, covering
+    /// `length` sectors from `start`.  Fails if `target_type` is not
+    /// a valid [`TargetType`].
+    pub fn new(
+        start: u64,
+        length: u64,
+        target_type: &str,
+        params: &str,
+    ) -> DmResult<TargetLine> {
+        Ok(TargetLine {
+            start,
+            length,
+            target_type: TargetTypeBuf::new(target_type.to_string())?,
+            params: params.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for TargetLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.start, self.length, &*self.target_type)?;
+        if !self.params.is_empty() {
+            write!(f, " {}", self.params)?;
+        }
+        Ok(())
+    }
+}
+
+/// Converts from `(sector_start, sector_length, type, params)`, the
+/// form that table lines took in earlier versions of this crate.
+impl TryFrom<(u64, u64, String, String)> for TargetLine {
+    type Error = DmError;
+
+    fn try_from(
+        (start, length, target_type, params): (u64, u64, String, String),
+    ) -> DmResult<TargetLine> {
+        Ok(TargetLine {
+            start,
+            length,
+            target_type: TargetTypeBuf::new(target_type)?,
+            params,
+        })
+    }
+}
+
+/// Converts to `(sector_start, sector_length, type, params)`, the
+/// form that table lines took in earlier versions of this crate.
+impl From<TargetLine> for (u64, u64, String, String) {
+    fn from(line: TargetLine) -> (u64, u64, String, String) {
+        (
+            line.start,
+            line.length,
+            line.target_type.to_string(),
+            line.params,
+        )
+    }
+}
+
+/// Builder for the table argument of [`DM::table_load`].
+///
+/// Each target added with [`Self::push`] is appended directly after
+/// the previous one, so the table is contiguous and starts at sector
+/// 0; the caller supplies only the length of each target.  A table
+/// can also be collected from [`TargetLine`]s, such as those returned
+/// by [`DM::table_status`], in which case it is up to the caller to
+/// lay them out correctly; see [`Self::check_layout`].
+///
+/// `Display` produces the table in the format used by `dmsetup
+/// table`, one line per target.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::TargetTable;
+///
+/// let mut table = TargetTable::new();
+/// table.push(2048, "linear", "8:16 0").unwrap();
+/// table.push(2048, "linear", "8:32 0").unwrap();
+/// assert_eq!(table.total_length(), 4096);
+/// assert_eq!(
+///     table.to_string(),
+///     "0 2048 linear 8:16 0\n\
+///      2048 2048 linear 8:32 0\n",
+/// );
+///
+/// let targets = table.into_targets();
+/// assert_eq!(targets[1].start, 2048);
+/// ```
+///
+/// [`DM::table_load`]: crate::DM::table_load
+/// [`DM::table_status`]: crate::DM::table_status
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TargetTable {
+    targets: Vec<TargetLine>,
+}
+
+impl TargetTable {
+    /// Create an empty table.
+    pub fn new() -> TargetTable {
+        TargetTable::default()
+    }
+
+    /// A table with a single `error` target, `length` sectors long,
+    /// which fails all I/O to the device.  Fails as [`Self::push`]
+    /// does if `length` is 0.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dm_ioctl::TargetTable;
+    ///
+    /// let table = TargetTable::error_table(2048).unwrap();
+    /// assert_eq!(table.to_string(), "0 2048 error\n");
+    /// ```
+    pub fn error_table(length: u64) -> DmResult<TargetTable> {
+        TargetTable::single(length, &ErrorTargetParams)
+    }
+
+    /// A table with a single `zero` target, `length` sectors long,
+    /// which reads as zeros and discards writes.  Fails as
+    /// [`Self::push`] does if `length` is 0.
+    pub fn zero_table(length: u64) -> DmResult<TargetTable> {
+        TargetTable::single(length, &ZeroTargetParams)
+    }
+
+    /// A table with a single target of the type given by `params`.
+    fn single<T: TargetParams>(
+        length: u64,
+        params: &T,
+    ) -> DmResult<TargetTable> {
+        let mut table = TargetTable::new();
+        table.push_typed(length, params)?;
+        Ok(table)
+    }
+
+    /// Append a target of type `target_type`, `length` sectors long,
+    /// with parameters `params`.
+    ///
+    /// Fails, as [`TargetLine::new`] does, if `target_type` is not a
+    /// valid [`TargetType`]; fails also if `length` is 0, or if the
+    /// table would extend past the largest possible sector number.
+    pub fn push(
+        &mut self,
+        length: u64,
+        target_type: &str,
+        params: &str,
+    ) -> DmResult<()> {
+        self.push_line(length, target_type, params.to_string())
+    }
+
+    /// Append a target, `length` sectors long, with the type and
+    /// parameters given by `params`.  Fails as [`Self::push`] does.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dm_ioctl::{targets::LinearTargetParams, Device, TargetTable};
+    ///
+    /// let mut table = TargetTable::new();
+    /// let dest = Device { major: 8, minor: 16 };
+    /// table
+    ///     .push_typed(2048, &LinearTargetParams { dest, offset: 0 })
+    ///     .unwrap();
+    /// assert_eq!(table.targets()[0].params, "8:16 0");
+    /// ```
+    pub fn push_typed<T: TargetParams>(
+        &mut self,
+        length: u64,
+        params: &T,
+    ) -> DmResult<()> {
+        self.push_line(length, params.target_type(), params.param_str())
+    }
+
+    /// Append a target, taking ownership of `params` so that no copy
+    /// of it is left behind; on failure, `params` is wiped.
+    fn push_line(
+        &mut self,
+        length: u64,
+        target_type: &str,
+        mut params: String,
+    ) -> DmResult<()> {
+        let start = self.total_length();
+        let target_type =
+            TargetTypeBuf::new(target_type.to_string()).and_then(|ty| {
+                if length == 0 {
+                    return Err(DmError::InvalidArgument(format!(
+                        "target at sector {start} has length 0"
+                    )));
+                }
+                start.checked_add(length).map(|_| ty).ok_or_else(|| {
+                    DmError::InvalidArgument(format!(
+                        "table length overflows: {start} + {length} sectors"
+                    ))
+                })
+            });
+        match target_type {
+            Ok(target_type) => {
+                self.targets.push(TargetLine {
+                    start,
+                    length,
+                    target_type,
+                    params,
+                });
+                Ok(())
+            }
+            Err(err) => {
+                zero_string(&mut params);
+                Err(err)
+            }
+        }
+    }
+
+    /// The total length of the table, in sectors.
+    ///
+    /// This is where the last target ends, which is only the total
+    /// length if the table passes [`Self::check_layout`].  If the
+    /// last target's start plus its length does not fit in a u64,
+    /// the result saturates at `u64::MAX`.
+    pub fn total_length(&self) -> u64 {
+        self.targets
+            .last()
+            .map_or(0, |line| line.start.saturating_add(line.length))
+    }
+
+    /// The targets, in the form accepted by [`DM::table_load`].
+    ///
+    /// [`DM::table_load`]: crate::DM::table_load
+    pub fn targets(&self) -> &[TargetLine] {
+        &self.targets
+    }
+
+    /// The targets, for modification in place; for instance, to
+    /// change the parameters of a table read back with
+    /// [`DM::table_status`] before loading it again.  Use
+    /// [`Self::check_layout`] afterwards if the starts or lengths
+    /// were changed.
+    ///
+    /// [`DM::table_status`]: crate::DM::table_status
+    pub fn targets_mut(&mut self) -> &mut [TargetLine] {
+        &mut self.targets
+    }
+
+    /// Check that the targets are laid out as the kernel requires:
+    /// the first starts at sector 0, each of the others starts where
+    /// the previous one ends, and none is empty.  This always holds
+    /// for a table built with [`Self::push`], but not necessarily for
+    /// one collected from [`TargetLine`]s.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use dm_ioctl::{TargetLine, TargetTable};
+    ///
+    /// let table: TargetTable = vec![
+    ///     TargetLine::new(0, 2048, "zero", "").unwrap(),
+    ///     TargetLine::new(4096, 2048, "zero", "").unwrap(),
+    /// ]
+    /// .into();
+    /// assert!(table.check_layout().is_err());
+    /// ```
+    pub fn check_layout(&self) -> DmResult<()> {
+        check_table_layout(&self.targets)
+    }
+
+    /// Whether any target in the table is of a type whose parameters
+    /// normally include key material, i.e. `crypt` or `integrity`.
+    /// [`DM::table_load_typed`] sets `DM_SECURE_DATA` when loading
+    /// such a table.
+    ///
+    /// This is a heuristic; it does not inspect the parameters, and
+    /// it knows nothing about out-of-tree target types.
+    ///
+    /// [`DM::table_load_typed`]: crate::DM::table_load_typed
+    pub fn contains_sensitive_params(&self) -> bool {
+        self.targets.iter().any(|line| {
+            SENSITIVE_TARGET_TYPES.contains(&line.target_type.as_ref())
+        })
+    }
+
+    /// Convert into the form accepted by [`DM::table_load`].
+    ///
+    /// The table's targets are moved out unchanged, so the parameters
+    /// of any `crypt` or `integrity` targets are no longer wiped when
+    /// the table is dropped; the caller now owns any key material
+    /// they hold, and must wipe it.
+    ///
+    /// [`DM::table_load`]: crate::DM::table_load
+    pub fn into_targets(mut self) -> Vec<TargetLine> {
+        mem::take(&mut self.targets)
+    }
+}
+
+impl fmt::Display for TargetTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.targets {
+            writeln!(f, "{line}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Wipes the parameters of any targets that normally include key
+/// material; see [`TargetTable::contains_sensitive_params`].
+impl Drop for TargetTable {
+    fn drop(&mut self) {
+        for line in &mut self.targets {
+            if SENSITIVE_TARGET_TYPES.contains(&line.target_type.as_ref()) {
+                zero_string(&mut line.params);
+            }
+        }
+    }
+}
+
+impl From<Vec<TargetLine>> for TargetTable {
+    fn from(targets: Vec<TargetLine>) -> Self {
+        TargetTable { targets }
+    }
+}
+
+impl FromIterator<TargetLine> for TargetTable {
+    fn from_iter<I: IntoIterator<Item = TargetLine>>(iter: I) -> Self {
+        TargetTable {
+            targets: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// Moves the targets out as [`TargetTable::into_targets`] does, so
+/// the caller owns, and must wipe, any key material in their
+/// parameters.
+impl IntoIterator for TargetTable {
+    type Item = TargetLine;
+    type IntoIter = vec::IntoIter<TargetLine>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_targets().into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a TargetTable {
+    type Item = &'a TargetLine;
+    type IntoIter = slice::Iter<'a, TargetLine>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.targets.iter()
+    }
+}
+
+/// Target types whose parameters normally include key material.
+const SENSITIVE_TARGET_TYPES: &[&str] = &["crypt", "integrity"];
+
+/// Returns an error describing the first problem with the layout of
+/// `targets`: each must have a nonzero length, the first must start
+/// at sector 0, and each of the others must start where the previous
+/// one ends.
+pub(crate) fn check_table_layout(targets: &[TargetLine]) -> DmResult<()> {
+    let mut next_start = 0u64;
+    for (i, line) in targets.iter().enumerate() {
+        let TargetLine { start, length, .. } = *line;
+        if start != next_start {
+            return Err(DmError::InvalidArgument(if i == 0 {
+                format!("target 0 starts at sector {start}, not sector 0")
+            } else {
+                format!(
+                    "target {i} starts at sector {start}, but target {} \
+                     ends at sector {next_start}",
+                    i - 1
+                )
+            }));
+        }
+        if length == 0 {
+            return Err(DmError::InvalidArgument(format!(
+                "target {i} at sector {start} has length 0"
+            )));
+        }
+        next_start = start.checked_add(length).ok_or_else(|| {
+            DmError::InvalidArgument(format!(
+                "target {i} overflows: {start} + {length} sectors"
+            ))
+        })?;
+    }
+    Ok(())
+}
@@ -0,0 +1,273 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Typed representations of the tables and parameters of specific
+//! device-mapper targets.
+//!
+//! [`DM::table_load`][crate::DM::table_load] and
+//! [`DM::table_status`][crate::DM::table_status] work with tables as
+//! lists of [`TargetLine`]s, in which `params` is an opaque string
+//! whose syntax depends on the target type.  The types in this
+//! module know the syntax for particular target types, and convert
+//! between those strings and structured values.
+//!
+//! The `...TargetParams` types represent just the `params` string,
+//! and implement [`TargetParams`], so that they can be added to a
+//! [`TargetTable`] with [`TargetTable::push_typed`].
+//! [`TypedTargetLine`] represents a whole table line with typed
+//! parameters.
+
+use core::str::FromStr;
+
+use crate::{
+    errors::{DmError, DmResult},
+    table::{TargetLine, TargetTypeBuf},
+    util::zero_bytes,
+};
+
+#[cfg(doc)]
+use crate::TargetTable;
+
+mod cache;
+pub use cache::{CacheFeature, CacheTargetParams};
+
+mod clone;
+pub use clone::{CloneFeatures, CloneTargetParams};
+
+mod crypt;
+pub use crypt::{CryptCipher, CryptKey, CryptOptions, CryptTargetParams};
+
+mod delay;
+pub use delay::{DelayClass, DelayTargetParams};
+
+mod dust;
+pub use dust::DustTargetParams;
+
+mod ebs;
+pub use ebs::EbsTargetParams;
+
+mod era;
+pub use era::EraTargetParams;
+
+mod error;
+pub use error::ErrorTargetParams;
+
+mod flakey;
+pub use flakey::{
+    FlakeyDirection, FlakeyFeature, FlakeyTargetParams, FLAKEY_PROBABILITY_MAX,
+};
+
+mod integrity;
+pub use integrity::{
+    IntegrityAlgorithm, IntegrityMode, IntegrityOptions, IntegrityTargetParams,
+};
+
+mod linear;
+pub use linear::LinearTargetParams;
+
+mod log_writes;
+pub use log_writes::LogWritesTargetParams;
+
+mod mirror;
+pub use mirror::{MirrorFeatures, MirrorLog, MirrorTargetParams};
+
+mod multipath;
+pub use multipath::{
+    MultipathFeatures, MultipathHwHandler, MultipathPath,
+    MultipathPriorityGroup, MultipathQueueMode, MultipathTargetParams,
+};
+
+mod raid;
+pub use raid::{
+    Raid10Format, RaidDevice, RaidDeviceHealth, RaidJournalHealth,
+    RaidJournalMode, RaidOptions, RaidSyncAction, RaidTargetParams,
+    RaidTargetStatus, RaidType,
+};
+
+mod snapshot;
+pub use snapshot::{
+    SnapshotFeatures, SnapshotMergeTargetParams, SnapshotOriginTargetParams,
+    SnapshotPersistence, SnapshotTargetParams,
+};
+
+mod striped;
+pub use striped::{StripedTargetParams, StripedTargetStatus};
+
+mod switch;
+pub use switch::{SwitchMapping, SwitchTargetParams};
+
+mod thin;
+pub use thin::ThinTargetParams;
+
+mod thin_pool;
+pub use thin_pool::{
+    ThinPoolDiscards, ThinPoolFeatures, ThinPoolMode, ThinPoolTargetParams,
+    ThinPoolTargetStatus, ThinPoolWorkingStatus,
+};
+
+mod unstripe;
+pub use unstripe::UnstripeTargetParams;
+
+mod verity;
+pub use verity::{
+    VerityCorruptionMode, VerityFec, VerityOptions, VerityTargetParams,
+};
+
+mod writecache;
+pub use writecache::{
+    WritecacheMode, WritecacheSettings, WritecacheTargetParams,
+};
+
+mod zero;
+pub use zero::ZeroTargetParams;
+
+/// The parameters of a target of a particular type, which can be
+/// converted to and from the `params` string of a table line.
+///
+/// Implement this for the parameters of target types this crate does
+/// not know about, to use them with [`TargetTable::push_typed`].
+pub trait TargetParams: Sized {
+    /// The target type name used by the kernel, e.g. `"linear"`.
+    fn target_type(&self) -> &str;
+
+    /// The `params` string for a table line, as accepted by
+    /// [`DM::table_load`][crate::DM::table_load].
+    fn param_str(&self) -> String;
+
+    /// Parse the `params` string of a table line, as returned by
+    /// [`DM::table_status`][crate::DM::table_status] with the
+    /// `DM_STATUS_TABLE` flag.
+    fn parse(params: &str) -> DmResult<Self>;
+}
+
+/// One line of a table, with the parameters of its target as a
+/// [`TargetParams`] type `P`.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::{
+///     targets::{SnapshotPersistence, SnapshotTargetParams, TypedTargetLine},
+///     TargetLine,
+/// };
+///
+/// let line = TargetLine {
+///     start: 0,
+///     length: 8192,
+///     target_type: "snapshot".parse().unwrap(),
+///     params: "253:2 253:3 P 8".to_string(),
+/// };
+/// let target = TypedTargetLine::<SnapshotTargetParams>::parse(&line).unwrap();
+/// assert_eq!(target.params.persistence, SnapshotPersistence::Persistent);
+/// assert_eq!(target.to_table_line().unwrap(), line);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TypedTargetLine<P> {
+    /// First sector of the DM device mapped by this line
+    pub start: u64,
+    /// Number of sectors mapped by this line
+    pub length: u64,
+    /// The target's parameters
+    pub params: P,
+}
+
+impl<P: TargetParams> TypedTargetLine<P> {
+    /// Parse one line of a table, in the format returned by
+    /// [`DM::table_status`][crate::DM::table_status] with the
+    /// `DM_STATUS_TABLE` flag.  Fails if the line is not for a target
+    /// of the type `P` describes.
+    pub fn parse(line: &TargetLine) -> DmResult<TypedTargetLine<P>> {
+        let params = P::parse(&line.params)?;
+        let target_type: &str = line.target_type.as_ref();
+        if target_type != params.target_type() {
+            return Err(DmError::InvalidArgument(format!(
+                "expected a {} target, found {target_type:?}",
+                params.target_type()
+            )));
+        }
+        Ok(TypedTargetLine {
+            start: line.start,
+            length: line.length,
+            params,
+        })
+    }
+
+    /// Convert back to a table line suitable for
+    /// [`DM::table_load`][crate::DM::table_load].  Fails if
+    /// `P::target_type` is not a valid target type.
+    pub fn to_table_line(&self) -> DmResult<TargetLine> {
+        Ok(TargetLine {
+            start: self.start,
+            length: self.length,
+            target_type: TargetTypeBuf::new(
+                self.params.target_type().to_string(),
+            )?,
+            params: self.params.param_str(),
+        })
+    }
+}
+
+/// Parse a numeric field of a `params` or status string, such as a
+/// sector offset or a count.
+fn parse_num<T: FromStr>(field: &str) -> DmResult<T> {
+    field.parse().map_err(|_| {
+        DmError::InvalidArgument(format!("{field:?} is not a valid number"))
+    })
+}
+
+/// Parse a field of a `params` string written in hexadecimal, such as
+/// a key or a digest, or `-` for no bytes.  The error message names
+/// the field as `what`, but does not include it, and the bytes parsed
+/// so far are wiped on failure, as the field may be a key.
+fn parse_hex(field: &str, what: &str) -> DmResult<Vec<u8>> {
+    if field == "-" {
+        return Ok(Vec::new());
+    }
+    let bad = || {
+        DmError::InvalidArgument(format!(
+            "{what} is not an even number of hexadecimal digits"
+        ))
+    };
+    if field.len() % 2 != 0 {
+        return Err(bad());
+    }
+
+    let mut bytes = Vec::with_capacity(field.len() / 2);
+    for pair in field.as_bytes().chunks(2) {
+        let digits = core::str::from_utf8(pair).map_err(|_| bad())?;
+        match u8::from_str_radix(digits, 16) {
+            Ok(byte) if !digits.starts_with('+') => bytes.push(byte),
+            _ => {
+                zero_bytes(&mut bytes);
+                return Err(bad());
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+/// Set `setting` to `value`, unless it is already set, for an option
+/// of a `params` string that may be given at most once.  The error
+/// message names the option as `what`.
+fn set_once<T>(setting: &mut Option<T>, value: T, what: &str) -> DmResult<()> {
+    match setting.replace(value) {
+        None => Ok(()),
+        Some(_) => Err(DmError::InvalidArgument(format!(
+            "{what} is given more than once"
+        ))),
+    }
+}
+
+/// Set `flag`, unless it is already set, for a flag option of a
+/// `params` string that may be given at most once.  The error message
+/// names the option as `what`.
+fn set_flag_once(flag: &mut bool, what: &str) -> DmResult<()> {
+    if *flag {
+        return Err(DmError::InvalidArgument(format!(
+            "{what} is given more than once"
+        )));
+    }
+    *flag = true;
+    Ok(())
+}
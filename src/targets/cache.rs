@@ -0,0 +1,328 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The `cache` target, which uses a fast device as a cache for a
+//! slower origin device.
+
+use core::{fmt, str::FromStr};
+
+use crate::{
+    device::Device,
+    errors::{DmError, DmResult},
+    targets::{parse_num, TargetParams},
+};
+
+#[cfg(test)]
+#[path = "../tests/targets/cache.rs"]
+mod tests;
+
+/// A feature argument of a `cache` target.
+///
+/// At most one of [`Self::Writethrough`], [`Self::Writeback`] and
+/// [`Self::Passthrough`] may be given; without any of them, the
+/// kernel uses writeback mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CacheFeature {
+    /// Write to both the cache and the origin before completing a
+    /// write (`writethrough`)
+    Writethrough,
+    /// Complete writes once they reach the cache (`writeback`)
+    Writeback,
+    /// Send all I/O to the origin, invalidating cached blocks as
+    /// they are written (`passthrough`)
+    Passthrough,
+    /// Use version 2 of the metadata format (`metadata2`)
+    Metadata2,
+    /// Process discards, but do not pass them down to the origin
+    /// device (`no_discard_passdown`)
+    NoDiscardPassdown,
+}
+
+/// The kernel's names for each of the [`CacheFeature`]s.
+const FEATURE_NAMES: [(CacheFeature, &str); 5] = [
+    (CacheFeature::Writethrough, "writethrough"),
+    (CacheFeature::Writeback, "writeback"),
+    (CacheFeature::Passthrough, "passthrough"),
+    (CacheFeature::Metadata2, "metadata2"),
+    (CacheFeature::NoDiscardPassdown, "no_discard_passdown"),
+];
+
+impl CacheFeature {
+    /// Whether this feature selects the I/O mode.
+    fn is_io_mode(self) -> bool {
+        matches!(
+            self,
+            CacheFeature::Writethrough
+                | CacheFeature::Writeback
+                | CacheFeature::Passthrough
+        )
+    }
+}
+
+impl fmt::Display for CacheFeature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (_, name) = FEATURE_NAMES
+            .iter()
+            .find(|(feature, _)| feature == self)
+            .expect("every feature has a name");
+        f.write_str(name)
+    }
+}
+
+impl FromStr for CacheFeature {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<CacheFeature> {
+        FEATURE_NAMES
+            .iter()
+            .find(|(_, name)| *name == s)
+            .map(|(feature, _)| *feature)
+            .ok_or_else(|| {
+                DmError::InvalidArgument(format!(
+                    "unrecognised cache feature {s:?}"
+                ))
+            })
+    }
+}
+
+/// The parameters of a `cache` target: `<metadata_dev> <cache_dev>
+/// <origin_dev> <block_size> <num_features> <feature> ... <policy>
+/// <num_policy_args> <key> <value> ...`.
+///
+/// The block size must be a multiple of [`Self::MIN_BLOCK_SIZE`]
+/// sectors, no larger than [`Self::MAX_BLOCK_SIZE`].  Policy
+/// arguments are pairs of a key and a value, so `Display` writes
+/// `<num_policy_args>` as twice the number of pairs.  Parsing, and
+/// [`Self::new`], also reject a repeated feature, more than one I/O
+/// mode, and a policy name, key or value that is empty or contains
+/// whitespace.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::{
+///     targets::{CacheFeature, CacheTargetParams},
+///     Device,
+/// };
+///
+/// let params = CacheTargetParams::new(
+///     Device { major: 8, minor: 16 },
+///     Device { major: 8, minor: 32 },
+///     Device { major: 8, minor: 48 },
+///     128,
+///     vec![CacheFeature::Writethrough],
+///     "smq".to_string(),
+///     vec![("migration_threshold".to_string(), "2048".to_string())],
+/// )
+/// .unwrap();
+/// assert_eq!(
+///     params.to_string(),
+///     "8:16 8:32 8:48 128 1 writethrough smq 2 migration_threshold 2048"
+/// );
+/// assert_eq!(params.to_string().parse(), Ok(params));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CacheTargetParams {
+    /// The device holding the cache's metadata
+    pub metadata_dev: Device,
+    /// The fast device holding the cached blocks
+    pub cache_dev: Device,
+    /// The slow device being cached
+    pub origin_dev: Device,
+    /// Size, in sectors, of each cache block
+    pub block_size: u64,
+    /// Feature arguments, in the order they are given to the kernel
+    pub features: Vec<CacheFeature>,
+    /// The name of the replacement policy, such as `smq` or
+    /// `default`
+    pub policy: String,
+    /// Policy arguments, as key and value pairs, in order
+    pub policy_args: Vec<(String, String)>,
+}
+
+impl CacheTargetParams {
+    /// The target type name used by the kernel.
+    pub const TARGET_TYPE: &'static str = "cache";
+
+    /// The smallest allowed block size, in sectors (32KiB).  Every
+    /// block size must be a multiple of this.
+    pub const MIN_BLOCK_SIZE: u64 = 64;
+
+    /// The largest allowed block size, in sectors (1GiB).
+    pub const MAX_BLOCK_SIZE: u64 = 2_097_152;
+
+    /// Parameters that cache `origin_dev` on `cache_dev`, keeping
+    /// metadata on `metadata_dev`.  Fails if the parameters are
+    /// invalid, as described above.
+    pub fn new(
+        metadata_dev: Device,
+        cache_dev: Device,
+        origin_dev: Device,
+        block_size: u64,
+        features: Vec<CacheFeature>,
+        policy: String,
+        policy_args: Vec<(String, String)>,
+    ) -> DmResult<CacheTargetParams> {
+        let params = CacheTargetParams {
+            metadata_dev,
+            cache_dev,
+            origin_dev,
+            block_size,
+            features,
+            policy,
+            policy_args,
+        };
+        params.check()?;
+        Ok(params)
+    }
+
+    /// Returns an error if the kernel would reject these parameters,
+    /// or if they could not be written as a `params` string.
+    fn check(&self) -> DmResult<()> {
+        if !(Self::MIN_BLOCK_SIZE..=Self::MAX_BLOCK_SIZE)
+            .contains(&self.block_size)
+            || self.block_size % Self::MIN_BLOCK_SIZE != 0
+        {
+            return Err(DmError::InvalidArgument(format!(
+                "cache block size {} is not a multiple of {} between {} \
+                 and {}",
+                self.block_size,
+                Self::MIN_BLOCK_SIZE,
+                Self::MIN_BLOCK_SIZE,
+                Self::MAX_BLOCK_SIZE
+            )));
+        }
+        for (i, feature) in self.features.iter().enumerate() {
+            if self.features[..i].contains(feature) {
+                return Err(DmError::InvalidArgument(format!(
+                    "cache feature {feature} is given more than once"
+                )));
+            }
+        }
+        if self.features.iter().filter(|f| f.is_io_mode()).count() > 1 {
+            return Err(DmError::InvalidArgument(
+                "cache writethrough, writeback and passthrough are mutually \
+                 exclusive"
+                    .to_string(),
+            ));
+        }
+        let words = Some(&self.policy).into_iter().chain(
+            self.policy_args
+                .iter()
+                .flat_map(|(key, value)| [key, value]),
+        );
+        for word in words {
+            if word.is_empty() || word.contains(char::is_whitespace) {
+                return Err(DmError::InvalidArgument(format!(
+                    "cache policy name or argument {word:?} is empty or \
+                     contains whitespace"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for CacheTargetParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {}",
+            self.metadata_dev,
+            self.cache_dev,
+            self.origin_dev,
+            self.block_size,
+            self.features.len()
+        )?;
+        for feature in &self.features {
+            write!(f, " {feature}")?;
+        }
+        write!(f, " {} {}", self.policy, self.policy_args.len() * 2)?;
+        for (key, value) in &self.policy_args {
+            write!(f, " {key} {value}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for CacheTargetParams {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<CacheTargetParams> {
+        <Self as TargetParams>::parse(s)
+    }
+}
+
+impl TargetParams for CacheTargetParams {
+    fn target_type(&self) -> &str {
+        Self::TARGET_TYPE
+    }
+
+    fn param_str(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(params: &str) -> DmResult<CacheTargetParams> {
+        let bad = || {
+            DmError::InvalidArgument(format!(
+                "expected \"<metadata_dev> <cache_dev> <origin_dev> \
+                 <block_size> <num_features> <feature> ... <policy> \
+                 <num_policy_args> <key> <value> ...\", found {params:?}"
+            ))
+        };
+
+        let fields = params.split_whitespace().collect::<Vec<_>>();
+        let (metadata_dev, cache_dev, origin_dev, block_size, rest) =
+            match fields[..] {
+                [metadata_dev, cache_dev, origin_dev, block_size, ref rest @ ..] => {
+                    (metadata_dev, cache_dev, origin_dev, block_size, rest)
+                }
+                _ => return Err(bad()),
+            };
+
+        let (num_features, rest) = rest.split_first().ok_or_else(bad)?;
+        let num_features = parse_num::<usize>(num_features)?;
+        if rest.len() < num_features {
+            return Err(bad());
+        }
+        let (features, rest) = rest.split_at(num_features);
+        let features = features
+            .iter()
+            .map(|name| name.parse())
+            .collect::<DmResult<Vec<_>>>()?;
+
+        let (policy, num_policy_args, policy_args) = match rest {
+            [policy, num_policy_args, ref policy_args @ ..] => {
+                (policy, num_policy_args, policy_args)
+            }
+            _ => return Err(bad()),
+        };
+        if parse_num::<usize>(num_policy_args)? != policy_args.len() {
+            return Err(DmError::InvalidArgument(format!(
+                "cache policy argument count {num_policy_args} does not \
+                 match {policy_args:?}"
+            )));
+        }
+        if policy_args.len() % 2 != 0 {
+            return Err(DmError::InvalidArgument(format!(
+                "cache policy arguments {policy_args:?} are not key and \
+                 value pairs"
+            )));
+        }
+        let policy_args = policy_args
+            .chunks(2)
+            .map(|pair| (pair[0].to_string(), pair[1].to_string()))
+            .collect();
+
+        CacheTargetParams::new(
+            metadata_dev.parse()?,
+            cache_dev.parse()?,
+            origin_dev.parse()?,
+            parse_num(block_size)?,
+            features,
+            policy.to_string(),
+            policy_args,
+        )
+    }
+}
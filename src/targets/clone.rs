@@ -0,0 +1,299 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The `clone` target, which presents a copy of a source device that
+//! is usable at once, while copying ("hydrating") the source's
+//! regions onto a destination device in the background.
+
+use core::{fmt, str::FromStr};
+
+use bitflags::bitflags;
+
+use crate::{
+    device::Device,
+    errors::{DmError, DmResult},
+    targets::{parse_num, set_once, TargetParams},
+};
+
+#[cfg(test)]
+#[path = "../tests/targets/clone.rs"]
+mod tests;
+
+bitflags! {
+    /// The feature arguments of a `clone` target.
+    #[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+    pub struct CloneFeatures: u32 {
+        /// Do not start hydrating the destination until hydration is
+        /// enabled by a message (`no_hydration`).
+        const NO_HYDRATION        = 1 << 0;
+        /// Do not pass discards down to the destination
+        /// (`no_discard_passdown`).
+        const NO_DISCARD_PASSDOWN = 1 << 1;
+    }
+}
+
+/// The kernel's names for each of the [`CloneFeatures`], in the order
+/// it reports them.
+const FEATURE_NAMES: [(CloneFeatures, &str); 2] = [
+    (CloneFeatures::NO_HYDRATION, "no_hydration"),
+    (CloneFeatures::NO_DISCARD_PASSDOWN, "no_discard_passdown"),
+];
+
+/// The parameters of a `clone` target: `<metadata_dev> <dest_dev>
+/// <source_dev> <region_size> [<num_features> <feature> ...
+/// [<num_core_args> <key> <value> ...]]`.
+///
+/// `Display` writes `<num_features>` if there are features or core
+/// arguments, and `<num_core_args>` if there are core arguments.  The
+/// kernel always reports both, with the hydration threshold and batch
+/// size it is using, so parameters read back from it have those set
+/// even if they were not given.  The region size, in sectors, must be
+/// a power of two between [`Self::MIN_REGION_SIZE`] and
+/// [`Self::MAX_REGION_SIZE`], and the hydration threshold and batch
+/// size must not be zero.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::{
+///     targets::{CloneFeatures, CloneTargetParams},
+///     Device,
+/// };
+///
+/// let params = CloneTargetParams::new(
+///     Device { major: 8, minor: 16 },
+///     Device { major: 8, minor: 32 },
+///     Device { major: 8, minor: 48 },
+///     8,
+///     CloneFeatures::NO_HYDRATION,
+///     Some(4),
+///     None,
+/// )
+/// .unwrap();
+/// assert_eq!(
+///     params.to_string(),
+///     "8:16 8:32 8:48 8 1 no_hydration 2 hydration_threshold 4"
+/// );
+/// assert_eq!(params.to_string().parse(), Ok(params));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CloneTargetParams {
+    /// The device that records which regions have been hydrated
+    pub metadata_dev: Device,
+    /// The device that the source is copied onto
+    pub dest_dev: Device,
+    /// The device that is copied
+    pub source_dev: Device,
+    /// Size, in sectors, of each region copied
+    pub region_size: u64,
+    /// Feature arguments
+    pub features: CloneFeatures,
+    /// How many regions may be hydrating at once, if not the kernel's
+    /// default (`hydration_threshold`)
+    pub hydration_threshold: Option<u32>,
+    /// How many adjacent regions to copy together, if not the
+    /// kernel's default (`hydration_batch_size`)
+    pub hydration_batch_size: Option<u32>,
+}
+
+impl CloneTargetParams {
+    /// The target type name used by the kernel.
+    pub const TARGET_TYPE: &'static str = "clone";
+
+    /// The smallest allowed region size, in sectors (4KiB).
+    pub const MIN_REGION_SIZE: u64 = 8;
+
+    /// The largest allowed region size, in sectors (1GiB).
+    pub const MAX_REGION_SIZE: u64 = 2_097_152;
+
+    /// Parameters that copy `source_dev` onto `dest_dev`, keeping
+    /// metadata on `metadata_dev`.  Fails if the parameters are
+    /// invalid, as described above.
+    pub fn new(
+        metadata_dev: Device,
+        dest_dev: Device,
+        source_dev: Device,
+        region_size: u64,
+        features: CloneFeatures,
+        hydration_threshold: Option<u32>,
+        hydration_batch_size: Option<u32>,
+    ) -> DmResult<CloneTargetParams> {
+        let params = CloneTargetParams {
+            metadata_dev,
+            dest_dev,
+            source_dev,
+            region_size,
+            features,
+            hydration_threshold,
+            hydration_batch_size,
+        };
+        params.check()?;
+        Ok(params)
+    }
+
+    /// Returns an error if the kernel would reject these parameters.
+    fn check(&self) -> DmResult<()> {
+        if !self.region_size.is_power_of_two()
+            || !(Self::MIN_REGION_SIZE..=Self::MAX_REGION_SIZE)
+                .contains(&self.region_size)
+        {
+            return Err(DmError::InvalidArgument(format!(
+                "clone region size {} is not a power of two between {} and \
+                 {}",
+                self.region_size,
+                Self::MIN_REGION_SIZE,
+                Self::MAX_REGION_SIZE
+            )));
+        }
+        for (name, value) in [
+            ("hydration_threshold", self.hydration_threshold),
+            ("hydration_batch_size", self.hydration_batch_size),
+        ] {
+            if value == Some(0) {
+                return Err(DmError::InvalidArgument(format!(
+                    "clone {name} must not be zero"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// The number of core arguments, counting keys and values.
+    fn num_core_args(&self) -> usize {
+        2 * (usize::from(self.hydration_threshold.is_some())
+            + usize::from(self.hydration_batch_size.is_some()))
+    }
+}
+
+impl fmt::Display for CloneTargetParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.metadata_dev, self.dest_dev, self.source_dev, self.region_size
+        )?;
+
+        let num_core_args = self.num_core_args();
+        if self.features.is_empty() && num_core_args == 0 {
+            return Ok(());
+        }
+        write!(f, " {}", self.features.bits().count_ones())?;
+        for (feature, name) in FEATURE_NAMES {
+            if self.features.contains(feature) {
+                write!(f, " {name}")?;
+            }
+        }
+
+        if num_core_args > 0 {
+            write!(f, " {num_core_args}")?;
+            if let Some(threshold) = self.hydration_threshold {
+                write!(f, " hydration_threshold {threshold}")?;
+            }
+            if let Some(batch_size) = self.hydration_batch_size {
+                write!(f, " hydration_batch_size {batch_size}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for CloneTargetParams {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<CloneTargetParams> {
+        <Self as TargetParams>::parse(s)
+    }
+}
+
+impl TargetParams for CloneTargetParams {
+    fn target_type(&self) -> &str {
+        Self::TARGET_TYPE
+    }
+
+    fn param_str(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(params: &str) -> DmResult<CloneTargetParams> {
+        let bad = || {
+            DmError::InvalidArgument(format!(
+                "expected \"<metadata_dev> <dest_dev> <source_dev> \
+                 <region_size> [<num_features> <feature> ... \
+                 [<num_core_args> <key> <value> ...]]\", found {params:?}"
+            ))
+        };
+        let fields = params.split_whitespace().collect::<Vec<_>>();
+        let [metadata_dev, dest_dev, source_dev, region_size, ref rest @ ..] =
+            fields[..]
+        else {
+            return Err(bad());
+        };
+
+        let mut features = CloneFeatures::empty();
+        let mut rest = rest;
+        if let Some((num_features, args)) = rest.split_first() {
+            let num_features = parse_num::<usize>(num_features)?;
+            if args.len() < num_features {
+                return Err(bad());
+            }
+            let (names, args) = args.split_at(num_features);
+            for name in names {
+                let (feature, _) = FEATURE_NAMES
+                    .iter()
+                    .find(|(_, known)| known == name)
+                    .ok_or_else(|| {
+                        DmError::InvalidArgument(format!(
+                            "unrecognised clone feature {name:?}"
+                        ))
+                    })?;
+                if features.contains(*feature) {
+                    return Err(DmError::InvalidArgument(format!(
+                        "clone feature {name} is given more than once"
+                    )));
+                }
+                features |= *feature;
+            }
+            rest = args;
+        }
+
+        let mut hydration_threshold = None;
+        let mut hydration_batch_size = None;
+        if let Some((num_core_args, args)) = rest.split_first() {
+            if parse_num::<usize>(num_core_args)? != args.len()
+                || args.len() % 2 != 0
+            {
+                return Err(DmError::InvalidArgument(format!(
+                    "clone core argument count {num_core_args} does not \
+                     match {args:?}"
+                )));
+            }
+            for pair in args.chunks_exact(2) {
+                let slot = match pair[0] {
+                    "hydration_threshold" => &mut hydration_threshold,
+                    "hydration_batch_size" => &mut hydration_batch_size,
+                    key => {
+                        return Err(DmError::InvalidArgument(format!(
+                            "unrecognised clone core argument {key:?}"
+                        )))
+                    }
+                };
+                set_once(
+                    slot,
+                    parse_num(pair[1])?,
+                    &format!("clone core argument {}", pair[0]),
+                )?;
+            }
+        }
+
+        CloneTargetParams::new(
+            metadata_dev.parse()?,
+            dest_dev.parse()?,
+            source_dev.parse()?,
+            parse_num(region_size)?,
+            features,
+            hydration_threshold,
+            hydration_batch_size,
+        )
+    }
+}
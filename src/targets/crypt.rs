@@ -0,0 +1,538 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The `crypt` target, which encrypts the data written to another
+//! block device and decrypts the data read from it.
+//!
+//! The parameters of a `crypt` target include the encryption key.
+//! [`CryptKey`] wipes its copy of the key when it is dropped, and
+//! does not show the key in its `Debug` output; a
+//! [`TargetTable`] wipes the parameters of its `crypt` targets when
+//! it is dropped; and [`DM::table_load_typed`] loads such a table
+//! with `DM_SECURE_DATA`, so that the kernel and this library wipe
+//! their copies too.  Strings produced some other way, such as by
+//! `to_string`, are the caller's responsibility, as are targets moved
+//! out of a table with [`TargetTable::into_targets`] or by iterating
+//! over it by value.
+//!
+//! [`TargetTable`]: crate::TargetTable
+//! [`TargetTable::into_targets`]: crate::TargetTable::into_targets
+//! [`DM::table_load_typed`]: crate::DM::table_load_typed
+
+use core::{
+    fmt::{self, Write},
+    str::FromStr,
+};
+
+use crate::{
+    device::Device,
+    errors::{DmError, DmResult},
+    targets::{parse_hex, parse_num, TargetParams},
+    util::{to_string_exact, zero_bytes},
+};
+
+#[cfg(test)]
+#[path = "../tests/targets/crypt.rs"]
+mod tests;
+
+/// The cipher specification of a `crypt` target.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::targets::CryptCipher;
+///
+/// let cipher: CryptCipher = "aes-cbc-essiv:sha256".parse().unwrap();
+/// assert_eq!(
+///     cipher,
+///     CryptCipher::Legacy {
+///         cipher: "aes".to_string(),
+///         key_count: None,
+///         chain_mode: Some("cbc".to_string()),
+///         iv_mode: Some("essiv".to_string()),
+///         iv_opts: Some("sha256".to_string()),
+///     }
+/// );
+/// assert_eq!(cipher.to_string(), "aes-cbc-essiv:sha256");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CryptCipher {
+    /// `<cipher>[:<key_count>][-<chain_mode>[-<iv_mode>[:<iv_opts>]]]`,
+    /// such as `aes-xts-plain64`.  `iv_mode` is only written if there
+    /// is a `chain_mode`, and `iv_opts` only if there is an `iv_mode`.
+    Legacy {
+        /// The block cipher, such as `aes`
+        cipher: String,
+        /// The number of keys the key is split into
+        key_count: Option<u32>,
+        /// The chaining mode, such as `xts` or `cbc`
+        chain_mode: Option<String>,
+        /// The IV generator, such as `plain64` or `essiv`
+        iv_mode: Option<String>,
+        /// Options for the IV generator, such as `sha256`
+        iv_opts: Option<String>,
+    },
+    /// `capi:<spec>[-<iv_mode>][:<iv_opts>]`, in which `spec` is in
+    /// the kernel crypto API's syntax, such as `capi:xts(aes)-plain64`.
+    Capi {
+        /// The kernel crypto API specification, such as `xts(aes)`
+        spec: String,
+        /// The IV generator, such as `plain64` or `random`
+        iv_mode: Option<String>,
+        /// Options for the IV generator
+        iv_opts: Option<String>,
+    },
+}
+
+impl fmt::Display for CryptCipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CryptCipher::Legacy {
+                cipher,
+                key_count,
+                chain_mode,
+                iv_mode,
+                iv_opts,
+            } => {
+                f.write_str(cipher)?;
+                if let Some(key_count) = key_count {
+                    write!(f, ":{key_count}")?;
+                }
+                if let Some(chain_mode) = chain_mode {
+                    write!(f, "-{chain_mode}")?;
+                    if let Some(iv_mode) = iv_mode {
+                        write!(f, "-{iv_mode}")?;
+                        if let Some(iv_opts) = iv_opts {
+                            write!(f, ":{iv_opts}")?;
+                        }
+                    }
+                }
+                Ok(())
+            }
+            CryptCipher::Capi {
+                spec,
+                iv_mode,
+                iv_opts,
+            } => {
+                write!(f, "capi:{spec}")?;
+                if let Some(iv_mode) = iv_mode {
+                    write!(f, "-{iv_mode}")?;
+                }
+                if let Some(iv_opts) = iv_opts {
+                    write!(f, ":{iv_opts}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Splits the cipher specification the same way the kernel does.
+impl FromStr for CryptCipher {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<CryptCipher> {
+        if s.is_empty() || s.contains(char::is_whitespace) {
+            return Err(DmError::InvalidArgument(format!(
+                "{s:?} is not a valid crypt cipher specification"
+            )));
+        }
+
+        if let Some(spec) = s.strip_prefix("capi:") {
+            let (spec, iv_opts) = split_at(spec, spec.rfind(':'));
+            let (spec, iv_mode) = split_at(spec, spec.rfind('-'));
+            return Ok(CryptCipher::Capi {
+                spec: spec.to_string(),
+                iv_mode: iv_mode.map(str::to_string),
+                iv_opts: iv_opts.map(str::to_string),
+            });
+        }
+
+        let (head, rest) = split_at(s, s.find('-'));
+        let (cipher, key_count) = split_at(head, head.find(':'));
+        let (chain_mode, iv) = match rest {
+            Some(rest) => {
+                let (chain_mode, iv) = split_at(rest, rest.find('-'));
+                (Some(chain_mode), iv)
+            }
+            None => (None, None),
+        };
+        let (iv_mode, iv_opts) = match iv {
+            Some(iv) => {
+                let (iv_mode, iv_opts) = split_at(iv, iv.find(':'));
+                (Some(iv_mode), iv_opts)
+            }
+            None => (None, None),
+        };
+        Ok(CryptCipher::Legacy {
+            cipher: cipher.to_string(),
+            key_count: key_count.map(parse_num).transpose()?,
+            chain_mode: chain_mode.map(str::to_string),
+            iv_mode: iv_mode.map(str::to_string),
+            iv_opts: iv_opts.map(str::to_string),
+        })
+    }
+}
+
+/// Split `s` around the one-byte separator at `at`, if there is one.
+fn split_at(s: &str, at: Option<usize>) -> (&str, Option<&str>) {
+    match at {
+        Some(at) => (&s[..at], Some(&s[at + 1..])),
+        None => (s, None),
+    }
+}
+
+/// The key of a `crypt` target.
+///
+/// Wipes the key bytes when dropped, and does not show them in its
+/// `Debug` output.
+#[derive(Clone, PartialEq, Eq)]
+pub enum CryptKey {
+    /// The key itself, written in hexadecimal, or as `-` if it is
+    /// empty, as for `cipher_null`
+    Bytes(Vec<u8>),
+    /// A key in the kernel keyring:
+    /// `:<size>:<key_type>:<description>`
+    Keyring {
+        /// Size of the key, in bytes
+        size: u32,
+        /// The type of the key, such as `logon` or `user`
+        key_type: String,
+        /// The description the key was added to the keyring under
+        description: String,
+    },
+}
+
+/// Shows the length of a key given as bytes, but not the bytes.
+impl fmt::Debug for CryptKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CryptKey::Bytes(bytes) => f
+                .debug_tuple("Bytes")
+                .field(&format_args!("<{} bytes>", bytes.len()))
+                .finish(),
+            CryptKey::Keyring {
+                size,
+                key_type,
+                description,
+            } => f
+                .debug_struct("Keyring")
+                .field("size", size)
+                .field("key_type", key_type)
+                .field("description", description)
+                .finish(),
+        }
+    }
+}
+
+/// Wipes the key bytes.
+impl Drop for CryptKey {
+    fn drop(&mut self) {
+        if let CryptKey::Bytes(bytes) = self {
+            zero_bytes(bytes);
+        }
+    }
+}
+
+/// Writes the key as the kernel expects it in the `params` string.
+impl fmt::Display for CryptKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CryptKey::Bytes(bytes) => {
+                if bytes.is_empty() {
+                    f.write_char('-')?;
+                }
+                for byte in bytes {
+                    write!(f, "{byte:02x}")?;
+                }
+                Ok(())
+            }
+            CryptKey::Keyring {
+                size,
+                key_type,
+                description,
+            } => write!(f, ":{size}:{key_type}:{description}"),
+        }
+    }
+}
+
+/// Parses a key written in hexadecimal, `-` for an empty key, or a
+/// keyring reference.  Error messages do not include the key.
+impl FromStr for CryptKey {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<CryptKey> {
+        if let Some(keyring) = s.strip_prefix(':') {
+            return match keyring.splitn(3, ':').collect::<Vec<_>>()[..] {
+                [size, key_type, description]
+                    if !key_type.is_empty() && !description.is_empty() =>
+                {
+                    Ok(CryptKey::Keyring {
+                        size: parse_num(size)?,
+                        key_type: key_type.to_string(),
+                        description: description.to_string(),
+                    })
+                }
+                _ => Err(DmError::InvalidArgument(format!(
+                    "expected a crypt keyring key \
+                     \":<size>:<key_type>:<description>\", found {s:?}"
+                ))),
+            };
+        }
+        parse_hex(s, "crypt key").map(CryptKey::Bytes)
+    }
+}
+
+/// The optional parameters of a `crypt` target.  Parameters that are
+/// `None` or `false` are not passed to the kernel, which then uses
+/// its defaults.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CryptOptions {
+    /// Pass discards down to the device (`allow_discards`)
+    pub allow_discards: bool,
+    /// Encrypt on the CPU that submitted the I/O (`same_cpu_crypt`)
+    pub same_cpu_crypt: bool,
+    /// Submit writes from the encryption threads
+    /// (`submit_from_crypt_cpus`)
+    pub submit_from_crypt_cpus: bool,
+    /// Decrypt reads without a workqueue (`no_read_workqueue`)
+    pub no_read_workqueue: bool,
+    /// Encrypt writes without a workqueue (`no_write_workqueue`)
+    pub no_write_workqueue: bool,
+    /// The size of the per-sector integrity tag, in bytes, and the
+    /// authenticated encryption type, such as `aead`
+    /// (`integrity:<bytes>:<type>`)
+    pub integrity: Option<(u32, String)>,
+    /// The encryption sector size, in bytes
+    /// (`sector_size:<bytes>`)
+    pub sector_size: Option<u32>,
+    /// Compute IVs from `sector_size` sectors rather than 512-byte
+    /// sectors (`iv_large_sectors`)
+    pub iv_large_sectors: bool,
+    /// Options this crate does not know about, such as
+    /// `high_priority`, written after the others as they are
+    pub other: Vec<String>,
+}
+
+impl CryptOptions {
+    /// The number of options, which is what the kernel expects for
+    /// `<num_options>`.
+    fn len(&self) -> usize {
+        [
+            self.allow_discards,
+            self.same_cpu_crypt,
+            self.submit_from_crypt_cpus,
+            self.no_read_workqueue,
+            self.no_write_workqueue,
+            self.integrity.is_some(),
+            self.sector_size.is_some(),
+            self.iv_large_sectors,
+        ]
+        .iter()
+        .filter(|set| **set)
+        .count()
+            + self.other.len()
+    }
+
+    /// Parse the options of a `crypt` target, which may be given in
+    /// any order.
+    fn parse(options: &[&str]) -> DmResult<CryptOptions> {
+        let mut parsed = CryptOptions::default();
+        for option in options {
+            match option.split_once(':') {
+                None => match *option {
+                    "allow_discards" => parsed.allow_discards = true,
+                    "same_cpu_crypt" => parsed.same_cpu_crypt = true,
+                    "submit_from_crypt_cpus" => {
+                        parsed.submit_from_crypt_cpus = true
+                    }
+                    "no_read_workqueue" => parsed.no_read_workqueue = true,
+                    "no_write_workqueue" => parsed.no_write_workqueue = true,
+                    "iv_large_sectors" => parsed.iv_large_sectors = true,
+                    other => parsed.other.push(other.to_string()),
+                },
+                Some(("sector_size", size)) => {
+                    parsed.sector_size = Some(parse_num(size)?)
+                }
+                Some(("integrity", integrity)) => {
+                    let (bytes, auth) =
+                        integrity.split_once(':').ok_or_else(|| {
+                            DmError::InvalidArgument(format!(
+                                "expected crypt option \
+                                 \"integrity:<bytes>:<type>\", found \
+                                 {option:?}"
+                            ))
+                        })?;
+                    parsed.integrity =
+                        Some((parse_num(bytes)?, auth.to_string()))
+                }
+                Some(_) => parsed.other.push(option.to_string()),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// Writes the options in the kernel's order, separated by spaces,
+/// without `<num_options>`.
+impl fmt::Display for CryptOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flags = [
+            (self.allow_discards, "allow_discards"),
+            (self.same_cpu_crypt, "same_cpu_crypt"),
+            (self.submit_from_crypt_cpus, "submit_from_crypt_cpus"),
+            (self.no_read_workqueue, "no_read_workqueue"),
+            (self.no_write_workqueue, "no_write_workqueue"),
+        ];
+        let mut sep = "";
+        for (_, name) in flags.iter().filter(|(set, _)| *set) {
+            write!(f, "{sep}{name}")?;
+            sep = " ";
+        }
+        if let Some((bytes, auth)) = &self.integrity {
+            write!(f, "{sep}integrity:{bytes}:{auth}")?;
+            sep = " ";
+        }
+        if let Some(sector_size) = self.sector_size {
+            write!(f, "{sep}sector_size:{sector_size}")?;
+            sep = " ";
+        }
+        if self.iv_large_sectors {
+            write!(f, "{sep}iv_large_sectors")?;
+            sep = " ";
+        }
+        for option in &self.other {
+            write!(f, "{sep}{option}")?;
+            sep = " ";
+        }
+        Ok(())
+    }
+}
+
+/// The parameters of a `crypt` target: `<cipher> <key> <iv_offset>
+/// <device> <offset> [<num_options> <option> ...]`.
+///
+/// `Display` writes `<num_options>` only if there are options; it
+/// counts each option, such as `allow_discards` or
+/// `sector_size:4096`, as one.  `Debug` does not show the key.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::{
+///     targets::{CryptKey, CryptOptions, CryptTargetParams},
+///     Device,
+/// };
+///
+/// let params = CryptTargetParams {
+///     cipher: "aes-xts-plain64".parse().unwrap(),
+///     key: CryptKey::Bytes(vec![0x01; 64]),
+///     iv_offset: 0,
+///     dest: Device { major: 8, minor: 16 },
+///     offset: 4096,
+///     options: CryptOptions {
+///         allow_discards: true,
+///         ..Default::default()
+///     },
+/// };
+/// let text = params.to_string();
+/// assert!(text.starts_with("aes-xts-plain64 010101"));
+/// assert!(text.ends_with(" 0 8:16 4096 1 allow_discards"));
+/// assert_eq!(text.parse(), Ok(params));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CryptTargetParams {
+    /// The cipher specification
+    pub cipher: CryptCipher,
+    /// The encryption key
+    pub key: CryptKey,
+    /// Added to the sector number when computing each sector's IV
+    pub iv_offset: u64,
+    /// The device holding the encrypted data
+    pub dest: Device,
+    /// Offset, in sectors, of the start of the encrypted data within
+    /// `dest`
+    pub offset: u64,
+    /// Optional parameters
+    pub options: CryptOptions,
+}
+
+impl CryptTargetParams {
+    /// The target type name used by the kernel.
+    pub const TARGET_TYPE: &'static str = "crypt";
+}
+
+impl fmt::Display for CryptTargetParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {}",
+            self.cipher, self.key, self.iv_offset, self.dest, self.offset
+        )?;
+        let num_options = self.options.len();
+        if num_options > 0 {
+            write!(f, " {num_options} {}", self.options)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for CryptTargetParams {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<CryptTargetParams> {
+        <Self as TargetParams>::parse(s)
+    }
+}
+
+impl TargetParams for CryptTargetParams {
+    fn target_type(&self) -> &str {
+        Self::TARGET_TYPE
+    }
+
+    /// The `params` string, allocated at its exact size up front so
+    /// that no partial copies of the key are left behind as it
+    /// grows.
+    fn param_str(&self) -> String {
+        to_string_exact(self)
+    }
+
+    fn parse(params: &str) -> DmResult<CryptTargetParams> {
+        let fields = params.split_whitespace().collect::<Vec<_>>();
+        let (cipher, key, iv_offset, dest, offset, rest) = match fields[..] {
+            [cipher, key, iv_offset, dest, offset, ref rest @ ..] => {
+                (cipher, key, iv_offset, dest, offset, rest)
+            }
+            _ => {
+                return Err(DmError::InvalidArgument(
+                    "expected \"<cipher> <key> <iv_offset> <device> \
+                     <offset> [<num_options> <option> ...]\""
+                        .to_string(),
+                ))
+            }
+        };
+
+        let options = match rest.split_first() {
+            None => CryptOptions::default(),
+            Some((num_options, options)) => {
+                if parse_num::<usize>(num_options)? != options.len() {
+                    return Err(DmError::InvalidArgument(format!(
+                        "crypt option count {num_options} does not match \
+                         {options:?}"
+                    )));
+                }
+                CryptOptions::parse(options)?
+            }
+        };
+
+        Ok(CryptTargetParams {
+            cipher: cipher.parse()?,
+            key: key.parse()?,
+            iv_offset: parse_num(iv_offset)?,
+            dest: dest.parse()?,
+            offset: parse_num(offset)?,
+            options,
+        })
+    }
+}
@@ -0,0 +1,185 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The `delay` target, which maps a range of sectors onto another
+//! block device, like `linear`, but holds each I/O request for a
+//! fixed time first.  It is mainly used to simulate slow storage.
+
+use core::{fmt, str::FromStr, time::Duration};
+
+use crate::{
+    device::Device,
+    errors::{DmError, DmResult},
+    targets::{parse_num, TargetParams},
+};
+
+#[cfg(test)]
+#[path = "../tests/targets/delay.rs"]
+mod tests;
+
+/// Where one class of I/O to a `delay` target is sent, and how long
+/// it is held first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DelayClass {
+    /// The device that the sectors are mapped onto
+    pub dest: Device,
+    /// Offset, in sectors, of the start of the mapping within `dest`
+    pub offset: u64,
+    /// How long each request is held, in milliseconds
+    pub delay_ms: u32,
+}
+
+impl DelayClass {
+    /// A class that maps onto `dest` starting `offset` sectors into
+    /// it, holding each request for `delay`.  The kernel counts in
+    /// whole milliseconds, so `delay` is rounded down; a delay of
+    /// more than `u32::MAX` milliseconds is rejected.
+    pub fn new(
+        dest: Device,
+        offset: u64,
+        delay: Duration,
+    ) -> DmResult<DelayClass> {
+        let delay_ms = u32::try_from(delay.as_millis()).map_err(|_| {
+            DmError::InvalidArgument(format!(
+                "delay of {delay:?} is too long for the delay target"
+            ))
+        })?;
+        Ok(DelayClass {
+            dest,
+            offset,
+            delay_ms,
+        })
+    }
+
+    /// How long each request is held.
+    pub fn delay(&self) -> Duration {
+        Duration::from_millis(self.delay_ms.into())
+    }
+}
+
+impl fmt::Display for DelayClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.dest, self.offset, self.delay_ms)
+    }
+}
+
+/// The parameters of a `delay` target:
+/// `<device> <offset> <delay> [<device> <offset> <delay>
+/// [<device> <offset> <delay>]]`.
+///
+/// The first class applies to reads.  If `write` is `None`, writes
+/// are treated like reads, and if `flush` is `None`, flushes are
+/// treated like writes.  `Display` writes only as many classes as
+/// are needed; setting `flush` without `write` writes `read` in the
+/// write position, as the kernel's fields are positional.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use dm_ioctl::{
+///     targets::{DelayClass, DelayTargetParams},
+///     Device,
+/// };
+///
+/// let dest = Device { major: 8, minor: 16 };
+/// let params = DelayTargetParams {
+///     read: DelayClass::new(dest, 0, Duration::ZERO).unwrap(),
+///     write: Some(
+///         DelayClass::new(dest, 0, Duration::from_millis(500)).unwrap(),
+///     ),
+///     flush: None,
+/// };
+/// assert_eq!(params.to_string(), "8:16 0 0 8:16 0 500");
+/// assert_eq!(params.to_string().parse(), Ok(params));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DelayTargetParams {
+    /// Where reads are sent, and how long they are held
+    pub read: DelayClass,
+    /// Where writes are sent, and how long they are held, if
+    /// different from reads
+    pub write: Option<DelayClass>,
+    /// Where flushes are sent, and how long they are held, if
+    /// different from writes
+    pub flush: Option<DelayClass>,
+}
+
+impl DelayTargetParams {
+    /// The target type name used by the kernel.
+    pub const TARGET_TYPE: &'static str = "delay";
+
+    /// Parameters that send all I/O to `dest`, starting `offset`
+    /// sectors into it, holding each request for `delay`.
+    pub fn new(
+        dest: Device,
+        offset: u64,
+        delay: Duration,
+    ) -> DmResult<DelayTargetParams> {
+        Ok(DelayTargetParams {
+            read: DelayClass::new(dest, offset, delay)?,
+            write: None,
+            flush: None,
+        })
+    }
+}
+
+impl fmt::Display for DelayTargetParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.read)?;
+        match (self.write, self.flush) {
+            (None, None) => Ok(()),
+            (Some(write), None) => write!(f, " {write}"),
+            (write, Some(flush)) => {
+                write!(f, " {} {flush}", write.unwrap_or(self.read))
+            }
+        }
+    }
+}
+
+impl FromStr for DelayTargetParams {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<DelayTargetParams> {
+        <Self as TargetParams>::parse(s)
+    }
+}
+
+impl TargetParams for DelayTargetParams {
+    fn target_type(&self) -> &str {
+        Self::TARGET_TYPE
+    }
+
+    fn param_str(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(params: &str) -> DmResult<DelayTargetParams> {
+        let fields = params.split_whitespace().collect::<Vec<_>>();
+        if !matches!(fields.len(), 3 | 6 | 9) {
+            return Err(DmError::InvalidArgument(format!(
+                "expected 1 to 3 \"<device> <offset> <delay>\" groups, \
+                 found {params:?}"
+            )));
+        }
+
+        let mut classes = fields
+            .chunks(3)
+            .map(|class| {
+                Ok(DelayClass {
+                    dest: class[0].parse()?,
+                    offset: parse_num(class[1])?,
+                    delay_ms: parse_num(class[2])?,
+                })
+            })
+            .collect::<DmResult<Vec<_>>>()?
+            .into_iter();
+        Ok(DelayTargetParams {
+            read: classes.next().expect("there are 1 to 3 classes"),
+            write: classes.next(),
+            flush: classes.next(),
+        })
+    }
+}
@@ -0,0 +1,123 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The `dust` target, which maps a range of sectors onto another
+//! block device, like `linear`, but fails reads of blocks that have
+//! been marked bad by messages.  It is used to simulate failing
+//! storage.
+
+use core::{fmt, str::FromStr};
+
+use crate::{
+    device::Device,
+    errors::{DmError, DmResult},
+    targets::{parse_num, TargetParams},
+};
+
+#[cfg(test)]
+#[path = "../tests/targets/dust.rs"]
+mod tests;
+
+/// The parameters of a `dust` target: `<dev> <offset> <block_size>`.
+///
+/// The block size, in bytes, is the unit in which bad blocks are
+/// marked.  It must be a power of two between
+/// [`Self::MIN_BLOCK_SIZE`] and [`Self::MAX_BLOCK_SIZE`]; the kernel
+/// also rejects a block size larger than the target itself.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::{targets::DustTargetParams, Device};
+///
+/// let params =
+///     DustTargetParams::new(Device { major: 8, minor: 16 }, 0, 4096)
+///         .unwrap();
+/// assert_eq!(params.to_string(), "8:16 0 4096");
+/// assert_eq!(params.to_string().parse(), Ok(params));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DustTargetParams {
+    /// The device that the sectors are mapped onto
+    pub dest: Device,
+    /// Offset, in sectors, of the start of the mapping within `dest`
+    pub offset: u64,
+    /// Size, in bytes, of each block that can be marked bad
+    pub block_size: u32,
+}
+
+impl DustTargetParams {
+    /// The target type name used by the kernel.
+    pub const TARGET_TYPE: &'static str = "dust";
+
+    /// The smallest block size, in bytes, that the kernel accepts.
+    pub const MIN_BLOCK_SIZE: u32 = 512;
+
+    /// The largest block size, in bytes, that the kernel accepts.
+    pub const MAX_BLOCK_SIZE: u32 = 1 << 30;
+
+    /// Parameters that map onto `dest`, starting `offset` sectors
+    /// into it, in blocks of `block_size` bytes.  Fails if
+    /// `block_size` is not a power of two in the range given above.
+    pub fn new(
+        dest: Device,
+        offset: u64,
+        block_size: u32,
+    ) -> DmResult<DustTargetParams> {
+        if !block_size.is_power_of_two()
+            || !(Self::MIN_BLOCK_SIZE..=Self::MAX_BLOCK_SIZE)
+                .contains(&block_size)
+        {
+            return Err(DmError::InvalidArgument(format!(
+                "dust block size {block_size} is not a power of two from {} \
+                 to {}",
+                Self::MIN_BLOCK_SIZE,
+                Self::MAX_BLOCK_SIZE
+            )));
+        }
+        Ok(DustTargetParams {
+            dest,
+            offset,
+            block_size,
+        })
+    }
+}
+
+impl fmt::Display for DustTargetParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.dest, self.offset, self.block_size)
+    }
+}
+
+impl FromStr for DustTargetParams {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<DustTargetParams> {
+        <Self as TargetParams>::parse(s)
+    }
+}
+
+impl TargetParams for DustTargetParams {
+    fn target_type(&self) -> &str {
+        Self::TARGET_TYPE
+    }
+
+    fn param_str(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(params: &str) -> DmResult<DustTargetParams> {
+        let fields = params.split_whitespace().collect::<Vec<_>>();
+        let [dest, offset, block_size] = fields[..] else {
+            return Err(DmError::InvalidArgument(format!(
+                "expected \"<dev> <offset> <block_size>\", found {params:?}"
+            )));
+        };
+        DustTargetParams::new(
+            dest.parse()?,
+            parse_num(offset)?,
+            parse_num(block_size)?,
+        )
+    }
+}
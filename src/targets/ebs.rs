@@ -0,0 +1,140 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The `ebs` target, which maps a range of sectors onto another block
+//! device, like `linear`, but presents a different logical block
+//! size from the device's own, emulating it with read-modify-write
+//! where necessary.
+
+use core::{fmt, str::FromStr};
+
+use crate::{
+    device::Device,
+    errors::{DmError, DmResult},
+    targets::{parse_num, TargetParams},
+};
+
+#[cfg(test)]
+#[path = "../tests/targets/ebs.rs"]
+mod tests;
+
+/// The parameters of an `ebs` target: `<dev> <offset>
+/// <emulated_block_size> [<underlying_block_size>]`.
+///
+/// Block sizes are in sectors, and must be powers of two, so that
+/// either size divides the other.  The kernel also limits them to
+/// one page (8 sectors with 4KiB pages), which is not checked here.
+/// If the underlying block size is not given, the kernel uses the
+/// device's logical block size.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::{targets::EbsTargetParams, Device};
+///
+/// let params =
+///     EbsTargetParams::new(Device { major: 8, minor: 16 }, 0, 1, Some(8))
+///         .unwrap();
+/// assert_eq!(params.to_string(), "8:16 0 1 8");
+/// assert_eq!(params.to_string().parse(), Ok(params));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EbsTargetParams {
+    /// The device that the sectors are mapped onto
+    pub dest: Device,
+    /// Offset, in sectors, of the start of the mapping within `dest`
+    pub offset: u64,
+    /// The logical block size, in sectors, that the target presents
+    pub emulated_block_size: u64,
+    /// The block size, in sectors, in which `dest` is accessed, if
+    /// not its logical block size
+    pub underlying_block_size: Option<u64>,
+}
+
+impl EbsTargetParams {
+    /// The target type name used by the kernel.
+    pub const TARGET_TYPE: &'static str = "ebs";
+
+    /// Parameters that map onto `dest`, starting `offset` sectors
+    /// into it, presenting blocks of `emulated_block_size` sectors.
+    /// Fails if either block size is not a power of two.
+    pub fn new(
+        dest: Device,
+        offset: u64,
+        emulated_block_size: u64,
+        underlying_block_size: Option<u64>,
+    ) -> DmResult<EbsTargetParams> {
+        for (what, size) in [
+            ("emulated", Some(emulated_block_size)),
+            ("underlying", underlying_block_size),
+        ] {
+            if let Some(size) = size.filter(|size| !size.is_power_of_two()) {
+                return Err(DmError::InvalidArgument(format!(
+                    "ebs {what} block size {size} is not a power of two"
+                )));
+            }
+        }
+        Ok(EbsTargetParams {
+            dest,
+            offset,
+            emulated_block_size,
+            underlying_block_size,
+        })
+    }
+}
+
+impl fmt::Display for EbsTargetParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.dest, self.offset, self.emulated_block_size
+        )?;
+        if let Some(underlying_block_size) = self.underlying_block_size {
+            write!(f, " {underlying_block_size}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for EbsTargetParams {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<EbsTargetParams> {
+        <Self as TargetParams>::parse(s)
+    }
+}
+
+impl TargetParams for EbsTargetParams {
+    fn target_type(&self) -> &str {
+        Self::TARGET_TYPE
+    }
+
+    fn param_str(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(params: &str) -> DmResult<EbsTargetParams> {
+        let fields = params.split_whitespace().collect::<Vec<_>>();
+        let (dest, offset, emulated_block_size, underlying_block_size) =
+            match fields[..] {
+                [dest, offset, emulated] => (dest, offset, emulated, None),
+                [dest, offset, emulated, underlying] => {
+                    (dest, offset, emulated, Some(parse_num(underlying)?))
+                }
+                _ => {
+                    return Err(DmError::InvalidArgument(format!(
+                        "expected \"<dev> <offset> <emulated_block_size> \
+                         [<underlying_block_size>]\", found {params:?}"
+                    )))
+                }
+            };
+        EbsTargetParams::new(
+            dest.parse()?,
+            parse_num(offset)?,
+            parse_num(emulated_block_size)?,
+            underlying_block_size,
+        )
+    }
+}
@@ -0,0 +1,118 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The `era` target, which maps onto an origin device, like
+//! `linear`, while recording in a metadata device the era in which
+//! each block was last written.
+
+use core::{fmt, str::FromStr};
+
+use crate::{
+    device::Device,
+    errors::{DmError, DmResult},
+    targets::{parse_num, TargetParams},
+};
+
+#[cfg(test)]
+#[path = "../tests/targets/era.rs"]
+mod tests;
+
+/// The parameters of an `era` target: `<metadata_dev> <origin_dev>
+/// <block_size>`.
+///
+/// The granularity is the size, in sectors, of the blocks whose
+/// writes are tracked.  It must not be zero, nor larger than
+/// `u32::MAX`; the kernel does not require it to be a power of two.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::{targets::EraTargetParams, Device};
+///
+/// let params = EraTargetParams::new(
+///     Device { major: 8, minor: 16 },
+///     Device { major: 8, minor: 32 },
+///     128,
+/// )
+/// .unwrap();
+/// assert_eq!(params.to_string(), "8:16 8:32 128");
+/// assert_eq!(params.to_string().parse(), Ok(params));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EraTargetParams {
+    /// The device that holds the era metadata
+    pub metadata_dev: Device,
+    /// The device that the sectors are mapped onto
+    pub origin_dev: Device,
+    /// Size, in sectors, of each block tracked
+    pub granularity: u64,
+}
+
+impl EraTargetParams {
+    /// The target type name used by the kernel.
+    pub const TARGET_TYPE: &'static str = "era";
+
+    /// Parameters that track writes to `origin_dev` in blocks of
+    /// `granularity` sectors, keeping the metadata on
+    /// `metadata_dev`.  Fails if `granularity` is zero or too large.
+    pub fn new(
+        metadata_dev: Device,
+        origin_dev: Device,
+        granularity: u64,
+    ) -> DmResult<EraTargetParams> {
+        if granularity == 0 || granularity > u64::from(u32::MAX) {
+            return Err(DmError::InvalidArgument(format!(
+                "era block size {granularity} is zero or too large"
+            )));
+        }
+        Ok(EraTargetParams {
+            metadata_dev,
+            origin_dev,
+            granularity,
+        })
+    }
+}
+
+impl fmt::Display for EraTargetParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.metadata_dev, self.origin_dev, self.granularity
+        )
+    }
+}
+
+impl FromStr for EraTargetParams {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<EraTargetParams> {
+        <Self as TargetParams>::parse(s)
+    }
+}
+
+impl TargetParams for EraTargetParams {
+    fn target_type(&self) -> &str {
+        Self::TARGET_TYPE
+    }
+
+    fn param_str(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(params: &str) -> DmResult<EraTargetParams> {
+        let fields = params.split_whitespace().collect::<Vec<_>>();
+        let [metadata_dev, origin_dev, granularity] = fields[..] else {
+            return Err(DmError::InvalidArgument(format!(
+                "expected \"<metadata_dev> <origin_dev> <block_size>\", \
+                 found {params:?}"
+            )));
+        };
+        EraTargetParams::new(
+            metadata_dev.parse()?,
+            origin_dev.parse()?,
+            parse_num(granularity)?,
+        )
+    }
+}
@@ -0,0 +1,63 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The `error` target, which fails every I/O request to the range of
+//! sectors it maps.
+
+use core::{fmt, str::FromStr};
+
+use crate::{
+    errors::{DmError, DmResult},
+    targets::TargetParams,
+};
+
+#[cfg(test)]
+#[path = "../tests/targets/error.rs"]
+mod tests;
+
+/// The parameters of an `error` target, which has none.  The `params`
+/// string used by `Display` and `FromStr` is empty.
+///
+/// [`TargetTable::error_table`][crate::TargetTable::error_table] builds a
+/// table that fails all I/O across a whole device.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ErrorTargetParams;
+
+impl ErrorTargetParams {
+    /// The target type name used by the kernel.
+    pub const TARGET_TYPE: &'static str = "error";
+}
+
+impl fmt::Display for ErrorTargetParams {
+    fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Ok(())
+    }
+}
+
+impl FromStr for ErrorTargetParams {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<ErrorTargetParams> {
+        <Self as TargetParams>::parse(s)
+    }
+}
+
+impl TargetParams for ErrorTargetParams {
+    fn target_type(&self) -> &str {
+        Self::TARGET_TYPE
+    }
+
+    fn param_str(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(params: &str) -> DmResult<ErrorTargetParams> {
+        if !params.trim().is_empty() {
+            return Err(DmError::InvalidArgument(format!(
+                "the error target takes no parameters, found {params:?}"
+            )));
+        }
+        Ok(ErrorTargetParams)
+    }
+}
@@ -0,0 +1,341 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The `flakey` target, which maps a range of sectors onto another
+//! block device, like `linear`, but periodically misbehaves.  It is
+//! used for fault injection.
+
+use core::{fmt, str::FromStr};
+
+use crate::{
+    device::Device,
+    errors::{DmError, DmResult},
+    targets::{parse_num, TargetParams},
+};
+
+#[cfg(test)]
+#[path = "../tests/targets/flakey.rs"]
+mod tests;
+
+/// The largest probability accepted by the `random_read_corrupt` and
+/// `random_write_corrupt` features, meaning "always".
+pub const FLAKEY_PROBABILITY_MAX: u32 = 1_000_000_000;
+
+/// Which I/O direction a `corrupt_bio_byte` feature applies to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FlakeyDirection {
+    /// Corrupt data being read (`r`)
+    Read,
+    /// Corrupt data being written (`w`)
+    Write,
+}
+
+/// A feature argument of a `flakey` target, which changes what
+/// happens to I/O during the down interval.  Without any, all I/O
+/// fails during the down interval.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FlakeyFeature {
+    /// Fail all reads (`error_reads`)
+    ErrorReads,
+    /// Silently discard all writes (`drop_writes`)
+    DropWrites,
+    /// Fail all writes without sending them to the device
+    /// (`error_writes`)
+    ErrorWrites,
+    /// Replace one byte of the data of each matching request
+    /// (`corrupt_bio_byte`)
+    CorruptBioByte {
+        /// Which byte of the request's data to replace, counting
+        /// from 1
+        nth_byte: u32,
+        /// Whether to corrupt reads or writes
+        direction: FlakeyDirection,
+        /// The value to put in its place
+        value: u8,
+        /// Only corrupt requests with all of these `REQ_*` flags set
+        flags: u32,
+    },
+    /// Corrupt a random byte of reads with probability `n` in
+    /// [`FLAKEY_PROBABILITY_MAX`] (`random_read_corrupt`)
+    RandomReadCorrupt(u32),
+    /// Corrupt a random byte of writes with probability `n` in
+    /// [`FLAKEY_PROBABILITY_MAX`] (`random_write_corrupt`)
+    RandomWriteCorrupt(u32),
+}
+
+impl FlakeyFeature {
+    /// The number of arguments the feature takes up, counting its
+    /// name, which is how the kernel counts `<num_features>`.
+    fn num_args(&self) -> usize {
+        match self {
+            FlakeyFeature::CorruptBioByte { .. } => 5,
+            FlakeyFeature::RandomReadCorrupt(_)
+            | FlakeyFeature::RandomWriteCorrupt(_) => 2,
+            _ => 1,
+        }
+    }
+}
+
+impl fmt::Display for FlakeyFeature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlakeyFeature::ErrorReads => write!(f, "error_reads"),
+            FlakeyFeature::DropWrites => write!(f, "drop_writes"),
+            FlakeyFeature::ErrorWrites => write!(f, "error_writes"),
+            FlakeyFeature::CorruptBioByte {
+                nth_byte,
+                direction,
+                value,
+                flags,
+            } => {
+                let direction = match direction {
+                    FlakeyDirection::Read => 'r',
+                    FlakeyDirection::Write => 'w',
+                };
+                write!(
+                    f,
+                    "corrupt_bio_byte {nth_byte} {direction} {value} {flags}"
+                )
+            }
+            FlakeyFeature::RandomReadCorrupt(probability) => {
+                write!(f, "random_read_corrupt {probability}")
+            }
+            FlakeyFeature::RandomWriteCorrupt(probability) => {
+                write!(f, "random_write_corrupt {probability}")
+            }
+        }
+    }
+}
+
+/// The parameters of a `flakey` target: `<device> <offset>
+/// <up_interval> <down_interval> [<num_features> <feature> ...]`.
+///
+/// The device behaves normally for `up_interval` seconds, then
+/// misbehaves as `features` direct for `down_interval` seconds, and
+/// so on.  `Display` computes `<num_features>`, which counts the
+/// arguments of the features rather than the features themselves.
+///
+/// Parsing, and [`Self::new`], reject combinations that the kernel
+/// would reject: both intervals zero, `drop_writes` together with
+/// `error_writes`, a `corrupt_bio_byte` byte number of zero, or a
+/// probability greater than [`FLAKEY_PROBABILITY_MAX`].
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::{
+///     targets::{FlakeyFeature, FlakeyTargetParams},
+///     Device,
+/// };
+///
+/// let dest = Device { major: 8, minor: 16 };
+/// let params =
+///     FlakeyTargetParams::new(dest, 0, 5, 1, vec![FlakeyFeature::DropWrites])
+///         .unwrap();
+/// assert_eq!(params.to_string(), "8:16 0 5 1 1 drop_writes");
+/// assert_eq!(params.to_string().parse(), Ok(params));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FlakeyTargetParams {
+    /// The device that the sectors are mapped onto
+    pub dest: Device,
+    /// Offset, in sectors, of the start of the mapping within `dest`
+    pub offset: u64,
+    /// How long, in seconds, the device behaves normally in each
+    /// cycle
+    pub up_interval: u32,
+    /// How long, in seconds, the device misbehaves in each cycle
+    pub down_interval: u32,
+    /// How the device misbehaves
+    pub features: Vec<FlakeyFeature>,
+}
+
+impl FlakeyTargetParams {
+    /// The target type name used by the kernel.
+    pub const TARGET_TYPE: &'static str = "flakey";
+
+    /// Parameters that map onto `dest`, starting `offset` sectors
+    /// into it, and misbehave as `features` direct.  Fails if the
+    /// combination is invalid, as described above.
+    pub fn new(
+        dest: Device,
+        offset: u64,
+        up_interval: u32,
+        down_interval: u32,
+        features: Vec<FlakeyFeature>,
+    ) -> DmResult<FlakeyTargetParams> {
+        let params = FlakeyTargetParams {
+            dest,
+            offset,
+            up_interval,
+            down_interval,
+            features,
+        };
+        params.check()?;
+        Ok(params)
+    }
+
+    /// Returns an error if the kernel would reject these parameters.
+    fn check(&self) -> DmResult<()> {
+        if self.up_interval == 0 && self.down_interval == 0 {
+            return Err(DmError::InvalidArgument(
+                "flakey up and down intervals are both zero".to_string(),
+            ));
+        }
+        if self.features.contains(&FlakeyFeature::DropWrites)
+            && self.features.contains(&FlakeyFeature::ErrorWrites)
+        {
+            return Err(DmError::InvalidArgument(
+                "flakey drop_writes and error_writes are mutually exclusive"
+                    .to_string(),
+            ));
+        }
+        for feature in &self.features {
+            match *feature {
+                FlakeyFeature::CorruptBioByte { nth_byte: 0, .. } => {
+                    return Err(DmError::InvalidArgument(
+                        "flakey corrupt_bio_byte counts bytes from 1"
+                            .to_string(),
+                    ))
+                }
+                FlakeyFeature::RandomReadCorrupt(probability)
+                | FlakeyFeature::RandomWriteCorrupt(probability)
+                    if probability > FLAKEY_PROBABILITY_MAX =>
+                {
+                    return Err(DmError::InvalidArgument(format!(
+                        "flakey probability {probability} is greater \
+                         than {FLAKEY_PROBABILITY_MAX}"
+                    )))
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for FlakeyTargetParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.dest, self.offset, self.up_interval, self.down_interval
+        )?;
+        if !self.features.is_empty() {
+            let num_args: usize =
+                self.features.iter().map(FlakeyFeature::num_args).sum();
+            write!(f, " {num_args}")?;
+            for feature in &self.features {
+                write!(f, " {feature}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for FlakeyTargetParams {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<FlakeyTargetParams> {
+        <Self as TargetParams>::parse(s)
+    }
+}
+
+impl TargetParams for FlakeyTargetParams {
+    fn target_type(&self) -> &str {
+        Self::TARGET_TYPE
+    }
+
+    fn param_str(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(params: &str) -> DmResult<FlakeyTargetParams> {
+        let fields = params.split_whitespace().collect::<Vec<_>>();
+        let (dest, offset, up_interval, down_interval, rest) = match fields[..]
+        {
+            [dest, offset, up, down, ref rest @ ..] => {
+                (dest, offset, up, down, rest)
+            }
+            _ => {
+                return Err(DmError::InvalidArgument(format!(
+                    "expected \"<device> <offset> <up_interval> \
+                     <down_interval> [<num_features> <feature> ...]\", \
+                     found {params:?}"
+                )))
+            }
+        };
+
+        let features = match rest.split_first() {
+            None => Vec::new(),
+            Some((num_args, args)) => {
+                if parse_num::<usize>(num_args)? != args.len() {
+                    return Err(DmError::InvalidArgument(format!(
+                        "flakey feature count {num_args} does not match \
+                         {args:?}"
+                    )));
+                }
+                parse_features(args)?
+            }
+        };
+
+        FlakeyTargetParams::new(
+            dest.parse()?,
+            parse_num(offset)?,
+            parse_num(up_interval)?,
+            parse_num(down_interval)?,
+            features,
+        )
+    }
+}
+
+/// Parse the feature arguments of a `flakey` target.
+fn parse_features(mut args: &[&str]) -> DmResult<Vec<FlakeyFeature>> {
+    let mut features = Vec::new();
+    while let Some((&name, rest)) = args.split_first() {
+        let (feature, rest) = match (name, rest) {
+            ("error_reads", rest) => (FlakeyFeature::ErrorReads, rest),
+            ("drop_writes", rest) => (FlakeyFeature::DropWrites, rest),
+            ("error_writes", rest) => (FlakeyFeature::ErrorWrites, rest),
+            (
+                "corrupt_bio_byte",
+                [nth_byte, direction, value, flags, rest @ ..],
+            ) => {
+                let direction = match *direction {
+                    "r" => FlakeyDirection::Read,
+                    "w" => FlakeyDirection::Write,
+                    _ => {
+                        return Err(DmError::InvalidArgument(format!(
+                            "flakey corrupt_bio_byte direction must be r \
+                             or w, found {direction:?}"
+                        )))
+                    }
+                };
+                let feature = FlakeyFeature::CorruptBioByte {
+                    nth_byte: parse_num(nth_byte)?,
+                    direction,
+                    value: parse_num(value)?,
+                    flags: parse_num(flags)?,
+                };
+                (feature, rest)
+            }
+            ("random_read_corrupt", [probability, rest @ ..]) => (
+                FlakeyFeature::RandomReadCorrupt(parse_num(probability)?),
+                rest,
+            ),
+            ("random_write_corrupt", [probability, rest @ ..]) => (
+                FlakeyFeature::RandomWriteCorrupt(parse_num(probability)?),
+                rest,
+            ),
+            _ => {
+                return Err(DmError::InvalidArgument(format!(
+                    "unrecognised or incomplete flakey feature at {args:?}"
+                )))
+            }
+        };
+        features.push(feature);
+        args = rest;
+    }
+    Ok(features)
+}
@@ -0,0 +1,531 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The `integrity` target, which stores a tag, such as a checksum or
+//! MAC, alongside each sector of another block device, and checks it
+//! when the sector is read.
+//!
+//! The parameters of an `integrity` target may include keys for its
+//! hash and journal algorithms.  [`IntegrityAlgorithm`] wipes its
+//! copy of the key when it is dropped, and does not show the key in
+//! its `Debug` output; a [`TargetTable`] wipes the parameters of its
+//! `integrity` targets when it is dropped; and
+//! [`DM::table_load_typed`] loads such a table with
+//! `DM_SECURE_DATA`.  Targets moved out of a table with
+//! [`TargetTable::into_targets`] or by iterating over it by value are
+//! the caller's responsibility.
+//!
+//! [`TargetTable`]: crate::TargetTable
+//! [`TargetTable::into_targets`]: crate::TargetTable::into_targets
+//! [`DM::table_load_typed`]: crate::DM::table_load_typed
+
+use core::{
+    fmt::{self, Write},
+    str::FromStr,
+};
+
+use crate::{
+    device::Device,
+    errors::{DmError, DmResult},
+    targets::{parse_hex, parse_num, set_flag_once, set_once, TargetParams},
+    util::{to_string_exact, zero_bytes},
+};
+
+#[cfg(test)]
+#[path = "../tests/targets/integrity.rs"]
+mod tests;
+
+/// How an `integrity` target keeps data and tags consistent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum IntegrityMode {
+    /// Write data and tags through a journal (`J`)
+    Journal,
+    /// Track regions being written in a bitmap, and recalculate
+    /// their tags after a crash (`B`)
+    Bitmap,
+    /// Write data and tags directly, with no crash consistency (`D`)
+    Direct,
+    /// Read-only recovery mode, which does not check tags or replay
+    /// the journal (`R`)
+    Recovery,
+    /// Store tags in the device's own per-sector metadata (`I`)
+    Inline,
+}
+
+impl fmt::Display for IntegrityMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_char(match self {
+            IntegrityMode::Journal => 'J',
+            IntegrityMode::Bitmap => 'B',
+            IntegrityMode::Direct => 'D',
+            IntegrityMode::Recovery => 'R',
+            IntegrityMode::Inline => 'I',
+        })
+    }
+}
+
+impl FromStr for IntegrityMode {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<IntegrityMode> {
+        match s {
+            "J" => Ok(IntegrityMode::Journal),
+            "B" => Ok(IntegrityMode::Bitmap),
+            "D" => Ok(IntegrityMode::Direct),
+            "R" => Ok(IntegrityMode::Recovery),
+            "I" => Ok(IntegrityMode::Inline),
+            _ => Err(DmError::InvalidArgument(format!(
+                "integrity mode must be J, B, D, R, or I, found {s:?}"
+            ))),
+        }
+    }
+}
+
+/// An algorithm used by an `integrity` target, with its key if it
+/// needs one: `<name>[:<key>]`, with the key in hexadecimal.
+///
+/// Wipes the key when dropped, and does not show it in its `Debug`
+/// output.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct IntegrityAlgorithm {
+    /// The algorithm, in the kernel crypto API's syntax, such as
+    /// `crc32c` or `hmac(sha256)`
+    pub name: String,
+    /// The key, for keyed algorithms
+    pub key: Option<Vec<u8>>,
+}
+
+/// Shows the length of the key, but not the key.
+impl fmt::Debug for IntegrityAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("IntegrityAlgorithm");
+        debug.field("name", &self.name);
+        match &self.key {
+            Some(key) => {
+                debug.field("key", &format_args!("<{} bytes>", key.len()))
+            }
+            None => debug.field("key", &None::<()>),
+        };
+        debug.finish()
+    }
+}
+
+/// Wipes the key.
+impl Drop for IntegrityAlgorithm {
+    fn drop(&mut self) {
+        if let Some(key) = &mut self.key {
+            zero_bytes(key);
+        }
+    }
+}
+
+impl fmt::Display for IntegrityAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)?;
+        if let Some(key) = &self.key {
+            f.write_char(':')?;
+            for byte in key {
+                write!(f, "{byte:02x}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The error message does not include the key.
+impl FromStr for IntegrityAlgorithm {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<IntegrityAlgorithm> {
+        let (name, key) = match s.split_once(':') {
+            Some((name, key)) => {
+                (name, Some(parse_hex(key, "integrity algorithm key")?))
+            }
+            None => (s, None),
+        };
+        if name.is_empty() {
+            return Err(DmError::InvalidArgument(
+                "integrity algorithm name is empty".to_string(),
+            ));
+        }
+        Ok(IntegrityAlgorithm {
+            name: name.to_string(),
+            key,
+        })
+    }
+}
+
+/// The optional parameters of an `integrity` target.  Parameters that
+/// are `None` or `false` are not passed to the kernel, which then
+/// uses its defaults.
+///
+/// The kernel always reports `buffer_sectors`, and reports some other
+/// parameters, such as `journal_sectors`, whenever the mode uses
+/// them, so parameters read back from a device may have values that
+/// were not given when it was created.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct IntegrityOptions {
+    /// A separate device holding the metadata (`meta_device`)
+    pub meta_device: Option<Device>,
+    /// Size of each data and tag block, in bytes (`block_size`)
+    pub block_size: Option<u32>,
+    /// Calculate the tags of sectors that do not have them yet
+    /// (`recalculate`)
+    pub recalculate: bool,
+    /// Restart recalculation from the beginning
+    /// (`reset_recalculate`)
+    pub reset_recalculate: bool,
+    /// Allow discards, which leave the tags of discarded sectors
+    /// invalid (`allow_discards`)
+    pub allow_discards: bool,
+    /// The number of interleaved data sectors between tag sectors
+    /// (`interleave_sectors`)
+    pub interleave_sectors: Option<u32>,
+    /// The number of sectors in each metadata buffer
+    /// (`buffer_sectors`)
+    pub buffer_sectors: Option<u32>,
+    /// Size of the journal, in sectors (`journal_sectors`)
+    pub journal_sectors: Option<u32>,
+    /// Flush the journal when it is this full, as a percentage
+    /// (`journal_watermark`)
+    pub journal_watermark: Option<u8>,
+    /// Commit the journal at least this often, in milliseconds
+    /// (`commit_time`)
+    pub commit_time: Option<u32>,
+    /// The number of sectors each bit of the bitmap covers
+    /// (`sectors_per_bit`)
+    pub sectors_per_bit: Option<u64>,
+    /// Write the bitmap at least this often, in milliseconds
+    /// (`bitmap_flush_interval`)
+    pub bitmap_flush_interval: Option<u32>,
+    /// Use the corrected layout of the journal padding
+    /// (`fix_padding`)
+    pub fix_padding: bool,
+    /// Include the sector number and superblock salt in the journal
+    /// MAC (`fix_hmac`)
+    pub fix_hmac: bool,
+    /// Allow recalculation with a keyed hash, which is insecure
+    /// without `fix_hmac` (`legacy_recalculate`)
+    pub legacy_recalculate: bool,
+    /// The algorithm computing the tags, if the kernel rather than an
+    /// upper layer computes them (`internal_hash`)
+    pub internal_hash: Option<IntegrityAlgorithm>,
+    /// The algorithm encrypting the journal (`journal_crypt`)
+    pub journal_crypt: Option<IntegrityAlgorithm>,
+    /// The algorithm authenticating the journal (`journal_mac`)
+    pub journal_mac: Option<IntegrityAlgorithm>,
+    /// Options this crate does not know about, written after the
+    /// others as they are
+    pub other: Vec<String>,
+}
+
+impl IntegrityOptions {
+    /// The number of options, which is what the kernel expects for
+    /// `<num_options>`.
+    fn len(&self) -> usize {
+        [
+            self.meta_device.is_some(),
+            self.block_size.is_some(),
+            self.recalculate,
+            self.reset_recalculate,
+            self.allow_discards,
+            self.interleave_sectors.is_some(),
+            self.buffer_sectors.is_some(),
+            self.journal_sectors.is_some(),
+            self.journal_watermark.is_some(),
+            self.commit_time.is_some(),
+            self.sectors_per_bit.is_some(),
+            self.bitmap_flush_interval.is_some(),
+            self.fix_padding,
+            self.fix_hmac,
+            self.legacy_recalculate,
+            self.internal_hash.is_some(),
+            self.journal_crypt.is_some(),
+            self.journal_mac.is_some(),
+        ]
+        .iter()
+        .filter(|set| **set)
+        .count()
+            + self.other.len()
+    }
+
+    /// Parse the options of an `integrity` target, which may be given
+    /// in any order, but at most once each, except for those this
+    /// crate does not know about.
+    fn parse(options: &[&str]) -> DmResult<IntegrityOptions> {
+        let mut parsed = IntegrityOptions::default();
+        for option in options {
+            let Some((name, value)) = option.split_once(':') else {
+                let flag = match *option {
+                    "recalculate" => &mut parsed.recalculate,
+                    "reset_recalculate" => &mut parsed.reset_recalculate,
+                    "allow_discards" => &mut parsed.allow_discards,
+                    "fix_padding" => &mut parsed.fix_padding,
+                    "fix_hmac" => &mut parsed.fix_hmac,
+                    "legacy_recalculate" => &mut parsed.legacy_recalculate,
+                    _ => {
+                        parsed.other.push(option.to_string());
+                        continue;
+                    }
+                };
+                set_flag_once(flag, &format!("integrity option {option}"))?;
+                continue;
+            };
+            let what = &format!("integrity option {name}");
+            match name {
+                "meta_device" => {
+                    set_once(&mut parsed.meta_device, value.parse()?, what)?
+                }
+                "block_size" => {
+                    set_once(&mut parsed.block_size, parse_num(value)?, what)?
+                }
+                "interleave_sectors" => set_once(
+                    &mut parsed.interleave_sectors,
+                    parse_num(value)?,
+                    what,
+                )?,
+                "buffer_sectors" => set_once(
+                    &mut parsed.buffer_sectors,
+                    parse_num(value)?,
+                    what,
+                )?,
+                "journal_sectors" => set_once(
+                    &mut parsed.journal_sectors,
+                    parse_num(value)?,
+                    what,
+                )?,
+                "journal_watermark" => set_once(
+                    &mut parsed.journal_watermark,
+                    parse_num(value)?,
+                    what,
+                )?,
+                "commit_time" => {
+                    set_once(&mut parsed.commit_time, parse_num(value)?, what)?
+                }
+                "sectors_per_bit" => set_once(
+                    &mut parsed.sectors_per_bit,
+                    parse_num(value)?,
+                    what,
+                )?,
+                "bitmap_flush_interval" => set_once(
+                    &mut parsed.bitmap_flush_interval,
+                    parse_num(value)?,
+                    what,
+                )?,
+                "internal_hash" => {
+                    set_once(&mut parsed.internal_hash, value.parse()?, what)?
+                }
+                "journal_crypt" => {
+                    set_once(&mut parsed.journal_crypt, value.parse()?, what)?
+                }
+                "journal_mac" => {
+                    set_once(&mut parsed.journal_mac, value.parse()?, what)?
+                }
+                _ => parsed.other.push(option.to_string()),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// Writes the options in the kernel's order, separated by spaces,
+/// without `<num_options>`.
+impl fmt::Display for IntegrityOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut sep = "";
+        let mut emit = |f: &mut fmt::Formatter<'_>,
+                        args: fmt::Arguments<'_>|
+         -> fmt::Result {
+            write!(f, "{sep}{args}")?;
+            sep = " ";
+            Ok(())
+        };
+
+        if let Some(meta_device) = self.meta_device {
+            emit(f, format_args!("meta_device:{meta_device}"))?;
+        }
+        if let Some(block_size) = self.block_size {
+            emit(f, format_args!("block_size:{block_size}"))?;
+        }
+        if self.recalculate {
+            emit(f, format_args!("recalculate"))?;
+        }
+        if self.reset_recalculate {
+            emit(f, format_args!("reset_recalculate"))?;
+        }
+        if self.allow_discards {
+            emit(f, format_args!("allow_discards"))?;
+        }
+        if let Some(sectors) = self.interleave_sectors {
+            emit(f, format_args!("interleave_sectors:{sectors}"))?;
+        }
+        if let Some(sectors) = self.buffer_sectors {
+            emit(f, format_args!("buffer_sectors:{sectors}"))?;
+        }
+        if let Some(sectors) = self.journal_sectors {
+            emit(f, format_args!("journal_sectors:{sectors}"))?;
+        }
+        if let Some(watermark) = self.journal_watermark {
+            emit(f, format_args!("journal_watermark:{watermark}"))?;
+        }
+        if let Some(commit_time) = self.commit_time {
+            emit(f, format_args!("commit_time:{commit_time}"))?;
+        }
+        if let Some(sectors) = self.sectors_per_bit {
+            emit(f, format_args!("sectors_per_bit:{sectors}"))?;
+        }
+        if let Some(interval) = self.bitmap_flush_interval {
+            emit(f, format_args!("bitmap_flush_interval:{interval}"))?;
+        }
+        if self.fix_padding {
+            emit(f, format_args!("fix_padding"))?;
+        }
+        if self.fix_hmac {
+            emit(f, format_args!("fix_hmac"))?;
+        }
+        if self.legacy_recalculate {
+            emit(f, format_args!("legacy_recalculate"))?;
+        }
+        if let Some(alg) = &self.internal_hash {
+            emit(f, format_args!("internal_hash:{alg}"))?;
+        }
+        if let Some(alg) = &self.journal_crypt {
+            emit(f, format_args!("journal_crypt:{alg}"))?;
+        }
+        if let Some(alg) = &self.journal_mac {
+            emit(f, format_args!("journal_mac:{alg}"))?;
+        }
+        for option in &self.other {
+            emit(f, format_args!("{option}"))?;
+        }
+        Ok(())
+    }
+}
+
+/// The parameters of an `integrity` target: `<device> <offset>
+/// <tag_size> <mode> [<num_options> <option> ...]`.
+///
+/// A tag size of zero, written `-` when loading a table, means the
+/// size of the `internal_hash`.  `Display` writes `<num_options>`
+/// only if there are options; each option is one argument, written
+/// `<name>` or `<name>:<value>`.  `Debug` does not show keys.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::{
+///     targets::{
+///         IntegrityAlgorithm, IntegrityMode, IntegrityOptions,
+///         IntegrityTargetParams,
+///     },
+///     Device,
+/// };
+///
+/// let params = IntegrityTargetParams {
+///     dest: Device { major: 8, minor: 16 },
+///     offset: 0,
+///     tag_size: 4,
+///     mode: IntegrityMode::Journal,
+///     options: IntegrityOptions {
+///         internal_hash: Some(IntegrityAlgorithm {
+///             name: "crc32c".to_string(),
+///             key: None,
+///         }),
+///         ..Default::default()
+///     },
+/// };
+/// assert_eq!(params.to_string(), "8:16 0 4 J 1 internal_hash:crc32c");
+/// assert_eq!(params.to_string().parse(), Ok(params));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct IntegrityTargetParams {
+    /// The device holding the data and tags
+    pub dest: Device,
+    /// Offset, in sectors, of the start of the integrity superblock
+    /// within `dest`
+    pub offset: u64,
+    /// Size, in bytes, of the tag stored for each sector
+    pub tag_size: u32,
+    /// How data and tags are kept consistent
+    pub mode: IntegrityMode,
+    /// Optional parameters
+    pub options: IntegrityOptions,
+}
+
+impl IntegrityTargetParams {
+    /// The target type name used by the kernel.
+    pub const TARGET_TYPE: &'static str = "integrity";
+}
+
+impl fmt::Display for IntegrityTargetParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.dest, self.offset, self.tag_size, self.mode
+        )?;
+        let num_options = self.options.len();
+        if num_options > 0 {
+            write!(f, " {num_options} {}", self.options)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for IntegrityTargetParams {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<IntegrityTargetParams> {
+        <Self as TargetParams>::parse(s)
+    }
+}
+
+impl TargetParams for IntegrityTargetParams {
+    fn target_type(&self) -> &str {
+        Self::TARGET_TYPE
+    }
+
+    /// The `params` string, allocated at its exact size up front so
+    /// that no partial copies of the keys are left behind as it
+    /// grows.
+    fn param_str(&self) -> String {
+        to_string_exact(self)
+    }
+
+    fn parse(params: &str) -> DmResult<IntegrityTargetParams> {
+        let fields = params.split_whitespace().collect::<Vec<_>>();
+        let [dest, offset, tag_size, mode, ref rest @ ..] = fields[..] else {
+            return Err(DmError::InvalidArgument(
+                "expected \"<device> <offset> <tag_size> <mode> \
+                 [<num_options> <option> ...]\""
+                    .to_string(),
+            ));
+        };
+
+        let options = match rest.split_first() {
+            None => IntegrityOptions::default(),
+            Some((num_options, options)) => {
+                if parse_num::<usize>(num_options)? != options.len() {
+                    return Err(DmError::InvalidArgument(format!(
+                        "integrity option count {num_options} does not \
+                         match the {} options given",
+                        options.len()
+                    )));
+                }
+                IntegrityOptions::parse(options)?
+            }
+        };
+
+        Ok(IntegrityTargetParams {
+            dest: dest.parse()?,
+            offset: parse_num(offset)?,
+            tag_size: match tag_size {
+                "-" => 0,
+                tag_size => parse_num(tag_size)?,
+            },
+            mode: mode.parse()?,
+            options,
+        })
+    }
+}
@@ -0,0 +1,108 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The `linear` target, which maps a range of sectors onto a
+//! contiguous range of sectors of another block device.
+
+use core::{fmt, str::FromStr};
+use std::path::Path;
+
+use crate::{
+    device::Device,
+    errors::{DmError, DmResult},
+    targets::TargetParams,
+};
+
+#[cfg(test)]
+#[path = "../tests/targets/linear.rs"]
+mod tests;
+
+/// The parameters of a `linear` target.
+///
+/// `Display` and `FromStr` use the `params` string of a table line,
+/// `<major>:<minor> <offset>`.  The kernel also accepts a device path
+/// in place of the device number, but always reports the number, and
+/// a path can come to refer to a different device; use
+/// [`Self::from_path`] to look up the number instead.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::{targets::LinearTargetParams, Device};
+///
+/// let params: LinearTargetParams = "8:16 2048".parse().unwrap();
+/// assert_eq!(params.dest, Device { major: 8, minor: 16 });
+/// assert_eq!(params.to_string(), "8:16 2048");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LinearTargetParams {
+    /// The device that the sectors are mapped onto
+    pub dest: Device,
+    /// Offset, in sectors, of the start of the mapping within `dest`
+    pub offset: u64,
+}
+
+impl LinearTargetParams {
+    /// The target type name used by the kernel.
+    pub const TARGET_TYPE: &'static str = "linear";
+
+    /// Parameters that map onto the block device whose node is at
+    /// `path`, starting `offset` sectors into it.  Fails if there is
+    /// no block device node at `path`.
+    pub fn from_path(path: &Path, offset: u64) -> DmResult<Self> {
+        let dest = Device::from_devnode(path)?.ok_or_else(|| {
+            DmError::InvalidArgument(format!(
+                "{} is not a block device",
+                path.display()
+            ))
+        })?;
+        Ok(LinearTargetParams { dest, offset })
+    }
+}
+
+impl fmt::Display for LinearTargetParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.dest, self.offset)
+    }
+}
+
+impl FromStr for LinearTargetParams {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<LinearTargetParams> {
+        <Self as TargetParams>::parse(s)
+    }
+}
+
+impl TargetParams for LinearTargetParams {
+    fn target_type(&self) -> &str {
+        Self::TARGET_TYPE
+    }
+
+    fn param_str(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(params: &str) -> DmResult<LinearTargetParams> {
+        let mut fields = params.split_whitespace();
+        let (dest, offset) = match (fields.next(), fields.next(), fields.next())
+        {
+            (Some(dest), Some(offset), None) => (dest, offset),
+            _ => {
+                return Err(DmError::InvalidArgument(format!(
+                    "expected \"<device> <offset>\", found {params:?}"
+                )))
+            }
+        };
+
+        Ok(LinearTargetParams {
+            dest: dest.parse()?,
+            offset: offset.parse().map_err(|_| {
+                DmError::InvalidArgument(format!(
+                    "{offset:?} is not a valid sector offset"
+                ))
+            })?,
+        })
+    }
+}
@@ -0,0 +1,103 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The `log-writes` target, which maps onto another block device,
+//! like `linear`, while recording every write, and any marks set by
+//! messages, on a log device so that they can be replayed.
+
+use core::{fmt, str::FromStr};
+
+use crate::{
+    device::Device,
+    errors::{DmError, DmResult},
+    targets::TargetParams,
+};
+
+#[cfg(test)]
+#[path = "../tests/targets/log_writes.rs"]
+mod tests;
+
+/// The parameters of a `log-writes` target: `<dev> <log_dev>`.
+///
+/// [`Self::mark_message`] builds a message that records a mark in
+/// the log, so that a replay can stop at it.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::{targets::LogWritesTargetParams, Device};
+///
+/// let params = LogWritesTargetParams {
+///     dest: Device { major: 8, minor: 16 },
+///     log_dev: Device { major: 8, minor: 32 },
+/// };
+/// assert_eq!(params.to_string(), "8:16 8:32");
+/// assert_eq!(params.to_string().parse(), Ok(params));
+///
+/// let msg = LogWritesTargetParams::mark_message("mkfs").unwrap();
+/// assert_eq!(msg, "mark mkfs");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LogWritesTargetParams {
+    /// The device that the sectors are mapped onto
+    pub dest: Device,
+    /// The device that writes and marks are logged to
+    pub log_dev: Device,
+}
+
+impl LogWritesTargetParams {
+    /// The target type name used by the kernel.
+    pub const TARGET_TYPE: &'static str = "log-writes";
+
+    /// The `mark` message that records `label` in the log, to send
+    /// with [`DM::target_msg`][crate::DM::target_msg].  Fails if
+    /// `label` is empty or contains whitespace, since the kernel
+    /// takes it as a single argument.  The kernel truncates labels
+    /// that do not fit in one sector of the log.
+    pub fn mark_message(label: &str) -> DmResult<String> {
+        if label.is_empty() || label.contains(char::is_whitespace) {
+            return Err(DmError::InvalidArgument(format!(
+                "log-writes mark {label:?} is empty or contains whitespace"
+            )));
+        }
+        Ok(format!("mark {label}"))
+    }
+}
+
+impl fmt::Display for LogWritesTargetParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.dest, self.log_dev)
+    }
+}
+
+impl FromStr for LogWritesTargetParams {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<LogWritesTargetParams> {
+        <Self as TargetParams>::parse(s)
+    }
+}
+
+impl TargetParams for LogWritesTargetParams {
+    fn target_type(&self) -> &str {
+        Self::TARGET_TYPE
+    }
+
+    fn param_str(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(params: &str) -> DmResult<LogWritesTargetParams> {
+        let fields = params.split_whitespace().collect::<Vec<_>>();
+        let [dest, log_dev] = fields[..] else {
+            return Err(DmError::InvalidArgument(format!(
+                "expected \"<dev> <log_dev>\", found {params:?}"
+            )));
+        };
+        Ok(LogWritesTargetParams {
+            dest: dest.parse()?,
+            log_dev: log_dev.parse()?,
+        })
+    }
+}
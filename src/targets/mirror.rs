@@ -0,0 +1,290 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The `mirror` target, which writes the same data to several block
+//! devices, keeping track of which regions are in sync in a dirty
+//! region log.
+
+use core::{fmt, str::FromStr};
+
+use bitflags::bitflags;
+
+use crate::{
+    device::Device,
+    errors::{DmError, DmResult},
+    targets::{parse_num, TargetParams},
+};
+
+#[cfg(test)]
+#[path = "../tests/targets/mirror.rs"]
+mod tests;
+
+/// Where a `mirror` target keeps its dirty region log.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MirrorLog {
+    /// In memory, so that the mirror must be resynchronised whenever
+    /// it is activated (`core`)
+    Core,
+    /// On the given device (`disk`)
+    Disk(Device),
+}
+
+bitflags! {
+    /// The feature arguments of a `mirror` target.
+    #[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+    pub struct MirrorFeatures: u32 {
+        /// Take a failed leg out of the mirror, rather than ignoring
+        /// the failure (`handle_errors`).
+        const HANDLE_ERRORS = 1 << 0;
+        /// Keep using the log device when it fails, rather than
+        /// failing the mirror (`keep_log`).  Requires
+        /// `HANDLE_ERRORS`.
+        const KEEP_LOG      = 1 << 1;
+    }
+}
+
+/// The kernel's names for each of the [`MirrorFeatures`], in the
+/// order it reports them.
+const FEATURE_NAMES: [(MirrorFeatures, &str); 2] = [
+    (MirrorFeatures::HANDLE_ERRORS, "handle_errors"),
+    (MirrorFeatures::KEEP_LOG, "keep_log"),
+];
+
+/// The parameters of a `mirror` target: `<log_type> <num_log_args>
+/// [<log_dev>] <region_size> [sync|nosync] <num_mirrors> <dev>
+/// <offset> ... [<num_features> <feature> ...]`.
+///
+/// `Display` writes `<num_log_args>` and `<num_mirrors>` from the log
+/// and mirrors given, and `<num_features>` only if there are
+/// features, as the kernel does.  The region size, in sectors, must
+/// be a power of two.  Parsing, and [`Self::new`], also reject fewer
+/// than [`Self::MIN_MIRRORS`] or more than [`Self::MAX_MIRRORS`]
+/// mirrors, and `keep_log` without `handle_errors`.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::{
+///     targets::{MirrorFeatures, MirrorLog, MirrorTargetParams},
+///     Device,
+/// };
+///
+/// let params = MirrorTargetParams::new(
+///     MirrorLog::Disk(Device { major: 8, minor: 16 }),
+///     1024,
+///     Some(false),
+///     vec![
+///         (Device { major: 8, minor: 32 }, 0),
+///         (Device { major: 8, minor: 48 }, 0),
+///     ],
+///     MirrorFeatures::HANDLE_ERRORS,
+/// )
+/// .unwrap();
+/// assert_eq!(
+///     params.to_string(),
+///     "disk 3 8:16 1024 nosync 2 8:32 0 8:48 0 1 handle_errors"
+/// );
+/// assert_eq!(params.to_string().parse(), Ok(params));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MirrorTargetParams {
+    /// Where the dirty region log is kept
+    pub log: MirrorLog,
+    /// Size, in sectors, of each region the log tracks
+    pub region_size: u64,
+    /// Whether to copy every region when the mirror is created
+    /// (`sync`), or to assume the legs are already in sync
+    /// (`nosync`); the kernel copies regions the log does not know
+    /// to be in sync if neither is given
+    pub sync: Option<bool>,
+    /// The device and starting sector of each leg
+    pub mirrors: Vec<(Device, u64)>,
+    /// Feature arguments
+    pub features: MirrorFeatures,
+}
+
+impl MirrorTargetParams {
+    /// The target type name used by the kernel.
+    pub const TARGET_TYPE: &'static str = "mirror";
+
+    /// The fewest legs a mirror may have.
+    pub const MIN_MIRRORS: usize = 2;
+
+    /// The most legs the kernel allows a mirror to have.
+    pub const MAX_MIRRORS: usize = 9;
+
+    /// Parameters for a mirror over `mirrors`.  Fails if the
+    /// parameters are invalid, as described above.
+    pub fn new(
+        log: MirrorLog,
+        region_size: u64,
+        sync: Option<bool>,
+        mirrors: Vec<(Device, u64)>,
+        features: MirrorFeatures,
+    ) -> DmResult<MirrorTargetParams> {
+        if !region_size.is_power_of_two() {
+            return Err(DmError::InvalidArgument(format!(
+                "mirror region size {region_size} is not a power of two"
+            )));
+        }
+        if !(Self::MIN_MIRRORS..=Self::MAX_MIRRORS).contains(&mirrors.len()) {
+            return Err(DmError::InvalidArgument(format!(
+                "mirror must have between {} and {} legs, found {}",
+                Self::MIN_MIRRORS,
+                Self::MAX_MIRRORS,
+                mirrors.len()
+            )));
+        }
+        if features.contains(MirrorFeatures::KEEP_LOG)
+            && !features.contains(MirrorFeatures::HANDLE_ERRORS)
+        {
+            return Err(DmError::InvalidArgument(
+                "mirror feature keep_log requires handle_errors".to_string(),
+            ));
+        }
+        Ok(MirrorTargetParams {
+            log,
+            region_size,
+            sync,
+            mirrors,
+            features,
+        })
+    }
+}
+
+impl fmt::Display for MirrorTargetParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let num_log_args = 1
+            + usize::from(matches!(self.log, MirrorLog::Disk(_)))
+            + usize::from(self.sync.is_some());
+        match self.log {
+            MirrorLog::Core => write!(f, "core {num_log_args}")?,
+            MirrorLog::Disk(log_dev) => {
+                write!(f, "disk {num_log_args} {log_dev}")?
+            }
+        }
+        write!(f, " {}", self.region_size)?;
+        match self.sync {
+            Some(true) => write!(f, " sync")?,
+            Some(false) => write!(f, " nosync")?,
+            None => (),
+        }
+
+        write!(f, " {}", self.mirrors.len())?;
+        for (dev, offset) in &self.mirrors {
+            write!(f, " {dev} {offset}")?;
+        }
+
+        if !self.features.is_empty() {
+            write!(f, " {}", self.features.bits().count_ones())?;
+            for (feature, name) in FEATURE_NAMES {
+                if self.features.contains(feature) {
+                    write!(f, " {name}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for MirrorTargetParams {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<MirrorTargetParams> {
+        <Self as TargetParams>::parse(s)
+    }
+}
+
+impl TargetParams for MirrorTargetParams {
+    fn target_type(&self) -> &str {
+        Self::TARGET_TYPE
+    }
+
+    fn param_str(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(params: &str) -> DmResult<MirrorTargetParams> {
+        let bad = || {
+            DmError::InvalidArgument(format!(
+                "expected \"<log_type> <num_log_args> [<log_dev>] \
+                 <region_size> [sync|nosync] <num_mirrors> <dev> <offset> \
+                 ... [<num_features> <feature> ...]\", found {params:?}"
+            ))
+        };
+        let fields = params.split_whitespace().collect::<Vec<_>>();
+        let [log_type, num_log_args, ref rest @ ..] = fields[..] else {
+            return Err(bad());
+        };
+        let num_log_args = parse_num::<usize>(num_log_args)?;
+        if rest.len() <= num_log_args {
+            return Err(bad());
+        }
+        let (log_args, rest) = rest.split_at(num_log_args);
+
+        let (log, log_args) = match (log_type, log_args) {
+            ("core", log_args) => (MirrorLog::Core, log_args),
+            ("disk", [log_dev, log_args @ ..]) => {
+                (MirrorLog::Disk(log_dev.parse()?), log_args)
+            }
+            ("disk", []) => return Err(bad()),
+            _ => {
+                return Err(DmError::InvalidArgument(format!(
+                    "unrecognised mirror log type {log_type:?}"
+                )))
+            }
+        };
+        let (region_size, sync) = match *log_args {
+            [region_size] => (region_size, None),
+            [region_size, "sync"] => (region_size, Some(true)),
+            [region_size, "nosync"] => (region_size, Some(false)),
+            _ => {
+                return Err(DmError::InvalidArgument(format!(
+                    "expected \"<region_size> [sync|nosync]\" mirror log \
+                     arguments, found {log_args:?}"
+                )))
+            }
+        };
+
+        let (num_mirrors, rest) = rest.split_first().ok_or_else(bad)?;
+        let num_mirrors = parse_num::<usize>(num_mirrors)?;
+        if num_mirrors > rest.len() / 2 {
+            return Err(bad());
+        }
+        let (mirror_args, rest) = rest.split_at(2 * num_mirrors);
+        let mirrors = mirror_args
+            .chunks_exact(2)
+            .map(|pair| Ok((pair[0].parse()?, parse_num(pair[1])?)))
+            .collect::<DmResult<_>>()?;
+
+        let mut features = MirrorFeatures::empty();
+        if let Some((num_features, names)) = rest.split_first() {
+            if parse_num::<usize>(num_features)? != names.len() {
+                return Err(DmError::InvalidArgument(format!(
+                    "mirror feature count {num_features} does not match \
+                     {names:?}"
+                )));
+            }
+            for name in names {
+                let (feature, _) = FEATURE_NAMES
+                    .iter()
+                    .find(|(_, known)| known == name)
+                    .ok_or_else(|| {
+                        DmError::InvalidArgument(format!(
+                            "unrecognised mirror feature {name:?}"
+                        ))
+                    })?;
+                features |= *feature;
+            }
+        }
+
+        MirrorTargetParams::new(
+            log,
+            parse_num(region_size)?,
+            sync,
+            mirrors,
+            features,
+        )
+    }
+}
@@ -0,0 +1,511 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The `multipath` target, which sends I/O for one device down any
+//! of several paths to it, grouped by priority, failing over between
+//! paths and groups as they fail.
+
+use core::{fmt, str::FromStr};
+
+use crate::{
+    device::Device,
+    errors::{DmError, DmResult},
+    targets::{parse_num, set_flag_once, set_once, TargetParams},
+};
+
+#[cfg(test)]
+#[path = "../tests/targets/multipath.rs"]
+mod tests;
+
+/// How a `multipath` target queues I/O.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MultipathQueueMode {
+    /// Queue bios, without a request queue (`bio`)
+    Bio,
+    /// Queue requests (`rq`); current kernels treat this as `mq`
+    Rq,
+    /// Queue requests through blk-mq (`mq`)
+    Mq,
+}
+
+impl fmt::Display for MultipathQueueMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MultipathQueueMode::Bio => f.write_str("bio"),
+            MultipathQueueMode::Rq => f.write_str("rq"),
+            MultipathQueueMode::Mq => f.write_str("mq"),
+        }
+    }
+}
+
+impl FromStr for MultipathQueueMode {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<MultipathQueueMode> {
+        match s {
+            "bio" => Ok(MultipathQueueMode::Bio),
+            "rq" => Ok(MultipathQueueMode::Rq),
+            "mq" => Ok(MultipathQueueMode::Mq),
+            _ => Err(DmError::InvalidArgument(format!(
+                "multipath queue mode must be bio, rq or mq, found {s:?}"
+            ))),
+        }
+    }
+}
+
+/// The feature arguments of a `multipath` target.  Features that are
+/// `None` or `false` are not passed to the kernel, which then uses
+/// its defaults.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MultipathFeatures {
+    /// Queue I/O while there are no working paths, rather than
+    /// failing it (`queue_if_no_path`)
+    pub queue_if_no_path: bool,
+    /// Retry path group initialisation this many times
+    /// (`pg_init_retries`)
+    pub pg_init_retries: Option<u32>,
+    /// Wait this many milliseconds between attempts to initialise a
+    /// path group (`pg_init_delay_msecs`)
+    pub pg_init_delay_msecs: Option<u32>,
+    /// Use the hardware handler already attached to each path, if
+    /// any, rather than the one given (`retain_attached_hw_handler`)
+    pub retain_attached_hw_handler: bool,
+    /// How I/O is queued (`queue_mode`)
+    pub queue_mode: Option<MultipathQueueMode>,
+}
+
+impl MultipathFeatures {
+    /// The largest allowed `pg_init_retries`.
+    pub const MAX_PG_INIT_RETRIES: u32 = 50;
+
+    /// The largest allowed `pg_init_delay_msecs`.
+    pub const MAX_PG_INIT_DELAY_MSECS: u32 = 60000;
+
+    /// The number of arguments the features take up, which is what
+    /// the kernel expects for `<num_features>`: two for each feature
+    /// with a value, and one for each flag.
+    fn num_args(&self) -> usize {
+        let values = [
+            self.pg_init_retries.is_some(),
+            self.pg_init_delay_msecs.is_some(),
+            self.queue_mode.is_some(),
+        ];
+        let flags = [self.queue_if_no_path, self.retain_attached_hw_handler];
+        2 * values.iter().filter(|set| **set).count()
+            + flags.iter().filter(|set| **set).count()
+    }
+
+    /// Returns an error if the kernel would reject these features.
+    fn check(&self) -> DmResult<()> {
+        if let Some(retries) = self.pg_init_retries {
+            if retries > Self::MAX_PG_INIT_RETRIES {
+                return Err(DmError::InvalidArgument(format!(
+                    "multipath pg_init_retries {retries} is greater than {}",
+                    Self::MAX_PG_INIT_RETRIES
+                )));
+            }
+        }
+        if let Some(delay) = self.pg_init_delay_msecs {
+            if delay > Self::MAX_PG_INIT_DELAY_MSECS {
+                return Err(DmError::InvalidArgument(format!(
+                    "multipath pg_init_delay_msecs {delay} is greater than {}",
+                    Self::MAX_PG_INIT_DELAY_MSECS
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse the feature arguments of a `multipath` target, which may
+    /// be given in any order, but at most once each.
+    fn parse(mut args: &[&str]) -> DmResult<MultipathFeatures> {
+        let mut features = MultipathFeatures::default();
+        while let Some((&name, rest)) = args.split_first() {
+            let what = &format!("multipath feature {name}");
+            args = match (name, rest) {
+                ("queue_if_no_path", rest) => {
+                    set_flag_once(&mut features.queue_if_no_path, what)?;
+                    rest
+                }
+                ("retain_attached_hw_handler", rest) => {
+                    set_flag_once(
+                        &mut features.retain_attached_hw_handler,
+                        what,
+                    )?;
+                    rest
+                }
+                ("pg_init_retries", [value, rest @ ..]) => {
+                    let retries = parse_num(value)?;
+                    set_once(&mut features.pg_init_retries, retries, what)?;
+                    rest
+                }
+                ("pg_init_delay_msecs", [value, rest @ ..]) => {
+                    let delay = parse_num(value)?;
+                    set_once(&mut features.pg_init_delay_msecs, delay, what)?;
+                    rest
+                }
+                ("queue_mode", [value, rest @ ..]) => {
+                    set_once(&mut features.queue_mode, value.parse()?, what)?;
+                    rest
+                }
+                _ => {
+                    return Err(DmError::InvalidArgument(format!(
+                        "unrecognised or incomplete multipath feature at \
+                         {args:?}"
+                    )))
+                }
+            };
+        }
+        Ok(features)
+    }
+}
+
+/// Writes the features in the kernel's order, preceded by
+/// `<num_features>`.
+impl fmt::Display for MultipathFeatures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.num_args())?;
+        if self.queue_if_no_path {
+            write!(f, " queue_if_no_path")?;
+        }
+        if let Some(retries) = self.pg_init_retries {
+            write!(f, " pg_init_retries {retries}")?;
+        }
+        if let Some(delay) = self.pg_init_delay_msecs {
+            write!(f, " pg_init_delay_msecs {delay}")?;
+        }
+        if self.retain_attached_hw_handler {
+            write!(f, " retain_attached_hw_handler")?;
+        }
+        if let Some(queue_mode) = self.queue_mode {
+            write!(f, " queue_mode {queue_mode}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The hardware handler of a `multipath` target, which knows how to
+/// switch paths on a particular kind of storage array.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MultipathHwHandler {
+    /// The handler's name, such as `alua` or `emc`
+    pub name: String,
+    /// Arguments for the handler
+    pub args: Vec<String>,
+}
+
+/// One path of a `multipath` target.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MultipathPath {
+    /// The device the path leads to
+    pub dev: Device,
+    /// Arguments for the path selector, such as the `round-robin`
+    /// repeat count
+    pub selector_args: Vec<String>,
+}
+
+/// One priority group of a `multipath` target: a set of paths, and
+/// the path selector that chooses between them.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MultipathPriorityGroup {
+    /// The path selector's name, such as `round-robin` or
+    /// `service-time`
+    pub selector: String,
+    /// Arguments for the path selector
+    pub selector_args: Vec<String>,
+    /// The paths in the group.  Every path must have the same number
+    /// of selector arguments.
+    pub paths: Vec<MultipathPath>,
+}
+
+/// The parameters of a `multipath` target: `<num_features>
+/// <feature> ... <num_hw_handler_args> [<hw_handler> <arg> ...]
+/// <num_groups> <initial_group>` followed, for each priority group,
+/// by `<selector> <num_selector_args> <arg> ... <num_paths>
+/// <num_path_args>` and, for each path, `<dev> <arg> ...`.
+///
+/// `Display` writes every count from what follows it, including
+/// `<num_hw_handler_args>`, which counts the handler's name.  The
+/// selectors, handler, and their arguments are not interpreted, so
+/// selectors this crate does not know about work too.  The kernel
+/// reports neither the handler's arguments nor the groups' selector
+/// arguments in its table, so parameters read back from a device may
+/// differ from those loaded.
+///
+/// The initial group counts from 1, and must be 0 exactly when there
+/// are no groups.  Parsing, and [`Self::new`], also reject an initial
+/// group past the last, paths within one group with different numbers
+/// of arguments, names and arguments that are empty or contain
+/// whitespace, and features out of range.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::{
+///     targets::{
+///         MultipathFeatures, MultipathPath, MultipathPriorityGroup,
+///         MultipathTargetParams,
+///     },
+///     Device,
+/// };
+///
+/// let path = |minor| MultipathPath {
+///     dev: Device { major: 8, minor },
+///     selector_args: vec!["1000".to_string()],
+/// };
+/// let params = MultipathTargetParams::new(
+///     MultipathFeatures {
+///         queue_if_no_path: true,
+///         ..Default::default()
+///     },
+///     None,
+///     1,
+///     vec![MultipathPriorityGroup {
+///         selector: "round-robin".to_string(),
+///         selector_args: vec![],
+///         paths: vec![path(16), path(32)],
+///     }],
+/// )
+/// .unwrap();
+/// assert_eq!(
+///     params.to_string(),
+///     "1 queue_if_no_path 0 1 1 round-robin 0 2 1 8:16 1000 8:32 1000"
+/// );
+/// assert_eq!(params.to_string().parse(), Ok(params));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MultipathTargetParams {
+    /// Feature arguments
+    pub features: MultipathFeatures,
+    /// The hardware handler, if any
+    pub hw_handler: Option<MultipathHwHandler>,
+    /// The priority group to use first, counting from 1
+    pub initial_group: u32,
+    /// The priority groups, in order of priority
+    pub priority_groups: Vec<MultipathPriorityGroup>,
+}
+
+impl MultipathTargetParams {
+    /// The target type name used by the kernel.
+    pub const TARGET_TYPE: &'static str = "multipath";
+
+    /// Parameters for a multipath device over `priority_groups`.
+    /// Fails if the parameters are invalid, as described above.
+    pub fn new(
+        features: MultipathFeatures,
+        hw_handler: Option<MultipathHwHandler>,
+        initial_group: u32,
+        priority_groups: Vec<MultipathPriorityGroup>,
+    ) -> DmResult<MultipathTargetParams> {
+        let params = MultipathTargetParams {
+            features,
+            hw_handler,
+            initial_group,
+            priority_groups,
+        };
+        params.check()?;
+        Ok(params)
+    }
+
+    /// Returns an error if the kernel would reject these parameters,
+    /// or if they could not be written as a `params` string.
+    fn check(&self) -> DmResult<()> {
+        self.features.check()?;
+
+        let num_groups = self.priority_groups.len();
+        if (num_groups == 0) != (self.initial_group == 0)
+            || self.initial_group as usize > num_groups
+        {
+            return Err(DmError::InvalidArgument(format!(
+                "multipath initial group {} is invalid for {num_groups} \
+                 groups",
+                self.initial_group
+            )));
+        }
+
+        for group in &self.priority_groups {
+            if let Some(path) = group.paths.iter().find(|path| {
+                path.selector_args.len() != group.paths[0].selector_args.len()
+            }) {
+                return Err(DmError::InvalidArgument(format!(
+                    "multipath path {} has a different number of selector \
+                     arguments from the other paths in its group",
+                    path.dev
+                )));
+            }
+        }
+
+        let handler_words = self.hw_handler.iter().flat_map(|handler| {
+            Some(&handler.name).into_iter().chain(&handler.args)
+        });
+        let group_words = self.priority_groups.iter().flat_map(|group| {
+            Some(&group.selector)
+                .into_iter()
+                .chain(&group.selector_args)
+                .chain(group.paths.iter().flat_map(|path| &path.selector_args))
+        });
+        for word in handler_words.chain(group_words) {
+            if word.is_empty() || word.contains(char::is_whitespace) {
+                return Err(DmError::InvalidArgument(format!(
+                    "multipath name or argument {word:?} is empty or \
+                     contains whitespace"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for MultipathTargetParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.features)?;
+        match &self.hw_handler {
+            None => write!(f, " 0")?,
+            Some(handler) => {
+                write!(f, " {} {}", 1 + handler.args.len(), handler.name)?;
+                for arg in &handler.args {
+                    write!(f, " {arg}")?;
+                }
+            }
+        }
+        write!(f, " {} {}", self.priority_groups.len(), self.initial_group)?;
+
+        for group in &self.priority_groups {
+            write!(f, " {} {}", group.selector, group.selector_args.len())?;
+            for arg in &group.selector_args {
+                write!(f, " {arg}")?;
+            }
+            let num_path_args = group
+                .paths
+                .first()
+                .map_or(0, |path| path.selector_args.len());
+            write!(f, " {} {num_path_args}", group.paths.len())?;
+            for path in &group.paths {
+                write!(f, " {}", path.dev)?;
+                for arg in &path.selector_args {
+                    write!(f, " {arg}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for MultipathTargetParams {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<MultipathTargetParams> {
+        <Self as TargetParams>::parse(s)
+    }
+}
+
+impl TargetParams for MultipathTargetParams {
+    fn target_type(&self) -> &str {
+        Self::TARGET_TYPE
+    }
+
+    fn param_str(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(params: &str) -> DmResult<MultipathTargetParams> {
+        /// Take the next field from `fields`, naming it `what` in the
+        /// error if there is none.
+        fn take<'a>(fields: &mut &[&'a str], what: &str) -> DmResult<&'a str> {
+            let (first, rest) = fields.split_first().ok_or_else(|| {
+                DmError::InvalidArgument(format!(
+                    "multipath parameters end before {what}"
+                ))
+            })?;
+            *fields = rest;
+            Ok(first)
+        }
+
+        /// Take `count` fields from `fields`, naming them `what` in
+        /// the error if there are too few.
+        fn take_n<'a, 'b>(
+            fields: &mut &'b [&'a str],
+            count: usize,
+            what: &str,
+        ) -> DmResult<&'b [&'a str]> {
+            if fields.len() < count {
+                return Err(DmError::InvalidArgument(format!(
+                    "multipath parameters end within {what}"
+                )));
+            }
+            let (taken, rest) = fields.split_at(count);
+            *fields = rest;
+            Ok(taken)
+        }
+
+        /// Take a count, and then that many fields.
+        fn take_counted<'a, 'b>(
+            fields: &mut &'b [&'a str],
+            what: &str,
+        ) -> DmResult<&'b [&'a str]> {
+            let count = parse_num(take(fields, what)?)?;
+            take_n(fields, count, what)
+        }
+
+        let to_strings = |args: &[&str]| {
+            args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>()
+        };
+
+        let all_fields = params.split_whitespace().collect::<Vec<_>>();
+        let mut fields = &all_fields[..];
+
+        let features =
+            MultipathFeatures::parse(take_counted(&mut fields, "features")?)?;
+        let hw_handler = match take_counted(&mut fields, "hardware handler")? {
+            [] => None,
+            [name, args @ ..] => Some(MultipathHwHandler {
+                name: name.to_string(),
+                args: to_strings(args),
+            }),
+        };
+        let num_groups = parse_num(take(&mut fields, "priority groups")?)?;
+        let initial_group = parse_num(take(&mut fields, "initial group")?)?;
+
+        let mut priority_groups = Vec::new();
+        for _ in 0..num_groups {
+            let selector = take(&mut fields, "path selector")?;
+            let selector_args =
+                take_counted(&mut fields, "path selector arguments")?;
+            let num_paths = parse_num(take(&mut fields, "paths")?)?;
+            let num_path_args =
+                parse_num::<usize>(take(&mut fields, "path arguments")?)?;
+            let paths = (0..num_paths)
+                .map(|_| {
+                    Ok(MultipathPath {
+                        dev: take(&mut fields, "path")?.parse()?,
+                        selector_args: to_strings(take_n(
+                            &mut fields,
+                            num_path_args,
+                            "path arguments",
+                        )?),
+                    })
+                })
+                .collect::<DmResult<_>>()?;
+            priority_groups.push(MultipathPriorityGroup {
+                selector: selector.to_string(),
+                selector_args: to_strings(selector_args),
+                paths,
+            });
+        }
+
+        if !fields.is_empty() {
+            return Err(DmError::InvalidArgument(format!(
+                "unexpected fields {fields:?} after multipath priority \
+                 groups"
+            )));
+        }
+
+        MultipathTargetParams::new(
+            features,
+            hw_handler,
+            initial_group,
+            priority_groups,
+        )
+    }
+}
@@ -0,0 +1,908 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The `raid` target, which uses the kernel's MD RAID code to combine
+//! several block devices into one.
+
+use core::{fmt, str::FromStr};
+
+use crate::{
+    device::Device,
+    errors::{DmError, DmResult},
+    targets::{parse_num, set_once, TargetParams},
+};
+
+#[cfg(test)]
+#[path = "../tests/targets/raid.rs"]
+mod tests;
+
+/// The RAID level and layout of a `raid` target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RaidType {
+    /// Striping, with no redundancy (`raid0`)
+    Raid0,
+    /// Mirroring (`raid1`)
+    Raid1,
+    /// Striped mirrors (`raid10`)
+    Raid10,
+    /// Dedicated parity on the first device (`raid4`)
+    Raid4,
+    /// Dedicated parity on the last device (`raid5_n`)
+    Raid5N,
+    /// Rotating parity, left asymmetric (`raid5_la`)
+    Raid5La,
+    /// Rotating parity, right asymmetric (`raid5_ra`)
+    Raid5Ra,
+    /// Rotating parity, left symmetric (`raid5_ls`, or `raid5`)
+    Raid5Ls,
+    /// Rotating parity, right symmetric (`raid5_rs`)
+    Raid5Rs,
+    /// Rotating parity, zero restart (`raid6_zr`, or `raid6`)
+    Raid6Zr,
+    /// Rotating parity, N restart (`raid6_nr`)
+    Raid6Nr,
+    /// Rotating parity, N continue (`raid6_nc`)
+    Raid6Nc,
+    /// Dedicated parity on the last two devices (`raid6_n_6`)
+    Raid6N6,
+    /// Left asymmetric with Q on the last device (`raid6_la_6`)
+    Raid6La6,
+    /// Right asymmetric with Q on the last device (`raid6_ra_6`)
+    Raid6Ra6,
+    /// Left symmetric with Q on the last device (`raid6_ls_6`)
+    Raid6Ls6,
+    /// Right symmetric with Q on the last device (`raid6_rs_6`)
+    Raid6Rs6,
+}
+
+/// The kernel's names for each [`RaidType`], and the minimum number
+/// of devices each needs.
+const RAID_TYPES: [(RaidType, &str, usize); 17] = [
+    (RaidType::Raid0, "raid0", 2),
+    (RaidType::Raid1, "raid1", 2),
+    (RaidType::Raid10, "raid10", 2),
+    (RaidType::Raid4, "raid4", 2),
+    (RaidType::Raid5N, "raid5_n", 2),
+    (RaidType::Raid5La, "raid5_la", 2),
+    (RaidType::Raid5Ra, "raid5_ra", 2),
+    (RaidType::Raid5Ls, "raid5_ls", 2),
+    (RaidType::Raid5Rs, "raid5_rs", 2),
+    (RaidType::Raid6Zr, "raid6_zr", 4),
+    (RaidType::Raid6Nr, "raid6_nr", 4),
+    (RaidType::Raid6Nc, "raid6_nc", 4),
+    (RaidType::Raid6N6, "raid6_n_6", 4),
+    (RaidType::Raid6La6, "raid6_la_6", 4),
+    (RaidType::Raid6Ra6, "raid6_ra_6", 4),
+    (RaidType::Raid6Ls6, "raid6_ls_6", 4),
+    (RaidType::Raid6Rs6, "raid6_rs_6", 4),
+];
+
+impl RaidType {
+    /// The smallest number of devices the kernel accepts for this
+    /// type.
+    pub fn min_devices(self) -> usize {
+        let (_, _, min_devices) = RAID_TYPES
+            .iter()
+            .find(|(raid_type, _, _)| *raid_type == self)
+            .expect("every raid type has an entry");
+        *min_devices
+    }
+
+    /// Whether this type keeps parity, and so can have a journal.
+    fn has_parity(self) -> bool {
+        !matches!(self, RaidType::Raid0 | RaidType::Raid1 | RaidType::Raid10)
+    }
+}
+
+impl fmt::Display for RaidType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (_, name, _) = RAID_TYPES
+            .iter()
+            .find(|(raid_type, _, _)| raid_type == self)
+            .expect("every raid type has a name");
+        f.write_str(name)
+    }
+}
+
+/// Also accepts the kernel's aliases `raid5` and `raid6`.
+impl FromStr for RaidType {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<RaidType> {
+        match s {
+            "raid5" => return Ok(RaidType::Raid5Ls),
+            "raid6" => return Ok(RaidType::Raid6Zr),
+            _ => (),
+        }
+        RAID_TYPES
+            .iter()
+            .find(|(_, name, _)| *name == s)
+            .map(|(raid_type, _, _)| *raid_type)
+            .ok_or_else(|| {
+                DmError::InvalidArgument(format!(
+                    "unrecognised raid type {s:?}"
+                ))
+            })
+    }
+}
+
+/// How the copies of a `raid10` target are laid out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Raid10Format {
+    /// Copies of a chunk are on adjacent devices (`near`)
+    Near,
+    /// Copies of a chunk are in different parts of the devices
+    /// (`far`)
+    Far,
+    /// Copies of a chunk are in adjacent stripes (`offset`)
+    Offset,
+}
+
+impl fmt::Display for Raid10Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Raid10Format::Near => f.write_str("near"),
+            Raid10Format::Far => f.write_str("far"),
+            Raid10Format::Offset => f.write_str("offset"),
+        }
+    }
+}
+
+impl FromStr for Raid10Format {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<Raid10Format> {
+        match s {
+            "near" => Ok(Raid10Format::Near),
+            "far" => Ok(Raid10Format::Far),
+            "offset" => Ok(Raid10Format::Offset),
+            _ => Err(DmError::InvalidArgument(format!(
+                "raid10 format must be near, far or offset, found {s:?}"
+            ))),
+        }
+    }
+}
+
+/// How the journal device of a `raid4`, `raid5` or `raid6` target is
+/// used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RaidJournalMode {
+    /// Writes complete once they reach the array (`writethrough`)
+    WriteThrough,
+    /// Writes complete once they reach the journal (`writeback`)
+    WriteBack,
+}
+
+impl fmt::Display for RaidJournalMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RaidJournalMode::WriteThrough => f.write_str("writethrough"),
+            RaidJournalMode::WriteBack => f.write_str("writeback"),
+        }
+    }
+}
+
+impl FromStr for RaidJournalMode {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<RaidJournalMode> {
+        match s {
+            "writethrough" => Ok(RaidJournalMode::WriteThrough),
+            "writeback" => Ok(RaidJournalMode::WriteBack),
+            _ => Err(DmError::InvalidArgument(format!(
+                "raid journal mode must be writethrough or writeback, \
+                 found {s:?}"
+            ))),
+        }
+    }
+}
+
+/// The optional parameters of a `raid` target.  Parameters that are
+/// `None` or empty are not passed to the kernel, which then uses its
+/// defaults.  Sizes are in sectors.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct RaidOptions {
+    /// Whether to synchronise the array when it is created (`sync`)
+    /// or to assume it is already in sync (`nosync`)
+    pub sync: Option<bool>,
+    /// Indices of devices to rebuild (`rebuild`)
+    pub rebuild: Vec<usize>,
+    /// Interval, in milliseconds, between clearing bits of the write
+    /// intent bitmap (`daemon_sleep`)
+    pub daemon_sleep: Option<u64>,
+    /// Minimum recovery rate, in KiB per second per device
+    /// (`min_recovery_rate`)
+    pub min_recovery_rate: Option<u64>,
+    /// Maximum recovery rate, in KiB per second per device
+    /// (`max_recovery_rate`)
+    pub max_recovery_rate: Option<u64>,
+    /// Indices of `raid1` devices to avoid reading from
+    /// (`write_mostly`)
+    pub write_mostly: Vec<usize>,
+    /// The largest number of outstanding writes to write-mostly
+    /// devices (`max_write_behind`)
+    pub max_write_behind: Option<u64>,
+    /// Size of the stripe cache of a `raid4`, `raid5` or `raid6`
+    /// target (`stripe_cache`)
+    pub stripe_cache: Option<u64>,
+    /// Size of the region each bit of the write intent bitmap covers
+    /// (`region_size`)
+    pub region_size: Option<u64>,
+    /// Number of copies of each chunk of a `raid10` target
+    /// (`raid10_copies`)
+    pub raid10_copies: Option<usize>,
+    /// Layout of the copies of a `raid10` target (`raid10_format`)
+    pub raid10_format: Option<Raid10Format>,
+    /// Number of devices being added, or if negative, removed, by a
+    /// reshape (`delta_disks`)
+    pub delta_disks: Option<i64>,
+    /// Offset of the data on each device, for reshaping
+    /// (`data_offset`)
+    pub data_offset: Option<u64>,
+    /// Device holding the journal of a `raid4`, `raid5` or `raid6`
+    /// target (`journal_dev`)
+    pub journal_dev: Option<Device>,
+    /// How the journal is used (`journal_mode`)
+    pub journal_mode: Option<RaidJournalMode>,
+}
+
+impl RaidOptions {
+    /// The number of arguments the options take up: two for each
+    /// option with a value, and one for `sync` or `nosync`.
+    fn num_args(&self) -> usize {
+        let values = [
+            self.daemon_sleep.is_some(),
+            self.min_recovery_rate.is_some(),
+            self.max_recovery_rate.is_some(),
+            self.max_write_behind.is_some(),
+            self.stripe_cache.is_some(),
+            self.region_size.is_some(),
+            self.raid10_copies.is_some(),
+            self.raid10_format.is_some(),
+            self.delta_disks.is_some(),
+            self.data_offset.is_some(),
+            self.journal_dev.is_some(),
+            self.journal_mode.is_some(),
+        ];
+        let count = values.iter().filter(|set| **set).count()
+            + self.rebuild.len()
+            + self.write_mostly.len();
+        2 * count + usize::from(self.sync.is_some())
+    }
+
+    /// Returns an error if the kernel would reject these options for
+    /// an array of type `raid_type` with `num_devices` devices.
+    fn check(&self, raid_type: RaidType, num_devices: usize) -> DmResult<()> {
+        let invalid = |msg: String| Err(DmError::InvalidArgument(msg));
+
+        if let Some(index) = self
+            .rebuild
+            .iter()
+            .chain(&self.write_mostly)
+            .find(|index| **index >= num_devices)
+        {
+            return invalid(format!(
+                "raid device index {index} is out of range for \
+                 {num_devices} devices"
+            ));
+        }
+        if !self.write_mostly.is_empty() && raid_type != RaidType::Raid1 {
+            return invalid(format!(
+                "write_mostly is only valid for raid1, not {raid_type}"
+            ));
+        }
+        if let Some(region_size) = self.region_size {
+            if !region_size.is_power_of_two() {
+                return invalid(format!(
+                    "raid region size {region_size} is not a power of two"
+                ));
+            }
+        }
+        if let (Some(min), Some(max)) =
+            (self.min_recovery_rate, self.max_recovery_rate)
+        {
+            if min > max {
+                return invalid(format!(
+                    "raid min_recovery_rate {min} is greater than \
+                     max_recovery_rate {max}"
+                ));
+            }
+        }
+        if raid_type != RaidType::Raid10 {
+            if self.raid10_copies.is_some() || self.raid10_format.is_some() {
+                return invalid(format!(
+                    "raid10_copies and raid10_format are only valid for \
+                     raid10, not {raid_type}"
+                ));
+            }
+        } else if let Some(copies) = self.raid10_copies {
+            if copies < 2 || copies > num_devices {
+                return invalid(format!(
+                    "raid10_copies {copies} must be between 2 and the \
+                     number of devices, {num_devices}"
+                ));
+            }
+        }
+        if self.journal_dev.is_some() && !raid_type.has_parity() {
+            return invalid(format!(
+                "journal_dev is only valid for raid4, raid5 and raid6, \
+                 not {raid_type}"
+            ));
+        }
+        if self.journal_mode.is_some() && self.journal_dev.is_none() {
+            return invalid("raid journal_mode requires journal_dev".into());
+        }
+        Ok(())
+    }
+
+    /// Parse the optional parameters of a `raid` target, which may be
+    /// given in any order.  `rebuild` and `write_mostly` may be
+    /// repeated; every other option may be given at most once.
+    fn parse(mut args: &[&str]) -> DmResult<RaidOptions> {
+        let mut options = RaidOptions::default();
+        while let Some((&name, rest)) = args.split_first() {
+            let what = &format!("raid parameter {name}");
+            args = match (name, rest) {
+                ("sync", rest) => {
+                    set_once(
+                        &mut options.sync,
+                        true,
+                        "raid parameter sync or nosync",
+                    )?;
+                    rest
+                }
+                ("nosync", rest) => {
+                    set_once(
+                        &mut options.sync,
+                        false,
+                        "raid parameter sync or nosync",
+                    )?;
+                    rest
+                }
+                ("rebuild", [value, rest @ ..]) => {
+                    options.rebuild.push(parse_num(value)?);
+                    rest
+                }
+                ("write_mostly", [value, rest @ ..]) => {
+                    options.write_mostly.push(parse_num(value)?);
+                    rest
+                }
+                ("daemon_sleep", [value, rest @ ..]) => {
+                    set_once(
+                        &mut options.daemon_sleep,
+                        parse_num(value)?,
+                        what,
+                    )?;
+                    rest
+                }
+                ("min_recovery_rate", [value, rest @ ..]) => {
+                    let rate = parse_num(value)?;
+                    set_once(&mut options.min_recovery_rate, rate, what)?;
+                    rest
+                }
+                ("max_recovery_rate", [value, rest @ ..]) => {
+                    let rate = parse_num(value)?;
+                    set_once(&mut options.max_recovery_rate, rate, what)?;
+                    rest
+                }
+                ("max_write_behind", [value, rest @ ..]) => {
+                    let writes = parse_num(value)?;
+                    set_once(&mut options.max_write_behind, writes, what)?;
+                    rest
+                }
+                ("stripe_cache", [value, rest @ ..]) => {
+                    set_once(
+                        &mut options.stripe_cache,
+                        parse_num(value)?,
+                        what,
+                    )?;
+                    rest
+                }
+                ("region_size", [value, rest @ ..]) => {
+                    set_once(
+                        &mut options.region_size,
+                        parse_num(value)?,
+                        what,
+                    )?;
+                    rest
+                }
+                ("raid10_copies", [value, rest @ ..]) => {
+                    set_once(
+                        &mut options.raid10_copies,
+                        parse_num(value)?,
+                        what,
+                    )?;
+                    rest
+                }
+                ("raid10_format", [value, rest @ ..]) => {
+                    set_once(&mut options.raid10_format, value.parse()?, what)?;
+                    rest
+                }
+                ("delta_disks", [value, rest @ ..]) => {
+                    set_once(
+                        &mut options.delta_disks,
+                        parse_num(value)?,
+                        what,
+                    )?;
+                    rest
+                }
+                ("data_offset", [value, rest @ ..]) => {
+                    set_once(
+                        &mut options.data_offset,
+                        parse_num(value)?,
+                        what,
+                    )?;
+                    rest
+                }
+                ("journal_dev", [value, rest @ ..]) => {
+                    set_once(&mut options.journal_dev, value.parse()?, what)?;
+                    rest
+                }
+                ("journal_mode", [value, rest @ ..]) => {
+                    set_once(&mut options.journal_mode, value.parse()?, what)?;
+                    rest
+                }
+                _ => {
+                    return Err(DmError::InvalidArgument(format!(
+                        "unrecognised or incomplete raid parameter at \
+                         {args:?}"
+                    )))
+                }
+            };
+        }
+        Ok(options)
+    }
+}
+
+/// Writes the options in the kernel's order, each preceded by a
+/// space, with `rebuild` and `write_mostly` indices in ascending
+/// order.
+impl fmt::Display for RaidOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.sync {
+            Some(true) => write!(f, " sync")?,
+            Some(false) => write!(f, " nosync")?,
+            None => (),
+        }
+        let mut rebuild = self.rebuild.clone();
+        rebuild.sort_unstable();
+        for index in rebuild {
+            write!(f, " rebuild {index}")?;
+        }
+        if let Some(daemon_sleep) = self.daemon_sleep {
+            write!(f, " daemon_sleep {daemon_sleep}")?;
+        }
+        if let Some(rate) = self.min_recovery_rate {
+            write!(f, " min_recovery_rate {rate}")?;
+        }
+        if let Some(rate) = self.max_recovery_rate {
+            write!(f, " max_recovery_rate {rate}")?;
+        }
+        let mut write_mostly = self.write_mostly.clone();
+        write_mostly.sort_unstable();
+        for index in write_mostly {
+            write!(f, " write_mostly {index}")?;
+        }
+        if let Some(writes) = self.max_write_behind {
+            write!(f, " max_write_behind {writes}")?;
+        }
+        if let Some(stripe_cache) = self.stripe_cache {
+            write!(f, " stripe_cache {stripe_cache}")?;
+        }
+        if let Some(region_size) = self.region_size {
+            write!(f, " region_size {region_size}")?;
+        }
+        if let Some(copies) = self.raid10_copies {
+            write!(f, " raid10_copies {copies}")?;
+        }
+        if let Some(format) = self.raid10_format {
+            write!(f, " raid10_format {format}")?;
+        }
+        if let Some(delta_disks) = self.delta_disks {
+            write!(f, " delta_disks {delta_disks}")?;
+        }
+        if let Some(data_offset) = self.data_offset {
+            write!(f, " data_offset {data_offset}")?;
+        }
+        if let Some(journal_dev) = self.journal_dev {
+            write!(f, " journal_dev {journal_dev}")?;
+        }
+        if let Some(journal_mode) = self.journal_mode {
+            write!(f, " journal_mode {journal_mode}")?;
+        }
+        Ok(())
+    }
+}
+
+/// One member of a `raid` target: its metadata and data devices,
+/// either of which may be absent (`-`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RaidDevice {
+    /// The device holding the superblock and write intent bitmap
+    pub metadata_dev: Option<Device>,
+    /// The device holding the data
+    pub data_dev: Option<Device>,
+}
+
+/// The parameters of a `raid` target: `<raid_type> <num_params>
+/// <chunk_size> [<option> ...] <num_devices> <metadata_dev>
+/// <data_dev> ...`.
+///
+/// `<num_params>` counts `<chunk_size>` and each argument of the
+/// options; `Display` writes it, and `<num_devices>`, from the
+/// options and devices given.  Absent devices are written `-`.  Sizes
+/// are in sectors.
+///
+/// The chunk size is ignored by the kernel for `raid1`, and reported
+/// as zero; for every other type it must be a power of two, no
+/// smaller than [`Self::MIN_CHUNK_SIZE`].  Parsing, and
+/// [`Self::new`], also reject too few devices for the type, device
+/// indices out of range, a region size that is not a power of two,
+/// and options that do not apply to the type.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::{
+///     targets::{RaidDevice, RaidOptions, RaidTargetParams, RaidType},
+///     Device,
+/// };
+///
+/// let device = |minor| RaidDevice {
+///     metadata_dev: Some(Device { major: 8, minor }),
+///     data_dev: Some(Device { major: 8, minor: minor + 1 }),
+/// };
+/// let params = RaidTargetParams::new(
+///     RaidType::Raid1,
+///     0,
+///     RaidOptions {
+///         region_size: Some(1024),
+///         ..Default::default()
+///     },
+///     vec![device(16), device(32)],
+/// )
+/// .unwrap();
+/// assert_eq!(
+///     params.to_string(),
+///     "raid1 3 0 region_size 1024 2 8:16 8:17 8:32 8:33"
+/// );
+/// assert_eq!(params.to_string().parse(), Ok(params));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RaidTargetParams {
+    /// The RAID level and layout
+    pub raid_type: RaidType,
+    /// Size, in sectors, of each chunk of data written to one device
+    pub chunk_size: u64,
+    /// Optional parameters
+    pub options: RaidOptions,
+    /// The members of the array, in order
+    pub devices: Vec<RaidDevice>,
+}
+
+impl RaidTargetParams {
+    /// The target type name used by the kernel.
+    pub const TARGET_TYPE: &'static str = "raid";
+
+    /// The smallest allowed chunk size, in sectors.
+    pub const MIN_CHUNK_SIZE: u64 = 8;
+
+    /// Parameters for an array of type `raid_type` over `devices`.
+    /// Fails if the parameters are invalid, as described above.
+    pub fn new(
+        raid_type: RaidType,
+        chunk_size: u64,
+        options: RaidOptions,
+        devices: Vec<RaidDevice>,
+    ) -> DmResult<RaidTargetParams> {
+        if devices.len() < raid_type.min_devices() {
+            return Err(DmError::InvalidArgument(format!(
+                "{raid_type} needs at least {} devices, found {}",
+                raid_type.min_devices(),
+                devices.len()
+            )));
+        }
+        if raid_type != RaidType::Raid1
+            && (chunk_size < Self::MIN_CHUNK_SIZE
+                || !chunk_size.is_power_of_two())
+        {
+            return Err(DmError::InvalidArgument(format!(
+                "raid chunk size {chunk_size} is not a power of two of at \
+                 least {}",
+                Self::MIN_CHUNK_SIZE
+            )));
+        }
+        options.check(raid_type, devices.len())?;
+        Ok(RaidTargetParams {
+            raid_type,
+            chunk_size,
+            options,
+            devices,
+        })
+    }
+}
+
+impl fmt::Display for RaidTargetParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}{} {}",
+            self.raid_type,
+            1 + self.options.num_args(),
+            self.chunk_size,
+            self.options,
+            self.devices.len()
+        )?;
+        for device in &self.devices {
+            for dev in [device.metadata_dev, device.data_dev] {
+                match dev {
+                    Some(dev) => write!(f, " {dev}")?,
+                    None => write!(f, " -")?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for RaidTargetParams {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<RaidTargetParams> {
+        <Self as TargetParams>::parse(s)
+    }
+}
+
+impl TargetParams for RaidTargetParams {
+    fn target_type(&self) -> &str {
+        Self::TARGET_TYPE
+    }
+
+    fn param_str(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(params: &str) -> DmResult<RaidTargetParams> {
+        let bad = || {
+            DmError::InvalidArgument(format!(
+                "expected \"<raid_type> <num_params> <chunk_size> \
+                 [<option> ...] <num_devices> <metadata_dev> <data_dev> \
+                 ...\", found {params:?}"
+            ))
+        };
+        let fields = params.split_whitespace().collect::<Vec<_>>();
+        let [raid_type, num_params, ref rest @ ..] = fields[..] else {
+            return Err(bad());
+        };
+        let num_params = parse_num::<usize>(num_params)?;
+        if num_params == 0 || rest.len() <= num_params {
+            return Err(bad());
+        }
+        let (params_args, rest) = rest.split_at(num_params);
+        let (chunk_size, options) =
+            params_args.split_first().expect("num_params is nonzero");
+        let (num_devices, devices) =
+            rest.split_first().expect("rest is longer than num_params");
+        if parse_num::<usize>(num_devices)?.checked_mul(2)
+            != Some(devices.len())
+        {
+            return Err(bad());
+        }
+
+        let parse_dev = |dev: &str| match dev {
+            "-" => Ok(None),
+            dev => dev.parse().map(Some),
+        };
+        let devices = devices
+            .chunks_exact(2)
+            .map(|pair| {
+                Ok(RaidDevice {
+                    metadata_dev: parse_dev(pair[0])?,
+                    data_dev: parse_dev(pair[1])?,
+                })
+            })
+            .collect::<DmResult<_>>()?;
+
+        RaidTargetParams::new(
+            raid_type.parse()?,
+            parse_num(chunk_size)?,
+            RaidOptions::parse(options)?,
+            devices,
+        )
+    }
+}
+
+/// The health of one device of a `raid` target, as reported in its
+/// status.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RaidDeviceHealth {
+    /// The device is alive and in sync (`A`)
+    InSync,
+    /// The device is alive but not in sync (`a`)
+    NotInSync,
+    /// The device has failed (`D`)
+    Failed,
+    /// There is no device in this position (`-`)
+    Missing,
+}
+
+/// The state of the journal device of a `raid4`, `raid5` or `raid6`
+/// target, as reported in its status.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RaidJournalHealth {
+    /// The journal is alive, in write-through mode (`A`)
+    WriteThrough,
+    /// The journal is alive, in write-back mode (`a`)
+    WriteBack,
+    /// The journal device has failed (`D`)
+    Failed,
+    /// There is no journal device (`-`)
+    None,
+}
+
+/// What the array is doing, as reported in the status of a `raid`
+/// target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RaidSyncAction {
+    /// Nothing (`idle`)
+    Idle,
+    /// All synchronisation is stopped (`frozen`)
+    Frozen,
+    /// Synchronising the array after it was created or uncleanly
+    /// shut down (`resync`)
+    Resync,
+    /// Rebuilding a replaced device (`recover`)
+    Recover,
+    /// Looking for inconsistencies, without correcting them
+    /// (`check`)
+    Check,
+    /// Looking for and correcting inconsistencies (`repair`)
+    Repair,
+    /// Changing the layout of the array (`reshape`)
+    Reshape,
+    /// The kernel could not tell (`undef`)
+    Undefined,
+}
+
+impl FromStr for RaidSyncAction {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<RaidSyncAction> {
+        match s {
+            "idle" => Ok(RaidSyncAction::Idle),
+            "frozen" => Ok(RaidSyncAction::Frozen),
+            "resync" => Ok(RaidSyncAction::Resync),
+            "recover" => Ok(RaidSyncAction::Recover),
+            "check" => Ok(RaidSyncAction::Check),
+            "repair" => Ok(RaidSyncAction::Repair),
+            "reshape" => Ok(RaidSyncAction::Reshape),
+            "undef" => Ok(RaidSyncAction::Undefined),
+            _ => Err(DmError::InvalidArgument(format!(
+                "unrecognised raid sync action {s:?}"
+            ))),
+        }
+    }
+}
+
+/// The status of a `raid` target, as returned by
+/// [`DM::table_status`][crate::DM::table_status] without the
+/// `DM_STATUS_TABLE` flag.
+///
+/// Fields that older kernels do not report are `None`.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::targets::{RaidDeviceHealth, RaidSyncAction, RaidTargetStatus};
+///
+/// let status: RaidTargetStatus =
+///     "raid1 2 Aa 1024/4096 recover 0 0 -".parse().unwrap();
+/// assert_eq!(status.raid_type, "raid1");
+/// assert_eq!(status.devices[1], RaidDeviceHealth::NotInSync);
+/// assert_eq!(status.sync_action, Some(RaidSyncAction::Recover));
+/// assert!(!status.is_in_sync());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RaidTargetStatus {
+    /// The RAID level and layout, such as `raid1` or `raid5_ls`
+    pub raid_type: String,
+    /// The health of each device, in order
+    pub devices: Vec<RaidDeviceHealth>,
+    /// How many sectors have been synchronised, or checked, repaired
+    /// or reshaped, according to `sync_action`
+    pub sync_current: u64,
+    /// The number of sectors to synchronise
+    pub sync_total: u64,
+    /// What the array is doing
+    pub sync_action: Option<RaidSyncAction>,
+    /// The number of discrepancies found by the last `check` or
+    /// `repair`
+    pub mismatch_count: Option<u64>,
+    /// The offset, in sectors, of the data on each device
+    pub data_offset: Option<u64>,
+    /// The state of the journal device
+    pub journal: Option<RaidJournalHealth>,
+}
+
+impl RaidTargetStatus {
+    /// Whether every device is alive and in sync, and the array is
+    /// fully synchronised.
+    pub fn is_in_sync(&self) -> bool {
+        self.devices
+            .iter()
+            .all(|health| *health == RaidDeviceHealth::InSync)
+            && self.sync_current == self.sync_total
+    }
+}
+
+/// Parses `<raid_type> <num_devices> <health> <sync_current>/<sync_total>
+/// [<sync_action> <mismatch_count> [<data_offset> [<journal>]]]`,
+/// where `<health>` has one character per device.
+impl FromStr for RaidTargetStatus {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<RaidTargetStatus> {
+        let bad = || {
+            DmError::InvalidArgument(format!(
+                "expected \"<raid_type> <num_devices> <health> \
+                 <sync_current>/<sync_total> [<sync_action> \
+                 <mismatch_count> [<data_offset> [<journal>]]]\", found {s:?}"
+            ))
+        };
+        let fields = s.split_whitespace().collect::<Vec<_>>();
+        let (raid_type, num_devices, health, sync_ratio, rest) = match fields[..]
+        {
+            [raid_type, num_devices, health, sync_ratio, ref rest @ ..] => {
+                (raid_type, num_devices, health, sync_ratio, rest)
+            }
+            _ => return Err(bad()),
+        };
+        let (sync_action, mismatch_count, data_offset, journal) = match *rest {
+            [] => (None, None, None, None),
+            [action, mismatch] => (Some(action), Some(mismatch), None, None),
+            [action, mismatch, offset] => {
+                (Some(action), Some(mismatch), Some(offset), None)
+            }
+            [action, mismatch, offset, journal] => {
+                (Some(action), Some(mismatch), Some(offset), Some(journal))
+            }
+            _ => return Err(bad()),
+        };
+
+        if parse_num::<usize>(num_devices)? != health.chars().count() {
+            return Err(bad());
+        }
+        let devices = health
+            .chars()
+            .map(|state| match state {
+                'A' => Ok(RaidDeviceHealth::InSync),
+                'a' => Ok(RaidDeviceHealth::NotInSync),
+                'D' => Ok(RaidDeviceHealth::Failed),
+                '-' => Ok(RaidDeviceHealth::Missing),
+                _ => Err(bad()),
+            })
+            .collect::<DmResult<_>>()?;
+        let (sync_current, sync_total) =
+            sync_ratio.split_once('/').ok_or_else(bad)?;
+
+        Ok(RaidTargetStatus {
+            raid_type: raid_type.to_string(),
+            devices,
+            sync_current: parse_num(sync_current)?,
+            sync_total: parse_num(sync_total)?,
+            sync_action: sync_action.map(str::parse).transpose()?,
+            mismatch_count: mismatch_count.map(parse_num).transpose()?,
+            data_offset: data_offset.map(parse_num).transpose()?,
+            journal: journal
+                .map(|journal| match journal {
+                    "A" => Ok(RaidJournalHealth::WriteThrough),
+                    "a" => Ok(RaidJournalHealth::WriteBack),
+                    "D" => Ok(RaidJournalHealth::Failed),
+                    "-" => Ok(RaidJournalHealth::None),
+                    _ => Err(bad()),
+                })
+                .transpose()?,
+        })
+    }
+}
@@ -0,0 +1,378 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The `snapshot-origin`, `snapshot`, and `snapshot-merge` targets,
+//! which together provide copy-on-write snapshots of a block device.
+//!
+//! A snapshot needs two DM devices, loaded as a pair:
+//!
+//! - one with a `snapshot-origin` table on the origin device, which
+//!   is the device to use in place of the origin from then on, and
+//!   which copies each chunk to the snapshots before it is first
+//!   overwritten; and
+//! - one with a `snapshot` table on the same origin device and a COW
+//!   (copy-on-write) device, which presents the origin as it was
+//!   when the snapshot was made.
+//!
+//! Writing to the origin device other than through the
+//! `snapshot-origin` device silently corrupts the snapshot, so load
+//! the `snapshot-origin` table first.  There may be several
+//! `snapshot` devices for the same origin.
+//!
+//! To merge a snapshot back into its origin, replace the
+//! `snapshot-origin` table with a `snapshot-merge` table that has the
+//! same parameters as the `snapshot` table, and remove the `snapshot`
+//! device.
+
+use core::{fmt, str::FromStr};
+
+use bitflags::bitflags;
+
+use crate::{
+    device::Device,
+    errors::{DmError, DmResult},
+    targets::{parse_num, TargetParams},
+};
+
+#[cfg(test)]
+#[path = "../tests/targets/snapshot.rs"]
+mod tests;
+
+/// The parameters of a `snapshot-origin` target: `<origin_dev>`.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::{targets::SnapshotOriginTargetParams, Device};
+///
+/// let params: SnapshotOriginTargetParams = "8:16".parse().unwrap();
+/// assert_eq!(params.origin, Device { major: 8, minor: 16 });
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SnapshotOriginTargetParams {
+    /// The device being snapshotted
+    pub origin: Device,
+}
+
+impl SnapshotOriginTargetParams {
+    /// The target type name used by the kernel.
+    pub const TARGET_TYPE: &'static str = "snapshot-origin";
+}
+
+impl fmt::Display for SnapshotOriginTargetParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.origin)
+    }
+}
+
+impl FromStr for SnapshotOriginTargetParams {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<SnapshotOriginTargetParams> {
+        <Self as TargetParams>::parse(s)
+    }
+}
+
+impl TargetParams for SnapshotOriginTargetParams {
+    fn target_type(&self) -> &str {
+        Self::TARGET_TYPE
+    }
+
+    fn param_str(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(params: &str) -> DmResult<SnapshotOriginTargetParams> {
+        let mut fields = params.split_whitespace();
+        match (fields.next(), fields.next()) {
+            (Some(origin), None) => Ok(SnapshotOriginTargetParams {
+                origin: origin.parse()?,
+            }),
+            _ => Err(DmError::InvalidArgument(format!(
+                "expected \"<origin_dev>\", found {params:?}"
+            ))),
+        }
+    }
+}
+
+/// Whether a snapshot's COW device survives the snapshot device
+/// being removed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SnapshotPersistence {
+    /// The COW device holds metadata, so the snapshot can be loaded
+    /// again later (`P`)
+    Persistent,
+    /// As `Persistent`, and the snapshot reports that it has
+    /// overflowed rather than being invalidated when the COW device
+    /// fills up (`PO`)
+    PersistentOverflow,
+    /// The snapshot is lost when the device is removed (`N`)
+    Transient,
+}
+
+impl SnapshotPersistence {
+    fn as_str(self) -> &'static str {
+        match self {
+            SnapshotPersistence::Persistent => "P",
+            SnapshotPersistence::PersistentOverflow => "PO",
+            SnapshotPersistence::Transient => "N",
+        }
+    }
+}
+
+impl fmt::Display for SnapshotPersistence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SnapshotPersistence {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<SnapshotPersistence> {
+        match s {
+            "P" | "p" => Ok(SnapshotPersistence::Persistent),
+            "PO" | "po" => Ok(SnapshotPersistence::PersistentOverflow),
+            "N" | "n" => Ok(SnapshotPersistence::Transient),
+            _ => Err(DmError::InvalidArgument(format!(
+                "snapshot persistence must be P, PO, or N, found {s:?}"
+            ))),
+        }
+    }
+}
+
+bitflags! {
+    /// The feature arguments of a `snapshot` or `snapshot-merge`
+    /// target.
+    #[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+    pub struct SnapshotFeatures: u32 {
+        /// Discards to the snapshot zero the corresponding chunks of
+        /// the COW device (`discard_zeroes_cow`).
+        const DISCARD_ZEROES_COW      = 1 << 0;
+        /// Discards to the snapshot are also passed down to the
+        /// origin (`discard_passdown_origin`).  Requires
+        /// `DISCARD_ZEROES_COW`.
+        const DISCARD_PASSDOWN_ORIGIN = 1 << 1;
+    }
+}
+
+/// The kernel's names for each of the [`SnapshotFeatures`], in the
+/// order it reports them.
+const FEATURE_NAMES: [(SnapshotFeatures, &str); 2] = [
+    (SnapshotFeatures::DISCARD_ZEROES_COW, "discard_zeroes_cow"),
+    (
+        SnapshotFeatures::DISCARD_PASSDOWN_ORIGIN,
+        "discard_passdown_origin",
+    ),
+];
+
+/// The parameters of a `snapshot` target: `<origin_dev> <cow_dev>
+/// <persistence> <chunk_size> [<num_features> <feature> ...]`.
+///
+/// The chunk size must be a power of two; parsing and [`Self::new`]
+/// reject any other size, and `DISCARD_PASSDOWN_ORIGIN` without
+/// `DISCARD_ZEROES_COW`.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::{
+///     targets::{SnapshotFeatures, SnapshotPersistence, SnapshotTargetParams},
+///     Device,
+/// };
+///
+/// let params = SnapshotTargetParams::new(
+///     Device { major: 8, minor: 16 },
+///     Device { major: 8, minor: 32 },
+///     SnapshotPersistence::Persistent,
+///     16,
+///     SnapshotFeatures::empty(),
+/// )
+/// .unwrap();
+/// assert_eq!(params.to_string(), "8:16 8:32 P 16");
+/// assert_eq!(params.to_string().parse(), Ok(params));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SnapshotTargetParams {
+    /// The device being snapshotted
+    pub origin: Device,
+    /// The device that chunks of the origin are copied to before
+    /// they are overwritten
+    pub cow: Device,
+    /// Whether the snapshot persists
+    pub persistence: SnapshotPersistence,
+    /// Size, in sectors, of the unit of copying
+    pub chunk_size: u64,
+    /// Feature arguments
+    pub features: SnapshotFeatures,
+}
+
+impl SnapshotTargetParams {
+    /// The target type name used by the kernel.
+    pub const TARGET_TYPE: &'static str = "snapshot";
+
+    /// Parameters for a snapshot of `origin` whose changes are kept
+    /// on `cow`.  Fails if the combination is invalid, as described
+    /// above.
+    pub fn new(
+        origin: Device,
+        cow: Device,
+        persistence: SnapshotPersistence,
+        chunk_size: u64,
+        features: SnapshotFeatures,
+    ) -> DmResult<SnapshotTargetParams> {
+        if !chunk_size.is_power_of_two() {
+            return Err(DmError::InvalidArgument(format!(
+                "snapshot chunk size {chunk_size} is not a power of two"
+            )));
+        }
+        if features.contains(SnapshotFeatures::DISCARD_PASSDOWN_ORIGIN)
+            && !features.contains(SnapshotFeatures::DISCARD_ZEROES_COW)
+        {
+            return Err(DmError::InvalidArgument(
+                "snapshot discard_passdown_origin requires \
+                 discard_zeroes_cow"
+                    .to_string(),
+            ));
+        }
+        Ok(SnapshotTargetParams {
+            origin,
+            cow,
+            persistence,
+            chunk_size,
+            features,
+        })
+    }
+}
+
+impl fmt::Display for SnapshotTargetParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.origin, self.cow, self.persistence, self.chunk_size
+        )?;
+        if !self.features.is_empty() {
+            write!(f, " {}", self.features.bits().count_ones())?;
+            for (feature, name) in FEATURE_NAMES {
+                if self.features.contains(feature) {
+                    write!(f, " {name}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SnapshotTargetParams {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<SnapshotTargetParams> {
+        <Self as TargetParams>::parse(s)
+    }
+}
+
+impl TargetParams for SnapshotTargetParams {
+    fn target_type(&self) -> &str {
+        Self::TARGET_TYPE
+    }
+
+    fn param_str(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(params: &str) -> DmResult<SnapshotTargetParams> {
+        let fields = params.split_whitespace().collect::<Vec<_>>();
+        let (origin, cow, persistence, chunk_size, rest) = match fields[..] {
+            [origin, cow, persistence, chunk_size, ref rest @ ..] => {
+                (origin, cow, persistence, chunk_size, rest)
+            }
+            _ => {
+                return Err(DmError::InvalidArgument(format!(
+                    "expected \"<origin_dev> <cow_dev> <persistence> \
+                     <chunk_size> [<num_features> <feature> ...]\", \
+                     found {params:?}"
+                )))
+            }
+        };
+
+        let mut features = SnapshotFeatures::empty();
+        if let Some((num_features, names)) = rest.split_first() {
+            if parse_num::<usize>(num_features)? != names.len() {
+                return Err(DmError::InvalidArgument(format!(
+                    "snapshot feature count {num_features} does not match \
+                     {names:?}"
+                )));
+            }
+            for name in names {
+                let (feature, _) = FEATURE_NAMES
+                    .iter()
+                    .find(|(_, known)| known == name)
+                    .ok_or_else(|| {
+                        DmError::InvalidArgument(format!(
+                            "unrecognised snapshot feature {name:?}"
+                        ))
+                    })?;
+                features |= *feature;
+            }
+        }
+
+        SnapshotTargetParams::new(
+            origin.parse()?,
+            cow.parse()?,
+            persistence.parse()?,
+            parse_num(chunk_size)?,
+            features,
+        )
+    }
+}
+
+/// The parameters of a `snapshot-merge` target, which are the same
+/// as those of the `snapshot` target being merged.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::targets::{SnapshotMergeTargetParams, TargetParams};
+///
+/// let params: SnapshotMergeTargetParams = "8:16 8:32 P 16".parse().unwrap();
+/// assert_eq!(params.target_type(), "snapshot-merge");
+/// assert_eq!(params.0.chunk_size, 16);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SnapshotMergeTargetParams(pub SnapshotTargetParams);
+
+impl SnapshotMergeTargetParams {
+    /// The target type name used by the kernel.
+    pub const TARGET_TYPE: &'static str = "snapshot-merge";
+}
+
+impl fmt::Display for SnapshotMergeTargetParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromStr for SnapshotMergeTargetParams {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<SnapshotMergeTargetParams> {
+        <Self as TargetParams>::parse(s)
+    }
+}
+
+impl TargetParams for SnapshotMergeTargetParams {
+    fn target_type(&self) -> &str {
+        Self::TARGET_TYPE
+    }
+
+    fn param_str(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(params: &str) -> DmResult<SnapshotMergeTargetParams> {
+        <SnapshotTargetParams as TargetParams>::parse(params)
+            .map(SnapshotMergeTargetParams)
+    }
+}
@@ -0,0 +1,230 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The `striped` target, which maps a range of sectors across
+//! several block devices in fixed-size chunks, RAID0-style.
+
+use core::{fmt, str::FromStr};
+
+use crate::{
+    device::Device,
+    errors::{DmError, DmResult},
+    targets::{parse_num, TargetParams},
+};
+
+#[cfg(test)]
+#[path = "../tests/targets/striped.rs"]
+mod tests;
+
+/// The parameters of a `striped` target.
+///
+/// `Display` and `FromStr` use the `params` string of a table line,
+/// `<num_stripes> <chunk_size> <device> <offset> ...`.  Parsing fails
+/// if the number of stripes listed does not match the declared
+/// number, or if the chunk size is not a power of two.
+///
+/// The length of a `striped` target must be a multiple of
+/// [`Self::stripe_width`]; use [`Self::aligned_length`] to find one.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::{targets::StripedTargetParams, Device};
+///
+/// let params: StripedTargetParams =
+///     "2 128 8:16 0 8:32 0".parse().unwrap();
+/// assert_eq!(params.stripe_width(), 256);
+/// assert_eq!(params.aligned_length(1000), 768);
+/// assert_eq!(params.to_string(), "2 128 8:16 0 8:32 0");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StripedTargetParams {
+    /// Size, in sectors, of each chunk written to one stripe before
+    /// moving on to the next
+    pub chunk_size: u64,
+    /// The devices that the chunks are spread across, each with the
+    /// offset, in sectors, of the start of the mapping within it
+    pub stripes: Vec<(Device, u64)>,
+}
+
+impl StripedTargetParams {
+    /// The target type name used by the kernel.
+    pub const TARGET_TYPE: &'static str = "striped";
+
+    /// Parameters that spread chunks of `chunk_size` sectors across
+    /// `stripes`.  Fails if `chunk_size` is not a power of two, or
+    /// if there are no stripes.
+    pub fn new(
+        chunk_size: u64,
+        stripes: Vec<(Device, u64)>,
+    ) -> DmResult<StripedTargetParams> {
+        check_chunk_size(chunk_size)?;
+        if stripes.is_empty() {
+            return Err(DmError::InvalidArgument(
+                "a striped target needs at least one stripe".to_string(),
+            ));
+        }
+        Ok(StripedTargetParams {
+            chunk_size,
+            stripes,
+        })
+    }
+
+    /// The number of sectors in one chunk from each stripe.  The
+    /// length of the target must be a multiple of this.
+    pub fn stripe_width(&self) -> u64 {
+        self.chunk_size.saturating_mul(self.stripes.len() as u64)
+    }
+
+    /// The largest valid length of a target with these parameters
+    /// that is no longer than `length` sectors.  This is zero if
+    /// `length` is less than one [`Self::stripe_width`].
+    pub fn aligned_length(&self, length: u64) -> u64 {
+        match self.stripe_width() {
+            0 => 0,
+            width => length - length % width,
+        }
+    }
+}
+
+impl fmt::Display for StripedTargetParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.stripes.len(), self.chunk_size)?;
+        for (dest, offset) in &self.stripes {
+            write!(f, " {dest} {offset}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for StripedTargetParams {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<StripedTargetParams> {
+        <Self as TargetParams>::parse(s)
+    }
+}
+
+impl TargetParams for StripedTargetParams {
+    fn target_type(&self) -> &str {
+        Self::TARGET_TYPE
+    }
+
+    fn param_str(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(params: &str) -> DmResult<StripedTargetParams> {
+        let fields = params.split_whitespace().collect::<Vec<_>>();
+        let (num_stripes, chunk_size, stripes) = match &fields[..] {
+            [num_stripes, chunk_size, stripes @ ..] => {
+                (parse_num(num_stripes)?, parse_num(chunk_size)?, stripes)
+            }
+            _ => {
+                return Err(DmError::InvalidArgument(format!(
+                    "expected \"<num_stripes> <chunk_size> <device> \
+                     <offset> ...\", found {params:?}"
+                )))
+            }
+        };
+        if num_stripes == 0
+            || stripes.len() % 2 != 0
+            || stripes.len() as u64 / 2 != num_stripes
+        {
+            return Err(DmError::InvalidArgument(format!(
+                "expected {num_stripes} <device> <offset> pairs, \
+                 found {params:?}"
+            )));
+        }
+
+        StripedTargetParams::new(
+            chunk_size,
+            stripes
+                .chunks(2)
+                .map(|pair| Ok((pair[0].parse()?, parse_num(pair[1])?)))
+                .collect::<DmResult<_>>()?,
+        )
+    }
+}
+
+/// The status of a `striped` target, as returned by
+/// [`DM::table_status`][crate::DM::table_status] without the
+/// `DM_STATUS_TABLE` flag: each stripe's device, and whether the
+/// kernel has seen I/O errors on it.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::targets::StripedTargetStatus;
+///
+/// let status: StripedTargetStatus = "2 8:16 8:32 1 AD".parse().unwrap();
+/// assert!(status.stripes[0].1);
+/// assert!(!status.stripes[1].1);
+/// assert!(!status.is_healthy());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StripedTargetStatus {
+    /// Each stripe's device, with true if the stripe is alive (`A`)
+    /// or false if it has had I/O errors (`D`)
+    pub stripes: Vec<(Device, bool)>,
+}
+
+impl StripedTargetStatus {
+    /// Whether no stripe has had I/O errors.
+    pub fn is_healthy(&self) -> bool {
+        self.stripes.iter().all(|&(_, alive)| alive)
+    }
+}
+
+/// Parses `<num_stripes> <device> ... 1 <health>`, where `<health>`
+/// has one character, `A` or `D`, per stripe.
+impl FromStr for StripedTargetStatus {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<StripedTargetStatus> {
+        let bad = || {
+            DmError::InvalidArgument(format!(
+                "expected \"<num_stripes> <device> ... 1 <health>\", \
+                 found {s:?}"
+            ))
+        };
+        let fields = s.split_whitespace().collect::<Vec<_>>();
+        let (num_stripes, rest) = fields.split_first().ok_or_else(bad)?;
+        let num_stripes: usize = parse_num(num_stripes)?;
+        let (devices, health) = match rest {
+            [devices @ .., "1", health] if devices.len() == num_stripes => {
+                (devices, health)
+            }
+            _ => return Err(bad()),
+        };
+        if health.len() != num_stripes {
+            return Err(bad());
+        }
+
+        Ok(StripedTargetStatus {
+            stripes: devices
+                .iter()
+                .zip(health.chars())
+                .map(|(device, state)| {
+                    let alive = match state {
+                        'A' => true,
+                        'D' => false,
+                        _ => return Err(bad()),
+                    };
+                    Ok((device.parse()?, alive))
+                })
+                .collect::<DmResult<_>>()?,
+        })
+    }
+}
+
+/// Returns an error if `chunk_size` is not a power of two.
+pub(super) fn check_chunk_size(chunk_size: u64) -> DmResult<()> {
+    if !chunk_size.is_power_of_two() {
+        return Err(DmError::InvalidArgument(format!(
+            "chunk size {chunk_size} is not a power of two"
+        )));
+    }
+    Ok(())
+}
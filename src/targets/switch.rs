@@ -0,0 +1,246 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The `switch` target, which divides a device into fixed-size
+//! regions and maps each region to one of several paths according to
+//! a table that is set through messages.
+
+use core::{
+    fmt::{self, Write},
+    str::FromStr,
+};
+
+use crate::{
+    device::Device,
+    errors::{DmError, DmResult},
+    targets::{parse_num, TargetParams},
+};
+
+#[cfg(test)]
+#[path = "../tests/targets/switch.rs"]
+mod tests;
+
+/// One argument of a `set_region_mappings` message to a `switch`
+/// target.  Built into a message by
+/// [`SwitchTargetParams::region_mappings_message`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SwitchMapping {
+    /// Map one region to a path: `<region>:<path>`, or `:<path>` for
+    /// the region after the one last mapped
+    Region {
+        /// The region, or `None` for the one after the region last
+        /// mapped
+        region: Option<u64>,
+        /// The index of the path, counting from 0
+        path: usize,
+    },
+    /// Map each region from `first` to `last` inclusive to one path,
+    /// written as a mapping of `first` and a repeat of it
+    Range {
+        /// The first region
+        first: u64,
+        /// The last region
+        last: u64,
+        /// The index of the path, counting from 0
+        path: usize,
+    },
+    /// Repeat the last `cycle_length` mappings, in order, over the
+    /// next `count` regions: `R<cycle_length>,<count>`
+    Repeat {
+        /// How many of the previous mappings to repeat
+        cycle_length: u64,
+        /// How many regions to map
+        count: u64,
+    },
+}
+
+/// Writes the mapping as the kernel expects it, with numbers in
+/// hexadecimal.
+impl fmt::Display for SwitchMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            SwitchMapping::Region { region, path } => {
+                if let Some(region) = region {
+                    write!(f, "{region:x}")?;
+                }
+                write!(f, ":{path:x}")
+            }
+            SwitchMapping::Range { first, last, path } => {
+                write!(f, "{first:x}:{path:x}")?;
+                if last > first {
+                    write!(f, " R1,{:x}", last - first)?;
+                }
+                Ok(())
+            }
+            SwitchMapping::Repeat {
+                cycle_length,
+                count,
+            } => write!(f, "R{cycle_length:x},{count:x}"),
+        }
+    }
+}
+
+/// The parameters of a `switch` target: `<num_paths> <region_size>
+/// <num_optional_args> <dev> <offset> ...`.
+///
+/// `Display` writes `<num_paths>` from the paths given, and
+/// `<num_optional_args>` as 0; the kernel defines no optional
+/// arguments, and parsing skips any that are given.  The region size,
+/// in sectors, must not be zero, and there must be at least one path.
+/// The kernel maps every region to a path, in rotation, when the
+/// device is created; [`Self::region_mappings_message`] builds a
+/// message to change the mapping.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::{
+///     targets::{SwitchMapping, SwitchTargetParams},
+///     Device,
+/// };
+///
+/// let params = SwitchTargetParams::new(
+///     64,
+///     vec![
+///         (Device { major: 8, minor: 16 }, 0),
+///         (Device { major: 8, minor: 32 }, 0),
+///     ],
+/// )
+/// .unwrap();
+/// assert_eq!(params.to_string(), "2 64 0 8:16 0 8:32 0");
+/// assert_eq!(params.to_string().parse(), Ok(params.clone()));
+///
+/// let msg = params
+///     .region_mappings_message(&[
+///         SwitchMapping::Range { first: 0, last: 15, path: 1 },
+///         SwitchMapping::Region { region: None, path: 0 },
+///     ])
+///     .unwrap();
+/// assert_eq!(msg, "set_region_mappings 0:1 R1,f :0");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SwitchTargetParams {
+    /// Size, in sectors, of each region
+    pub region_size: u64,
+    /// The device and starting sector of each path
+    pub paths: Vec<(Device, u64)>,
+}
+
+impl SwitchTargetParams {
+    /// The target type name used by the kernel.
+    pub const TARGET_TYPE: &'static str = "switch";
+
+    /// Parameters that divide the device into regions of
+    /// `region_size` sectors over `paths`.  Fails if `region_size` is
+    /// zero or there are no paths.
+    pub fn new(
+        region_size: u64,
+        paths: Vec<(Device, u64)>,
+    ) -> DmResult<SwitchTargetParams> {
+        if region_size == 0 || region_size > u64::from(u32::MAX) {
+            return Err(DmError::InvalidArgument(format!(
+                "switch region size {region_size} is zero or too large"
+            )));
+        }
+        if paths.is_empty() {
+            return Err(DmError::InvalidArgument(
+                "switch target has no paths".to_string(),
+            ));
+        }
+        Ok(SwitchTargetParams { region_size, paths })
+    }
+
+    /// The `set_region_mappings` message that applies `mappings`, in
+    /// order, to send with [`DM::target_msg`][crate::DM::target_msg].
+    /// Fails if a mapping refers to a path that these parameters do
+    /// not have, or if `mappings` is empty.
+    pub fn region_mappings_message(
+        &self,
+        mappings: &[SwitchMapping],
+    ) -> DmResult<String> {
+        if mappings.is_empty() {
+            return Err(DmError::InvalidArgument(
+                "switch region mappings message has no mappings".to_string(),
+            ));
+        }
+        let mut msg = String::from("set_region_mappings");
+        for mapping in mappings {
+            match *mapping {
+                SwitchMapping::Region { path, .. }
+                | SwitchMapping::Range { path, .. }
+                    if path >= self.paths.len() =>
+                {
+                    return Err(DmError::InvalidArgument(format!(
+                        "switch path {path} is out of range for {} paths",
+                        self.paths.len()
+                    )));
+                }
+                SwitchMapping::Range { first, last, .. } if last < first => {
+                    return Err(DmError::InvalidArgument(format!(
+                        "switch region range {first}..={last} is empty"
+                    )));
+                }
+                _ => (),
+            }
+            write!(msg, " {mapping}").expect("writing to a String succeeds");
+        }
+        Ok(msg)
+    }
+}
+
+impl fmt::Display for SwitchTargetParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} 0", self.paths.len(), self.region_size)?;
+        for (dev, offset) in &self.paths {
+            write!(f, " {dev} {offset}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SwitchTargetParams {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<SwitchTargetParams> {
+        <Self as TargetParams>::parse(s)
+    }
+}
+
+impl TargetParams for SwitchTargetParams {
+    fn target_type(&self) -> &str {
+        Self::TARGET_TYPE
+    }
+
+    fn param_str(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(params: &str) -> DmResult<SwitchTargetParams> {
+        let bad = || {
+            DmError::InvalidArgument(format!(
+                "expected \"<num_paths> <region_size> <num_optional_args> \
+                 [<arg> ...] <dev> <offset> ...\", found {params:?}"
+            ))
+        };
+        let fields = params.split_whitespace().collect::<Vec<_>>();
+        let [num_paths, region_size, num_optional_args, ref rest @ ..] =
+            fields[..]
+        else {
+            return Err(bad());
+        };
+        let num_optional_args = parse_num::<usize>(num_optional_args)?;
+        let path_args = rest.get(num_optional_args..).ok_or_else(bad)?;
+        if parse_num::<usize>(num_paths)?.checked_mul(2)
+            != Some(path_args.len())
+        {
+            return Err(bad());
+        }
+        let paths = path_args
+            .chunks_exact(2)
+            .map(|pair| Ok((pair[0].parse()?, parse_num(pair[1])?)))
+            .collect::<DmResult<_>>()?;
+
+        SwitchTargetParams::new(parse_num(region_size)?, paths)
+    }
+}
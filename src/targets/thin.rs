@@ -0,0 +1,123 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The `thin` target, which maps a range of sectors onto a thin
+//! device in a `thin-pool`.
+
+use core::{fmt, str::FromStr};
+
+use crate::{
+    device::Device,
+    errors::{DmError, DmResult},
+    targets::{parse_num, TargetParams},
+};
+
+#[cfg(test)]
+#[path = "../tests/targets/thin.rs"]
+mod tests;
+
+/// The parameters of a `thin` target: `<pool_dev> <dev_id>
+/// [<external_origin_dev>]`.
+///
+/// The thin device numbered `device_id` must already have been
+/// created in the pool, with a `create_thin` or `create_snap`
+/// message to the pool.  Device ids are 24-bit numbers; parsing and
+/// [`Self::new`] reject any larger id.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::{targets::ThinTargetParams, Device};
+///
+/// let pool = Device { major: 253, minor: 0 };
+/// let params = ThinTargetParams::new(pool, 1, None).unwrap();
+/// assert_eq!(params.to_string(), "253:0 1");
+/// assert_eq!(params.to_string().parse(), Ok(params));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ThinTargetParams {
+    /// The `thin-pool` device
+    pub pool: Device,
+    /// The number of the thin device within the pool
+    pub device_id: u64,
+    /// A read-only device supplying the contents of blocks that have
+    /// not been written to the thin device
+    pub external_origin: Option<Device>,
+}
+
+impl ThinTargetParams {
+    /// The target type name used by the kernel.
+    pub const TARGET_TYPE: &'static str = "thin";
+
+    /// The largest thin device id the kernel accepts.
+    pub const MAX_DEVICE_ID: u64 = (1 << 24) - 1;
+
+    /// Parameters for the thin device numbered `device_id` in `pool`.
+    /// Fails if `device_id` is larger than [`Self::MAX_DEVICE_ID`].
+    pub fn new(
+        pool: Device,
+        device_id: u64,
+        external_origin: Option<Device>,
+    ) -> DmResult<ThinTargetParams> {
+        if device_id > Self::MAX_DEVICE_ID {
+            return Err(DmError::InvalidArgument(format!(
+                "thin device id {device_id} is larger than {}",
+                Self::MAX_DEVICE_ID
+            )));
+        }
+        Ok(ThinTargetParams {
+            pool,
+            device_id,
+            external_origin,
+        })
+    }
+}
+
+impl fmt::Display for ThinTargetParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.pool, self.device_id)?;
+        if let Some(origin) = self.external_origin {
+            write!(f, " {origin}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ThinTargetParams {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<ThinTargetParams> {
+        <Self as TargetParams>::parse(s)
+    }
+}
+
+impl TargetParams for ThinTargetParams {
+    fn target_type(&self) -> &str {
+        Self::TARGET_TYPE
+    }
+
+    fn param_str(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(params: &str) -> DmResult<ThinTargetParams> {
+        let fields = params.split_whitespace().collect::<Vec<_>>();
+        let (pool, device_id, external_origin) = match fields[..] {
+            [pool, device_id] => (pool, device_id, None),
+            [pool, device_id, origin] => (pool, device_id, Some(origin)),
+            _ => {
+                return Err(DmError::InvalidArgument(format!(
+                    "expected \"<pool_dev> <dev_id> [<external_origin_dev>]\", \
+                     found {params:?}"
+                )))
+            }
+        };
+
+        ThinTargetParams::new(
+            pool.parse()?,
+            parse_num(device_id)?,
+            external_origin.map(str::parse).transpose()?,
+        )
+    }
+}
@@ -0,0 +1,405 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The `thin-pool` target, which manages a pool of storage from
+//! which thin devices allocate blocks on demand.
+
+use core::{fmt, str::FromStr};
+
+use bitflags::bitflags;
+
+use crate::{
+    device::Device,
+    errors::{DmError, DmResult},
+    targets::{parse_num, TargetParams},
+};
+
+#[cfg(test)]
+#[path = "../tests/targets/thin_pool.rs"]
+mod tests;
+
+bitflags! {
+    /// The feature arguments of a `thin-pool` target.
+    #[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+    pub struct ThinPoolFeatures: u32 {
+        /// Do not zero newly allocated blocks (`skip_block_zeroing`).
+        const SKIP_BLOCK_ZEROING  = 1 << 0;
+        /// Do not process discards (`ignore_discard`).
+        const IGNORE_DISCARD      = 1 << 1;
+        /// Process discards, but do not pass them down to the data
+        /// device (`no_discard_passdown`).
+        const NO_DISCARD_PASSDOWN = 1 << 2;
+        /// Do not allow any changes to the pool metadata
+        /// (`read_only`).
+        const READ_ONLY           = 1 << 3;
+        /// Fail I/O at once when the pool is out of data space,
+        /// rather than queueing it (`error_if_no_space`).
+        const ERROR_IF_NO_SPACE   = 1 << 4;
+    }
+}
+
+/// The kernel's names for each of the [`ThinPoolFeatures`], in the
+/// order it reports them.
+const FEATURE_NAMES: [(ThinPoolFeatures, &str); 5] = [
+    (ThinPoolFeatures::SKIP_BLOCK_ZEROING, "skip_block_zeroing"),
+    (ThinPoolFeatures::IGNORE_DISCARD, "ignore_discard"),
+    (ThinPoolFeatures::NO_DISCARD_PASSDOWN, "no_discard_passdown"),
+    (ThinPoolFeatures::READ_ONLY, "read_only"),
+    (ThinPoolFeatures::ERROR_IF_NO_SPACE, "error_if_no_space"),
+];
+
+/// The parameters of a `thin-pool` target: `<metadata_dev>
+/// <data_dev> <data_block_size> <low_water_mark> [<num_features>
+/// <feature> ...]`.
+///
+/// The data block size must be a multiple of
+/// [`Self::MIN_DATA_BLOCK_SIZE`] sectors, no larger than
+/// [`Self::MAX_DATA_BLOCK_SIZE`]; parsing and [`Self::new`] reject
+/// any other size.  `Display` always writes `<num_features>`, as the
+/// kernel does.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::{
+///     targets::{ThinPoolFeatures, ThinPoolTargetParams},
+///     Device,
+/// };
+///
+/// let params = ThinPoolTargetParams::new(
+///     Device { major: 8, minor: 16 },
+///     Device { major: 8, minor: 32 },
+///     128,
+///     1024,
+///     ThinPoolFeatures::SKIP_BLOCK_ZEROING,
+/// )
+/// .unwrap();
+/// assert_eq!(params.to_string(), "8:16 8:32 128 1024 1 skip_block_zeroing");
+/// assert_eq!(params.to_string().parse(), Ok(params));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ThinPoolTargetParams {
+    /// The device holding the pool's metadata
+    pub metadata_dev: Device,
+    /// The device holding the pool's data blocks
+    pub data_dev: Device,
+    /// Size, in sectors, of each data block
+    pub data_block_size: u64,
+    /// When fewer than this many data blocks are free, the kernel
+    /// raises an event
+    pub low_water_mark: u64,
+    /// Feature arguments
+    pub features: ThinPoolFeatures,
+}
+
+impl ThinPoolTargetParams {
+    /// The target type name used by the kernel.
+    pub const TARGET_TYPE: &'static str = "thin-pool";
+
+    /// The smallest allowed data block size, in sectors (64KiB).
+    /// Every data block size must be a multiple of this.
+    pub const MIN_DATA_BLOCK_SIZE: u64 = 128;
+
+    /// The largest allowed data block size, in sectors (1GiB).
+    pub const MAX_DATA_BLOCK_SIZE: u64 = 2_097_152;
+
+    /// Parameters for a pool with its metadata on `metadata_dev` and
+    /// its data on `data_dev`.  Fails if `data_block_size` is not
+    /// allowed.
+    pub fn new(
+        metadata_dev: Device,
+        data_dev: Device,
+        data_block_size: u64,
+        low_water_mark: u64,
+        features: ThinPoolFeatures,
+    ) -> DmResult<ThinPoolTargetParams> {
+        if !(Self::MIN_DATA_BLOCK_SIZE..=Self::MAX_DATA_BLOCK_SIZE)
+            .contains(&data_block_size)
+            || data_block_size % Self::MIN_DATA_BLOCK_SIZE != 0
+        {
+            return Err(DmError::InvalidArgument(format!(
+                "thin-pool data block size {data_block_size} is not a \
+                 multiple of {} between {} and {}",
+                Self::MIN_DATA_BLOCK_SIZE,
+                Self::MIN_DATA_BLOCK_SIZE,
+                Self::MAX_DATA_BLOCK_SIZE
+            )));
+        }
+        Ok(ThinPoolTargetParams {
+            metadata_dev,
+            data_dev,
+            data_block_size,
+            low_water_mark,
+            features,
+        })
+    }
+}
+
+impl fmt::Display for ThinPoolTargetParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {}",
+            self.metadata_dev,
+            self.data_dev,
+            self.data_block_size,
+            self.low_water_mark,
+            self.features.bits().count_ones()
+        )?;
+        for (feature, name) in FEATURE_NAMES {
+            if self.features.contains(feature) {
+                write!(f, " {name}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ThinPoolTargetParams {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<ThinPoolTargetParams> {
+        <Self as TargetParams>::parse(s)
+    }
+}
+
+impl TargetParams for ThinPoolTargetParams {
+    fn target_type(&self) -> &str {
+        Self::TARGET_TYPE
+    }
+
+    fn param_str(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(params: &str) -> DmResult<ThinPoolTargetParams> {
+        let fields = params.split_whitespace().collect::<Vec<_>>();
+        let (metadata_dev, data_dev, data_block_size, low_water_mark, rest) =
+            match fields[..] {
+                [metadata_dev, data_dev, block_size, low_water, ref rest @ ..] => {
+                    (metadata_dev, data_dev, block_size, low_water, rest)
+                }
+                _ => {
+                    return Err(DmError::InvalidArgument(format!(
+                        "expected \"<metadata_dev> <data_dev> \
+                         <data_block_size> <low_water_mark> \
+                         [<num_features> <feature> ...]\", found {params:?}"
+                    )))
+                }
+            };
+
+        let mut features = ThinPoolFeatures::empty();
+        if let Some((num_features, names)) = rest.split_first() {
+            if parse_num::<usize>(num_features)? != names.len() {
+                return Err(DmError::InvalidArgument(format!(
+                    "thin-pool feature count {num_features} does not match \
+                     {names:?}"
+                )));
+            }
+            for name in names {
+                let (feature, _) = FEATURE_NAMES
+                    .iter()
+                    .find(|(_, known)| known == name)
+                    .ok_or_else(|| {
+                        DmError::InvalidArgument(format!(
+                            "unrecognised thin-pool feature {name:?}"
+                        ))
+                    })?;
+                features |= *feature;
+            }
+        }
+
+        ThinPoolTargetParams::new(
+            metadata_dev.parse()?,
+            data_dev.parse()?,
+            parse_num(data_block_size)?,
+            parse_num(low_water_mark)?,
+            features,
+        )
+    }
+}
+
+/// Whether a thin pool can be written to, as reported in its status.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ThinPoolMode {
+    /// The pool is working normally (`rw`)
+    ReadWrite,
+    /// The pool's metadata cannot be changed (`ro`)
+    ReadOnly,
+    /// The pool has run out of data space (`out_of_data_space`)
+    OutOfDataSpace,
+}
+
+/// How a thin pool handles discards, as reported in its status.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ThinPoolDiscards {
+    /// Discards are ignored (`ignore_discard`)
+    Ignore,
+    /// Discards are processed but not passed down to the data device
+    /// (`no_discard_passdown`)
+    NoPassdown,
+    /// Discards are processed and passed down to the data device
+    /// (`discard_passdown`)
+    Passdown,
+}
+
+/// The status of a `thin-pool` target that is working.
+///
+/// Metadata and data usage are counted in blocks: metadata blocks
+/// are always 4KiB, and data blocks are the pool's data block size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ThinPoolWorkingStatus {
+    /// The pool's current transaction id
+    pub transaction_id: u64,
+    /// The number of metadata blocks in use
+    pub metadata_used: u64,
+    /// The total number of metadata blocks
+    pub metadata_total: u64,
+    /// The number of data blocks in use
+    pub data_used: u64,
+    /// The total number of data blocks
+    pub data_total: u64,
+    /// The location of the held metadata root, if there is one
+    pub held_metadata_root: Option<u64>,
+    /// Whether the pool can be written to
+    pub mode: ThinPoolMode,
+    /// How the pool handles discards
+    pub discards: ThinPoolDiscards,
+    /// Whether I/O fails at once when the pool is out of data space
+    /// (`error_if_no_space`), rather than being queued
+    /// (`queue_if_no_space`)
+    pub error_if_no_space: bool,
+    /// Whether the metadata needs to be checked with `thin_check`
+    /// before the pool can be written to again (`needs_check`)
+    pub needs_check: bool,
+    /// When fewer than this many metadata blocks are free, the
+    /// kernel raises an event.  Not reported before Linux 4.19.
+    pub metadata_low_watermark: Option<u64>,
+}
+
+/// The status of a `thin-pool` target: the line returned by
+/// [`DM::table_status`][crate::DM::table_status] without the
+/// `DM_STATUS_TABLE` flag.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::targets::{ThinPoolMode, ThinPoolTargetStatus};
+///
+/// let status: ThinPoolTargetStatus =
+///     "0 141/1024 0/512 - rw discard_passdown queue_if_no_space - 256"
+///         .parse()
+///         .unwrap();
+/// let ThinPoolTargetStatus::Working(status) = status else {
+///     panic!("pool is not working");
+/// };
+/// assert_eq!(status.metadata_used, 141);
+/// assert_eq!(status.held_metadata_root, None);
+/// assert_eq!(status.mode, ThinPoolMode::ReadWrite);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ThinPoolTargetStatus {
+    /// The pool is working
+    Working(ThinPoolWorkingStatus),
+    /// The kernel could not read the pool's status (`Error`)
+    Error,
+    /// The pool has failed, and all I/O to it fails (`Fail`)
+    Fail,
+}
+
+/// Parses `<transaction_id> <used_metadata>/<total_metadata>
+/// <used_data>/<total_data> <held_metadata_root> <mode> <discards>
+/// <no_space_policy> [<needs_check> [<metadata_low_watermark>]]`, or
+/// `Error`, or `Fail`.  `-` for the held metadata root means there is
+/// none, and `-` for `needs_check` means the metadata does not need
+/// checking.
+impl FromStr for ThinPoolTargetStatus {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<ThinPoolTargetStatus> {
+        let bad = || {
+            DmError::InvalidArgument(format!(
+                "expected a thin-pool status line, found {s:?}"
+            ))
+        };
+        let fields = s.split_whitespace().collect::<Vec<_>>();
+        let (
+            transaction_id,
+            metadata,
+            data,
+            held_metadata_root,
+            mode,
+            discards,
+            no_space_policy,
+            rest,
+        ) = match fields[..] {
+            ["Error"] => return Ok(ThinPoolTargetStatus::Error),
+            ["Fail"] => return Ok(ThinPoolTargetStatus::Fail),
+            [transaction_id, metadata, data, held_metadata_root, mode, discards, no_space_policy, ref rest @ ..] => {
+                (
+                    transaction_id,
+                    metadata,
+                    data,
+                    held_metadata_root,
+                    mode,
+                    discards,
+                    no_space_policy,
+                    rest,
+                )
+            }
+            _ => return Err(bad()),
+        };
+
+        let used_total = |field: &str| -> DmResult<(u64, u64)> {
+            let (used, total) = field.split_once('/').ok_or_else(bad)?;
+            Ok((parse_num(used)?, parse_num(total)?))
+        };
+        let (metadata_used, metadata_total) = used_total(metadata)?;
+        let (data_used, data_total) = used_total(data)?;
+
+        let (needs_check, metadata_low_watermark) = match *rest {
+            [] => ("-", None),
+            [needs_check] => (needs_check, None),
+            [needs_check, watermark] => {
+                (needs_check, Some(parse_num(watermark)?))
+            }
+            _ => return Err(bad()),
+        };
+
+        Ok(ThinPoolTargetStatus::Working(ThinPoolWorkingStatus {
+            transaction_id: parse_num(transaction_id)?,
+            metadata_used,
+            metadata_total,
+            data_used,
+            data_total,
+            held_metadata_root: match held_metadata_root {
+                "-" => None,
+                root => Some(parse_num(root)?),
+            },
+            mode: match mode {
+                "rw" => ThinPoolMode::ReadWrite,
+                "ro" => ThinPoolMode::ReadOnly,
+                "out_of_data_space" => ThinPoolMode::OutOfDataSpace,
+                _ => return Err(bad()),
+            },
+            discards: match discards {
+                "ignore_discard" => ThinPoolDiscards::Ignore,
+                "no_discard_passdown" => ThinPoolDiscards::NoPassdown,
+                "discard_passdown" => ThinPoolDiscards::Passdown,
+                _ => return Err(bad()),
+            },
+            error_if_no_space: match no_space_policy {
+                "error_if_no_space" => true,
+                "queue_if_no_space" => false,
+                _ => return Err(bad()),
+            },
+            needs_check: match needs_check {
+                "needs_check" => true,
+                "-" => false,
+                _ => return Err(bad()),
+            },
+            metadata_low_watermark,
+        }))
+    }
+}
@@ -0,0 +1,148 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The `unstriped` target, which maps onto the chunks of one member
+//! of a striped (RAID0) device, so that the member can be used on its
+//! own.
+
+use core::{fmt, str::FromStr};
+
+use crate::{
+    device::Device,
+    errors::{DmError, DmResult},
+    targets::{parse_num, striped::check_chunk_size, TargetParams},
+};
+
+#[cfg(test)]
+#[path = "../tests/targets/unstripe.rs"]
+mod tests;
+
+/// The parameters of an `unstriped` target: `<num_stripes>
+/// <chunk_size> <stripe_index> <dev> <offset>`.
+///
+/// The chunk size, in sectors, must be a power of two, and the stripe
+/// index must be less than the number of stripes.  The length of the
+/// target must be a multiple of the chunk size; use
+/// [`Self::unstriped_length`] to find the length that covers the
+/// whole member.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::{targets::UnstripeTargetParams, Device};
+///
+/// let params =
+///     UnstripeTargetParams::new(4, 256, 1, Device { major: 8, minor: 16 }, 0)
+///         .unwrap();
+/// assert_eq!(params.to_string(), "4 256 1 8:16 0");
+/// assert_eq!(params.to_string().parse(), Ok(params));
+/// assert_eq!(params.unstriped_length(1_048_576), 262_144);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct UnstripeTargetParams {
+    /// The number of stripes in the striped device
+    pub stripes: u32,
+    /// Size, in sectors, of each chunk written to one stripe before
+    /// moving on to the next
+    pub chunk_size: u64,
+    /// The stripe to map onto, counting from 0
+    pub stripe_index: u32,
+    /// The striped device
+    pub dest: Device,
+    /// Offset, in sectors, of the start of the striped data within
+    /// `dest`
+    pub offset: u64,
+}
+
+impl UnstripeTargetParams {
+    /// The target type name used by the kernel.
+    pub const TARGET_TYPE: &'static str = "unstriped";
+
+    /// Parameters that map onto stripe `stripe_index` of the
+    /// `stripes` stripes of `dest`, starting `offset` sectors into
+    /// it.  Fails if the parameters are invalid, as described above.
+    pub fn new(
+        stripes: u32,
+        chunk_size: u64,
+        stripe_index: u32,
+        dest: Device,
+        offset: u64,
+    ) -> DmResult<UnstripeTargetParams> {
+        check_chunk_size(chunk_size)?;
+        if stripe_index >= stripes {
+            return Err(DmError::InvalidArgument(format!(
+                "unstriped stripe index {stripe_index} is out of range for \
+                 {stripes} stripes"
+            )));
+        }
+        Ok(UnstripeTargetParams {
+            stripes,
+            chunk_size,
+            stripe_index,
+            dest,
+            offset,
+        })
+    }
+
+    /// The length, in sectors, of an `unstriped` target that covers
+    /// the whole of its stripe of a striped device `striped_length`
+    /// sectors long.  Any partial stripe width at the end of the
+    /// striped device is left out.
+    pub fn unstriped_length(&self, striped_length: u64) -> u64 {
+        match self.chunk_size.saturating_mul(self.stripes.into()) {
+            0 => 0,
+            width => (striped_length / width) * self.chunk_size,
+        }
+    }
+}
+
+impl fmt::Display for UnstripeTargetParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {}",
+            self.stripes,
+            self.chunk_size,
+            self.stripe_index,
+            self.dest,
+            self.offset
+        )
+    }
+}
+
+impl FromStr for UnstripeTargetParams {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<UnstripeTargetParams> {
+        <Self as TargetParams>::parse(s)
+    }
+}
+
+impl TargetParams for UnstripeTargetParams {
+    fn target_type(&self) -> &str {
+        Self::TARGET_TYPE
+    }
+
+    fn param_str(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(params: &str) -> DmResult<UnstripeTargetParams> {
+        let fields = params.split_whitespace().collect::<Vec<_>>();
+        let [stripes, chunk_size, stripe_index, dest, offset] = fields[..]
+        else {
+            return Err(DmError::InvalidArgument(format!(
+                "expected \"<num_stripes> <chunk_size> <stripe_index> <dev> \
+                 <offset>\", found {params:?}"
+            )));
+        };
+        UnstripeTargetParams::new(
+            parse_num(stripes)?,
+            parse_num(chunk_size)?,
+            parse_num(stripe_index)?,
+            dest.parse()?,
+            parse_num(offset)?,
+        )
+    }
+}
@@ -0,0 +1,510 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The `verity` target, which checks each block read from a
+//! read-only device against a tree of hashes on another device.
+
+use core::{fmt, str::FromStr};
+
+use crate::{
+    device::Device,
+    errors::{DmError, DmResult},
+    targets::{parse_hex, parse_num, set_flag_once, set_once, TargetParams},
+};
+
+#[cfg(test)]
+#[path = "../tests/targets/verity.rs"]
+mod tests;
+
+/// The digest sizes, in bytes, of the hash algorithms commonly used
+/// with `verity`.  Digests for other algorithms are not checked.
+const DIGEST_SIZES: [(&str, usize); 6] = [
+    ("md5", 16),
+    ("sha1", 20),
+    ("sha224", 28),
+    ("sha256", 32),
+    ("sha384", 48),
+    ("sha512", 64),
+];
+
+/// What a `verity` target does when a block does not match its hash.
+/// Without any of these, reads of the block fail.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum VerityCorruptionMode {
+    /// Log the corruption, and return the data anyway
+    /// (`ignore_corruption`)
+    Ignore,
+    /// Restart the system (`restart_on_corruption`)
+    Restart,
+    /// Panic the kernel (`panic_on_corruption`)
+    Panic,
+}
+
+impl VerityCorruptionMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            VerityCorruptionMode::Ignore => "ignore_corruption",
+            VerityCorruptionMode::Restart => "restart_on_corruption",
+            VerityCorruptionMode::Panic => "panic_on_corruption",
+        }
+    }
+}
+
+/// Forward error correction for a `verity` target, which uses
+/// Reed-Solomon codes to repair corrupted blocks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct VerityFec {
+    /// The device holding the error correction codes
+    /// (`use_fec_from_device`)
+    pub dev: Device,
+    /// The number of parity bytes in each 255-byte codeword
+    /// (`fec_roots`)
+    pub roots: u32,
+    /// The number of blocks covered by error correction, including
+    /// the hash blocks (`fec_blocks`)
+    pub blocks: u64,
+    /// The block of `dev` at which the codes start (`fec_start`)
+    pub start: u64,
+}
+
+/// The optional parameters of a `verity` target.  Parameters that are
+/// `None` or `false` are not passed to the kernel, which then uses its
+/// defaults.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct VerityOptions {
+    /// What to do when a block does not match its hash
+    pub corruption_mode: Option<VerityCorruptionMode>,
+    /// Return zeros for blocks whose hash is all zeros, without
+    /// reading them (`ignore_zero_blocks`)
+    pub ignore_zero_blocks: bool,
+    /// Check each block only the first time it is read
+    /// (`check_at_most_once`)
+    pub check_at_most_once: bool,
+    /// Check hashes in softirq context when they are cached
+    /// (`try_verify_in_tasklet`)
+    pub try_verify_in_tasklet: bool,
+    /// Forward error correction
+    pub fec: Option<VerityFec>,
+    /// The description of a key in the kernel keyring with which to
+    /// check the signature of the root digest
+    /// (`root_hash_sig_key_desc`)
+    pub root_hash_sig_key_desc: Option<String>,
+    /// Single-word options this crate does not know about, such as
+    /// `restart_on_error`, written after the others as they are
+    pub other: Vec<String>,
+}
+
+impl VerityOptions {
+    /// The number of arguments the options take up, which is what the
+    /// kernel expects for `<num_options>`: one for each flag, eight
+    /// for forward error correction, two for the signature key, and
+    /// one for each option this crate does not know about.
+    fn num_args(&self) -> usize {
+        let flags = [
+            self.corruption_mode.is_some(),
+            self.ignore_zero_blocks,
+            self.check_at_most_once,
+            self.try_verify_in_tasklet,
+        ];
+        flags.iter().filter(|set| **set).count()
+            + if self.fec.is_some() { 8 } else { 0 }
+            + if self.root_hash_sig_key_desc.is_some() {
+                2
+            } else {
+                0
+            }
+            + self.other.len()
+    }
+
+    /// Parse the optional parameters of a `verity` target, which may
+    /// be given in any order, but at most once each.  Words that are
+    /// not known options are kept in `other`.
+    fn parse(mut args: &[&str]) -> DmResult<VerityOptions> {
+        let mut options = VerityOptions::default();
+        let (mut fec_dev, mut fec_roots, mut fec_blocks, mut fec_start) =
+            (None, None, None, None);
+        while let Some((&name, rest)) = args.split_first() {
+            let what = &format!("verity option {name}");
+            args = match (name, rest) {
+                (
+                    "ignore_corruption"
+                    | "restart_on_corruption"
+                    | "panic_on_corruption",
+                    rest,
+                ) => {
+                    let mode = match name {
+                        "ignore_corruption" => VerityCorruptionMode::Ignore,
+                        "restart_on_corruption" => {
+                            VerityCorruptionMode::Restart
+                        }
+                        _ => VerityCorruptionMode::Panic,
+                    };
+                    if options.corruption_mode.replace(mode).is_some() {
+                        return Err(DmError::InvalidArgument(
+                            "verity ignore_corruption, restart_on_corruption \
+                             and panic_on_corruption are mutually exclusive"
+                                .to_string(),
+                        ));
+                    }
+                    rest
+                }
+                ("ignore_zero_blocks", rest) => {
+                    set_flag_once(&mut options.ignore_zero_blocks, what)?;
+                    rest
+                }
+                ("check_at_most_once", rest) => {
+                    set_flag_once(&mut options.check_at_most_once, what)?;
+                    rest
+                }
+                ("try_verify_in_tasklet", rest) => {
+                    set_flag_once(&mut options.try_verify_in_tasklet, what)?;
+                    rest
+                }
+                ("use_fec_from_device", [value, rest @ ..]) => {
+                    set_once(&mut fec_dev, value.parse()?, what)?;
+                    rest
+                }
+                ("fec_roots", [value, rest @ ..]) => {
+                    set_once(&mut fec_roots, parse_num(value)?, what)?;
+                    rest
+                }
+                ("fec_blocks", [value, rest @ ..]) => {
+                    set_once(&mut fec_blocks, parse_num(value)?, what)?;
+                    rest
+                }
+                ("fec_start", [value, rest @ ..]) => {
+                    set_once(&mut fec_start, parse_num(value)?, what)?;
+                    rest
+                }
+                ("root_hash_sig_key_desc", [value, rest @ ..]) => {
+                    set_once(
+                        &mut options.root_hash_sig_key_desc,
+                        value.to_string(),
+                        what,
+                    )?;
+                    rest
+                }
+                (
+                    "use_fec_from_device"
+                    | "fec_roots"
+                    | "fec_blocks"
+                    | "fec_start"
+                    | "root_hash_sig_key_desc",
+                    [],
+                ) => {
+                    return Err(DmError::InvalidArgument(format!(
+                        "verity option {name} has no value"
+                    )))
+                }
+                (name, rest) => {
+                    options.other.push(name.to_string());
+                    rest
+                }
+            };
+        }
+
+        options.fec =
+            match (fec_dev, fec_roots, fec_blocks, fec_start) {
+                (None, None, None, None) => None,
+                (Some(dev), Some(roots), Some(blocks), Some(start)) => {
+                    Some(VerityFec {
+                        dev,
+                        roots,
+                        blocks,
+                        start,
+                    })
+                }
+                _ => return Err(DmError::InvalidArgument(
+                    "verity use_fec_from_device, fec_roots, fec_blocks and \
+                     fec_start must be given together"
+                        .to_string(),
+                )),
+            };
+        Ok(options)
+    }
+}
+
+/// Writes the options in the kernel's order, preceded by
+/// `<num_options>`, or nothing if there are none.
+impl fmt::Display for VerityOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let num_args = self.num_args();
+        if num_args == 0 {
+            return Ok(());
+        }
+        write!(f, "{num_args}")?;
+        if let Some(mode) = self.corruption_mode {
+            write!(f, " {}", mode.as_str())?;
+        }
+        if self.ignore_zero_blocks {
+            write!(f, " ignore_zero_blocks")?;
+        }
+        if self.check_at_most_once {
+            write!(f, " check_at_most_once")?;
+        }
+        if self.try_verify_in_tasklet {
+            write!(f, " try_verify_in_tasklet")?;
+        }
+        if let Some(fec) = &self.fec {
+            write!(
+                f,
+                " use_fec_from_device {} fec_roots {} fec_blocks {} \
+                 fec_start {}",
+                fec.dev, fec.roots, fec.blocks, fec.start
+            )?;
+        }
+        if let Some(key_desc) = &self.root_hash_sig_key_desc {
+            write!(f, " root_hash_sig_key_desc {key_desc}")?;
+        }
+        for option in &self.other {
+            write!(f, " {option}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The parameters of a `verity` target: `<version> <data_dev>
+/// <hash_dev> <data_block_size> <hash_block_size> <num_data_blocks>
+/// <hash_start_block> <algorithm> <root_digest> <salt>
+/// [<num_options> <option> ...]`.
+///
+/// The root digest and salt are written in hexadecimal, and an empty
+/// salt as `-`.  Block sizes are in bytes, and must be powers of two
+/// no smaller than 512; `num_data_blocks` and `hash_start_block`
+/// count blocks of the respective sizes.  Parsing, and [`Self::new`],
+/// also reject a version other than 0 or 1, and a root digest whose
+/// length does not match a well-known algorithm.  `Display` computes
+/// `<num_options>`, which counts the arguments of the options rather
+/// than the options themselves.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::{
+///     targets::{VerityOptions, VerityTargetParams},
+///     Device,
+/// };
+///
+/// let params = VerityTargetParams::new(
+///     1,
+///     Device { major: 8, minor: 16 },
+///     Device { major: 8, minor: 32 },
+///     4096,
+///     4096,
+///     262144,
+///     1,
+///     "sha256".to_string(),
+///     vec![0xab; 32],
+///     vec![],
+///     VerityOptions {
+///         ignore_zero_blocks: true,
+///         ..Default::default()
+///     },
+/// )
+/// .unwrap();
+/// let text = params.to_string();
+/// assert!(text.starts_with("1 8:16 8:32 4096 4096 262144 1 sha256 abab"));
+/// assert!(text.ends_with("abab - 1 ignore_zero_blocks"));
+/// assert_eq!(text.parse(), Ok(params));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VerityTargetParams {
+    /// The hash format version: 0 for Chrome OS, 1 otherwise
+    pub version: u32,
+    /// The device holding the data being checked
+    pub data_dev: Device,
+    /// The device holding the hash tree
+    pub hash_dev: Device,
+    /// Size, in bytes, of each data block
+    pub data_block_size: u32,
+    /// Size, in bytes, of each hash block
+    pub hash_block_size: u32,
+    /// The number of data blocks
+    pub num_data_blocks: u64,
+    /// The hash block of `hash_dev` at which the hash tree starts
+    pub hash_start_block: u64,
+    /// The hash algorithm, such as `sha256`
+    pub algorithm: String,
+    /// The digest of the root of the hash tree
+    pub root_digest: Vec<u8>,
+    /// The salt mixed into every hash, which may be empty
+    pub salt: Vec<u8>,
+    /// Optional parameters
+    pub options: VerityOptions,
+}
+
+impl VerityTargetParams {
+    /// The target type name used by the kernel.
+    pub const TARGET_TYPE: &'static str = "verity";
+
+    /// The smallest allowed block size, in bytes.
+    pub const MIN_BLOCK_SIZE: u32 = 512;
+
+    /// Parameters that check `data_dev` against the hash tree on
+    /// `hash_dev`.  Fails if the parameters are invalid, as described
+    /// above.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        version: u32,
+        data_dev: Device,
+        hash_dev: Device,
+        data_block_size: u32,
+        hash_block_size: u32,
+        num_data_blocks: u64,
+        hash_start_block: u64,
+        algorithm: String,
+        root_digest: Vec<u8>,
+        salt: Vec<u8>,
+        options: VerityOptions,
+    ) -> DmResult<VerityTargetParams> {
+        if version > 1 {
+            return Err(DmError::InvalidArgument(format!(
+                "verity version {version} is not 0 or 1"
+            )));
+        }
+        for block_size in [data_block_size, hash_block_size] {
+            if block_size < Self::MIN_BLOCK_SIZE
+                || !block_size.is_power_of_two()
+            {
+                return Err(DmError::InvalidArgument(format!(
+                    "verity block size {block_size} is not a power of two \
+                     no smaller than {}",
+                    Self::MIN_BLOCK_SIZE
+                )));
+            }
+        }
+        if algorithm.is_empty() || algorithm.contains(char::is_whitespace) {
+            return Err(DmError::InvalidArgument(format!(
+                "{algorithm:?} is not a valid verity hash algorithm"
+            )));
+        }
+        let expected = DIGEST_SIZES
+            .iter()
+            .find(|(name, _)| *name == algorithm)
+            .map(|(_, size)| *size);
+        if root_digest.is_empty()
+            || expected.is_some_and(|size| size != root_digest.len())
+        {
+            return Err(DmError::InvalidArgument(format!(
+                "verity root digest of {} bytes does not match algorithm \
+                 {algorithm}",
+                root_digest.len()
+            )));
+        }
+        Ok(VerityTargetParams {
+            version,
+            data_dev,
+            hash_dev,
+            data_block_size,
+            hash_block_size,
+            num_data_blocks,
+            hash_start_block,
+            algorithm,
+            root_digest,
+            salt,
+            options,
+        })
+    }
+}
+
+impl fmt::Display for VerityTargetParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {} {} {} {} ",
+            self.version,
+            self.data_dev,
+            self.hash_dev,
+            self.data_block_size,
+            self.hash_block_size,
+            self.num_data_blocks,
+            self.hash_start_block,
+            self.algorithm
+        )?;
+        for byte in &self.root_digest {
+            write!(f, "{byte:02x}")?;
+        }
+        f.write_str(" ")?;
+        if self.salt.is_empty() {
+            f.write_str("-")?;
+        }
+        for byte in &self.salt {
+            write!(f, "{byte:02x}")?;
+        }
+        if self.options.num_args() > 0 {
+            write!(f, " {}", self.options)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for VerityTargetParams {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<VerityTargetParams> {
+        <Self as TargetParams>::parse(s)
+    }
+}
+
+impl TargetParams for VerityTargetParams {
+    fn target_type(&self) -> &str {
+        Self::TARGET_TYPE
+    }
+
+    fn param_str(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(params: &str) -> DmResult<VerityTargetParams> {
+        let bad = || {
+            DmError::InvalidArgument(format!(
+                "expected \"<version> <data_dev> <hash_dev> \
+                 <data_block_size> <hash_block_size> <num_data_blocks> \
+                 <hash_start_block> <algorithm> <root_digest> <salt> \
+                 [<num_options> <option> ...]\", found {params:?}"
+            ))
+        };
+        let fields = params.split_whitespace().collect::<Vec<_>>();
+        let [version, data_dev, hash_dev, ref rest @ ..] = fields[..] else {
+            return Err(bad());
+        };
+        let [data_block_size, hash_block_size, num_data_blocks, ref rest @ ..] =
+            *rest
+        else {
+            return Err(bad());
+        };
+        let [hash_start_block, algorithm, root_digest, salt, ref rest @ ..] =
+            *rest
+        else {
+            return Err(bad());
+        };
+
+        let options = match rest.split_first() {
+            None => VerityOptions::default(),
+            Some((num_options, options)) => {
+                if parse_num::<usize>(num_options)? != options.len() {
+                    return Err(DmError::InvalidArgument(format!(
+                        "verity option count {num_options} does not match \
+                         {options:?}"
+                    )));
+                }
+                VerityOptions::parse(options)?
+            }
+        };
+
+        VerityTargetParams::new(
+            parse_num(version)?,
+            data_dev.parse()?,
+            hash_dev.parse()?,
+            parse_num(data_block_size)?,
+            parse_num(hash_block_size)?,
+            parse_num(num_data_blocks)?,
+            parse_num(hash_start_block)?,
+            algorithm.to_string(),
+            parse_hex(root_digest, "verity root digest")?,
+            parse_hex(salt, "verity salt")?,
+            options,
+        )
+    }
+}
@@ -0,0 +1,437 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The `writecache` target, which caches writes to a slow origin
+//! device on persistent memory or a fast SSD.
+
+use core::{fmt, str::FromStr};
+
+use crate::{
+    device::Device,
+    errors::{DmError, DmResult},
+    targets::{parse_num, set_flag_once, set_once, TargetParams},
+};
+
+#[cfg(test)]
+#[path = "../tests/targets/writecache.rs"]
+mod tests;
+
+/// The kind of device a `writecache` target caches writes on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WritecacheMode {
+    /// Persistent memory (`p`)
+    PersistentMemory,
+    /// A block device such as an SSD (`s`)
+    Ssd,
+}
+
+impl fmt::Display for WritecacheMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WritecacheMode::PersistentMemory => f.write_str("p"),
+            WritecacheMode::Ssd => f.write_str("s"),
+        }
+    }
+}
+
+impl FromStr for WritecacheMode {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<WritecacheMode> {
+        match s {
+            "p" | "P" => Ok(WritecacheMode::PersistentMemory),
+            "s" | "S" => Ok(WritecacheMode::Ssd),
+            _ => Err(DmError::InvalidArgument(format!(
+                "writecache mode must be p or s, found {s:?}"
+            ))),
+        }
+    }
+}
+
+/// The optional settings of a `writecache` target.  Settings that are
+/// `None` or `false` are not passed to the kernel, which then uses
+/// its defaults.
+///
+/// Times are in milliseconds, and watermarks are percentages of the
+/// cache that is in use.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct WritecacheSettings {
+    /// Sector of the cache device at which the cache starts
+    /// (`start_sector`)
+    pub start_sector: Option<u64>,
+    /// Start writing back when the cache is this full
+    /// (`high_watermark`)
+    pub high_watermark: Option<u8>,
+    /// Stop writing back when the cache is this full
+    /// (`low_watermark`)
+    pub low_watermark: Option<u8>,
+    /// Limit on the number of blocks being written back at once
+    /// (`writeback_jobs`)
+    pub writeback_jobs: Option<u32>,
+    /// Commit after this many blocks are written, in SSD mode
+    /// (`autocommit_blocks`)
+    pub autocommit_blocks: Option<u32>,
+    /// Commit at least this often, in SSD mode (`autocommit_time`)
+    pub autocommit_time: Option<u32>,
+    /// Write back blocks that have been cached for this long
+    /// (`max_age`)
+    pub max_age: Option<u32>,
+    /// Write back everything and stop caching new writes (`cleaner`)
+    pub cleaner: bool,
+    /// Whether to use FUA writes when writing back, in persistent
+    /// memory mode (`fua` or `nofua`)
+    pub fua: Option<bool>,
+    /// Keep only metadata in persistent memory, and data on the
+    /// cache device (`metadata_only`)
+    pub metadata_only: bool,
+    /// Pause writeback for this long after the device was last
+    /// used (`pause_writeback`)
+    pub pause_writeback: Option<u32>,
+}
+
+impl WritecacheSettings {
+    /// The kernel's high watermark when none is given.
+    pub const DEFAULT_HIGH_WATERMARK: u8 = 50;
+
+    /// The kernel's low watermark when none is given.
+    pub const DEFAULT_LOW_WATERMARK: u8 = 45;
+
+    /// The number of arguments the settings take up, which is what
+    /// the kernel expects for `<num_settings>`: two for each setting
+    /// with a value, and one for each flag.
+    fn num_args(&self) -> usize {
+        let values = [
+            self.start_sector.is_some(),
+            self.high_watermark.is_some(),
+            self.low_watermark.is_some(),
+            self.writeback_jobs.is_some(),
+            self.autocommit_blocks.is_some(),
+            self.autocommit_time.is_some(),
+            self.max_age.is_some(),
+            self.pause_writeback.is_some(),
+        ];
+        let flags = [self.cleaner, self.fua.is_some(), self.metadata_only];
+        2 * values.iter().filter(|set| **set).count()
+            + flags.iter().filter(|set| **set).count()
+    }
+
+    /// Returns an error if the kernel would reject these settings.
+    fn check(&self) -> DmResult<()> {
+        let high = self.high_watermark.unwrap_or(Self::DEFAULT_HIGH_WATERMARK);
+        let low = self.low_watermark.unwrap_or(Self::DEFAULT_LOW_WATERMARK);
+        if high > 100 || low > 100 {
+            return Err(DmError::InvalidArgument(format!(
+                "writecache watermarks {high} and {low} must be between 0 \
+                 and 100"
+            )));
+        }
+        if high < low {
+            return Err(DmError::InvalidArgument(format!(
+                "writecache high watermark {high} is less than low \
+                 watermark {low}"
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for WritecacheSettings {
+    /// Writes the settings in the kernel's order, preceded by
+    /// `<num_settings>`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.num_args())?;
+        if let Some(start_sector) = self.start_sector {
+            write!(f, " start_sector {start_sector}")?;
+        }
+        if let Some(high_watermark) = self.high_watermark {
+            write!(f, " high_watermark {high_watermark}")?;
+        }
+        if let Some(low_watermark) = self.low_watermark {
+            write!(f, " low_watermark {low_watermark}")?;
+        }
+        if let Some(writeback_jobs) = self.writeback_jobs {
+            write!(f, " writeback_jobs {writeback_jobs}")?;
+        }
+        if let Some(autocommit_blocks) = self.autocommit_blocks {
+            write!(f, " autocommit_blocks {autocommit_blocks}")?;
+        }
+        if let Some(autocommit_time) = self.autocommit_time {
+            write!(f, " autocommit_time {autocommit_time}")?;
+        }
+        if let Some(max_age) = self.max_age {
+            write!(f, " max_age {max_age}")?;
+        }
+        if self.cleaner {
+            write!(f, " cleaner")?;
+        }
+        match self.fua {
+            Some(true) => write!(f, " fua")?,
+            Some(false) => write!(f, " nofua")?,
+            None => (),
+        }
+        if self.metadata_only {
+            write!(f, " metadata_only")?;
+        }
+        if let Some(pause_writeback) = self.pause_writeback {
+            write!(f, " pause_writeback {pause_writeback}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The parameters of a `writecache` target: `<mode> <origin_dev>
+/// <cache_dev> <block_size> <num_settings> <setting> ...`.
+///
+/// The block size, in bytes, must be a power of two between 512 and
+/// 4096.  `Display` always writes `<num_settings>`, which counts the
+/// arguments of the settings rather than the settings themselves.
+/// Parsing, and [`Self::new`], also reject watermarks greater than
+/// 100, and a high watermark less than the low watermark, taking
+/// account of the kernel's defaults.
+///
+/// # Example
+///
+/// ```
+/// use dm_ioctl::{
+///     targets::{WritecacheMode, WritecacheSettings, WritecacheTargetParams},
+///     Device,
+/// };
+///
+/// let settings = WritecacheSettings {
+///     high_watermark: Some(60),
+///     cleaner: true,
+///     ..Default::default()
+/// };
+/// let params = WritecacheTargetParams::new(
+///     WritecacheMode::Ssd,
+///     Device { major: 8, minor: 16 },
+///     Device { major: 8, minor: 32 },
+///     4096,
+///     settings,
+/// )
+/// .unwrap();
+/// assert_eq!(
+///     params.to_string(),
+///     "s 8:16 8:32 4096 3 high_watermark 60 cleaner",
+/// );
+/// assert_eq!(params.to_string().parse(), Ok(params));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WritecacheTargetParams {
+    /// The kind of device the cache is on
+    pub mode: WritecacheMode,
+    /// The slow device being cached
+    pub origin_dev: Device,
+    /// The device holding the cache
+    pub cache_dev: Device,
+    /// Size, in bytes, of each cache block
+    pub block_size: u32,
+    /// Optional settings
+    pub settings: WritecacheSettings,
+}
+
+impl WritecacheTargetParams {
+    /// The target type name used by the kernel.
+    pub const TARGET_TYPE: &'static str = "writecache";
+
+    /// The smallest allowed block size, in bytes.
+    pub const MIN_BLOCK_SIZE: u32 = 512;
+
+    /// The largest allowed block size, in bytes, on systems with 4KiB
+    /// pages.
+    pub const MAX_BLOCK_SIZE: u32 = 4096;
+
+    /// Parameters that cache writes to `origin_dev` on `cache_dev`.
+    /// Fails if the parameters are invalid, as described above.
+    pub fn new(
+        mode: WritecacheMode,
+        origin_dev: Device,
+        cache_dev: Device,
+        block_size: u32,
+        settings: WritecacheSettings,
+    ) -> DmResult<WritecacheTargetParams> {
+        if !(Self::MIN_BLOCK_SIZE..=Self::MAX_BLOCK_SIZE).contains(&block_size)
+            || !block_size.is_power_of_two()
+        {
+            return Err(DmError::InvalidArgument(format!(
+                "writecache block size {block_size} is not a power of two \
+                 between {} and {}",
+                Self::MIN_BLOCK_SIZE,
+                Self::MAX_BLOCK_SIZE
+            )));
+        }
+        settings.check()?;
+        Ok(WritecacheTargetParams {
+            mode,
+            origin_dev,
+            cache_dev,
+            block_size,
+            settings,
+        })
+    }
+}
+
+impl fmt::Display for WritecacheTargetParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {}",
+            self.mode,
+            self.origin_dev,
+            self.cache_dev,
+            self.block_size,
+            self.settings
+        )
+    }
+}
+
+impl FromStr for WritecacheTargetParams {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<WritecacheTargetParams> {
+        <Self as TargetParams>::parse(s)
+    }
+}
+
+impl TargetParams for WritecacheTargetParams {
+    fn target_type(&self) -> &str {
+        Self::TARGET_TYPE
+    }
+
+    fn param_str(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(params: &str) -> DmResult<WritecacheTargetParams> {
+        let fields = params.split_whitespace().collect::<Vec<_>>();
+        let (mode, origin_dev, cache_dev, block_size, num_settings, args) =
+            match fields[..] {
+                [mode, origin_dev, cache_dev, block_size, num_settings, ref args @ ..] => {
+                    (
+                        mode,
+                        origin_dev,
+                        cache_dev,
+                        block_size,
+                        num_settings,
+                        args,
+                    )
+                }
+                _ => {
+                    return Err(DmError::InvalidArgument(format!(
+                        "expected \"<mode> <origin_dev> <cache_dev> \
+                         <block_size> <num_settings> <setting> ...\", \
+                         found {params:?}"
+                    )))
+                }
+            };
+        if parse_num::<usize>(num_settings)? != args.len() {
+            return Err(DmError::InvalidArgument(format!(
+                "writecache setting count {num_settings} does not match \
+                 {args:?}"
+            )));
+        }
+
+        WritecacheTargetParams::new(
+            mode.parse()?,
+            origin_dev.parse()?,
+            cache_dev.parse()?,
+            parse_num(block_size)?,
+            parse_settings(args)?,
+        )
+    }
+}
+
+/// Parse the optional settings of a `writecache` target, which may
+/// be given in any order, but at most once each.
+fn parse_settings(mut args: &[&str]) -> DmResult<WritecacheSettings> {
+    let mut settings = WritecacheSettings::default();
+    while let Some((&name, rest)) = args.split_first() {
+        let what = &format!("writecache setting {name}");
+        args = match (name, rest) {
+            ("cleaner", rest) => {
+                set_flag_once(&mut settings.cleaner, what)?;
+                rest
+            }
+            ("metadata_only", rest) => {
+                set_flag_once(&mut settings.metadata_only, what)?;
+                rest
+            }
+            ("fua", rest) => {
+                set_once(
+                    &mut settings.fua,
+                    true,
+                    "writecache setting fua or nofua",
+                )?;
+                rest
+            }
+            ("nofua", rest) => {
+                set_once(
+                    &mut settings.fua,
+                    false,
+                    "writecache setting fua or nofua",
+                )?;
+                rest
+            }
+            ("start_sector", [value, rest @ ..]) => {
+                set_once(&mut settings.start_sector, parse_num(value)?, what)?;
+                rest
+            }
+            ("high_watermark", [value, rest @ ..]) => {
+                set_once(
+                    &mut settings.high_watermark,
+                    parse_num(value)?,
+                    what,
+                )?;
+                rest
+            }
+            ("low_watermark", [value, rest @ ..]) => {
+                set_once(&mut settings.low_watermark, parse_num(value)?, what)?;
+                rest
+            }
+            ("writeback_jobs", [value, rest @ ..]) => {
+                set_once(
+                    &mut settings.writeback_jobs,
+                    parse_num(value)?,
+                    what,
+                )?;
+                rest
+            }
+            ("autocommit_blocks", [value, rest @ ..]) => {
+                set_once(
+                    &mut settings.autocommit_blocks,
+                    parse_num(value)?,
+                    what,
+                )?;
+                rest
+            }
+            ("autocommit_time", [value, rest @ ..]) => {
+                set_once(
+                    &mut settings.autocommit_time,
+                    parse_num(value)?,
+                    what,
+                )?;
+                rest
+            }
+            ("max_age", [value, rest @ ..]) => {
+                set_once(&mut settings.max_age, parse_num(value)?, what)?;
+                rest
+            }
+            ("pause_writeback", [value, rest @ ..]) => {
+                set_once(
+                    &mut settings.pause_writeback,
+                    parse_num(value)?,
+                    what,
+                )?;
+                rest
+            }
+            _ => {
+                return Err(DmError::InvalidArgument(format!(
+                    "unrecognised or incomplete writecache setting at \
+                     {args:?}"
+                )))
+            }
+        };
+    }
+    Ok(settings)
+}
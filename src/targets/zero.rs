@@ -0,0 +1,63 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The `zero` target, which reads as zeros and discards writes to the
+//! range of sectors it maps.
+
+use core::{fmt, str::FromStr};
+
+use crate::{
+    errors::{DmError, DmResult},
+    targets::TargetParams,
+};
+
+#[cfg(test)]
+#[path = "../tests/targets/zero.rs"]
+mod tests;
+
+/// The parameters of a `zero` target, which has none.  The `params`
+/// string used by `Display` and `FromStr` is empty.
+///
+/// [`TargetTable::zero_table`][crate::TargetTable::zero_table] builds a
+/// table that reads as zeros and discards writes across a whole device.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ZeroTargetParams;
+
+impl ZeroTargetParams {
+    /// The target type name used by the kernel.
+    pub const TARGET_TYPE: &'static str = "zero";
+}
+
+impl fmt::Display for ZeroTargetParams {
+    fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Ok(())
+    }
+}
+
+impl FromStr for ZeroTargetParams {
+    type Err = DmError;
+
+    fn from_str(s: &str) -> DmResult<ZeroTargetParams> {
+        <Self as TargetParams>::parse(s)
+    }
+}
+
+impl TargetParams for ZeroTargetParams {
+    fn target_type(&self) -> &str {
+        Self::TARGET_TYPE
+    }
+
+    fn param_str(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(params: &str) -> DmResult<ZeroTargetParams> {
+        if !params.trim().is_empty() {
+            return Err(DmError::InvalidArgument(format!(
+                "the zero target takes no parameters, found {params:?}"
+            )));
+        }
+        Ok(ZeroTargetParams)
+    }
+}
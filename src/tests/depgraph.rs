@@ -0,0 +1,142 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use super::*;
+
+fn name(name: &str) -> &DmName {
+    DmName::new(name).unwrap()
+}
+
+fn dm_dep(name: &str, minor: u32) -> DepEntry {
+    DepEntry {
+        device: Device { major: 253, minor },
+        name: Some(name.parse().unwrap()),
+    }
+}
+
+fn disk_dep(minor: u32) -> DepEntry {
+    DepEntry {
+        device: Device { major: 8, minor },
+        name: None,
+    }
+}
+
+/// A three-deep stack, top -> middle -> bottom -> sda, next to a
+/// device on sdb and sdc that nothing is stacked on.
+fn example() -> DepGraph {
+    let mut graph = DepGraph::default();
+    graph.insert(
+        "top".parse().unwrap(),
+        Device {
+            major: 253,
+            minor: 2,
+        },
+        vec![dm_dep("middle", 1)],
+    );
+    graph.insert(
+        "middle".parse().unwrap(),
+        Device {
+            major: 253,
+            minor: 1,
+        },
+        vec![dm_dep("bottom", 0)],
+    );
+    graph.insert(
+        "bottom".parse().unwrap(),
+        Device {
+            major: 253,
+            minor: 0,
+        },
+        vec![disk_dep(0)],
+    );
+    graph.insert(
+        "other".parse().unwrap(),
+        Device {
+            major: 253,
+            minor: 3,
+        },
+        vec![disk_dep(16), disk_dep(32)],
+    );
+    graph
+}
+
+#[test]
+/// Test the relationships in a stack of devices.
+fn test_relationships() {
+    let graph = example();
+    assert_eq!(
+        graph.devices().collect::<Vec<_>>(),
+        vec![name("bottom"), name("middle"), name("other"), name("top")]
+    );
+    assert_eq!(
+        graph.device(name("middle")),
+        Some(Device {
+            major: 253,
+            minor: 1
+        })
+    );
+    assert_eq!(
+        graph.children(name("top")),
+        Some(&[dm_dep("middle", 1)][..])
+    );
+    assert_eq!(graph.children(name("missing")), None);
+    assert_eq!(graph.parents(name("bottom")), vec![name("middle")]);
+    assert!(graph.parents(name("top")).is_empty());
+    assert_eq!(graph.roots(), vec![name("other"), name("top")]);
+    assert_eq!(
+        graph.leaves(name("top")),
+        vec![Device { major: 8, minor: 0 }]
+    );
+    assert_eq!(
+        graph.leaves(name("other")),
+        vec![
+            Device {
+                major: 8,
+                minor: 16
+            },
+            Device {
+                major: 8,
+                minor: 32
+            }
+        ]
+    );
+}
+
+#[test]
+/// Test that every device is ordered before the devices it refers
+/// to.
+fn test_topological_order() {
+    let graph = example();
+    let order = graph.topological_order().unwrap();
+    assert_eq!(order.len(), 4);
+    let position = |dev| {
+        order
+            .iter()
+            .position(|&dm_name| dm_name == name(dev))
+            .unwrap()
+    };
+    assert!(position("top") < position("middle"));
+    assert!(position("middle") < position("bottom"));
+}
+
+#[test]
+/// Test that a cycle is reported rather than looping forever.
+fn test_cycle() {
+    let mut graph = example();
+    graph.insert(
+        "bottom".parse().unwrap(),
+        Device {
+            major: 253,
+            minor: 0,
+        },
+        vec![dm_dep("middle", 1)],
+    );
+    assert_matches!(
+        graph.topological_order(),
+        Err(DmError::IoctlResultMalformed(
+            MalformedReason::DependencyCycle
+        ))
+    );
+    assert!(graph.leaves(name("top")).is_empty());
+}
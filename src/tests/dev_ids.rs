@@ -83,6 +83,10 @@ fn test_interface() {
     assert_eq!(id.as_bytes(), b"id");
     assert_eq!(id_buf.as_bytes(), b"id");
 
+    // Test AsRef<str>.
+    assert_eq!(AsRef::<str>::as_ref(id), "id");
+    assert_eq!(AsRef::<str>::as_ref(&id_buf), "id");
+
     // Test ToOwned implementation.
     // $B.to_owned() == $O
     assert_eq!(id.to_owned(), id_buf);
@@ -96,3 +100,87 @@ fn test_interface() {
     assert_eq!(id_buf.deref(), id);
     assert_eq!(*id_buf, *id);
 }
+
+#[test]
+/// Test comparing ids with strings.
+fn test_eq_str() {
+    let id = Id::new("id").expect("is valid id");
+    let id_buf = IdBuf::new("id".into()).expect("is valid id");
+
+    assert!(*id == *"id");
+    assert!(id_buf == *"id");
+    assert_eq!(*id, "id");
+    assert_eq!(id_buf, "id");
+    assert_ne!(*id, "other");
+    assert_ne!(id_buf, "other");
+}
+
+#[test]
+/// Test matching a prefix or suffix of an id.
+fn test_starts_ends_with() {
+    let id = Id::new("pre-id-suf").expect("is valid id");
+    let id_buf = id.to_owned();
+
+    assert!(id.starts_with("pre-"));
+    assert!(id_buf.starts_with("pre-"));
+    assert!(!id.starts_with("suf"));
+    assert!(id.ends_with("-suf"));
+    assert!(id_buf.ends_with("-suf"));
+    assert!(!id.ends_with("pre"));
+    assert!(id.starts_with(""));
+}
+
+#[test]
+/// Test parsing owned ids with FromStr.
+fn test_from_str() {
+    let id_buf: IdBuf = "id".parse().expect("is valid id");
+    assert_eq!(id_buf, IdBuf::new("id".into()).unwrap());
+
+    assert_matches!("".parse::<IdBuf>(), Err(DmError::DeviceIdEmpty));
+    assert_matches!(
+        "a".repeat(TYPE_LEN).parse::<IdBuf>(),
+        Err(DmError::DeviceIdTooLong(_, _))
+    );
+    assert_matches!(
+        "a\u{2014}b".parse::<IdBuf>(),
+        Err(DmError::DeviceIdHasBadChars)
+    );
+}
+
+#[test]
+/// Test conversion with TryFrom / TryInto.
+fn test_try_from() {
+    let id: &Id = "id".try_into().expect("is valid id");
+    assert_eq!(id, Id::new("id").unwrap());
+    assert_matches!(<&Id>::try_from(""), Err(DmError::DeviceIdEmpty));
+
+    let value = String::from("id");
+    let ptr = value.as_ptr();
+    let id_buf: IdBuf = value.try_into().expect("is valid id");
+    assert_eq!(&*id_buf, id);
+    // The string was moved into the id without reallocating.
+    assert_eq!(id_buf.as_bytes().as_ptr(), ptr);
+    assert_matches!(
+        IdBuf::try_from(String::new()),
+        Err(DmError::DeviceIdEmpty)
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+/// Test that deserialization runs the same checks as `new`.
+fn test_deserialize() {
+    use serde::de::{value, Deserialize, IntoDeserializer};
+
+    fn deserialize(s: &str) -> Result<IdBuf, value::Error> {
+        IdBuf::deserialize(s.into_deserializer())
+    }
+
+    assert_eq!(
+        &*deserialize("example").unwrap(),
+        Id::new("example").unwrap()
+    );
+    assert!(deserialize("").is_err());
+    assert!(deserialize(&"a".repeat(TYPE_LEN)).is_err());
+    assert!(deserialize("a\u{1f980}").is_err());
+}
@@ -51,3 +51,106 @@ fn test_device_from_kdev_t() {
     assert_eq!(dev2.minor, 0xF123_4590);
     assert_eq!(dev2.to_string(), "2882397816:4045620624");
 }
+
+#[test]
+/// Tests of parsing `Device` from `<major>:<minor>` strings.
+fn test_device_from_str() {
+    let dev: Device = "253:2".parse().expect("is valid device number");
+    assert_eq!(
+        dev,
+        Device {
+            major: 253,
+            minor: 2
+        }
+    );
+    assert_eq!(dev.to_string().parse::<Device>().unwrap(), dev);
+
+    for bad in [
+        "", "253", "253:", ":2", "253:2:1", "-1:2", "a:b", "/dev/sda",
+    ] {
+        assert_matches!(
+            bad.parse::<Device>(),
+            Err(DmError::InvalidArgument(_)),
+            "{bad:?} should not parse"
+        );
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+/// Tests of deserializing `Device` from `<major>:<minor>` strings.
+fn test_device_deserialize() {
+    use serde::de::{value, Deserialize, IntoDeserializer};
+
+    let de: value::StrDeserializer<'_, value::Error> =
+        "253:2".into_deserializer();
+    assert_eq!(
+        Device::deserialize(de).unwrap(),
+        Device {
+            major: 253,
+            minor: 2
+        }
+    );
+
+    let de: value::StrDeserializer<'_, value::Error> =
+        "/dev/sda".into_deserializer();
+    assert!(Device::deserialize(de).is_err());
+}
+
+#[test]
+/// Tests of `Device::from_devnode` on paths that are not block devices.
+fn test_device_from_devnode_not_block() {
+    assert_eq!(Device::from_devnode(Path::new("/dev/null")).unwrap(), None);
+    assert_eq!(Device::from_devnode(Path::new("/")).unwrap(), None);
+    assert_eq!(
+        Device::from_devnode(Path::new("/nonexistent/device/node")).unwrap(),
+        None
+    );
+}
+
+#[test]
+/// Devices sort numerically by major, then minor, not by their
+/// string representations.
+fn test_device_ord() {
+    let mut devs: Vec<Device> = ["10:0", "2:0", "2:10", "2:9"]
+        .iter()
+        .map(|s| s.parse().unwrap())
+        .collect();
+    devs.sort();
+    let sorted: Vec<String> = devs.iter().map(|d| d.to_string()).collect();
+    assert_eq!(sorted, ["2:0", "2:9", "2:10", "10:0"]);
+}
+
+#[test]
+/// Test finding the device-mapper block major in `/proc/devices`,
+/// where a character device may share its name.
+fn test_parse_proc_devices() {
+    let devices = "\
+Character devices:
+  1 mem
+ 10 misc
+ 99 device-mapper
+
+Block devices:
+  7 loop
+  8 sd
+253 device-mapper
+254 mdp
+";
+    assert_eq!(parse_proc_devices(devices), Some(253));
+    assert_eq!(
+        parse_proc_devices("Character devices:\n 99 device-mapper\n"),
+        None
+    );
+    assert_eq!(parse_proc_devices("Block devices:\n  8 sd\n"), None);
+}
+
+#[test]
+/// The DM node path depends only on the minor number.
+fn test_to_dm_node_path() {
+    let dev = Device {
+        major: 253,
+        minor: 12,
+    };
+    assert_eq!(dev.to_dm_node_path(), Path::new("/dev/dm-12"));
+}
@@ -0,0 +1,943 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests of parsing ioctl result buffers, using buffers constructed
+//! the way the kernel constructs them.
+
+use std::os::unix::io::AsFd;
+
+use super::*;
+
+/// Append a table status record to `buf`, in the format produced by
+/// the kernel's `retrieve_status`.  The record's `next` points past
+/// the padding that aligns the following record, but the padding is
+/// only added to `buf` if `pad` is true; the kernel omits it after
+/// the last record.
+fn push_status(
+    buf: &mut Vec<u8>,
+    start: u64,
+    length: u64,
+    target_type: &str,
+    params: &[u8],
+    pad: bool,
+) {
+    let mut spec = Struct_dm_target_spec {
+        sector_start: start,
+        length,
+        ..Default::default()
+    };
+    for (dst, src) in spec.target_type.iter_mut().zip(target_type.bytes()) {
+        *dst = src as _;
+    }
+    let end = buf.len() + size_of::<Struct_dm_target_spec>() + params.len();
+    spec.next = align_to(end, 8) as u32;
+
+    buf.extend(slice_from_c_struct(&spec));
+    buf.extend(params);
+    if pad {
+        buf.resize(align_to(end, 8), 0);
+    }
+}
+
+#[test]
+/// Test that raw status keeps the params region untouched, and that
+/// the parsed status is layered on top of it.
+fn test_parse_table_status() {
+    let mut buf = Vec::new();
+    push_status(&mut buf, 0, 2048, "linear", b"8:16 0\0", true);
+    push_status(&mut buf, 2048, 1024, "error", b"\0", false);
+
+    let raw = DM::parse_table_status_raw(2, &buf).unwrap();
+    assert_eq!(
+        raw,
+        vec![
+            RawTargetStatus {
+                sector_start: 0,
+                length: 2048,
+                target_type: "linear".to_string(),
+                params: b"8:16 0\0\0".to_vec(),
+            },
+            RawTargetStatus {
+                sector_start: 2048,
+                length: 1024,
+                target_type: "error".to_string(),
+                params: b"\0".to_vec(),
+            },
+        ]
+    );
+
+    let mut lines = Vec::new();
+    DM::parse_table_status(2, &buf, DmFlags::empty(), &mut lines).unwrap();
+    assert_eq!(
+        lines,
+        vec![
+            TargetLine::new(0, 2048, "linear", "8:16 0").unwrap(),
+            TargetLine::new(2048, 1024, "error", "").unwrap(),
+        ]
+    );
+}
+
+#[test]
+/// Test that status is only parsed if the table asked about is
+/// present, so that targets the kernel reports for another table are
+/// not returned, and that a secure buffer is wiped either way.
+fn test_table_status_reply_absent_table() {
+    let mut buf = Vec::new();
+    push_status(&mut buf, 0, 2048, "crypt", b"secret\0", false);
+    let info = |flags: DmFlags| {
+        DeviceInfo::new(Struct_dm_ioctl {
+            flags: flags.bits(),
+            target_count: 1,
+            ..Default::default()
+        })
+        .unwrap()
+    };
+
+    let active = info(DmFlags::DM_ACTIVE_PRESENT);
+    let mut data = buf.clone();
+    assert_eq!(
+        DM::table_status_reply(
+            &active,
+            &mut data,
+            DmFlags::DM_QUERY_INACTIVE_TABLE | DmFlags::DM_SECURE_DATA
+        ),
+        Ok(vec![])
+    );
+    assert!(data.iter().all(|&byte| byte == 0));
+
+    let mut data = buf.clone();
+    assert_eq!(
+        DM::table_status_reply(&active, &mut data, DmFlags::empty()),
+        Ok(vec![TargetLine::new(0, 2048, "crypt", "secret").unwrap()])
+    );
+    assert_eq!(data, buf);
+
+    let inactive = info(DmFlags::DM_INACTIVE_PRESENT);
+    let mut data = buf.clone();
+    assert_eq!(
+        DM::table_status_reply(&inactive, &mut data, DmFlags::empty()),
+        Ok(vec![])
+    );
+}
+
+#[test]
+/// Test that raw status does not require the params to be text.
+fn test_parse_table_status_binary() {
+    let mut buf = Vec::new();
+    push_status(&mut buf, 0, 8, "example", b"\xff\xfe\0", false);
+
+    let raw = DM::parse_table_status_raw(1, &buf).unwrap();
+    assert_eq!(raw[0].params, b"\xff\xfe\0");
+    assert_matches!(
+        DM::parse_table_status(1, &buf, DmFlags::empty(), &mut Vec::new()),
+        Err(DmError::IoctlResultMalformed(
+            MalformedReason::TargetParamsInvalid
+        ))
+    );
+}
+
+#[test]
+/// Test that truncated buffers and bogus offsets are errors, not
+/// out-of-bounds reads.
+fn test_parse_table_status_malformed() {
+    let mut buf = Vec::new();
+    push_status(&mut buf, 0, 8, "error", b"\0", false);
+
+    assert_matches!(
+        DM::parse_table_status_raw(2, &buf),
+        Err(DmError::IoctlResultMalformed(
+            MalformedReason::TruncatedRecord
+        ))
+    );
+    assert_matches!(
+        DM::parse_table_status_raw(1, &buf[..buf.len() / 2]),
+        Err(DmError::IoctlResultMalformed(
+            MalformedReason::TruncatedRecord
+        ))
+    );
+
+    // A record whose next offset points back into itself.  `next`
+    // follows sector_start, length, and status in dm_target_spec.
+    buf[20..24].copy_from_slice(&0u32.to_ne_bytes());
+    assert_matches!(
+        DM::parse_table_status_raw(1, &buf),
+        Err(DmError::IoctlResultMalformed(
+            MalformedReason::BadNextOffset
+        ))
+    );
+
+    // The last record's next offset may point past the end of the
+    // buffer by its padding, but no further.
+    let padded = align_to(buf.len(), 8) as u32;
+    buf[20..24].copy_from_slice(&padded.to_ne_bytes());
+    assert!(DM::parse_table_status_raw(1, &buf).is_ok());
+    buf[20..24].copy_from_slice(&(padded + 8).to_ne_bytes());
+    assert_matches!(
+        DM::parse_table_status_raw(1, &buf),
+        Err(DmError::IoctlResultMalformed(
+            MalformedReason::BadNextOffset
+        ))
+    );
+}
+
+/// Append a device list record to `buf`, in the format produced by
+/// the kernel's `list_devices`, and point the previous record's
+/// `next` at it.  `prev` is the offset of the previous record, if
+/// there is one.  If `uuid_requested` is false, the flags word is
+/// zero, as the kernel leaves it when uuids were not requested.
+fn push_name(
+    buf: &mut Vec<u8>,
+    prev: Option<usize>,
+    dev: u64,
+    name: &str,
+    event_nr: u32,
+    uuid_requested: bool,
+    uuid: Option<&str>,
+) -> usize {
+    let start = buf.len();
+    if let Some(prev) = prev {
+        let next = (start - prev) as u32;
+        buf[prev + 8..prev + 12].copy_from_slice(&next.to_ne_bytes());
+    }
+
+    buf.extend(dev.to_ne_bytes());
+    buf.extend(0u32.to_ne_bytes());
+    buf.extend(name.as_bytes());
+    buf.push(0);
+    buf.resize(align_to(buf.len() - start, 8) + start, 0);
+
+    let flags = match (uuid_requested, uuid) {
+        (false, _) => DmNameListFlags::empty(),
+        (true, Some(_)) => DmNameListFlags::HAS_UUID,
+        (true, None) => DmNameListFlags::DOESNT_HAVE_UUID,
+    };
+    buf.extend(event_nr.to_ne_bytes());
+    buf.extend(flags.bits().to_ne_bytes());
+    if let (true, Some(uuid)) = (uuid_requested, uuid) {
+        buf.extend(uuid.as_bytes());
+        buf.push(0);
+    }
+    buf.resize(align_to(buf.len() - start, 8) + start, 0);
+    start
+}
+
+/// Decode every record of `buf`, a device list.
+fn parse_name_list(
+    buf: &[u8],
+    event_nr_set: bool,
+    flags_set: bool,
+) -> DmResult<Vec<DeviceListEntry>> {
+    DeviceIter::new(buf.to_vec(), event_nr_set, flags_set).collect_entries()
+}
+
+#[test]
+/// Test decoding names, device numbers, event numbers, flags, and
+/// uuids from a device list.
+fn test_parse_name_list() {
+    let mut buf = Vec::new();
+    let first = push_name(&mut buf, None, 0xfd00, "a", 3, true, Some("u-a"));
+    push_name(&mut buf, Some(first), 0xfd01, "bb", 0, true, None);
+
+    let devs = parse_name_list(&buf, true, true).unwrap();
+    assert_eq!(devs.len(), 2);
+    assert_eq!(&*devs[0].name, DmName::new("a").unwrap());
+    assert_eq!(devs[0].device, Device::from_kdev_t(0xfd00));
+    assert_eq!(devs[0].event_nr, Some(3));
+    assert_eq!(devs[0].flags, Some(DmNameListFlags::HAS_UUID));
+    assert_eq!(devs[0].uuid.as_deref(), Some(DmUuid::new("u-a").unwrap()));
+    assert_eq!(&*devs[1].name, DmName::new("bb").unwrap());
+    assert_eq!(devs[1].device, Device::from_kdev_t(0xfd01));
+    assert_eq!(devs[1].event_nr, Some(0));
+    assert_eq!(devs[1].flags, Some(DmNameListFlags::DOESNT_HAVE_UUID));
+    assert_eq!(devs[1].uuid, None);
+}
+
+#[test]
+/// Test that older kernels' lists, which lack event numbers or
+/// uuids, decode without them.
+fn test_parse_name_list_old_kernel() {
+    let mut buf = Vec::new();
+    let first = push_name(&mut buf, None, 0xfd00, "a", 0, false, None);
+    push_name(&mut buf, Some(first), 0xfd01, "b", 0, false, None);
+
+    let devs = parse_name_list(&buf, false, false).unwrap();
+    assert_eq!(devs.len(), 2);
+    assert!(devs.iter().all(|dev| dev.event_nr.is_none()
+        && dev.flags.is_none()
+        && dev.uuid.is_none()));
+
+    // The kernel sets no flags if uuids were not requested.
+    let devs = parse_name_list(&buf, true, true).unwrap();
+    assert!(devs
+        .iter()
+        .all(|dev| dev.flags == Some(DmNameListFlags::empty())
+            && dev.uuid.is_none()));
+
+    assert!(parse_name_list(&[], true, true).unwrap().is_empty());
+}
+
+#[test]
+/// Test that truncated records and bogus offsets are errors, not
+/// out-of-bounds reads.
+fn test_parse_name_list_malformed() {
+    let mut buf = Vec::new();
+    push_name(&mut buf, None, 0xfd00, "a", 0, true, Some("u-a"));
+
+    assert_matches!(
+        parse_name_list(&buf[..10], true, true),
+        Err(DmError::IoctlResultMalformed(
+            MalformedReason::SliceSizeMismatch
+        ))
+    );
+    assert_matches!(
+        parse_name_list(&buf[..buf.len() - 8], true, true),
+        Err(DmError::IoctlResultMalformed(MalformedReason::UuidInvalid))
+    );
+
+    buf[8..12].copy_from_slice(&0x1000u32.to_ne_bytes());
+    assert_matches!(
+        parse_name_list(&buf, true, true),
+        Err(DmError::IoctlResultMalformed(
+            MalformedReason::BadNextOffset
+        ))
+    );
+}
+
+#[test]
+/// Test that a `next` offset that does not move past the current
+/// record is an error, rather than a loop over the same records.
+fn test_parse_name_list_back_pointing_next() {
+    let mut buf = Vec::new();
+    let first = push_name(&mut buf, None, 0xfd00, "a", 0, true, None);
+    let second = push_name(&mut buf, Some(first), 0xfd01, "b", 0, true, None);
+    assert_eq!(parse_name_list(&buf, true, true).unwrap().len(), 2);
+
+    // Point the second record's `next` back into its own header, so
+    // that the third record would overlap it.
+    buf[second + 8..second + 12].copy_from_slice(&8u32.to_ne_bytes());
+    assert_matches!(
+        parse_name_list(&buf, true, true),
+        Err(DmError::IoctlResultMalformed(
+            MalformedReason::BadNextOffset
+        ))
+    );
+
+    // A `next` that points into the record's uuid is just as bad.
+    let mut buf = Vec::new();
+    push_name(&mut buf, None, 0xfd00, "a", 0, true, Some("u-a"));
+    let uuid_start = buf.len() - 8;
+    buf[8..12].copy_from_slice(&(uuid_start as u32).to_ne_bytes());
+    assert_matches!(
+        parse_name_list(&buf, true, true),
+        Err(DmError::IoctlResultMalformed(
+            MalformedReason::BadNextOffset
+        ))
+    );
+}
+
+#[test]
+/// Test that the iterator yields each device in turn, and ends after
+/// yielding an error for a bogus offset.
+fn test_device_iter() {
+    let mut buf = Vec::new();
+    let first = push_name(&mut buf, None, 0xfd00, "a", 3, false, None);
+    push_name(&mut buf, Some(first), 0xfd01, "b", 4, false, None);
+
+    let devs = DeviceIter::new(buf.clone(), true, false)
+        .collect::<DmResult<Vec<_>>>()
+        .unwrap();
+    assert_eq!(
+        devs,
+        [
+            (
+                DmName::new("a").unwrap().to_owned(),
+                Device::from_kdev_t(0xfd00),
+                Some(3)
+            ),
+            (
+                DmName::new("b").unwrap().to_owned(),
+                Device::from_kdev_t(0xfd01),
+                Some(4)
+            ),
+        ]
+    );
+
+    buf[8..12].copy_from_slice(&0x1000u32.to_ne_bytes());
+    let mut iter = DeviceIter::new(buf, true, false);
+    assert_matches!(
+        iter.next(),
+        Some(Err(DmError::IoctlResultMalformed(
+            MalformedReason::BadNextOffset
+        )))
+    );
+    assert_matches!(iter.next(), None);
+    assert_matches!(DeviceIter::new(Vec::new(), true, true).next(), None);
+}
+
+/// The file descriptor flags and file status flags of `dm`'s fd.
+fn fd_flags(dm: &DM) -> (i32, i32) {
+    let fd = dm.as_raw_fd();
+    // SAFETY: F_GETFD and F_GETFL take no further arguments.
+    unsafe {
+        (
+            libc::fcntl(fd, libc::F_GETFD),
+            libc::fcntl(fd, libc::F_GETFL),
+        )
+    }
+}
+
+#[test]
+/// Test that open_with opens the given path with the given flags.
+/// `/dev/null` stands in for the control node, which does not need
+/// to be a DM control node until an ioctl is issued.
+fn test_open_with() {
+    let dm = DM::open_with(DmOpenOptions::default().path("/dev/null")).unwrap();
+    let (descriptor_flags, status_flags) = fd_flags(&dm);
+    assert_ne!(descriptor_flags & libc::FD_CLOEXEC, 0);
+    assert_eq!(status_flags & libc::O_NONBLOCK, 0);
+
+    let dm = DM::open_with(
+        DmOpenOptions::default()
+            .path("/dev/null")
+            .cloexec(false)
+            .nonblock(true),
+    )
+    .unwrap();
+    let (descriptor_flags, status_flags) = fd_flags(&dm);
+    assert_eq!(descriptor_flags & libc::FD_CLOEXEC, 0);
+    assert_ne!(status_flags & libc::O_NONBLOCK, 0);
+}
+
+#[test]
+/// Test that a relative path is resolved relative to the directory.
+fn test_open_with_dir() {
+    let dev = File::open("/dev").unwrap();
+    let dir = dev.as_fd();
+    assert!(
+        DM::open_with(DmOpenOptions::default().dir(dir).path("null")).is_ok()
+    );
+    assert_matches!(
+        DM::open_with(
+            DmOpenOptions::default()
+                .dir(dir)
+                .path("no-such-control-node")
+        )
+        .err(),
+        Some(DmError::ContextInit(err))
+            if err.kind() == io::ErrorKind::NotFound
+    );
+    assert_matches!(
+        DM::open_with(DmOpenOptions::default().path("/dev/\0null")).err(),
+        Some(DmError::ContextInit(err))
+            if err.kind() == io::ErrorKind::InvalidInput
+    );
+}
+
+#[test]
+/// Test finding the control device's minor number in /proc/misc.
+fn test_parse_proc_misc() {
+    let misc =
+        "259 cpu_dma_latency\n236 device-mapper\n 58 network_throughput\n";
+    assert_eq!(
+        parse_proc_misc(misc),
+        Some(Device {
+            major: MISC_MAJOR,
+            minor: 236
+        })
+    );
+    assert_eq!(parse_proc_misc("259 cpu_dma_latency\n200 tun\n"), None);
+    assert_eq!(parse_proc_misc("x device-mapper\n"), None);
+}
+
+/// Append a target version record for `name` to `buf`, linking the
+/// record starting at `prev` to it.
+fn push_target_version(
+    buf: &mut Vec<u8>,
+    prev: Option<usize>,
+    name: &str,
+    version: [u32; 3],
+) -> usize {
+    let start = buf.len();
+    if let Some(prev) = prev {
+        let next = (start - prev) as u32;
+        buf[prev..prev + 4].copy_from_slice(&next.to_ne_bytes());
+    }
+
+    buf.extend(0u32.to_ne_bytes());
+    for part in version {
+        buf.extend(part.to_ne_bytes());
+    }
+    buf.extend(name.as_bytes());
+    buf.push(0);
+    buf.resize(align_to(buf.len() - start, 8) + start, 0);
+    start
+}
+
+#[test]
+/// Test decoding target names and versions, and that bogus offsets
+/// and unterminated names are errors.
+fn test_parse_target_versions() {
+    let mut buf = Vec::new();
+    let first = push_target_version(&mut buf, None, "linear", [1, 4, 0]);
+    push_target_version(&mut buf, Some(first), "thin-pool", [1, 23, 0]);
+
+    assert_eq!(
+        DM::parse_target_versions(&buf).unwrap(),
+        vec![
+            ("linear".to_string(), 1, 4, 0),
+            ("thin-pool".to_string(), 1, 23, 0)
+        ]
+    );
+    assert!(DM::parse_target_versions(&[]).unwrap().is_empty());
+
+    assert_matches!(
+        DM::parse_target_versions(&buf[..20]),
+        Err(DmError::IoctlResultMalformed(
+            MalformedReason::TargetTypeInvalid
+        ))
+    );
+
+    buf[0..4].copy_from_slice(&0x1000u32.to_ne_bytes());
+    assert_matches!(
+        DM::parse_target_versions(&buf),
+        Err(DmError::IoctlResultMalformed(
+            MalformedReason::TruncatedRecord
+        ))
+    );
+}
+
+#[test]
+/// Test that operations the kernel's interface version does not
+/// support are refused without issuing them, unless the check is
+/// disabled.  The version is cached in advance, so `/dev/null` can
+/// stand in for the control node.
+fn test_unsupported_kernel() {
+    let dm = DM::open_with(DmOpenOptions::default().path("/dev/null")).unwrap();
+    dm.version.set(Version::new(4, 36, 0)).unwrap();
+    assert_eq!(dm.interface_version().unwrap(), (4, 36, 0));
+    assert_matches!(
+        dm.arm_poll(),
+        Err(DmError::UnsupportedKernel {
+            cmd: DmIoctlCmd::DM_DEV_ARM_POLL,
+            required: (4, 37, 0),
+            actual: (4, 36, 0),
+        })
+    );
+
+    let dm = DM::open_with(
+        DmOpenOptions::default()
+            .path("/dev/null")
+            .check_kernel_version(false),
+    )
+    .unwrap();
+    dm.version.set(Version::new(4, 36, 0)).unwrap();
+    assert_matches!(
+        dm.arm_poll(),
+        Err(DmError::Ioctl(DmIoctlCmd::DM_DEV_ARM_POLL, _, _, _))
+    );
+}
+
+#[test]
+/// Test that a batch records each device's outcome, skipping the
+/// devices after a failure only when told to stop on error.
+fn test_batch() {
+    let info = DeviceInfo::new(Struct_dm_ioctl::default()).unwrap();
+    let name = DmName::new("a").unwrap();
+    let uuid = DmUuid::new("u").unwrap();
+    // Operations on names succeed; operations on uuids fail.
+    let op = |id: &DevId<'_>| match id {
+        DevId::Uuid(_) => Err(DmError::IoctlResultTooLarge),
+        _ => Ok(info.clone()),
+    };
+
+    let ids = [DevId::Name(name), DevId::Name(name)];
+    assert_eq!(
+        DM::batch(&ids, BatchMode::StopOnError, op).unwrap().len(),
+        2
+    );
+
+    let ids = [DevId::Name(name), DevId::Uuid(uuid), DevId::Name(name)];
+    let err = DM::batch(&ids, BatchMode::StopOnError, op).unwrap_err();
+    assert_matches!(
+        &err.outcomes[..],
+        [
+            BatchOutcome::Succeeded(_),
+            BatchOutcome::Failed(DmError::IoctlResultTooLarge),
+            BatchOutcome::Skipped
+        ]
+    );
+    assert_matches!(err.first_error(), Some(DmError::IoctlResultTooLarge));
+
+    let err = DM::batch(&ids, BatchMode::ContinueOnError, op).unwrap_err();
+    assert_matches!(
+        &err.outcomes[..],
+        [
+            BatchOutcome::Succeeded(_),
+            BatchOutcome::Failed(DmError::IoctlResultTooLarge),
+            BatchOutcome::Succeeded(_)
+        ]
+    );
+}
+
+#[test]
+/// Test that the ioctl buffer is kept and reused between calls,
+/// even when the ioctl fails.
+fn test_buffer_reuse() {
+    let dm = DM::open_with(DmOpenOptions::default().path("/dev/null")).unwrap();
+    assert!(dm.version().is_err());
+    let (ptr, capacity) = {
+        let buffer = dm.buffer.lock().unwrap();
+        (buffer.as_ptr(), buffer.capacity())
+    };
+    assert!(capacity >= MIN_BUF_SIZE);
+
+    for _ in 0..10 {
+        assert!(dm.version().is_err());
+        let buffer = dm.buffer.lock().unwrap();
+        assert_eq!(buffer.as_ptr(), ptr);
+        assert_eq!(buffer.capacity(), capacity);
+    }
+}
+
+#[test]
+/// Test that poll_event reports readiness and times out.  A FIFO
+/// stands in for the control node: it is ready once written to.
+fn test_poll_event() {
+    let dm = DM::open_with(DmOpenOptions::default().path("/dev/null")).unwrap();
+    assert!(dm.poll_event(None).unwrap());
+
+    let path = std::env::temp_dir()
+        .join(format!("dm_ioctl-test-poll-{}", std::process::id()));
+    let c_path = path_to_cstring(&path).unwrap();
+    // SAFETY: c_path is a valid C string.
+    assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+
+    let dm = DM::open_with(DmOpenOptions::default().path(&path).nonblock(true))
+        .unwrap();
+    let mut writer = fs::OpenOptions::new().write(true).open(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let start = Instant::now();
+    assert!(!dm.poll_event(Some(Duration::from_millis(50))).unwrap());
+    assert!(start.elapsed() >= Duration::from_millis(50));
+
+    writer.write_all(b"x").unwrap();
+    assert!(dm.poll_event(Some(Duration::from_secs(10))).unwrap());
+}
+
+#[test]
+/// Test that loading a table with key material sets DM_SECURE_DATA
+/// and leaves no copy of the table in the saved ioctl buffer.
+/// `/dev/null` stands in for the control node; the ioctl fails, but
+/// only after the buffer has been filled in.
+fn test_table_load_secure() {
+    let dm = DM::open_with(DmOpenOptions::default().path("/dev/null")).unwrap();
+    let name = DmName::new("example-dev").unwrap();
+    let key = "0123456789abcdef0123456789abcdef";
+    let mut table = TargetTable::new();
+    table
+        .push(8, "crypt", &format!("aes-cbc-essiv:sha256 {key} 0 8:16 0"))
+        .unwrap();
+    let holds_key = |dm: &DM| {
+        dm.buffer
+            .lock()
+            .unwrap()
+            .windows(key.len())
+            .any(|window| window == key.as_bytes())
+    };
+
+    dm.table_load(&DevId::Name(name), table.targets(), DmFlags::empty())
+        .unwrap_err();
+    assert!(holds_key(&dm));
+
+    assert_matches!(
+        dm.table_load_typed(&DevId::Name(name), &table, DmFlags::empty()),
+        Err(DmError::Ioctl(_, Some(hdr_in), _, _))
+            if hdr_in.flags().contains(DmFlags::DM_SECURE_DATA)
+    );
+    let buffer = dm.buffer.lock().unwrap();
+    assert!(!buffer.is_empty());
+    assert!(buffer.iter().all(|&byte| byte == 0));
+}
+
+#[test]
+/// Test that parsing a secure table status makes no copy of the
+/// params other than the returned lines, and that when a later
+/// record is malformed, the lines already parsed are wiped.
+fn test_parse_table_status_secure() {
+    let mut buf = Vec::new();
+    push_status(&mut buf, 0, 8, "crypt", b"aes-xts-plain64 0123abcd\0", true);
+    push_status(&mut buf, 8, 8, "crypt", b"\xff\0", false);
+
+    let mut lines = Vec::new();
+    DM::parse_table_status(1, &buf, DmFlags::DM_SECURE_DATA, &mut lines)
+        .unwrap();
+    assert_eq!(lines[0].params, "aes-xts-plain64 0123abcd");
+    assert_eq!(lines[0].params.capacity(), lines[0].params.len());
+
+    let mut lines = Vec::new();
+    assert_matches!(
+        DM::parse_table_status(2, &buf, DmFlags::empty(), &mut lines),
+        Err(DmError::IoctlResultMalformed(
+            MalformedReason::TargetParamsInvalid
+        ))
+    );
+    assert_eq!(lines[0].params, "aes-xts-plain64 0123abcd");
+
+    let mut lines = Vec::new();
+    assert_matches!(
+        DM::parse_table_status(2, &buf, DmFlags::DM_SECURE_DATA, &mut lines),
+        Err(DmError::IoctlResultMalformed(
+            MalformedReason::TargetParamsInvalid
+        ))
+    );
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].params.is_empty());
+}
+
+#[test]
+/// Test that a device number id is put in the header's dev field,
+/// and that one the kernel cannot represent is rejected.
+fn test_hdr_devno() {
+    let device = Device {
+        major: 253,
+        minor: 300,
+    };
+    let hdr = DmFlags::empty()
+        .to_ioctl_hdr(Some(&DevId::Devno(device)), DmFlags::empty())
+        .unwrap();
+    assert_eq!(Device::from_kdev_t(hdr.dev), device);
+    assert!(hdr.name.iter().all(|&c| c == 0));
+    assert!(hdr.uuid.iter().all(|&c| c == 0));
+
+    let device = Device {
+        major: 0x1000,
+        minor: 0,
+    };
+    assert_matches!(
+        DmFlags::empty()
+            .to_ioctl_hdr(Some(&DevId::Devno(device)), DmFlags::empty()),
+        Err(DmError::InvalidArgument(_))
+    );
+}
+
+#[test]
+/// Test that an out-of-range persistent minor number is rejected
+/// before the ioctl is issued.
+fn test_create_persistent_range() {
+    let dm = DM::open_with(DmOpenOptions::default().path("/dev/null")).unwrap();
+    dm.version.set(Version::new(4, 48, 0)).unwrap();
+    let name = DmName::new("example-dev").unwrap();
+    assert_matches!(
+        dm.device_create_persistent(name, None, 0x10_0000, DmFlags::empty()),
+        Err(DmError::InvalidArgument(_))
+    );
+    let err = dm
+        .device_create_persistent(name, None, 0xf_ffff, DmFlags::empty())
+        .unwrap_err();
+    assert_eq!(err.ioctl_cmd(), Some(DmIoctlCmd::DM_DEV_CREATE));
+}
+
+#[test]
+/// Test creating, keeping, replacing, and removing `/dev/mapper`
+/// style symlinks, in a scratch directory that does not exist yet.
+fn test_mapper_node() {
+    let dir = std::env::temp_dir()
+        .join(format!("dm_ioctl-test-mapper-{}", std::process::id()))
+        .join("mapper");
+    let name = DmName::new("example-dev").unwrap();
+    let device = Device {
+        major: 253,
+        minor: 4,
+    };
+
+    let path =
+        make_mapper_node(&dir, name, device, MapperNode::Symlink).unwrap();
+    assert_eq!(path, dir.join("example-dev"));
+    assert_eq!(fs::read_link(&path).unwrap(), Path::new("../dm-4"));
+    assert_eq!(
+        make_mapper_node(&dir, name, device, MapperNode::Symlink).unwrap(),
+        path
+    );
+
+    let device = Device {
+        major: 253,
+        minor: 5,
+    };
+    make_mapper_node(&dir, name, device, MapperNode::Symlink).unwrap();
+    assert_eq!(fs::read_link(&path).unwrap(), Path::new("../dm-5"));
+
+    remove_mapper_node(&dir, name).unwrap();
+    assert!(fs::symlink_metadata(&path).is_err());
+    remove_mapper_node(&dir, name).unwrap();
+
+    fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+
+    let control = DmName::new("control").unwrap();
+    assert_matches!(
+        remove_mapper_node(Path::new(DM_DEV_DIR), control),
+        Err(DmError::InvalidArgument(_))
+    );
+}
+
+#[test]
+/// Test that ioctl_raw issues the requested command, subject to the
+/// kernel version check.
+fn test_ioctl_raw() {
+    let dm = DM::open_with(DmOpenOptions::default().path("/dev/null")).unwrap();
+    dm.version.set(Version::new(4, 36, 0)).unwrap();
+    let name = DmName::new("example-dev").unwrap();
+    let err = dm
+        .ioctl_raw(
+            DmIoctlCmd::DM_DEV_STATUS,
+            Some(&DevId::Name(name)),
+            DmFlags::DM_SECURE_DATA,
+            Some(b"data"),
+        )
+        .unwrap_err();
+    assert_eq!(err.ioctl_cmd(), Some(DmIoctlCmd::DM_DEV_STATUS));
+    assert_eq!(err.errno(), Some(Errno::ENOTTY));
+
+    assert_matches!(
+        dm.ioctl_raw(DmIoctlCmd::DM_DEV_ARM_POLL, None, DmFlags::empty(), None),
+        Err(DmError::UnsupportedKernel {
+            cmd: DmIoctlCmd::DM_DEV_ARM_POLL,
+            ..
+        })
+    );
+}
+
+#[test]
+/// Test that device_remove_retry only retries on EBUSY.
+fn test_device_remove_retry() {
+    let dm = DM::open_with(DmOpenOptions::default().path("/dev/null")).unwrap();
+    dm.version.set(Version::new(4, 36, 0)).unwrap();
+    let name = DmName::new("example-dev").unwrap();
+    let start = Instant::now();
+    let err = dm
+        .device_remove_retry(
+            &DevId::Name(name),
+            DmFlags::default(),
+            3,
+            Duration::from_secs(60),
+        )
+        .unwrap_err();
+    assert_eq!(err.ioctl_cmd(), Some(DmIoctlCmd::DM_DEV_REMOVE));
+    assert_eq!(err.errno(), Some(Errno::ENOTTY));
+    assert!(start.elapsed() < Duration::from_secs(60));
+}
+
+#[test]
+/// Test that the methods that can carry key material accept
+/// DM_SECURE_DATA.
+fn test_secure_data_accepted() {
+    let dm = DM::open_with(DmOpenOptions::default().path("/dev/null")).unwrap();
+    dm.version.set(Version::new(4, 36, 0)).unwrap();
+    let name = DmName::new("example-dev").unwrap();
+    let id = DevId::Name(name);
+    let table = [TargetLine::new(0, 1024, "crypt", "secret").unwrap()];
+
+    let err = dm
+        .table_load(&id, &table, DmFlags::DM_SECURE_DATA)
+        .unwrap_err();
+    assert_eq!(err.errno(), Some(Errno::ENOTTY));
+    let err = dm
+        .table_status(&id, DmFlags::DM_STATUS_TABLE | DmFlags::DM_SECURE_DATA)
+        .unwrap_err();
+    assert_eq!(err.errno(), Some(Errno::ENOTTY));
+    let opts = ReloadOptions {
+        secure_data: true,
+        ..Default::default()
+    };
+    assert_matches!(
+        dm.table_reload(&id, &table, opts),
+        Err(DmError::TableReload(ReloadStep::Load, _))
+    );
+}
+
+#[test]
+/// Test that wipe_table asks only for the INFO status of a device,
+/// which gives the extent of each target but not its parameters.
+fn test_wipe_table_flags() {
+    let dm = DM::open_with(DmOpenOptions::default().path("/dev/null")).unwrap();
+    dm.version.set(Version::new(4, 36, 0)).unwrap();
+    let name = DmName::new("example-dev").unwrap();
+    assert_matches!(
+        dm.wipe_table(&DevId::Name(name), ReloadOptions::default()),
+        Err(DmError::Ioctl(DmIoctlCmd::DM_TABLE_STATUS, Some(hdr_in), _, _))
+            if hdr_in.flags() == DmFlags::empty()
+    );
+}
+
+#[test]
+/// Test deciding whether the table a request asked about is present.
+fn test_queried_table_present() {
+    let info = |flags: DmFlags| {
+        DeviceInfo::new(Struct_dm_ioctl {
+            flags: flags.bits(),
+            ..Default::default()
+        })
+        .unwrap()
+    };
+
+    let active = info(DmFlags::DM_ACTIVE_PRESENT);
+    assert!(queried_table_present(&active, DmFlags::DM_STATUS_TABLE));
+    assert!(!queried_table_present(
+        &active,
+        DmFlags::DM_QUERY_INACTIVE_TABLE
+    ));
+
+    let inactive = info(DmFlags::DM_INACTIVE_PRESENT);
+    assert!(!queried_table_present(&inactive, DmFlags::empty()));
+    assert!(queried_table_present(
+        &inactive,
+        DmFlags::DM_QUERY_INACTIVE_TABLE | DmFlags::DM_STATUS_TABLE
+    ));
+}
+
+#[test]
+/// Test that a table load request holds each target in turn, each
+/// record's next offset leading to the following one, and that
+/// validation checks the layout without a DM context.
+fn test_validate_table_load() {
+    let id = DevId::Name(DmName::new("example-dev").unwrap());
+    let table = [
+        TargetLine::new(0, 1024, "linear", "8:16 0").unwrap(),
+        TargetLine::new(1024, 512, "zero", "").unwrap(),
+        TargetLine::new(1536, 8, "error", "x").unwrap(),
+    ];
+
+    let (hdr, data) =
+        DM::table_load_request(&id, &table, DmFlags::DM_READONLY).unwrap();
+    assert_eq!(hdr.target_count, 3);
+    assert_eq!(hdr.flags, DmFlags::DM_READONLY.bits());
+    let mut offset = 0;
+    for line in &table {
+        let spec = unsafe {
+            ptr::read_unaligned(
+                data[offset..].as_ptr() as *const Struct_dm_target_spec
+            )
+        };
+        assert_eq!((spec.sector_start, spec.length), (line.start, line.length));
+        let params = &data[offset + size_of::<Struct_dm_target_spec>()..];
+        assert_eq!(str_from_byte_slice(params), Some(&*line.params));
+        offset += spec.next as usize;
+    }
+    assert_eq!(offset, data.len());
+
+    assert_matches!(
+        DM::validate_table_load(&id, &table, DmFlags::default()),
+        Ok(())
+    );
+    assert_matches!(
+        DM::validate_table_load(&id, &table[1..], DmFlags::default()),
+        Err(DmError::InvalidArgument(_))
+    );
+
+    let name = DmName::new("example-dev").unwrap();
+    assert_matches!(
+        DM::validate_device_create(name, None, DmFlags::default()),
+        Ok(())
+    );
+}
@@ -0,0 +1,83 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use nix::errno::Errno;
+
+use super::*;
+
+#[test]
+/// Test that errors compare by variant and contents, ignoring what
+/// cannot be compared.
+fn test_dm_error_eq() {
+    assert_eq!(DmError::DeviceIdEmpty, DmError::DeviceIdEmpty);
+    assert_ne!(DmError::DeviceIdEmpty, DmError::DeviceIdHasBadChars);
+    assert_eq!(
+        DmError::DeviceIdTooLong(130, 128),
+        DmError::DeviceIdTooLong(130, 128)
+    );
+    assert_ne!(
+        DmError::DeviceIdTooLong(130, 128),
+        DmError::DeviceIdTooLong(129, 128)
+    );
+    assert_eq!(
+        DmError::IoctlResultMalformed(MalformedReason::BadNextOffset),
+        DmError::IoctlResultMalformed(MalformedReason::BadNextOffset)
+    );
+
+    assert_eq!(
+        DmError::ContextInit(io::Error::from(io::ErrorKind::NotFound)),
+        DmError::ContextInit(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no control node"
+        ))
+    );
+    assert_ne!(
+        DmError::ContextInit(io::Error::from(io::ErrorKind::NotFound)),
+        DmError::RequestConstruction(io::Error::from(io::ErrorKind::NotFound))
+    );
+    assert_ne!(
+        DmError::DeviceNode(
+            "/dev/a".into(),
+            io::Error::from(io::ErrorKind::NotFound)
+        ),
+        DmError::DeviceNode(
+            "/dev/b".into(),
+            io::Error::from(io::ErrorKind::NotFound)
+        )
+    );
+
+    let info = DeviceInfo::new(Default::default()).ok().map(Box::new);
+    assert_eq!(
+        DmError::Ioctl(DmIoctlCmd::DM_DEV_REMOVE, info, None, Errno::EBUSY),
+        DmError::Ioctl(DmIoctlCmd::DM_DEV_REMOVE, None, None, Errno::EBUSY)
+    );
+    assert_ne!(
+        DmError::Ioctl(DmIoctlCmd::DM_DEV_REMOVE, None, None, Errno::EBUSY),
+        DmError::Ioctl(DmIoctlCmd::DM_DEV_REMOVE, None, None, Errno::ENXIO)
+    );
+    assert_eq!(
+        DmError::TableReload(
+            ReloadStep::Resume,
+            Box::new(DmError::IoctlResultTooLarge)
+        ),
+        DmError::TableReload(
+            ReloadStep::Resume,
+            Box::new(DmError::IoctlResultTooLarge)
+        )
+    );
+}
+
+#[test]
+/// The opcode and system error code of an `Ioctl` error can be
+/// retrieved without matching on the variant.
+fn test_errno_and_ioctl_cmd() {
+    let err =
+        DmError::Ioctl(DmIoctlCmd::DM_DEV_RENAME, None, None, Errno::EBUSY);
+    assert_eq!(err.errno(), Some(Errno::EBUSY));
+    assert_eq!(err.ioctl_cmd(), Some(DmIoctlCmd::DM_DEV_RENAME));
+
+    let err = DmError::InvalidArgument("bad".to_string());
+    assert_eq!(err.errno(), None);
+    assert_eq!(err.ioctl_cmd(), None);
+}
@@ -0,0 +1,51 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests of translating option types to flags.
+
+use super::*;
+
+#[test]
+/// Each DmOptions setter controls exactly one flag.
+fn test_dm_options_flags() {
+    assert_eq!(DmFlags::from(DmOptions::default()), DmFlags::empty());
+
+    let options = DmOptions::default()
+        .read_only(true)
+        .deferred_remove(true)
+        .secure_data(true);
+    assert_eq!(
+        options.flags(),
+        DmFlags::DM_READONLY
+            | DmFlags::DM_DEFERRED_REMOVE
+            | DmFlags::DM_SECURE_DATA
+    );
+    assert_eq!(
+        options.read_only(false).flags(),
+        DmFlags::DM_DEFERRED_REMOVE | DmFlags::DM_SECURE_DATA
+    );
+
+    let options = DmOptions::default()
+        .suspend(true)
+        .noflush(true)
+        .skip_lockfs(true);
+    assert_eq!(
+        options.flags(),
+        DmFlags::DM_SUSPEND | DmFlags::DM_NOFLUSH | DmFlags::DM_SKIP_LOCKFS
+    );
+}
+
+#[test]
+/// SuspendOptions always requests a suspend.
+fn test_suspend_options_flags() {
+    assert_eq!(SuspendOptions::default().to_flags(), DmFlags::DM_SUSPEND);
+    assert_eq!(
+        SuspendOptions {
+            noflush: true,
+            skip_lockfs: true,
+        }
+        .to_flags(),
+        DmFlags::DM_SUSPEND | DmFlags::DM_NOFLUSH | DmFlags::DM_SKIP_LOCKFS
+    );
+}
@@ -0,0 +1,151 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests for DM statistics.
+
+use super::*;
+
+#[test]
+/// Steps are written as the kernel expects them.
+fn test_step_display() {
+    assert_eq!(StatsStep::AreaSize(128).to_string(), "128");
+    assert_eq!(StatsStep::Areas(4).to_string(), "/4");
+}
+
+#[test]
+/// Lines of a `@stats_list` reply parse, with and without their
+/// optional fields.
+fn test_region_parse() {
+    assert_eq!(
+        "0: 0+2048 512 - -".parse(),
+        Ok(StatsRegion {
+            region_id: 0,
+            start: 0,
+            length: 2048,
+            step: 512,
+            program_id: "-".to_string(),
+            aux_data: "-".to_string(),
+            precise_timestamps: false,
+            histogram: Vec::new(),
+        })
+    );
+    assert_eq!(
+        "3: 1024+1024 1024 example data precise_timestamps \
+         histogram:10,100,1000"
+            .parse(),
+        Ok(StatsRegion {
+            region_id: 3,
+            start: 1024,
+            length: 1024,
+            step: 1024,
+            program_id: "example".to_string(),
+            aux_data: "data".to_string(),
+            precise_timestamps: true,
+            histogram: vec![10, 100, 1000],
+        })
+    );
+}
+
+#[test]
+/// Malformed `@stats_list` lines are rejected.
+fn test_region_parse_invalid() {
+    for text in [
+        "",
+        "0: 0+2048 512 -",
+        "0 0+2048 512 - -",
+        "0: 0-2048 512 - -",
+        "0: 0+x 512 - -",
+        "0: 0+2048 512 - - bogus",
+        "0: 0+2048 512 - - histogram:1,,2",
+    ] {
+        assert_matches!(
+            text.parse::<StatsRegion>(),
+            Err(DmError::IoctlResultMalformed(
+                MalformedReason::MessageReplyInvalid
+            )),
+            "{text:?}"
+        );
+    }
+}
+
+#[test]
+/// Program ids that cannot be sent as one argument, or that the
+/// kernel would read as a number, are rejected.
+fn test_check_program_id() {
+    for program_id in ["example", "-", "2x", "v1"] {
+        assert_matches!(check_program_id(program_id), Ok(()));
+    }
+    for program_id in ["", "two words", "tab\there", "0", "2", "+7", "-1"] {
+        assert_matches!(
+            check_program_id(program_id),
+            Err(DmError::InvalidArgument(_))
+        );
+    }
+}
+
+#[test]
+/// A `@stats_print` reply parses into the bounds and counters of each
+/// area, with or without a histogram.
+fn test_parse_print_output() {
+    let output = "0+256 1 0 8 2 3 1 24 5 0 7 9 2 5\n\
+                  256+256 0 0 0 0 0 0 0 0 1 0 0 0 0 4:0:1\n";
+    let expected = vec![
+        (
+            RegionBounds {
+                start: 0,
+                length: 256,
+            },
+            StatsCounters {
+                reads: 1,
+                read_merges: 0,
+                read_sectors: 8,
+                read_time: 2,
+                writes: 3,
+                write_merges: 1,
+                write_sectors: 24,
+                write_time: 5,
+                in_flight: 0,
+                io_ticks: 7,
+                time_in_queue: 9,
+                read_ticks: 2,
+                write_ticks: 5,
+                histogram: Vec::new(),
+            },
+        ),
+        (
+            RegionBounds {
+                start: 256,
+                length: 256,
+            },
+            StatsCounters {
+                in_flight: 1,
+                histogram: vec![4, 0, 1],
+                ..Default::default()
+            },
+        ),
+    ];
+    assert_eq!(parse_print_output(output), Ok(expected));
+    assert_eq!(parse_print_output(""), Ok(Vec::new()));
+}
+
+#[test]
+/// Malformed `@stats_print` lines are rejected.
+fn test_parse_print_output_invalid() {
+    for text in [
+        "0+256",
+        "0 1 0 8 2 3 1 24 5 0 7 9 2 5",
+        "0+256 1 0 8 2 3 1 24 5 0 7 9 2",
+        "0+256 1 0 8 2 3 1 24 5 0 7 9 2 x",
+        "0+256 1 0 8 2 3 1 24 5 0 7 9 2 5 1:2 3",
+        "0+256 1 0 8 2 3 1 24 5 0 7 9 2 5 1::2",
+    ] {
+        assert_matches!(
+            parse_print_output(text),
+            Err(DmError::IoctlResultMalformed(
+                MalformedReason::MessageReplyInvalid
+            )),
+            "{text:?}"
+        );
+    }
+}
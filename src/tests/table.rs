@@ -0,0 +1,260 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests for table construction.
+
+use super::*;
+
+#[test]
+/// Each pushed target starts where the previous one ended.
+fn test_push_is_contiguous() {
+    let mut table = TargetTable::new();
+    assert_eq!(table.total_length(), 0);
+
+    table.push(100, "zero", "").unwrap();
+    table.push(50, "linear", "8:16 0").unwrap();
+    table.push(25, "error", "").unwrap();
+    assert_eq!(table.total_length(), 175);
+
+    assert_eq!(
+        table.into_targets(),
+        vec![
+            TargetLine::new(0, 100, "zero", "").unwrap(),
+            TargetLine::new(100, 50, "linear", "8:16 0").unwrap(),
+            TargetLine::new(150, 25, "error", "").unwrap(),
+        ]
+    );
+}
+
+#[test]
+/// Empty targets, which the kernel rejects, are rejected.
+fn test_push_zero_length() {
+    let mut table = TargetTable::new();
+    table.push(100, "zero", "").unwrap();
+    assert_matches!(
+        table.push(0, "linear", "8:16 0"),
+        Err(DmError::InvalidArgument(_))
+    );
+    assert_eq!(table.total_length(), 100);
+}
+
+#[test]
+/// Target types that do not fit in the kernel's structure, and
+/// tables that overflow the sector count, are rejected.
+fn test_push_bad() {
+    let mut table = TargetTable::new();
+    for bad in ["", "a-very-long-name", "a\u{0}b", "caf\u{e9}"] {
+        assert_eq!(
+            table.push(1, bad, ""),
+            Err(TargetLine::new(0, 1, bad, "").unwrap_err()),
+            "{bad:?} should be rejected"
+        );
+    }
+    assert_eq!(table.push(1, "", ""), Err(DmError::DeviceIdEmpty));
+    assert_eq!(
+        table.push(1, "a-very-long-name", ""),
+        Err(DmError::DeviceIdTooLong(15, 16))
+    );
+    table.push(1, "abcdefghijklmno", "").unwrap();
+
+    assert_matches!(
+        table.push(u64::MAX, "zero", ""),
+        Err(DmError::InvalidArgument(_))
+    );
+    assert_eq!(table.total_length(), 1);
+}
+
+#[test]
+/// A table whose last target ends past the largest sector number
+/// saturates its total length, and cannot be extended.
+fn test_total_length_overflow() {
+    let mut table: TargetTable = [TargetLine {
+        start: u64::MAX,
+        length: 1,
+        ..TargetLine::new(0, 1, "zero", "").unwrap()
+    }]
+    .into_iter()
+    .collect();
+    assert_eq!(table.total_length(), u64::MAX);
+    assert_matches!(
+        table.push(1, "zero", ""),
+        Err(DmError::InvalidArgument(_))
+    );
+}
+
+#[test]
+/// Only tables with crypt or integrity targets are sensitive.
+fn test_contains_sensitive_params() {
+    let mut table = TargetTable::new();
+    table.push(100, "linear", "8:16 0").unwrap();
+    assert!(!table.contains_sensitive_params());
+
+    table
+        .push(100, "crypt", "aes-xts-plain64 :64:logon:key 0 8:32 0")
+        .unwrap();
+    assert!(table.contains_sensitive_params());
+}
+
+#[test]
+/// Typed parameters supply the target type and params string.
+fn test_push_typed() {
+    use crate::{
+        device::Device,
+        targets::{LinearTargetParams, StripedTargetParams},
+    };
+
+    let dest = Device {
+        major: 8,
+        minor: 16,
+    };
+    let mut table = TargetTable::new();
+    table
+        .push_typed(100, &LinearTargetParams { dest, offset: 2048 })
+        .unwrap();
+    table
+        .push_typed(
+            256,
+            &StripedTargetParams {
+                chunk_size: 128,
+                stripes: vec![(dest, 0), (dest, 4096)],
+            },
+        )
+        .unwrap();
+
+    assert_eq!(
+        table.into_targets(),
+        vec![
+            TargetLine::new(0, 100, "linear", "8:16 2048").unwrap(),
+            TargetLine::new(100, 256, "striped", "2 128 8:16 0 8:16 4096")
+                .unwrap(),
+        ]
+    );
+}
+
+#[test]
+/// Test that the layout check reports gaps, overlaps, and other
+/// problems, and accepts tables built with TargetTable.
+fn test_check_table_layout() {
+    let target =
+        |start, length| TargetLine::new(start, length, "zero", "").unwrap();
+    let message = |targets: &[TargetLine]| match check_table_layout(targets) {
+        Err(DmError::InvalidArgument(message)) => message,
+        other => panic!("unexpected result {other:?}"),
+    };
+
+    assert_matches!(check_table_layout(&[]), Ok(()));
+    assert_matches!(
+        check_table_layout(&[target(0, 1024), target(1024, 1)]),
+        Ok(())
+    );
+
+    assert_eq!(
+        message(&[target(1, 1024)]),
+        "target 0 starts at sector 1, not sector 0"
+    );
+    assert_eq!(
+        message(&[target(0, 1024), target(1023, 1)]),
+        "target 1 starts at sector 1023, but target 0 ends at sector 1024"
+    );
+    assert_eq!(
+        message(&[target(0, 1024), target(1025, 1)]),
+        "target 1 starts at sector 1025, but target 0 ends at sector 1024"
+    );
+    assert_eq!(
+        message(&[target(0, 1024), target(1024, 0)]),
+        "target 1 at sector 1024 has length 0"
+    );
+    assert_matches!(
+        check_table_layout(&[target(0, u64::MAX), target(u64::MAX, 1)]),
+        Err(DmError::InvalidArgument(_))
+    );
+
+    let mut table = TargetTable::new();
+    table.push(1024, "zero", "").unwrap();
+    table.push(2048, "error", "").unwrap();
+    assert_matches!(check_table_layout(table.targets()), Ok(()));
+}
+
+#[test]
+/// Target types are checked like device IDs, and lines convert to
+/// and from the tuples used by earlier versions.
+fn test_target_line() {
+    assert_matches!(TargetLine::new(0, 1, "", ""), Err(DmError::DeviceIdEmpty));
+    assert_matches!(
+        TargetLine::new(0, 1, "a-very-long-name", ""),
+        Err(DmError::DeviceIdTooLong(15, 16))
+    );
+
+    let line = TargetLine::new(0, 2048, "linear", "8:16 0").unwrap();
+    let tuple: (u64, u64, String, String) = line.clone().into();
+    assert_eq!(tuple, (0, 2048, "linear".to_string(), "8:16 0".to_string()));
+    assert_eq!(TargetLine::try_from(tuple).unwrap(), line);
+    assert_matches!(
+        TargetLine::try_from((0, 1, "caf\u{e9}".to_string(), String::new())),
+        Err(DmError::DeviceIdHasBadChars)
+    );
+}
+
+#[test]
+/// Tables are displayed as `dmsetup table` displays them, and can be
+/// collected from lines.
+fn test_display_and_collect() {
+    let table = [
+        TargetLine::new(0, 100, "zero", "").unwrap(),
+        TargetLine::new(100, 50, "linear", "8:16 0").unwrap(),
+    ]
+    .into_iter()
+    .collect::<TargetTable>();
+    assert_eq!(table.total_length(), 150);
+    assert_eq!(table.to_string(), "0 100 zero\n100 50 linear 8:16 0\n");
+    assert_eq!(table.targets()[1].to_string(), "100 50 linear 8:16 0");
+    assert_eq!(
+        (&table).into_iter().map(|line| line.length).sum::<u64>(),
+        150
+    );
+    assert_eq!(TargetTable::new().to_string(), "");
+}
+
+#[test]
+/// Single-target error and zero tables cover the requested length.
+fn test_error_zero_table() {
+    let table = TargetTable::error_table(2048).unwrap();
+    assert_eq!(table.total_length(), 2048);
+    assert_eq!(
+        table.targets(),
+        [TargetLine::new(0, 2048, "error", "").unwrap()]
+    );
+    assert_eq!(
+        TargetTable::zero_table(4096).unwrap().to_string(),
+        "0 4096 zero\n"
+    );
+
+    assert_matches!(
+        TargetTable::error_table(0),
+        Err(DmError::InvalidArgument(_))
+    );
+    assert_matches!(
+        TargetTable::zero_table(0),
+        Err(DmError::InvalidArgument(_))
+    );
+}
+
+#[test]
+/// A table read back as lines can be modified and checked before it
+/// is loaded again.
+fn test_modify_and_check_layout() {
+    let mut table: TargetTable = vec![
+        TargetLine::new(0, 2048, "linear", "8:16 0").unwrap(),
+        TargetLine::new(2048, 2048, "linear", "8:32 0").unwrap(),
+    ]
+    .into();
+    assert_eq!(table.check_layout(), Ok(()));
+
+    table.targets_mut()[1].params = "8:48 0".to_string();
+    assert_eq!(table.targets()[1].params, "8:48 0");
+    assert_eq!(table.check_layout(), Ok(()));
+
+    table.targets_mut()[1].start = 1024;
+    assert_matches!(table.check_layout(), Err(DmError::InvalidArgument(_)));
+}
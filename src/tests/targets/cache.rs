@@ -0,0 +1,143 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests for the cache target.
+
+use super::*;
+
+fn params(
+    features: Vec<CacheFeature>,
+    policy: &str,
+    policy_args: &[(&str, &str)],
+) -> CacheTargetParams {
+    CacheTargetParams {
+        metadata_dev: Device {
+            major: 8,
+            minor: 16,
+        },
+        cache_dev: Device {
+            major: 8,
+            minor: 32,
+        },
+        origin_dev: Device {
+            major: 8,
+            minor: 48,
+        },
+        block_size: 128,
+        features,
+        policy: policy.to_string(),
+        policy_args: policy_args
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect(),
+    }
+}
+
+#[test]
+/// Parameters as printed by the kernel parse and round-trip.
+fn test_params_round_trip() {
+    for (text, params) in [
+        (
+            "8:16 8:32 8:48 128 0 default 0",
+            params(vec![], "default", &[]),
+        ),
+        (
+            "8:16 8:32 8:48 128 1 passthrough smq 0",
+            params(vec![CacheFeature::Passthrough], "smq", &[]),
+        ),
+        (
+            "8:16 8:32 8:48 128 3 metadata2 writeback no_discard_passdown \
+             smq 4 migration_threshold 2048 cleaner 1",
+            params(
+                vec![
+                    CacheFeature::Metadata2,
+                    CacheFeature::Writeback,
+                    CacheFeature::NoDiscardPassdown,
+                ],
+                "smq",
+                &[("migration_threshold", "2048"), ("cleaner", "1")],
+            ),
+        ),
+    ] {
+        let parsed: CacheTargetParams = text.parse().unwrap();
+        assert_eq!(parsed, params);
+        assert_eq!(parsed.to_string(), text);
+    }
+}
+
+#[test]
+/// The feature count counts features, and the policy argument count
+/// counts keys and values separately.
+fn test_params_counts() {
+    let text = params(
+        vec![CacheFeature::Writethrough, CacheFeature::Metadata2],
+        "smq",
+        &[("migration_threshold", "2048")],
+    )
+    .to_string();
+    let fields = text.split_whitespace().collect::<Vec<_>>();
+    assert_eq!(fields[4], "2");
+    assert_eq!(&fields[5..7], ["writethrough", "metadata2"]);
+    assert_eq!(fields[7], "smq");
+    assert_eq!(fields[8], "2");
+    assert_eq!(fields.len(), 11);
+
+    // A policy name that looks like a feature is not counted as one.
+    assert_eq!(
+        "8:16 8:32 8:48 128 0 writeback 0".parse::<CacheTargetParams>(),
+        Ok(params(vec![], "writeback", &[]))
+    );
+}
+
+#[test]
+/// Malformed parameters, and combinations the kernel rejects, are
+/// rejected.
+fn test_params_bad() {
+    for text in [
+        "",
+        "8:16 8:32 8:48 128",
+        "8:16 8:32 8:48 128 0",
+        "8:16 8:32 8:48 128 0 smq",
+        "8:16 8:32 8:48 128 1 smq 0",
+        "8:16 8:32 8:48 128 2 writeback smq 0",
+        "8:16 8:32 8:48 128 1 bogus smq 0",
+        "8:16 8:32 8:48 128 2 writeback writethrough smq 0",
+        "8:16 8:32 8:48 128 2 metadata2 metadata2 smq 0",
+        "8:16 8:32 8:48 128 0 smq 2 migration_threshold",
+        "8:16 8:32 8:48 128 0 smq 1 migration_threshold 2048",
+        "8:16 8:32 8:48 128 0 smq 1 migration_threshold",
+        "8:16 8:32 8:48 128 0 smq 2 migration_threshold 2048 1",
+        "8:16 8:32 8:48 32 0 smq 0",
+        "8:16 8:32 8:48 96 0 smq 0",
+        "8:16 8:32 8:48 4194304 0 smq 0",
+        "8:16 8:32 8:48 0 0 smq 0",
+    ] {
+        assert_matches!(
+            text.parse::<CacheTargetParams>(),
+            Err(DmError::InvalidArgument(_)),
+            "{text:?} should not parse"
+        );
+    }
+
+    let base = params(vec![], "smq", &[]);
+    for (policy, policy_args) in [
+        ("", vec![]),
+        ("s mq", vec![]),
+        ("smq", vec![("".to_string(), "1".to_string())]),
+        ("smq", vec![("cleaner".to_string(), "1 2".to_string())]),
+    ] {
+        assert_matches!(
+            CacheTargetParams::new(
+                base.metadata_dev,
+                base.cache_dev,
+                base.origin_dev,
+                128,
+                vec![],
+                policy.to_string(),
+                policy_args,
+            ),
+            Err(DmError::InvalidArgument(_))
+        );
+    }
+}
@@ -0,0 +1,97 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests for the clone target.
+
+use super::*;
+
+fn params(
+    features: CloneFeatures,
+    hydration_threshold: Option<u32>,
+    hydration_batch_size: Option<u32>,
+) -> CloneTargetParams {
+    CloneTargetParams::new(
+        Device {
+            major: 8,
+            minor: 16,
+        },
+        Device {
+            major: 8,
+            minor: 32,
+        },
+        Device {
+            major: 8,
+            minor: 48,
+        },
+        8,
+        features,
+        hydration_threshold,
+        hydration_batch_size,
+    )
+    .unwrap()
+}
+
+#[test]
+/// Parameters parse and round-trip, with each count written only
+/// when something follows it.
+fn test_params_round_trip() {
+    for (text, expected) in [
+        (
+            "8:16 8:32 8:48 8",
+            params(CloneFeatures::empty(), None, None),
+        ),
+        (
+            "8:16 8:32 8:48 8 2 no_hydration no_discard_passdown",
+            params(CloneFeatures::all(), None, None),
+        ),
+        (
+            "8:16 8:32 8:48 8 0 4 hydration_threshold 1 \
+             hydration_batch_size 1",
+            params(CloneFeatures::empty(), Some(1), Some(1)),
+        ),
+        (
+            "8:16 8:32 8:48 8 1 no_hydration 2 hydration_batch_size 16",
+            params(CloneFeatures::NO_HYDRATION, None, Some(16)),
+        ),
+    ] {
+        let parsed: CloneTargetParams = text.parse().unwrap();
+        assert_eq!(parsed, expected, "{text}");
+        assert_eq!(parsed.to_string(), text);
+    }
+
+    // Core arguments may be given in any order.
+    assert_eq!(
+        "8:16 8:32 8:48 8 0 4 hydration_batch_size 2 hydration_threshold 3"
+            .parse(),
+        Ok(params(CloneFeatures::empty(), Some(3), Some(2)))
+    );
+}
+
+#[test]
+/// Malformed or invalid parameters are rejected.
+fn test_params_parse_invalid() {
+    for text in [
+        "",
+        "8:16 8:32 8:48",
+        "8:16 8:32 8:48 12",
+        "8:16 8:32 8:48 4",
+        "8:16 8:32 8:48 4194304",
+        "8:16 8:32 8:48 8 1",
+        "8:16 8:32 8:48 8 1 bogus",
+        "8:16 8:32 8:48 8 2 no_hydration no_hydration",
+        "8:16 8:32 8:48 8 0 2 hydration_threshold",
+        "8:16 8:32 8:48 8 0 1 hydration_threshold 4",
+        "8:16 8:32 8:48 8 0 2 hydration_threshold 0",
+        "8:16 8:32 8:48 8 0 2 hydration_batch_size 0",
+        "8:16 8:32 8:48 8 0 2 hydration_bogus 4",
+        "8:16 8:32 8:48 8 0 4 hydration_threshold 1 hydration_threshold 2",
+        "8:16 8:32 8:48 8 0 2 hydration_threshold 1 extra",
+    ] {
+        assert_matches!(
+            text.parse::<CloneTargetParams>(),
+            Err(DmError::InvalidArgument(_)),
+            "{text:?}"
+        );
+    }
+}
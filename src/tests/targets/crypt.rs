@@ -0,0 +1,237 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests for the crypt target.
+
+use super::*;
+use crate::{table::TargetLine, targets::TypedTargetLine};
+
+fn params(key: CryptKey, options: CryptOptions) -> CryptTargetParams {
+    CryptTargetParams {
+        cipher: CryptCipher::Legacy {
+            cipher: "aes".to_string(),
+            key_count: None,
+            chain_mode: Some("xts".to_string()),
+            iv_mode: Some("plain64".to_string()),
+            iv_opts: None,
+        },
+        key,
+        iv_offset: 0,
+        dest: Device {
+            major: 8,
+            minor: 16,
+        },
+        offset: 4096,
+        options,
+    }
+}
+
+#[test]
+/// Lines as printed by the kernel parse and round-trip.
+fn test_parse_crypt() {
+    for (text, key, options) in [
+        (
+            "aes-xts-plain64 00112233 0 8:16 4096",
+            CryptKey::Bytes(vec![0x00, 0x11, 0x22, 0x33]),
+            CryptOptions::default(),
+        ),
+        (
+            "aes-xts-plain64 abcdef 0 8:16 4096 2 allow_discards \
+             sector_size:4096",
+            CryptKey::Bytes(vec![0xab, 0xcd, 0xef]),
+            CryptOptions {
+                allow_discards: true,
+                sector_size: Some(4096),
+                ..Default::default()
+            },
+        ),
+        (
+            "aes-xts-plain64 - 0 8:16 4096",
+            CryptKey::Bytes(vec![]),
+            CryptOptions::default(),
+        ),
+        (
+            "aes-xts-plain64 :64:logon:cryptsetup:volume 0 8:16 4096",
+            CryptKey::Keyring {
+                size: 64,
+                key_type: "logon".to_string(),
+                description: "cryptsetup:volume".to_string(),
+            },
+            CryptOptions::default(),
+        ),
+    ] {
+        let line = TargetLine::new(0, 8192, "crypt", text).unwrap();
+        let target =
+            TypedTargetLine::<CryptTargetParams>::parse(&line).unwrap();
+        assert_eq!(target.params, params(key, options));
+        assert_eq!(target.params.param_str(), text);
+        assert_eq!(target.to_table_line().unwrap(), line);
+    }
+
+    // Upper case hex is accepted, though the kernel prints lower case.
+    assert_eq!(
+        "aes-xts-plain64 ABCDEF 0 8:16 4096".parse(),
+        Ok(params(
+            CryptKey::Bytes(vec![0xab, 0xcd, 0xef]),
+            CryptOptions::default()
+        ))
+    );
+}
+
+#[test]
+/// Every option is counted and written in the kernel's order, and
+/// options this crate does not know about are kept.
+fn test_parse_options() {
+    let options = CryptOptions {
+        allow_discards: true,
+        same_cpu_crypt: true,
+        submit_from_crypt_cpus: true,
+        no_read_workqueue: true,
+        no_write_workqueue: true,
+        integrity: Some((28, "aead".to_string())),
+        sector_size: Some(4096),
+        iv_large_sectors: true,
+        other: vec!["high_priority".to_string()],
+    };
+    let text = "aes-xts-plain64 00 0 8:16 4096 9 allow_discards \
+                same_cpu_crypt submit_from_crypt_cpus no_read_workqueue \
+                no_write_workqueue integrity:28:aead sector_size:4096 \
+                iv_large_sectors high_priority";
+    let parsed: CryptTargetParams = text.parse().unwrap();
+    assert_eq!(parsed, params(CryptKey::Bytes(vec![0]), options));
+    assert_eq!(parsed.to_string(), text);
+
+    assert_eq!(
+        "aes-xts-plain64 00 0 8:16 4096 2 iv_large_sectors allow_discards"
+            .parse::<CryptTargetParams>()
+            .unwrap()
+            .to_string(),
+        "aes-xts-plain64 00 0 8:16 4096 2 allow_discards iv_large_sectors"
+    );
+}
+
+#[test]
+/// Cipher specifications are split as the kernel splits them.
+fn test_parse_cipher() {
+    for (text, cipher) in [
+        (
+            "cipher_null",
+            CryptCipher::Legacy {
+                cipher: "cipher_null".to_string(),
+                key_count: None,
+                chain_mode: None,
+                iv_mode: None,
+                iv_opts: None,
+            },
+        ),
+        (
+            "aes:64-cbc-lmk",
+            CryptCipher::Legacy {
+                cipher: "aes".to_string(),
+                key_count: Some(64),
+                chain_mode: Some("cbc".to_string()),
+                iv_mode: Some("lmk".to_string()),
+                iv_opts: None,
+            },
+        ),
+        (
+            "capi:xts(aes)-plain64",
+            CryptCipher::Capi {
+                spec: "xts(aes)".to_string(),
+                iv_mode: Some("plain64".to_string()),
+                iv_opts: None,
+            },
+        ),
+        (
+            "capi:cbc(aes)-essiv:sha256",
+            CryptCipher::Capi {
+                spec: "cbc(aes)".to_string(),
+                iv_mode: Some("essiv".to_string()),
+                iv_opts: Some("sha256".to_string()),
+            },
+        ),
+    ] {
+        assert_eq!(text.parse(), Ok(cipher.clone()));
+        assert_eq!(cipher.to_string(), text);
+    }
+
+    for text in ["", "aes:x-cbc-plain", "aes xts"] {
+        assert_matches!(
+            text.parse::<CryptCipher>(),
+            Err(DmError::InvalidArgument(_)),
+            "{text:?} should not parse"
+        );
+    }
+}
+
+#[test]
+/// Malformed lines are rejected, without the key in the message.
+fn test_parse_crypt_bad() {
+    for text in [
+        "",
+        "aes-xts-plain64 00 0 8:16",
+        "aes-xts-plain64 001 0 8:16 4096",
+        "aes-xts-plain64 0g 0 8:16 4096",
+        "aes-xts-plain64 +1 0 8:16 4096",
+        "aes-xts-plain64 :64:logon 0 8:16 4096",
+        "aes-xts-plain64 :x:logon:key 0 8:16 4096",
+        "aes-xts-plain64 00 0 8:16 4096 2 allow_discards",
+        "aes-xts-plain64 00 0 8:16 4096 1 sector_size:x",
+        "aes-xts-plain64 00 0 8:16 4096 1 integrity:28",
+        "aes-xts-plain64 00 0 sdb 4096",
+    ] {
+        match text.parse::<CryptTargetParams>() {
+            Err(DmError::InvalidArgument(msg)) => {
+                assert!(!msg.contains("0g") && !msg.contains("+1"), "{msg}")
+            }
+            other => panic!("{text:?} should not parse: {other:?}"),
+        }
+    }
+
+    assert_matches!(
+        TypedTargetLine::<CryptTargetParams>::parse(
+            &TargetLine::new(0, 8192, "linear", "8:16 0").unwrap()
+        ),
+        Err(DmError::InvalidArgument(_))
+    );
+}
+
+#[test]
+/// The params string is allocated at exactly its final length, however
+/// long the fields besides the key are, so it never grows.
+fn test_param_str_exact_capacity() {
+    let mut params = params(
+        CryptKey::Keyring {
+            size: 64,
+            key_type: "logon".to_string(),
+            description: "d".repeat(300),
+        },
+        CryptOptions {
+            integrity: Some((28, "aead".to_string())),
+            other: vec!["x".repeat(300); 3],
+            ..Default::default()
+        },
+    );
+    if let CryptCipher::Legacy { cipher, .. } = &mut params.cipher {
+        *cipher = "c".repeat(300);
+    }
+    let text = params.param_str();
+    assert_eq!(text, params.to_string());
+    assert_eq!(text.capacity(), text.len());
+}
+
+#[test]
+/// Debug output does not include the key.
+fn test_debug_redacts_key() {
+    let debug = format!(
+        "{:?}",
+        params(
+            CryptKey::Bytes(vec![0xab, 0xcd, 0xef]),
+            CryptOptions::default()
+        )
+    );
+    assert!(!debug.contains("171"), "{debug}");
+    assert!(!debug.contains("abcdef"), "{debug}");
+    assert!(debug.contains("<3 bytes>"), "{debug}");
+}
@@ -0,0 +1,95 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests for the delay target.
+
+use super::*;
+
+fn class(minor: u32, delay_ms: u32) -> DelayClass {
+    DelayClass {
+        dest: Device { major: 8, minor },
+        offset: 2048,
+        delay_ms,
+    }
+}
+
+#[test]
+/// The 3, 6, and 9 field forms all parse and round-trip.
+fn test_params_round_trip() {
+    for (params, write, flush) in [
+        ("8:16 2048 100", None, None),
+        ("8:16 2048 100 8:32 2048 200", Some(class(32, 200)), None),
+        (
+            "8:16 2048 100 8:32 2048 200 8:48 2048 0",
+            Some(class(32, 200)),
+            Some(class(48, 0)),
+        ),
+    ] {
+        let parsed: DelayTargetParams = params.parse().unwrap();
+        assert_eq!(
+            parsed,
+            DelayTargetParams {
+                read: class(16, 100),
+                write,
+                flush,
+            }
+        );
+        assert_eq!(parsed.to_string(), params);
+    }
+}
+
+#[test]
+/// A flush class without a write class writes the read class in its
+/// place.
+fn test_flush_without_write() {
+    let params = DelayTargetParams {
+        read: class(16, 100),
+        write: None,
+        flush: Some(class(48, 0)),
+    };
+    assert_eq!(
+        params.to_string(),
+        "8:16 2048 100 8:16 2048 100 8:48 2048 0"
+    );
+}
+
+#[test]
+/// Malformed parameters are rejected.
+fn test_params_bad() {
+    for params in [
+        "",
+        "8:16 2048",
+        "8:16 2048 100 8:32",
+        "8:16 2048 100 8:32 2048 200 8:48 2048 0 1",
+        "sdb 2048 100",
+        "8:16 2048 -1",
+        "8:16 2048 4294967296",
+    ] {
+        assert_matches!(
+            params.parse::<DelayTargetParams>(),
+            Err(DmError::InvalidArgument(_)),
+            "{params:?} should not parse"
+        );
+    }
+}
+
+#[test]
+/// Delays convert to and from Durations in whole milliseconds.
+fn test_duration() {
+    let dest = Device {
+        major: 8,
+        minor: 16,
+    };
+    let class = DelayClass::new(dest, 0, Duration::from_micros(1500)).unwrap();
+    assert_eq!(class.delay_ms, 1);
+    assert_eq!(class.delay(), Duration::from_millis(1));
+    assert_matches!(
+        DelayClass::new(dest, 0, Duration::from_secs(5_000_000)),
+        Err(DmError::InvalidArgument(_))
+    );
+
+    let params =
+        DelayTargetParams::new(dest, 0, Duration::from_secs(1)).unwrap();
+    assert_eq!(params.to_string(), "8:16 0 1000");
+}
@@ -0,0 +1,55 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests for the dust target.
+
+use super::*;
+
+#[test]
+/// Parameters as printed by the kernel parse and round-trip, at both
+/// ends of the block size range.
+fn test_params_round_trip() {
+    for (text, offset, block_size) in [
+        ("8:16 0 512", 0, 512),
+        ("8:16 2048 4096", 2048, 4096),
+        ("8:16 0 1073741824", 0, 1 << 30),
+    ] {
+        let parsed: DustTargetParams = text.parse().unwrap();
+        assert_eq!(
+            parsed,
+            DustTargetParams::new(
+                Device {
+                    major: 8,
+                    minor: 16
+                },
+                offset,
+                block_size
+            )
+            .unwrap()
+        );
+        assert_eq!(parsed.to_string(), text);
+    }
+}
+
+#[test]
+/// Malformed or invalid parameters are rejected.
+fn test_params_parse_invalid() {
+    for text in [
+        "",
+        "8:16 0",
+        "8:16 0 512 0",
+        "8:16 0 0",
+        "8:16 0 256",
+        "8:16 0 1000",
+        "8:16 0 2147483648",
+        "8:16 x 512",
+        "8 0 512",
+    ] {
+        assert_matches!(
+            text.parse::<DustTargetParams>(),
+            Err(DmError::InvalidArgument(_)),
+            "{text:?}"
+        );
+    }
+}
@@ -0,0 +1,56 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests for the ebs target.
+
+use super::*;
+
+#[test]
+/// Parameters as printed by the kernel parse and round-trip, with and
+/// without the underlying block size.
+fn test_params_round_trip() {
+    for (text, offset, emulated, underlying) in [
+        ("8:16 0 8", 0, 8, None),
+        ("8:16 2048 1 8", 2048, 1, Some(8)),
+        ("8:16 0 8 1", 0, 8, Some(1)),
+    ] {
+        let parsed: EbsTargetParams = text.parse().unwrap();
+        assert_eq!(
+            parsed,
+            EbsTargetParams::new(
+                Device {
+                    major: 8,
+                    minor: 16
+                },
+                offset,
+                emulated,
+                underlying
+            )
+            .unwrap()
+        );
+        assert_eq!(parsed.to_string(), text);
+    }
+}
+
+#[test]
+/// Malformed or invalid parameters are rejected.
+fn test_params_parse_invalid() {
+    for text in [
+        "",
+        "8:16 0",
+        "8:16 0 8 8 8",
+        "8:16 0 0",
+        "8:16 0 3",
+        "8:16 0 8 0",
+        "8:16 0 8 6",
+        "8:16 x 8",
+        "8 0 8",
+    ] {
+        assert_matches!(
+            text.parse::<EbsTargetParams>(),
+            Err(DmError::InvalidArgument(_)),
+            "{text:?}"
+        );
+    }
+}
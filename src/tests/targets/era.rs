@@ -0,0 +1,59 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests for the era target.
+
+use super::*;
+
+#[test]
+/// Parameters as printed by the kernel parse and round-trip.
+fn test_params_round_trip() {
+    let text = "8:16 8:32 128";
+    let parsed: EraTargetParams = text.parse().unwrap();
+    assert_eq!(
+        parsed,
+        EraTargetParams::new(
+            Device {
+                major: 8,
+                minor: 16
+            },
+            Device {
+                major: 8,
+                minor: 32
+            },
+            128
+        )
+        .unwrap()
+    );
+    assert_eq!(parsed.to_string(), text);
+
+    // The kernel does not require a power-of-two block size.
+    assert_eq!(
+        "8:16 8:32 100"
+            .parse::<EraTargetParams>()
+            .unwrap()
+            .granularity,
+        100
+    );
+}
+
+#[test]
+/// Malformed or invalid parameters are rejected.
+fn test_params_parse_invalid() {
+    for text in [
+        "",
+        "8:16 8:32",
+        "8:16 8:32 128 0",
+        "8:16 8:32 0",
+        "8:16 8:32 4294967296",
+        "8:16 8:32 x",
+        "8 8:32 128",
+    ] {
+        assert_matches!(
+            text.parse::<EraTargetParams>(),
+            Err(DmError::InvalidArgument(_)),
+            "{text:?}"
+        );
+    }
+}
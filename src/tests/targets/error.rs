@@ -0,0 +1,20 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests for the error target.
+
+use super::*;
+
+#[test]
+/// The empty params string round-trips; anything else is rejected.
+fn test_params_round_trip() {
+    assert_eq!(ErrorTargetParams.to_string(), "");
+    assert_eq!("".parse::<ErrorTargetParams>(), Ok(ErrorTargetParams));
+    assert_eq!(" ".parse::<ErrorTargetParams>(), Ok(ErrorTargetParams));
+    assert_eq!(ErrorTargetParams.target_type(), "error");
+    assert_matches!(
+        "8:16 0".parse::<ErrorTargetParams>(),
+        Err(DmError::InvalidArgument(_))
+    );
+}
@@ -0,0 +1,85 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests for the flakey target.
+
+use super::*;
+
+fn params(features: Vec<FlakeyFeature>) -> FlakeyTargetParams {
+    FlakeyTargetParams {
+        dest: Device {
+            major: 8,
+            minor: 16,
+        },
+        offset: 2048,
+        up_interval: 10,
+        down_interval: 2,
+        features,
+    }
+}
+
+#[test]
+/// Parameters as printed by the kernel parse and round-trip, with
+/// the feature count covering every feature argument.
+fn test_params_round_trip() {
+    for (text, features) in [
+        ("8:16 2048 10 2", vec![]),
+        (
+            "8:16 2048 10 2 1 drop_writes",
+            vec![FlakeyFeature::DropWrites],
+        ),
+        (
+            "8:16 2048 10 2 2 error_reads error_writes",
+            vec![FlakeyFeature::ErrorReads, FlakeyFeature::ErrorWrites],
+        ),
+        (
+            "8:16 2048 10 2 7 corrupt_bio_byte 32 r 1 0 \
+             random_write_corrupt 500000000",
+            vec![
+                FlakeyFeature::CorruptBioByte {
+                    nth_byte: 32,
+                    direction: FlakeyDirection::Read,
+                    value: 1,
+                    flags: 0,
+                },
+                FlakeyFeature::RandomWriteCorrupt(500_000_000),
+            ],
+        ),
+    ] {
+        let parsed: FlakeyTargetParams = text.parse().unwrap();
+        assert_eq!(parsed, params(features));
+        assert_eq!(parsed.to_string(), text);
+    }
+}
+
+#[test]
+/// Malformed parameters, and combinations the kernel rejects, are
+/// rejected.
+fn test_params_bad() {
+    for text in [
+        "",
+        "8:16 2048 10",
+        "8:16 2048 0 0",
+        "8:16 2048 10 2 1",
+        "8:16 2048 10 2 2 drop_writes",
+        "8:16 2048 10 2 1 bogus",
+        "8:16 2048 10 2 2 drop_writes error_writes",
+        "8:16 2048 10 2 5 corrupt_bio_byte 0 w 1 0",
+        "8:16 2048 10 2 5 corrupt_bio_byte 1 x 1 0",
+        "8:16 2048 10 2 5 corrupt_bio_byte 1 w 256 0",
+        "8:16 2048 10 2 4 corrupt_bio_byte 1 w 1",
+        "8:16 2048 10 2 2 random_read_corrupt 1000000001",
+    ] {
+        assert_matches!(
+            text.parse::<FlakeyTargetParams>(),
+            Err(DmError::InvalidArgument(_)),
+            "{text:?} should not parse"
+        );
+    }
+
+    assert_matches!(
+        FlakeyTargetParams::new(params(vec![]).dest, 0, 0, 0, vec![]),
+        Err(DmError::InvalidArgument(_))
+    );
+}
@@ -0,0 +1,185 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests for the integrity target.
+
+use super::*;
+
+fn params(
+    mode: IntegrityMode,
+    options: IntegrityOptions,
+) -> IntegrityTargetParams {
+    IntegrityTargetParams {
+        dest: Device {
+            major: 8,
+            minor: 16,
+        },
+        offset: 0,
+        tag_size: 4,
+        mode,
+        options,
+    }
+}
+
+fn alg(name: &str, key: Option<&[u8]>) -> Option<IntegrityAlgorithm> {
+    Some(IntegrityAlgorithm {
+        name: name.to_string(),
+        key: key.map(<[u8]>::to_vec),
+    })
+}
+
+#[test]
+/// Parameters as printed by the kernel parse and round-trip.
+fn test_params_round_trip() {
+    for (text, mode, options) in [
+        (
+            "8:16 0 4 D",
+            IntegrityMode::Direct,
+            IntegrityOptions::default(),
+        ),
+        (
+            "8:16 0 4 J 5 interleave_sectors:32768 buffer_sectors:128 \
+             journal_sectors:8192 journal_watermark:50 commit_time:10000",
+            IntegrityMode::Journal,
+            IntegrityOptions {
+                interleave_sectors: Some(32768),
+                buffer_sectors: Some(128),
+                journal_sectors: Some(8192),
+                journal_watermark: Some(50),
+                commit_time: Some(10000),
+                ..Default::default()
+            },
+        ),
+        (
+            "8:16 0 4 B 9 meta_device:8:32 block_size:4096 recalculate \
+             allow_discards buffer_sectors:128 sectors_per_bit:65536 \
+             bitmap_flush_interval:10000 fix_padding \
+             internal_hash:crc32c",
+            IntegrityMode::Bitmap,
+            IntegrityOptions {
+                meta_device: Some(Device {
+                    major: 8,
+                    minor: 32,
+                }),
+                block_size: Some(4096),
+                recalculate: true,
+                allow_discards: true,
+                buffer_sectors: Some(128),
+                sectors_per_bit: Some(65536),
+                bitmap_flush_interval: Some(10000),
+                fix_padding: true,
+                internal_hash: alg("crc32c", None),
+                ..Default::default()
+            },
+        ),
+        (
+            "8:16 0 4 R 6 reset_recalculate fix_hmac legacy_recalculate \
+             internal_hash:hmac(sha256):00ff journal_crypt:ctr(aes):0102 \
+             journal_mac:hmac(sha256):abcd",
+            IntegrityMode::Recovery,
+            IntegrityOptions {
+                reset_recalculate: true,
+                fix_hmac: true,
+                legacy_recalculate: true,
+                internal_hash: alg("hmac(sha256)", Some(&[0x00, 0xff])),
+                journal_crypt: alg("ctr(aes)", Some(&[0x01, 0x02])),
+                journal_mac: alg("hmac(sha256)", Some(&[0xab, 0xcd])),
+                ..Default::default()
+            },
+        ),
+        (
+            "8:16 0 4 J 3 buffer_sectors:128 new_flag new_option:1",
+            IntegrityMode::Journal,
+            IntegrityOptions {
+                buffer_sectors: Some(128),
+                other: vec!["new_flag".to_string(), "new_option:1".to_string()],
+                ..Default::default()
+            },
+        ),
+    ] {
+        let expected = params(mode, options);
+        let parsed: IntegrityTargetParams = text.parse().unwrap();
+        assert_eq!(parsed, expected, "{text}");
+        assert_eq!(parsed.to_string(), text);
+        assert_eq!(parsed.param_str(), text);
+    }
+}
+
+#[test]
+/// Options may appear in any order, and a tag size of `-` is zero.
+fn test_params_parse_reordered() {
+    let parsed: IntegrityTargetParams =
+        "8:16 8 - I 2 internal_hash:sha256 allow_discards"
+            .parse()
+            .unwrap();
+    assert_eq!(parsed.offset, 8);
+    assert_eq!(parsed.tag_size, 0);
+    assert_eq!(parsed.mode, IntegrityMode::Inline);
+    assert!(parsed.options.allow_discards);
+    assert_eq!(parsed.options.internal_hash, alg("sha256", None));
+    assert_eq!(
+        parsed.to_string(),
+        "8:16 8 0 I 2 allow_discards internal_hash:sha256"
+    );
+}
+
+#[test]
+/// Malformed parameters are rejected.
+fn test_params_parse_invalid() {
+    for text in [
+        "",
+        "8:16 0 4",
+        "8:16 0 4 X",
+        "8:16 0 4 J 1",
+        "8:16 0 4 J 2 recalculate",
+        "8:16 0 4 J 1 buffer_sectors:many",
+        "8:16 0 4 J 2 recalculate recalculate",
+        "8:16 0 4 J 2 commit_time:1 commit_time:2",
+        "8:16 0 4 J 1 internal_hash:",
+        "8:16 0 4 J 1 internal_hash:hmac(sha256):0g",
+        "8:16 0 4 J 1 internal_hash::00",
+    ] {
+        assert_matches!(
+            text.parse::<IntegrityTargetParams>(),
+            Err(DmError::InvalidArgument(_)),
+            "{text:?}"
+        );
+    }
+}
+
+#[test]
+/// Neither `Debug` nor parse errors show keys.
+fn test_keys_not_shown() {
+    let parsed: IntegrityTargetParams =
+        "8:16 0 32 J 2 internal_hash:hmac(sha256):deadbeef \
+         journal_mac:hmac(sha256):cafe"
+            .parse()
+            .unwrap();
+    let debug = format!("{parsed:?}");
+    assert!(!debug.contains("deadbeef"), "{debug}");
+    assert!(!debug.contains("cafe"), "{debug}");
+    assert!(debug.contains("<4 bytes>"), "{debug}");
+
+    let err = "8:16 0 32 J 1 internal_hash:hmac(sha256):deadbeefx"
+        .parse::<IntegrityTargetParams>()
+        .unwrap_err();
+    assert!(!format!("{err:?}").contains("deadbeef"));
+}
+
+#[test]
+/// The params string is allocated at exactly its final length, however
+/// long the algorithm names are, so it never grows.
+fn test_param_str_exact_capacity() {
+    let params = params(
+        IntegrityMode::Journal,
+        IntegrityOptions {
+            internal_hash: alg(&"h".repeat(600), Some(&[0xde, 0xad])),
+            journal_crypt: alg(&"c".repeat(600), Some(&[0xbe, 0xef])),
+            ..Default::default()
+        },
+    );
+    let text = params.param_str();
+    assert_eq!(text, params.to_string());
+    assert_eq!(text.capacity(), text.len());
+}
@@ -0,0 +1,88 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests for the linear target.
+
+use super::*;
+use crate::{table::TargetLine, targets::TypedTargetLine};
+
+fn line(target_type: &str, params: &str) -> TargetLine {
+    TargetLine::new(2048, 4096, target_type, params).unwrap()
+}
+
+#[test]
+/// A linear line as printed by the kernel parses and round-trips.
+fn test_parse_linear() {
+    let status = line("linear", "8:16 2048");
+    let target = TypedTargetLine::<LinearTargetParams>::parse(&status)
+        .expect("is valid linear line");
+    assert_eq!(
+        target,
+        TypedTargetLine {
+            start: 2048,
+            length: 4096,
+            params: LinearTargetParams {
+                dest: Device {
+                    major: 8,
+                    minor: 16
+                },
+                offset: 2048,
+            },
+        }
+    );
+    assert_eq!(target.to_table_line().unwrap(), status);
+}
+
+#[test]
+/// Lines for other targets, and malformed parameters, are rejected.
+fn test_parse_linear_bad() {
+    for (target_type, params) in [
+        ("striped", "8:16 2048"),
+        ("linear", ""),
+        ("linear", "8:16"),
+        ("linear", "8:16 2048 1"),
+        ("linear", "/dev/sdb 2048"),
+        ("linear", "8:16 -1"),
+    ] {
+        assert_matches!(
+            TypedTargetLine::<LinearTargetParams>::parse(&line(
+                target_type,
+                params
+            )),
+            Err(DmError::InvalidArgument(_)),
+            "{target_type} {params:?} should not parse"
+        );
+    }
+}
+
+#[test]
+/// The params string round-trips through Display and FromStr.
+fn test_params_display_from_str() {
+    let params = LinearTargetParams {
+        dest: Device {
+            major: 253,
+            minor: 3,
+        },
+        offset: 0,
+    };
+    assert_eq!(params.to_string(), "253:3 0");
+    assert_eq!(params.to_string().parse::<LinearTargetParams>(), Ok(params));
+    assert_matches!(
+        "/dev/sdb 0".parse::<LinearTargetParams>(),
+        Err(DmError::InvalidArgument(_))
+    );
+}
+
+#[test]
+/// Only block device nodes are accepted as paths.
+fn test_params_from_path() {
+    assert_matches!(
+        LinearTargetParams::from_path(Path::new("/dev/null"), 0),
+        Err(DmError::InvalidArgument(_))
+    );
+    assert_matches!(
+        LinearTargetParams::from_path(Path::new("/nonexistent"), 0),
+        Err(DmError::InvalidArgument(_))
+    );
+}
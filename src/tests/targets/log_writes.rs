@@ -0,0 +1,52 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests for the log-writes target.
+
+use super::*;
+
+#[test]
+/// Parameters as printed by the kernel parse and round-trip, and
+/// malformed ones are rejected.
+fn test_params_round_trip() {
+    let text = "8:16 8:32";
+    let parsed: LogWritesTargetParams = text.parse().unwrap();
+    assert_eq!(
+        parsed,
+        LogWritesTargetParams {
+            dest: Device {
+                major: 8,
+                minor: 16
+            },
+            log_dev: Device {
+                major: 8,
+                minor: 32
+            },
+        }
+    );
+    assert_eq!(parsed.to_string(), text);
+
+    for text in ["", "8:16", "8:16 8:32 8:48", "8:16 x"] {
+        assert_matches!(
+            text.parse::<LogWritesTargetParams>(),
+            Err(DmError::InvalidArgument(_)),
+            "{text:?}"
+        );
+    }
+}
+
+#[test]
+/// Mark messages carry their label as a single argument.
+fn test_mark_message() {
+    assert_eq!(
+        LogWritesTargetParams::mark_message("after-fsync"),
+        Ok("mark after-fsync".to_string())
+    );
+    for label in ["", "two words", "tab\there"] {
+        assert_matches!(
+            LogWritesTargetParams::mark_message(label),
+            Err(DmError::InvalidArgument(_))
+        );
+    }
+}
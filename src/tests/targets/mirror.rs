@@ -0,0 +1,87 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests for the mirror target.
+
+use super::*;
+
+fn dev(minor: u32) -> Device {
+    Device { major: 8, minor }
+}
+
+#[test]
+/// Parameters as printed by the kernel parse and round-trip, with
+/// each count computed from what follows it.
+fn test_params_round_trip() {
+    for (text, log, sync, features) in [
+        (
+            "core 1 1024 2 8:32 0 8:48 0",
+            MirrorLog::Core,
+            None,
+            MirrorFeatures::empty(),
+        ),
+        (
+            "core 2 1024 sync 2 8:32 0 8:48 0",
+            MirrorLog::Core,
+            Some(true),
+            MirrorFeatures::empty(),
+        ),
+        (
+            "disk 2 8:16 1024 2 8:32 0 8:48 0 2 handle_errors keep_log",
+            MirrorLog::Disk(dev(16)),
+            None,
+            MirrorFeatures::HANDLE_ERRORS | MirrorFeatures::KEEP_LOG,
+        ),
+        (
+            "disk 3 8:16 1024 nosync 2 8:32 0 8:48 0 1 handle_errors",
+            MirrorLog::Disk(dev(16)),
+            Some(false),
+            MirrorFeatures::HANDLE_ERRORS,
+        ),
+    ] {
+        let expected = MirrorTargetParams::new(
+            log,
+            1024,
+            sync,
+            vec![(dev(32), 0), (dev(48), 0)],
+            features,
+        )
+        .unwrap();
+        let parsed: MirrorTargetParams = text.parse().unwrap();
+        assert_eq!(parsed, expected, "{text}");
+        assert_eq!(parsed.to_string(), text);
+    }
+}
+
+#[test]
+/// Malformed or invalid parameters are rejected.
+fn test_params_parse_invalid() {
+    for text in [
+        "",
+        "core",
+        "core 1",
+        "core 1 1024",
+        "core 0 2 8:32 0 8:48 0",
+        "core 2 1024 2 8:32 0 8:48 0",
+        "core 2 1024 maybe 2 8:32 0 8:48 0",
+        "core 1 1000 2 8:32 0 8:48 0",
+        "disk 1 1024 2 8:32 0 8:48 0",
+        "disk 0 2 8:32 0 8:48 0",
+        "other 1 1024 2 8:32 0 8:48 0",
+        "core 1 1024 1 8:32 0",
+        "core 1 1024 3 8:32 0 8:48 0",
+        "core 1 1024 2 8:32 0 8:48",
+        "core 1 1024 2 8:32 0 8:48 0 1",
+        "core 1 1024 2 8:32 0 8:48 0 1 keep_log",
+        "core 1 1024 2 8:32 0 8:48 0 1 bogus",
+        "core 1 1024 10 8:1 0 8:2 0 8:3 0 8:4 0 8:5 0 8:6 0 8:7 0 8:8 0 \
+         8:9 0 8:10 0",
+    ] {
+        assert_matches!(
+            text.parse::<MirrorTargetParams>(),
+            Err(DmError::InvalidArgument(_)),
+            "{text:?}"
+        );
+    }
+}
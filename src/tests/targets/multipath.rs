@@ -0,0 +1,177 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests for the multipath target.
+
+use super::*;
+
+fn path(minor: u32, args: &[&str]) -> MultipathPath {
+    MultipathPath {
+        dev: Device { major: 8, minor },
+        selector_args: args.iter().map(|arg| arg.to_string()).collect(),
+    }
+}
+
+fn group(selector: &str, paths: Vec<MultipathPath>) -> MultipathPriorityGroup {
+    MultipathPriorityGroup {
+        selector: selector.to_string(),
+        selector_args: vec![],
+        paths,
+    }
+}
+
+#[test]
+/// Tables as printed by `dmsetup table` parse and round-trip.
+fn test_params_round_trip() {
+    for (text, features, hw_handler, initial_group, priority_groups) in [
+        ("0 0 0 0", MultipathFeatures::default(), None, 0, vec![]),
+        (
+            "0 0 1 1 round-robin 0 2 1 8:16 1000 8:32 1000",
+            MultipathFeatures::default(),
+            None,
+            1,
+            vec![group(
+                "round-robin",
+                vec![path(16, &["1000"]), path(32, &["1000"])],
+            )],
+        ),
+        (
+            "1 queue_if_no_path 1 alua 2 1 service-time 0 1 2 8:16 1 1 \
+             service-time 0 1 2 8:32 1 1",
+            MultipathFeatures {
+                queue_if_no_path: true,
+                ..Default::default()
+            },
+            Some(MultipathHwHandler {
+                name: "alua".to_string(),
+                args: vec![],
+            }),
+            1,
+            vec![
+                group("service-time", vec![path(16, &["1", "1"])]),
+                group("service-time", vec![path(32, &["1", "1"])]),
+            ],
+        ),
+        (
+            "8 queue_if_no_path pg_init_retries 50 pg_init_delay_msecs 100 \
+             retain_attached_hw_handler queue_mode bio 2 emc 1 2 2 \
+             queue-length 0 1 1 8:16 1 historical-service-time 0 1 2 8:32 \
+             1 2",
+            MultipathFeatures {
+                queue_if_no_path: true,
+                pg_init_retries: Some(50),
+                pg_init_delay_msecs: Some(100),
+                retain_attached_hw_handler: true,
+                queue_mode: Some(MultipathQueueMode::Bio),
+            },
+            Some(MultipathHwHandler {
+                name: "emc".to_string(),
+                args: vec!["1".to_string()],
+            }),
+            2,
+            vec![
+                group("queue-length", vec![path(16, &["1"])]),
+                group("historical-service-time", vec![path(32, &["1", "2"])]),
+            ],
+        ),
+    ] {
+        let expected = MultipathTargetParams::new(
+            features,
+            hw_handler,
+            initial_group,
+            priority_groups,
+        )
+        .unwrap();
+        let parsed: MultipathTargetParams = text.parse().unwrap();
+        assert_eq!(parsed, expected, "{text}");
+        assert_eq!(parsed.to_string(), text);
+    }
+}
+
+#[test]
+/// Selectors and selector arguments are passed through uninterpreted,
+/// and the kernel's trailing space is ignored.
+fn test_params_parse_unknown_selector() {
+    let parsed: MultipathTargetParams =
+        "0 0 1 1 my-selector 2 x y 1 3 8:16 a b c ".parse().unwrap();
+    let group = &parsed.priority_groups[0];
+    assert_eq!(group.selector, "my-selector");
+    assert_eq!(group.selector_args, ["x", "y"]);
+    assert_eq!(group.paths, [path(16, &["a", "b", "c"])]);
+    assert_eq!(
+        parsed.to_string(),
+        "0 0 1 1 my-selector 2 x y 1 3 8:16 a b c"
+    );
+}
+
+#[test]
+/// Malformed or invalid parameters are rejected.
+fn test_params_parse_invalid() {
+    for text in [
+        "",
+        "0",
+        "0 0",
+        "0 0 0",
+        "0 0 0 1",
+        "0 0 1 0 round-robin 0 1 1 8:16 1000",
+        "0 0 1 2 round-robin 0 1 1 8:16 1000",
+        "0 0 1 1 round-robin 0 1 1 8:16",
+        "0 0 1 1 round-robin 0 2 1 8:16 1000",
+        "0 0 1 1 round-robin 1 1 1 8:16 1000",
+        "0 0 1 1 round-robin 0 1 1 x 1000",
+        "0 0 1 1 round-robin 0 1 1 8:16 1000 extra",
+        "0 1 1 1 round-robin 0 1 1 8:16 1000",
+        "1 bogus 0 0 0",
+        "1 pg_init_retries 0 0 0",
+        "2 pg_init_retries 51 0 0 0",
+        "2 pg_init_delay_msecs 60001 0 0 0",
+        "2 queue_mode sq 0 0 0",
+        "2 queue_if_no_path queue_if_no_path 0 0 0",
+    ] {
+        assert_matches!(
+            text.parse::<MultipathTargetParams>(),
+            Err(DmError::InvalidArgument(_)),
+            "{text:?}"
+        );
+    }
+}
+
+#[test]
+/// Paths in one group must have the same number of selector
+/// arguments, and names must be single words.
+fn test_params_new_invalid() {
+    assert_matches!(
+        MultipathTargetParams::new(
+            MultipathFeatures::default(),
+            None,
+            1,
+            vec![group(
+                "round-robin",
+                vec![path(16, &["1000"]), path(32, &[])],
+            )],
+        ),
+        Err(DmError::InvalidArgument(_))
+    );
+    assert_matches!(
+        MultipathTargetParams::new(
+            MultipathFeatures::default(),
+            None,
+            1,
+            vec![group("round robin", vec![path(16, &["1000"])])],
+        ),
+        Err(DmError::InvalidArgument(_))
+    );
+    assert_matches!(
+        MultipathTargetParams::new(
+            MultipathFeatures::default(),
+            Some(MultipathHwHandler {
+                name: String::new(),
+                args: vec![],
+            }),
+            0,
+            vec![],
+        ),
+        Err(DmError::InvalidArgument(_))
+    );
+}
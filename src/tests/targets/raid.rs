@@ -0,0 +1,234 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests for the raid target.
+
+use super::*;
+
+#[test]
+/// Status lines from old and new kernels parse.
+fn test_parse_status() {
+    let status: RaidTargetStatus =
+        "raid5_ls 4 AADa 8192/16384 recover 0 2048 A"
+            .parse()
+            .unwrap();
+    assert_eq!(
+        status,
+        RaidTargetStatus {
+            raid_type: "raid5_ls".to_string(),
+            devices: vec![
+                RaidDeviceHealth::InSync,
+                RaidDeviceHealth::InSync,
+                RaidDeviceHealth::Failed,
+                RaidDeviceHealth::NotInSync,
+            ],
+            sync_current: 8192,
+            sync_total: 16384,
+            sync_action: Some(RaidSyncAction::Recover),
+            mismatch_count: Some(0),
+            data_offset: Some(2048),
+            journal: Some(RaidJournalHealth::WriteThrough),
+        }
+    );
+    assert!(!status.is_in_sync());
+
+    let status: RaidTargetStatus =
+        "raid1 2 AA 4096/4096 idle 3 0".parse().unwrap();
+    assert_eq!(status.mismatch_count, Some(3));
+    assert_eq!(status.data_offset, Some(0));
+    assert_eq!(status.journal, None);
+    assert!(status.is_in_sync());
+
+    let status: RaidTargetStatus = "raid1 2 A- 4096/4096".parse().unwrap();
+    assert_eq!(status.devices[1], RaidDeviceHealth::Missing);
+    assert_eq!(status.sync_action, None);
+    assert_eq!(status.mismatch_count, None);
+}
+
+#[test]
+/// Malformed status lines, including ones whose device count does
+/// not match the health characters, are rejected.
+fn test_parse_status_bad() {
+    for bad in [
+        "",
+        "raid1 2 AA",
+        "raid1 3 AA 4096/4096",
+        "raid1 2 AAA 4096/4096",
+        "raid1 2 AX 4096/4096",
+        "raid1 2 AA 4096",
+        "raid1 2 AA 4096/x",
+        "raid1 2 AA 4096/4096 idle",
+        "raid1 2 AA 4096/4096 bogus 0",
+        "raid1 2 AA 4096/4096 idle x",
+        "raid1 2 AA 4096/4096 idle 0 0 X",
+        "raid1 2 AA 4096/4096 idle 0 0 - extra",
+    ] {
+        assert_matches!(
+            bad.parse::<RaidTargetStatus>(),
+            Err(DmError::InvalidArgument(_)),
+            "{bad:?} should not parse"
+        );
+    }
+}
+
+fn device(minor: u32) -> Option<Device> {
+    Some(Device { major: 8, minor })
+}
+
+#[test]
+/// Parameters as printed by the kernel parse and round-trip, with
+/// the parameter count covering the chunk size and every option
+/// argument, and absent devices written `-`.
+fn test_params_round_trip() {
+    for (text, raid_type, chunk_size, options, devices) in [
+        (
+            "raid1 1 0 2 8:16 8:17 8:32 8:33",
+            RaidType::Raid1,
+            0,
+            RaidOptions::default(),
+            vec![(device(16), device(17)), (device(32), device(33))],
+        ),
+        (
+            "raid1 6 0 nosync rebuild 1 write_mostly 0 2 - 8:17 8:32 8:33",
+            RaidType::Raid1,
+            0,
+            RaidOptions {
+                sync: Some(false),
+                rebuild: vec![1],
+                write_mostly: vec![0],
+                ..Default::default()
+            },
+            vec![(None, device(17)), (device(32), device(33))],
+        ),
+        (
+            "raid5_ls 11 128 daemon_sleep 5000 min_recovery_rate 100 \
+             max_recovery_rate 1000 stripe_cache 256 region_size 8192 3 \
+             8:16 8:17 8:32 8:33 8:48 8:49",
+            RaidType::Raid5Ls,
+            128,
+            RaidOptions {
+                daemon_sleep: Some(5000),
+                min_recovery_rate: Some(100),
+                max_recovery_rate: Some(1000),
+                stripe_cache: Some(256),
+                region_size: Some(8192),
+                ..Default::default()
+            },
+            vec![
+                (device(16), device(17)),
+                (device(32), device(33)),
+                (device(48), device(49)),
+            ],
+        ),
+        (
+            "raid10 7 64 raid10_copies 2 raid10_format far delta_disks -1 \
+             3 8:16 8:17 8:32 8:33 8:48 8:49",
+            RaidType::Raid10,
+            64,
+            RaidOptions {
+                raid10_copies: Some(2),
+                raid10_format: Some(Raid10Format::Far),
+                delta_disks: Some(-1),
+                ..Default::default()
+            },
+            vec![
+                (device(16), device(17)),
+                (device(32), device(33)),
+                (device(48), device(49)),
+            ],
+        ),
+        (
+            "raid6_zr 7 8 data_offset 2048 journal_dev 8:80 journal_mode \
+             writeback 4 8:16 8:17 8:32 8:33 8:48 8:49 8:64 8:65",
+            RaidType::Raid6Zr,
+            8,
+            RaidOptions {
+                data_offset: Some(2048),
+                journal_dev: device(80),
+                journal_mode: Some(RaidJournalMode::WriteBack),
+                ..Default::default()
+            },
+            vec![
+                (device(16), device(17)),
+                (device(32), device(33)),
+                (device(48), device(49)),
+                (device(64), device(65)),
+            ],
+        ),
+    ] {
+        let devices = devices
+            .into_iter()
+            .map(|(metadata_dev, data_dev)| RaidDevice {
+                metadata_dev,
+                data_dev,
+            })
+            .collect();
+        let expected =
+            RaidTargetParams::new(raid_type, chunk_size, options, devices)
+                .unwrap();
+        let parsed: RaidTargetParams = text.parse().unwrap();
+        assert_eq!(parsed, expected, "{text}");
+        assert_eq!(parsed.to_string(), text);
+    }
+}
+
+#[test]
+/// Options may appear in any order, and the `raid5` and `raid6`
+/// aliases parse; `Display` writes the kernel's order and names.
+fn test_params_parse_reordered() {
+    let parsed: RaidTargetParams =
+        "raid5 6 64 rebuild 2 sync rebuild 0 3 - 8:1 - 8:2 - 8:3"
+            .parse()
+            .unwrap();
+    assert_eq!(parsed.raid_type, RaidType::Raid5Ls);
+    assert_eq!(
+        parsed.to_string(),
+        "raid5_ls 6 64 sync rebuild 0 rebuild 2 3 - 8:1 - 8:2 - 8:3"
+    );
+    assert_eq!(
+        "raid6 1 8 4 - 8:1 - 8:2 - 8:3 - 8:4"
+            .parse::<RaidTargetParams>()
+            .unwrap()
+            .raid_type,
+        RaidType::Raid6Zr
+    );
+}
+
+#[test]
+/// Malformed or invalid parameters are rejected.
+fn test_params_parse_invalid() {
+    for text in [
+        "",
+        "raid1",
+        "raid1 0 2 8:1 8:2 8:3 8:4",
+        "raid1 1 0",
+        "raid1 1 0 2 8:1 8:2 8:3",
+        "raid1 2 0 2 8:1 8:2 8:3 8:4",
+        "raid1 1 0 1 8:1 8:2",
+        "raid7 1 64 2 8:1 8:2 8:3 8:4",
+        "raid0 1 4 2 8:1 8:2 8:3 8:4",
+        "raid0 1 96 2 8:1 8:2 8:3 8:4",
+        "raid6_zr 1 64 3 8:1 8:2 8:3 8:4 8:5 8:6",
+        "raid1 2 0 bogus 2 8:1 8:2 8:3 8:4",
+        "raid1 2 0 region_size 2 8:1 8:2 8:3 8:4",
+        "raid1 3 0 region_size 1000 2 8:1 8:2 8:3 8:4",
+        "raid1 3 0 sync nosync 2 8:1 8:2 8:3 8:4",
+        "raid1 3 0 rebuild 2 2 8:1 8:2 8:3 8:4",
+        "raid0 3 64 write_mostly 0 2 8:1 8:2 8:3 8:4",
+        "raid1 3 0 raid10_copies 2 2 8:1 8:2 8:3 8:4",
+        "raid10 3 64 raid10_copies 3 2 8:1 8:2 8:3 8:4",
+        "raid10 3 64 raid10_format wide 2 8:1 8:2 8:3 8:4",
+        "raid1 3 0 journal_dev 8:9 2 8:1 8:2 8:3 8:4",
+        "raid5 3 64 journal_mode writeback 2 8:1 8:2 8:3 8:4",
+        "raid1 5 0 min_recovery_rate 10 max_recovery_rate 5 \
+         2 8:1 8:2 8:3 8:4",
+        "raid1 1 0 2 8:1 8:2 8:3 x",
+    ] {
+        assert_matches!(
+            text.parse::<RaidTargetParams>(),
+            Err(DmError::InvalidArgument(_)),
+            "{text:?}"
+        );
+    }
+}
@@ -0,0 +1,157 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests for the snapshot family of targets.
+
+use super::*;
+use crate::{table::TargetLine, targets::TypedTargetLine};
+
+fn params(
+    persistence: SnapshotPersistence,
+    features: SnapshotFeatures,
+) -> SnapshotTargetParams {
+    SnapshotTargetParams {
+        origin: Device {
+            major: 8,
+            minor: 16,
+        },
+        cow: Device {
+            major: 8,
+            minor: 32,
+        },
+        persistence,
+        chunk_size: 16,
+        features,
+    }
+}
+
+#[test]
+/// Parameters as printed by the kernel parse and round-trip.
+fn test_params_round_trip() {
+    for (text, persistence, features) in [
+        (
+            "8:16 8:32 P 16",
+            SnapshotPersistence::Persistent,
+            SnapshotFeatures::empty(),
+        ),
+        (
+            "8:16 8:32 PO 16",
+            SnapshotPersistence::PersistentOverflow,
+            SnapshotFeatures::empty(),
+        ),
+        (
+            "8:16 8:32 N 16 2 discard_zeroes_cow discard_passdown_origin",
+            SnapshotPersistence::Transient,
+            SnapshotFeatures::all(),
+        ),
+    ] {
+        let parsed: SnapshotTargetParams = text.parse().unwrap();
+        assert_eq!(parsed, params(persistence, features));
+        assert_eq!(parsed.to_string(), text);
+
+        let merge: SnapshotMergeTargetParams = text.parse().unwrap();
+        assert_eq!(merge.0, parsed);
+        assert_eq!(merge.to_string(), text);
+    }
+
+    // The kernel accepts lower case persistence letters.
+    assert_eq!(
+        "8:16 8:32 p 16".parse::<SnapshotTargetParams>(),
+        Ok(params(
+            SnapshotPersistence::Persistent,
+            SnapshotFeatures::empty()
+        ))
+    );
+
+    let origin: SnapshotOriginTargetParams = "8:16".parse().unwrap();
+    assert_eq!(origin.to_string(), "8:16");
+}
+
+#[test]
+/// Malformed parameters and invalid combinations are rejected.
+fn test_params_bad() {
+    for text in [
+        "",
+        "8:16 8:32 P",
+        "8:16 8:32 X 16",
+        "8:16 8:32 P 24",
+        "8:16 8:32 P 0",
+        "8:16 8:32 P 16 1",
+        "8:16 8:32 P 16 1 bogus",
+        "8:16 8:32 P 16 1 discard_passdown_origin",
+    ] {
+        assert_matches!(
+            text.parse::<SnapshotTargetParams>(),
+            Err(DmError::InvalidArgument(_)),
+            "{text:?} should not parse"
+        );
+    }
+
+    for text in ["", "8:16 8:32", "sdb"] {
+        assert_matches!(
+            text.parse::<SnapshotOriginTargetParams>(),
+            Err(DmError::InvalidArgument(_)),
+            "{text:?} should not parse"
+        );
+    }
+}
+
+#[test]
+/// Lines as returned by table_status parse into typed lines, which
+/// convert back to the same lines, but only for the right target type.
+fn test_target_lines() {
+    let line = TargetLine {
+        start: 0,
+        length: 8192,
+        target_type: "snapshot".parse().unwrap(),
+        params: "253:2 253:3 P 8".to_string(),
+    };
+    let target = TypedTargetLine::<SnapshotTargetParams>::parse(&line).unwrap();
+    assert_eq!(
+        target,
+        TypedTargetLine {
+            start: 0,
+            length: 8192,
+            params: SnapshotTargetParams {
+                origin: Device {
+                    major: 253,
+                    minor: 2,
+                },
+                cow: Device {
+                    major: 253,
+                    minor: 3,
+                },
+                persistence: SnapshotPersistence::Persistent,
+                chunk_size: 8,
+                features: SnapshotFeatures::empty(),
+            },
+        }
+    );
+    assert_eq!(target.to_table_line().unwrap(), line);
+    assert_matches!(
+        TypedTargetLine::<SnapshotOriginTargetParams>::parse(&line),
+        Err(DmError::InvalidArgument(_))
+    );
+
+    let line = TargetLine {
+        start: 0,
+        length: 8192,
+        target_type: "snapshot-origin".parse().unwrap(),
+        params: "253:2".to_string(),
+    };
+    let target =
+        TypedTargetLine::<SnapshotOriginTargetParams>::parse(&line).unwrap();
+    assert_eq!(
+        target.params.origin,
+        Device {
+            major: 253,
+            minor: 2
+        }
+    );
+    assert_eq!(target.to_table_line().unwrap(), line);
+    assert_matches!(
+        TypedTargetLine::<SnapshotTargetParams>::parse(&line),
+        Err(DmError::InvalidArgument(_))
+    );
+}
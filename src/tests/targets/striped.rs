@@ -0,0 +1,150 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests for the striped target.
+
+use super::*;
+use crate::{table::TargetLine, targets::TypedTargetLine};
+
+fn line(target_type: &str, params: &str) -> TargetLine {
+    TargetLine::new(0, 8192, target_type, params).unwrap()
+}
+
+#[test]
+/// A striped line as printed by the kernel parses and round-trips.
+fn test_parse_striped() {
+    let status = line("striped", "2 128 8:16 0 8:32 2048");
+    let target = TypedTargetLine::<StripedTargetParams>::parse(&status)
+        .expect("is valid striped line");
+    assert_eq!(
+        target,
+        TypedTargetLine {
+            start: 0,
+            length: 8192,
+            params: StripedTargetParams {
+                chunk_size: 128,
+                stripes: vec![
+                    (
+                        Device {
+                            major: 8,
+                            minor: 16
+                        },
+                        0
+                    ),
+                    (
+                        Device {
+                            major: 8,
+                            minor: 32
+                        },
+                        2048
+                    ),
+                ],
+            },
+        }
+    );
+    assert_eq!(target.params.param_str(), "2 128 8:16 0 8:32 2048");
+    assert_eq!(target.to_table_line().unwrap(), status);
+}
+
+#[test]
+/// Lines for other targets, stripe counts that do not match the
+/// stripes listed, and malformed parameters, are rejected.
+fn test_parse_striped_bad() {
+    for (target_type, params) in [
+        ("linear", "1 128 8:16 0"),
+        ("striped", ""),
+        ("striped", "1"),
+        ("striped", "0 128"),
+        ("striped", "2 128 8:16 0"),
+        ("striped", "1 128 8:16 0 8:32 0"),
+        ("striped", "1 128 8:16"),
+        ("striped", "x 128 8:16 0"),
+        ("striped", "1 128 /dev/sdb 0"),
+        ("striped", "1 128 8:16 -1"),
+        ("striped", "1 100 8:16 0"),
+        ("striped", "1 0 8:16 0"),
+    ] {
+        assert_matches!(
+            TypedTargetLine::<StripedTargetParams>::parse(&line(
+                target_type,
+                params
+            )),
+            Err(DmError::InvalidArgument(_)),
+            "{target_type} {params:?} should not parse"
+        );
+    }
+}
+
+#[test]
+/// Lengths are rounded down to a whole number of stripe widths.
+fn test_aligned_length() {
+    let dest = Device {
+        major: 8,
+        minor: 16,
+    };
+    let params = StripedTargetParams::new(
+        64,
+        vec![(dest, 0), (dest, 4096), (dest, 8192)],
+    )
+    .unwrap();
+    assert_eq!(params.stripe_width(), 192);
+    assert_eq!(params.aligned_length(192), 192);
+    assert_eq!(params.aligned_length(1000), 960);
+    assert_eq!(params.aligned_length(191), 0);
+    assert_eq!(
+        params.to_string().parse::<StripedTargetParams>(),
+        Ok(params)
+    );
+
+    assert_matches!(
+        StripedTargetParams::new(96, vec![(dest, 0)]),
+        Err(DmError::InvalidArgument(_))
+    );
+    assert_matches!(
+        StripedTargetParams::new(64, vec![]),
+        Err(DmError::InvalidArgument(_))
+    );
+}
+
+#[test]
+/// The status line reports each stripe's device and health.
+fn test_parse_status() {
+    let status: StripedTargetStatus = "2 8:16 8:32 1 AA".parse().unwrap();
+    assert_eq!(
+        status.stripes,
+        vec![
+            (
+                Device {
+                    major: 8,
+                    minor: 16
+                },
+                true
+            ),
+            (
+                Device {
+                    major: 8,
+                    minor: 32
+                },
+                true
+            ),
+        ]
+    );
+    assert!(status.is_healthy());
+
+    for bad in [
+        "",
+        "2 8:16 8:32",
+        "2 8:16 8:32 1 A",
+        "2 8:16 8:32 1 AX",
+        "1 8:16 8:32 1 AA",
+        "2 8:16 8:32 2 AA",
+        "1 sdb 1 A",
+    ] {
+        assert_matches!(
+            bad.parse::<StripedTargetStatus>(),
+            Err(DmError::InvalidArgument(_)),
+            "{bad:?} should not parse"
+        );
+    }
+}
@@ -0,0 +1,143 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests for the switch target.
+
+use super::*;
+
+fn params() -> SwitchTargetParams {
+    SwitchTargetParams::new(
+        128,
+        vec![
+            (
+                Device {
+                    major: 8,
+                    minor: 16,
+                },
+                0,
+            ),
+            (
+                Device {
+                    major: 8,
+                    minor: 32,
+                },
+                2048,
+            ),
+            (
+                Device {
+                    major: 8,
+                    minor: 48,
+                },
+                0,
+            ),
+        ],
+    )
+    .unwrap()
+}
+
+#[test]
+/// Parameters as printed by the kernel parse and round-trip, and
+/// optional arguments are skipped.
+fn test_params_round_trip() {
+    let text = "3 128 0 8:16 0 8:32 2048 8:48 0";
+    let parsed: SwitchTargetParams = text.parse().unwrap();
+    assert_eq!(parsed, params());
+    assert_eq!(parsed.to_string(), text);
+
+    let parsed: SwitchTargetParams =
+        "3 128 2 x y 8:16 0 8:32 2048 8:48 0".parse().unwrap();
+    assert_eq!(parsed, params());
+
+    // The kernel does not require a power-of-two region size.
+    assert_eq!(
+        "1 100 0 8:16 0"
+            .parse::<SwitchTargetParams>()
+            .unwrap()
+            .region_size,
+        100
+    );
+}
+
+#[test]
+/// Malformed or invalid parameters are rejected.
+fn test_params_parse_invalid() {
+    for text in [
+        "",
+        "1 128",
+        "1 128 0",
+        "0 128 0",
+        "1 0 0 8:16 0",
+        "1 128 0 8:16",
+        "2 128 0 8:16 0",
+        "1 128 1 8:16 0",
+        "1 128 0 8:16 0 8:32 0",
+        "1 128 0 x 0",
+        "1 4294967296 0 8:16 0",
+    ] {
+        assert_matches!(
+            text.parse::<SwitchTargetParams>(),
+            Err(DmError::InvalidArgument(_)),
+            "{text:?}"
+        );
+    }
+}
+
+#[test]
+/// Region mapping messages use the kernel's hexadecimal syntax, and
+/// paths must exist.
+fn test_region_mappings_message() {
+    let params = params();
+    assert_eq!(
+        params
+            .region_mappings_message(&[
+                SwitchMapping::Region {
+                    region: Some(0x10),
+                    path: 2,
+                },
+                SwitchMapping::Region {
+                    region: None,
+                    path: 1,
+                },
+                SwitchMapping::Repeat {
+                    cycle_length: 2,
+                    count: 0x20,
+                },
+                SwitchMapping::Range {
+                    first: 100,
+                    last: 100,
+                    path: 0,
+                },
+                SwitchMapping::Range {
+                    first: 200,
+                    last: 299,
+                    path: 1,
+                },
+            ])
+            .unwrap(),
+        "set_region_mappings 10:2 :1 R2,20 64:0 c8:1 R1,63"
+    );
+
+    for mappings in [
+        &[][..],
+        &[SwitchMapping::Region {
+            region: None,
+            path: 3,
+        }],
+        &[SwitchMapping::Range {
+            first: 0,
+            last: 10,
+            path: 3,
+        }],
+        &[SwitchMapping::Range {
+            first: 10,
+            last: 0,
+            path: 0,
+        }],
+    ] {
+        assert_matches!(
+            params.region_mappings_message(mappings),
+            Err(DmError::InvalidArgument(_))
+        );
+    }
+}
@@ -0,0 +1,58 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests for the thin target.
+
+use super::*;
+
+#[test]
+/// Parameters as printed by the kernel parse and round-trip.
+fn test_params_round_trip() {
+    let pool = Device {
+        major: 253,
+        minor: 0,
+    };
+    for (text, device_id, external_origin) in [
+        ("253:0 0", 0, None),
+        ("253:0 16777215", ThinTargetParams::MAX_DEVICE_ID, None),
+        (
+            "253:0 7 8:16",
+            7,
+            Some(Device {
+                major: 8,
+                minor: 16,
+            }),
+        ),
+    ] {
+        let parsed: ThinTargetParams = text.parse().unwrap();
+        assert_eq!(
+            parsed,
+            ThinTargetParams {
+                pool,
+                device_id,
+                external_origin
+            }
+        );
+        assert_eq!(parsed.to_string(), text);
+    }
+}
+
+#[test]
+/// Malformed parameters and out of range device ids are rejected.
+fn test_params_bad() {
+    for text in [
+        "",
+        "253:0",
+        "253:0 1 8:16 8:32",
+        "253:0 16777216",
+        "253:0 -1",
+        "253:0 1 sdb",
+    ] {
+        assert_matches!(
+            text.parse::<ThinTargetParams>(),
+            Err(DmError::InvalidArgument(_)),
+            "{text:?} should not parse"
+        );
+    }
+}
@@ -0,0 +1,154 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests for the thin-pool target.
+
+use super::*;
+
+fn params(
+    data_block_size: u64,
+    features: ThinPoolFeatures,
+) -> ThinPoolTargetParams {
+    ThinPoolTargetParams {
+        metadata_dev: Device {
+            major: 8,
+            minor: 16,
+        },
+        data_dev: Device {
+            major: 8,
+            minor: 32,
+        },
+        data_block_size,
+        low_water_mark: 1024,
+        features,
+    }
+}
+
+#[test]
+/// Parameters as printed by the kernel parse and round-trip.
+fn test_params_round_trip() {
+    for (text, data_block_size, features) in [
+        ("8:16 8:32 128 1024 0", 128, ThinPoolFeatures::empty()),
+        (
+            "8:16 8:32 2097152 1024 1 error_if_no_space",
+            2097152,
+            ThinPoolFeatures::ERROR_IF_NO_SPACE,
+        ),
+        (
+            "8:16 8:32 384 1024 5 skip_block_zeroing ignore_discard \
+             no_discard_passdown read_only error_if_no_space",
+            384,
+            ThinPoolFeatures::all(),
+        ),
+    ] {
+        let parsed: ThinPoolTargetParams = text.parse().unwrap();
+        assert_eq!(parsed, params(data_block_size, features));
+        assert_eq!(parsed.to_string(), text);
+    }
+
+    // The feature count may be omitted when loading a table.
+    assert_eq!(
+        "8:16 8:32 128 1024".parse::<ThinPoolTargetParams>(),
+        Ok(params(128, ThinPoolFeatures::empty()))
+    );
+}
+
+#[test]
+/// Malformed parameters and disallowed block sizes are rejected.
+fn test_params_bad() {
+    for text in [
+        "",
+        "8:16 8:32 128",
+        "8:16 8:32 128 1024 1",
+        "8:16 8:32 128 1024 1 bogus",
+        "8:16 8:32 128 1024 2 read_only",
+        "8:16 8:32 64 1024 0",
+        "8:16 8:32 200 1024 0",
+        "8:16 8:32 4194304 1024 0",
+        "8:16 8:32 0 1024 0",
+    ] {
+        assert_matches!(
+            text.parse::<ThinPoolTargetParams>(),
+            Err(DmError::InvalidArgument(_)),
+            "{text:?} should not parse"
+        );
+    }
+}
+
+#[test]
+/// Status lines, with and without the fields added by later kernels,
+/// parse, and `-` placeholders mean absent.
+fn test_parse_status() {
+    let working = ThinPoolWorkingStatus {
+        transaction_id: 3,
+        metadata_used: 141,
+        metadata_total: 1024,
+        data_used: 20,
+        data_total: 512,
+        held_metadata_root: None,
+        mode: ThinPoolMode::ReadWrite,
+        discards: ThinPoolDiscards::Passdown,
+        error_if_no_space: false,
+        needs_check: false,
+        metadata_low_watermark: Some(256),
+    };
+    for (text, status) in [
+        (
+            "3 141/1024 20/512 - rw discard_passdown queue_if_no_space - 256",
+            working,
+        ),
+        (
+            "3 141/1024 20/512 17 out_of_data_space ignore_discard \
+             error_if_no_space needs_check 256",
+            ThinPoolWorkingStatus {
+                held_metadata_root: Some(17),
+                mode: ThinPoolMode::OutOfDataSpace,
+                discards: ThinPoolDiscards::Ignore,
+                error_if_no_space: true,
+                needs_check: true,
+                ..working
+            },
+        ),
+        (
+            "3 141/1024 20/512 - ro no_discard_passdown queue_if_no_space -",
+            ThinPoolWorkingStatus {
+                mode: ThinPoolMode::ReadOnly,
+                discards: ThinPoolDiscards::NoPassdown,
+                metadata_low_watermark: None,
+                ..working
+            },
+        ),
+        (
+            "3 141/1024 20/512 - rw discard_passdown queue_if_no_space",
+            ThinPoolWorkingStatus {
+                metadata_low_watermark: None,
+                ..working
+            },
+        ),
+    ] {
+        assert_eq!(text.parse(), Ok(ThinPoolTargetStatus::Working(status)));
+    }
+    assert_eq!("Fail".parse(), Ok(ThinPoolTargetStatus::Fail));
+    assert_eq!("Error".parse(), Ok(ThinPoolTargetStatus::Error));
+
+    for bad in [
+        "",
+        "fail",
+        "3 141/1024 20/512 - rw discard_passdown",
+        "3 141 20/512 - rw discard_passdown queue_if_no_space",
+        "3 141/x 20/512 - rw discard_passdown queue_if_no_space",
+        "3 141/1024 20/512 x rw discard_passdown queue_if_no_space",
+        "3 141/1024 20/512 - rx discard_passdown queue_if_no_space",
+        "3 141/1024 20/512 - rw discard queue_if_no_space",
+        "3 141/1024 20/512 - rw discard_passdown no_space",
+        "3 141/1024 20/512 - rw discard_passdown queue_if_no_space x",
+        "3 141/1024 20/512 - rw discard_passdown queue_if_no_space - 1 2",
+    ] {
+        assert_matches!(
+            bad.parse::<ThinPoolTargetStatus>(),
+            Err(DmError::InvalidArgument(_)),
+            "{bad:?} should not parse"
+        );
+    }
+}
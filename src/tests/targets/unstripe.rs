@@ -0,0 +1,62 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests for the unstriped target.
+
+use super::*;
+
+fn params(stripes: u32, chunk_size: u64) -> UnstripeTargetParams {
+    UnstripeTargetParams::new(
+        stripes,
+        chunk_size,
+        0,
+        Device {
+            major: 8,
+            minor: 16,
+        },
+        2048,
+    )
+    .unwrap()
+}
+
+#[test]
+/// Parameters as printed by the kernel parse and round-trip.
+fn test_params_round_trip() {
+    let text = "2 128 0 8:16 2048";
+    let parsed: UnstripeTargetParams = text.parse().unwrap();
+    assert_eq!(parsed, params(2, 128));
+    assert_eq!(parsed.to_string(), text);
+}
+
+#[test]
+/// Malformed or invalid parameters are rejected.
+fn test_params_parse_invalid() {
+    for text in [
+        "",
+        "2 128 0 8:16",
+        "2 128 0 8:16 0 0",
+        "2 100 0 8:16 0",
+        "2 0 0 8:16 0",
+        "2 128 2 8:16 0",
+        "0 128 0 8:16 0",
+        "2 128 x 8:16 0",
+        "2 128 0 8 0",
+    ] {
+        assert_matches!(
+            text.parse::<UnstripeTargetParams>(),
+            Err(DmError::InvalidArgument(_)),
+            "{text:?}"
+        );
+    }
+}
+
+#[test]
+/// The unstriped length is one stripe's share of the whole stripe
+/// widths of the striped device.
+fn test_unstriped_length() {
+    assert_eq!(params(4, 128).unstriped_length(4096), 1024);
+    assert_eq!(params(4, 128).unstriped_length(4096 + 511), 1024);
+    assert_eq!(params(4, 128).unstriped_length(511), 0);
+    assert_eq!(params(1, 8).unstriped_length(100), 96);
+}
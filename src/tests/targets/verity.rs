@@ -0,0 +1,171 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests for the verity target.
+
+use super::*;
+
+const DIGEST: &str =
+    "4392712b4dbbab3e2b5a8ad6e3f85ebd3a32c2c4a88bd7e4f1c4d1bdc9a8b3f1";
+
+fn params(salt: Vec<u8>, options: VerityOptions) -> VerityTargetParams {
+    VerityTargetParams {
+        version: 1,
+        data_dev: Device {
+            major: 8,
+            minor: 16,
+        },
+        hash_dev: Device {
+            major: 8,
+            minor: 32,
+        },
+        data_block_size: 4096,
+        hash_block_size: 4096,
+        num_data_blocks: 262144,
+        hash_start_block: 1,
+        algorithm: "sha256".to_string(),
+        root_digest: parse_hex(DIGEST, "digest").unwrap(),
+        salt,
+        options,
+    }
+}
+
+#[test]
+/// Parameters as printed by the kernel parse and round-trip, with
+/// the option count covering every option argument.
+fn test_params_round_trip() {
+    let fec = VerityFec {
+        dev: Device {
+            major: 8,
+            minor: 48,
+        },
+        roots: 2,
+        blocks: 264192,
+        start: 0,
+    };
+    for (tail, salt, options) in [
+        ("-", vec![], VerityOptions::default()),
+        ("0011ff", vec![0x00, 0x11, 0xff], VerityOptions::default()),
+        (
+            "- 2 restart_on_corruption ignore_zero_blocks",
+            vec![],
+            VerityOptions {
+                corruption_mode: Some(VerityCorruptionMode::Restart),
+                ignore_zero_blocks: true,
+                ..Default::default()
+            },
+        ),
+        (
+            "- 8 use_fec_from_device 8:48 fec_roots 2 fec_blocks 264192 \
+             fec_start 0",
+            vec![],
+            VerityOptions {
+                fec: Some(fec),
+                ..Default::default()
+            },
+        ),
+        (
+            "ab 14 panic_on_corruption ignore_zero_blocks \
+             check_at_most_once try_verify_in_tasklet use_fec_from_device \
+             8:48 fec_roots 2 fec_blocks 264192 fec_start 0 \
+             root_hash_sig_key_desc verity:root",
+            vec![0xab],
+            VerityOptions {
+                corruption_mode: Some(VerityCorruptionMode::Panic),
+                ignore_zero_blocks: true,
+                check_at_most_once: true,
+                try_verify_in_tasklet: true,
+                fec: Some(fec),
+                root_hash_sig_key_desc: Some("verity:root".to_string()),
+                other: vec![],
+            },
+        ),
+        (
+            "- 3 ignore_zero_blocks restart_on_error panic_on_error",
+            vec![],
+            VerityOptions {
+                ignore_zero_blocks: true,
+                other: vec![
+                    "restart_on_error".to_string(),
+                    "panic_on_error".to_string(),
+                ],
+                ..Default::default()
+            },
+        ),
+    ] {
+        let text =
+            format!("1 8:16 8:32 4096 4096 262144 1 sha256 {DIGEST} {tail}");
+        let parsed: VerityTargetParams = text.parse().unwrap();
+        assert_eq!(parsed, params(salt, options));
+        assert_eq!(parsed.to_string(), text);
+    }
+
+    // Options may be given in any order.
+    let text = format!(
+        "1 8:16 8:32 4096 4096 262144 1 sha256 {DIGEST} - 9 fec_start 0 \
+         fec_blocks 264192 fec_roots 2 use_fec_from_device 8:48 \
+         ignore_corruption"
+    );
+    let parsed: VerityTargetParams = text.parse().unwrap();
+    assert_eq!(
+        parsed.options,
+        VerityOptions {
+            corruption_mode: Some(VerityCorruptionMode::Ignore),
+            fec: Some(fec),
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+/// Malformed parameters, and parameters the kernel rejects, are
+/// rejected.
+fn test_params_bad() {
+    for tail in [
+        "sha256 00",
+        "sha256 -",
+        "sha1 {DIGEST}",
+        "sha256 {DIGEST}",
+        "sha256 {DIGEST} 0g",
+        "sha256 {DIGEST} - 1",
+        "sha256 {DIGEST} - 2 ignore_corruption",
+        "sha256 {DIGEST} - 2 ignore_corruption restart_on_corruption",
+        "sha256 {DIGEST} - 2 ignore_zero_blocks ignore_zero_blocks",
+        "sha256 {DIGEST} - 1 root_hash_sig_key_desc",
+        "sha256 {DIGEST} - 2 use_fec_from_device 8:48",
+        "sha256 {DIGEST} - 4 fec_roots 2 fec_roots 2",
+    ] {
+        let text = format!(
+            "1 8:16 8:32 4096 4096 262144 1 {}",
+            tail.replace("{DIGEST}", DIGEST)
+        );
+        assert_matches!(
+            text.parse::<VerityTargetParams>(),
+            Err(DmError::InvalidArgument(_)),
+            "{text:?} should not parse"
+        );
+    }
+
+    for head in [
+        "2 8:16 8:32 4096 4096 262144 1",
+        "1 8:16 8:32 256 4096 262144 1",
+        "1 8:16 8:32 4096 3000 262144 1",
+        "1 8:16 8:32 4096 4096",
+        "1 sdb 8:32 4096 4096 262144 1",
+    ] {
+        let text = format!("{head} sha256 {DIGEST} -");
+        assert_matches!(
+            text.parse::<VerityTargetParams>(),
+            Err(DmError::InvalidArgument(_)),
+            "{text:?} should not parse"
+        );
+    }
+
+    // Digests are only checked for algorithms this crate knows.
+    assert_matches!(
+        "1 8:16 8:32 4096 4096 262144 1 blake2b-256 00 -"
+            .parse::<VerityTargetParams>(),
+        Ok(_)
+    );
+}
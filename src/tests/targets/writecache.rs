@@ -0,0 +1,121 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Tests for the writecache target.
+
+use super::*;
+
+fn params(
+    mode: WritecacheMode,
+    settings: WritecacheSettings,
+) -> WritecacheTargetParams {
+    WritecacheTargetParams {
+        mode,
+        origin_dev: Device {
+            major: 8,
+            minor: 16,
+        },
+        cache_dev: Device {
+            major: 8,
+            minor: 32,
+        },
+        block_size: 4096,
+        settings,
+    }
+}
+
+#[test]
+/// Parameters as printed by the kernel parse and round-trip, with
+/// the setting count covering every setting argument.
+fn test_params_round_trip() {
+    for (text, mode, settings) in [
+        (
+            "s 8:16 8:32 4096 0",
+            WritecacheMode::Ssd,
+            WritecacheSettings::default(),
+        ),
+        (
+            "p 8:16 8:32 4096 1 nofua",
+            WritecacheMode::PersistentMemory,
+            WritecacheSettings {
+                fua: Some(false),
+                ..Default::default()
+            },
+        ),
+        (
+            "s 8:16 8:32 4096 4 high_watermark 60 low_watermark 50",
+            WritecacheMode::Ssd,
+            WritecacheSettings {
+                high_watermark: Some(60),
+                low_watermark: Some(50),
+                ..Default::default()
+            },
+        ),
+        (
+            "p 8:16 8:32 4096 19 start_sector 8 high_watermark 100 \
+             low_watermark 0 writeback_jobs 256 autocommit_blocks 64 \
+             autocommit_time 1000 max_age 30000 cleaner fua metadata_only \
+             pause_writeback 3000",
+            WritecacheMode::PersistentMemory,
+            WritecacheSettings {
+                start_sector: Some(8),
+                high_watermark: Some(100),
+                low_watermark: Some(0),
+                writeback_jobs: Some(256),
+                autocommit_blocks: Some(64),
+                autocommit_time: Some(1000),
+                max_age: Some(30000),
+                cleaner: true,
+                fua: Some(true),
+                metadata_only: true,
+                pause_writeback: Some(3000),
+            },
+        ),
+    ] {
+        let parsed: WritecacheTargetParams = text.parse().unwrap();
+        assert_eq!(parsed, params(mode, settings));
+        assert_eq!(parsed.to_string(), text);
+    }
+
+    // Settings may be given in any order.
+    assert_eq!(
+        "s 8:16 8:32 4096 3 cleaner max_age 10"
+            .parse::<WritecacheTargetParams>()
+            .unwrap()
+            .to_string(),
+        "s 8:16 8:32 4096 3 max_age 10 cleaner"
+    );
+}
+
+#[test]
+/// Malformed parameters, and settings the kernel rejects, are
+/// rejected.
+fn test_params_bad() {
+    for text in [
+        "",
+        "s 8:16 8:32 4096",
+        "x 8:16 8:32 4096 0",
+        "s 8:16 8:32 256 0",
+        "s 8:16 8:32 1000 0",
+        "s 8:16 8:32 8192 0",
+        "s 8:16 8:32 4096 1",
+        "s 8:16 8:32 4096 2 cleaner",
+        "s 8:16 8:32 4096 1 bogus",
+        "s 8:16 8:32 4096 1 max_age",
+        "s 8:16 8:32 4096 2 max_age x",
+        "s 8:16 8:32 4096 2 cleaner cleaner",
+        "s 8:16 8:32 4096 2 fua nofua",
+        "s 8:16 8:32 4096 4 max_age 1 max_age 2",
+        "s 8:16 8:32 4096 2 high_watermark 101",
+        "s 8:16 8:32 4096 4 high_watermark 40 low_watermark 50",
+        "s 8:16 8:32 4096 2 high_watermark 40",
+        "s 8:16 8:32 4096 2 low_watermark 60",
+    ] {
+        assert_matches!(
+            text.parse::<WritecacheTargetParams>(),
+            Err(DmError::InvalidArgument(_)),
+            "{text:?} should not parse"
+        );
+    }
+}
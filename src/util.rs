@@ -2,7 +2,12 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use core::{mem::size_of, slice, str};
+use core::{
+    fmt::{self, Write},
+    mem::size_of,
+    ptr, slice, str,
+    sync::atomic::{compiler_fence, Ordering},
+};
 
 use nix::libc::c_char;
 
@@ -50,7 +55,41 @@ pub fn slice_from_c_struct<T>(strct: &T) -> &[u8] {
     }
 }
 
-/// Convert the byte slice into a properly sized C string reference
-pub fn c_struct_from_slice<T>(slice: &[u8]) -> Option<&T> {
-    unsafe { (slice as *const _ as *const T).as_ref() }
+/// Overwrite `buf` with zeroes, in a way the compiler will not
+/// optimize out even if `buf` is about to be freed.
+pub fn zero_bytes(buf: &mut [u8]) {
+    for byte in buf.iter_mut() {
+        // SAFETY: byte is a valid, aligned &mut u8.
+        unsafe { ptr::write_volatile(byte, 0) };
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// Overwrite the contents of `string` with zeroes, as
+/// [`zero_bytes`] does, and then empty it.
+pub fn zero_string(string: &mut String) {
+    // SAFETY: A string of NUL characters is valid UTF-8.
+    zero_bytes(unsafe { string.as_bytes_mut() });
+    string.clear();
+}
+
+/// Format `value` into a string allocated at exactly the length it
+/// needs, so that the string never grows, and so never leaves partial
+/// copies of what it holds in freed memory.
+pub fn to_string_exact(value: &impl fmt::Display) -> String {
+    /// Counts the bytes written to it, and discards them.
+    struct Counter(usize);
+
+    impl Write for Counter {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.0 += s.len();
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    write!(counter, "{value}").expect("counting bytes succeeds");
+    let mut string = String::with_capacity(counter.0);
+    write!(string, "{value}").expect("writing to a String succeeds");
+    string
 }
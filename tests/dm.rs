@@ -9,10 +9,44 @@
 #[macro_use]
 extern crate assert_matches;
 
+use std::{
+    fs::{self, File},
+    os::unix::{fs::FileExt, io::AsFd},
+    path::PathBuf,
+    thread,
+    time::Duration,
+};
+
 mod support;
-use support::{list_test_devices, test_name, test_uuid};
+use support::{
+    assert_params_round_trip, list_test_devices, test_name, test_string,
+    test_uuid, with_target_device, LoopDevice,
+};
 
-use dm_ioctl::{DevId, DmError, DmFlags, DmIoctlCmd, DM};
+use dm_ioctl::{
+    dm_major,
+    errors::{BatchOutcome, ReloadStep},
+    stats::StatsStep,
+    targets::{
+        CacheFeature, CacheTargetParams, CloneFeatures, CloneTargetParams,
+        CryptKey, CryptOptions, CryptTargetParams, DelayClass,
+        DelayTargetParams, DustTargetParams, EbsTargetParams, EraTargetParams,
+        FlakeyTargetParams, IntegrityAlgorithm, IntegrityMode,
+        IntegrityOptions, IntegrityTargetParams, LinearTargetParams,
+        LogWritesTargetParams, MirrorFeatures, MirrorLog, MirrorTargetParams,
+        RaidDevice, RaidOptions, RaidTargetParams, RaidType, SnapshotFeatures,
+        SnapshotOriginTargetParams, SnapshotPersistence, SnapshotTargetParams,
+        StripedTargetParams, StripedTargetStatus, SwitchMapping,
+        SwitchTargetParams, ThinPoolFeatures, ThinPoolMode,
+        ThinPoolTargetParams, ThinPoolTargetStatus, ThinTargetParams,
+        TypedTargetLine, UnstripeTargetParams, VerityOptions,
+        VerityTargetParams, WritecacheMode, WritecacheSettings,
+        WritecacheTargetParams,
+    },
+    BatchMode, DepEntry, DevId, Device, DmError, DmFlags, DmIoctlCmd,
+    DmNameBuf, DmNameListFlags, DmOpenOptions, MapperNode, ReloadOptions,
+    RemovalOutcome, SuspendOptions, TargetLine, TargetTable, DM,
+};
 
 #[test]
 /// Test that some version can be obtained.
@@ -20,12 +54,54 @@ fn sudo_test_version() {
     assert_matches!(DM::new().unwrap().version(), Ok(_));
 }
 
+#[test]
+/// Test that the cached version agrees with the version reported by
+/// the kernel, and survives a refresh.
+fn sudo_test_cached_version() {
+    let mut dm = DM::new().unwrap();
+    let (major, minor, patch) = dm.version().unwrap();
+    let cached = dm.cached_version().unwrap().clone();
+    assert_eq!(
+        (cached.major, cached.minor, cached.patch),
+        (major.into(), minor.into(), patch.into())
+    );
+    assert_eq!(dm.interface_version().unwrap(), (major, minor, patch));
+    assert_eq!(dm.refresh_version().unwrap(), &cached);
+}
+
 #[test]
 /// Test that versions for some targets can be obtained.
 fn sudo_test_versions() {
     assert!(!DM::new().unwrap().list_versions().unwrap().is_empty());
 }
 
+#[test]
+/// Test probing for a target type that is always built in and for
+/// one that does not exist.
+fn sudo_test_target_version() {
+    let mut dm = DM::new().unwrap();
+
+    let linear = dm.target_version("linear").unwrap();
+    assert!(linear.is_some());
+    assert!(dm.has_target("linear").unwrap());
+    assert!(dm.require_target_version("linear", (1, 0, 0)).is_ok());
+    assert_matches!(
+        dm.require_target_version("linear", (u32::MAX, 0, 0)),
+        Err(DmError::TargetUnavailable(_, _, Some(version)))
+            if Some(version) == linear
+    );
+
+    assert_eq!(dm.target_version("no-such-target").unwrap(), None);
+    assert!(!dm.has_target("no-such-target").unwrap());
+    assert_matches!(
+        dm.require_target_version("no-such-target", (1, 0, 0)),
+        Err(DmError::TargetUnavailable(_, _, None))
+    );
+
+    dm.refresh_target_versions();
+    assert_eq!(dm.target_version("linear").unwrap(), linear);
+}
+
 #[test]
 /// Verify that if no devices have been created the list of test devices
 /// is empty.
@@ -55,6 +131,59 @@ fn sudo_test_list_devices() {
         .unwrap();
 }
 
+#[test]
+/// Verify that iterating over the devices yields what listing them
+/// does, and can stop at the device being looked for.
+fn sudo_test_iter_devices() {
+    let dm = DM::new().unwrap();
+    let name = test_name("example-dev").expect("is valid DM name");
+    dm.device_create(&name, None, DmFlags::default()).unwrap();
+
+    let devices = dm.iter_devices().unwrap().collect::<Result<Vec<_>, _>>();
+    assert_eq!(devices.unwrap(), dm.list_devices().unwrap());
+
+    let found = dm
+        .iter_devices()
+        .unwrap()
+        .find(|dev| dev.as_ref().map_or(true, |dev| dev.0 == name));
+    assert_matches!(found, Some(Ok((nm, _, _))) if nm == name);
+
+    dm.device_remove(&DevId::Name(&name), DmFlags::default())
+        .unwrap();
+}
+
+#[test]
+/// Verify that listing devices with uuids reports the uuid of a device
+/// that has one, and no uuid for a device that does not.
+fn sudo_test_list_devices_with_uuids() {
+    let dm = DM::new().unwrap();
+    let name = test_name("example-dev").expect("is valid DM name");
+    let uuid = test_uuid("example-363333333333333").expect("is valid DM uuid");
+    dm.device_create(&name, Some(&uuid), DmFlags::default())
+        .unwrap();
+    let name_alt = test_name("example-dev-2").expect("is valid DM name");
+    dm.device_create(&name_alt, None, DmFlags::default())
+        .unwrap();
+
+    let mut devices = dm.list_devices_with_uuids().unwrap();
+    devices.retain(|d| d.0 == name || d.0 == name_alt);
+    assert_eq!(devices.len(), 2);
+
+    let supports_uuids = dm.version().unwrap().1 >= 45;
+    for (nm, _, _, dev_uuid) in &devices {
+        if nm == &name && supports_uuids {
+            assert_eq!(dev_uuid.as_ref(), Some(&uuid));
+        } else {
+            assert_eq!(dev_uuid, &None);
+        }
+    }
+
+    dm.device_remove(&DevId::Name(&name), DmFlags::default())
+        .unwrap();
+    dm.device_remove(&DevId::Name(&name_alt), DmFlags::default())
+        .unwrap();
+}
+
 #[test]
 /// Test that device creation gives a device with the expected name.
 fn sudo_test_create() {
@@ -144,6 +273,30 @@ fn sudo_test_set_uuid() {
         .unwrap();
 }
 
+#[test]
+/// Verify that the refreshed rename returns the new name and uuid.
+fn sudo_test_rename_refreshed() {
+    let dm = DM::new().unwrap();
+    let name = test_name("example-dev").expect("is valid DM name");
+    dm.device_create(&name, None, DmFlags::default()).unwrap();
+
+    let uuid = test_uuid("example-363333333333333").expect("is valid DM uuid");
+    let result = dm
+        .device_rename_refreshed(&name, &DevId::Uuid(&uuid))
+        .unwrap();
+    assert_eq!(result.uuid(), Some(&*uuid));
+
+    let new_name = test_name("example-dev-2").expect("is valid DM name");
+    let result = dm
+        .device_rename_refreshed(&name, &DevId::Name(&new_name))
+        .unwrap();
+    assert_eq!(result.name(), Some(&*new_name));
+    assert_eq!(result.uuid(), Some(&*uuid));
+
+    dm.device_remove(&DevId::Name(&new_name), DmFlags::default())
+        .unwrap();
+}
+
 #[test]
 /// Test that device rename to same name fails.
 /// Since a device with that name already exists, the name can not be used.
@@ -333,3 +486,1673 @@ fn sudo_test_double_creation() {
     dm.device_remove(&DevId::Name(&name), DmFlags::default())
         .unwrap();
 }
+
+#[test]
+/// Verify that creating a device with a table leaves it active, with
+/// the table in place.
+fn sudo_test_create_with_table() {
+    let dm = DM::new().unwrap();
+    let name = test_name("example-dev").expect("is valid DM name");
+    let table = vec![TargetLine::new(0, 1024, "zero", "").unwrap()];
+
+    let info = dm
+        .device_create_with_table(&name, None, &table, DmFlags::default())
+        .unwrap();
+    assert!(!info.is_suspended());
+    assert!(info.is_active_table_present());
+
+    let (_, status) = dm
+        .table_status(&DevId::Name(&name), DmFlags::DM_STATUS_TABLE)
+        .unwrap();
+    assert_eq!(status, table);
+
+    dm.device_remove(&DevId::Name(&name), DmFlags::default())
+        .unwrap();
+}
+
+#[test]
+/// Verify that if the table cannot be loaded, no device is left behind.
+fn sudo_test_create_with_table_rollback() {
+    let dm = DM::new().unwrap();
+    let name = test_name("example-dev").expect("is valid DM name");
+    let table = vec![TargetLine::new(0, 1024, "no-such-target", "").unwrap()];
+
+    assert_matches!(
+        dm.device_create_with_table(&name, None, &table, DmFlags::default()),
+        Err(DmError::Ioctl(op, _, _, _)) if op == DmIoctlCmd::DM_TABLE_LOAD
+    );
+    assert!(list_test_devices(&dm).unwrap().is_empty());
+}
+
+#[test]
+/// Verify that suspend and resume are reflected in the DM_SUSPEND
+/// out-flag, and that resuming a device that is not suspended is
+/// allowed.
+fn sudo_test_suspend_resume() {
+    let dm = DM::new().unwrap();
+    let name = test_name("example-dev").expect("is valid DM name");
+    let table = vec![TargetLine::new(0, 1024, "zero", "").unwrap()];
+    dm.device_create_with_table(&name, None, &table, DmFlags::default())
+        .unwrap();
+    let id = DevId::Name(&name);
+
+    let info = dm.resume(&id).unwrap();
+    assert!(!info.is_suspended());
+
+    let opts = SuspendOptions {
+        noflush: true,
+        ..Default::default()
+    };
+    let info = dm.suspend(&id, opts).unwrap();
+    assert!(info.is_suspended());
+    assert!(dm.device_info(&id).unwrap().is_suspended());
+
+    let info = dm.resume(&id).unwrap();
+    assert!(!info.is_suspended());
+
+    dm.device_remove(&id, DmFlags::default()).unwrap();
+}
+
+#[test]
+/// Verify that reloading a device's table replaces the active table,
+/// and that a failed reload leaves the old table in place.
+fn sudo_test_table_reload() {
+    let dm = DM::new().unwrap();
+    let name = test_name("example-dev").expect("is valid DM name");
+    let table = vec![TargetLine::new(0, 1024, "zero", "").unwrap()];
+    dm.device_create_with_table(&name, None, &table, DmFlags::default())
+        .unwrap();
+    let id = DevId::Name(&name);
+
+    let new_table = vec![TargetLine::new(0, 2048, "error", "").unwrap()];
+    let opts = ReloadOptions {
+        suspend: true,
+        noflush: true,
+        ..Default::default()
+    };
+    let info = dm.table_reload(&id, &new_table, opts).unwrap();
+    assert!(!info.is_suspended());
+    assert!(!info.is_inactive_table_present());
+    let (_, status) = dm.table_status(&id, DmFlags::DM_STATUS_TABLE).unwrap();
+    assert_eq!(status, new_table);
+
+    let bad_table =
+        vec![TargetLine::new(0, 1024, "no-such-target", "").unwrap()];
+    assert_matches!(
+        dm.table_reload(&id, &bad_table, ReloadOptions::default()),
+        Err(DmError::TableReload(ReloadStep::Load, _))
+    );
+    let (_, status) = dm.table_status(&id, DmFlags::DM_STATUS_TABLE).unwrap();
+    assert_eq!(status, new_table);
+
+    dm.device_remove(&id, DmFlags::default()).unwrap();
+}
+
+#[test]
+/// Verify that a deferred removal of an unused device happens
+/// immediately, and that the device is gone afterwards.
+fn sudo_test_remove_deferred() {
+    let dm = DM::new().unwrap();
+    let name = test_name("example-dev").expect("is valid DM name");
+    dm.device_create(&name, None, DmFlags::default()).unwrap();
+
+    assert_eq!(
+        dm.device_remove_deferred(
+            &DevId::Name(&name),
+            Some(Duration::from_secs(1))
+        )
+        .unwrap(),
+        RemovalOutcome::Removed
+    );
+    assert!(list_test_devices(&dm).unwrap().is_empty());
+}
+
+#[test]
+/// Verify that a device created with a linear table over a loop
+/// device maps onto the loop device, and that the returned info
+/// reflects the resumed device.
+fn sudo_test_create_with_table_linear() {
+    let dm = DM::new().unwrap();
+    let loop_dev = LoopDevice::new("example-loop", 4096);
+    let name = test_name("example-dev").expect("is valid DM name");
+    let table = vec![TargetLine::new(
+        0,
+        2048,
+        "linear",
+        &format!("{} 1024", loop_dev.device()),
+    )
+    .unwrap()];
+
+    let info = dm
+        .device_create_with_table(&name, None, &table, DmFlags::default())
+        .unwrap();
+    assert_eq!(info.name(), Some(&*name));
+    assert!(!info.is_suspended());
+
+    let id = DevId::Name(&name);
+    let (_, status) = dm.table_status(&id, DmFlags::DM_STATUS_TABLE).unwrap();
+    assert_eq!(status, table);
+    assert_eq!(
+        dm.table_deps(&id, DmFlags::default()).unwrap(),
+        vec![loop_dev.device()]
+    );
+
+    dm.device_remove(&id, DmFlags::default()).unwrap();
+}
+
+#[test]
+/// Verify that the IMA measurement of a linear device names the
+/// target and its backing device, and is what table_status reports
+/// for DM_IMA_MEASUREMENT.
+fn sudo_test_ima_measurement() {
+    let dm = DM::new().unwrap();
+    let loop_dev = LoopDevice::new("example-loop", 4096);
+    let name = test_name("example-dev").expect("is valid DM name");
+    let table = vec![TargetLine::new(
+        0,
+        2048,
+        "linear",
+        &format!("{} 1024", loop_dev.device()),
+    )
+    .unwrap()];
+    dm.device_create_with_table(&name, None, &table, DmFlags::default())
+        .unwrap();
+
+    let id = DevId::Name(&name);
+    let measurement = dm.ima_measurement(&id).unwrap();
+    assert!(measurement.starts_with("target_name=linear,"));
+    assert!(
+        measurement.contains(&format!(",device_name={},", loop_dev.device()))
+    );
+    assert!(measurement.ends_with(';'));
+
+    let (_, status) =
+        dm.table_status(&id, DmFlags::DM_IMA_MEASUREMENT).unwrap();
+    assert_eq!(status.len(), 1);
+    assert_eq!(status[0].params, measurement);
+
+    dm.device_remove(&id, DmFlags::default()).unwrap();
+}
+
+#[test]
+/// Verify that a table referring to a nonexistent backing device
+/// leaves no device behind.
+fn sudo_test_create_with_table_missing_backing_device() {
+    let dm = DM::new().unwrap();
+    let name = test_name("example-dev").expect("is valid DM name");
+    let table = vec![TargetLine::new(
+        0,
+        2048,
+        "linear",
+        &format!("/dev/{} 0", test_string("no-such-device")),
+    )
+    .unwrap()];
+
+    assert_matches!(
+        dm.device_create_with_table(&name, None, &table, DmFlags::default()),
+        Err(DmError::Ioctl(op, _, _, _)) if op == DmIoctlCmd::DM_TABLE_LOAD
+    );
+    assert!(list_test_devices(&dm).unwrap().is_empty());
+}
+
+#[test]
+/// Verify that remove_all_matching removes only the devices selected
+/// by the predicate, and reports their names.
+fn sudo_test_remove_all_matching() {
+    let dm = DM::new().unwrap();
+    let tagged = test_name("example-tagged").expect("is valid DM name");
+    let tagged_uuid = test_uuid("example-tagged").expect("is valid DM uuid");
+    let untagged = test_name("example-untagged").expect("is valid DM name");
+    dm.device_create(&tagged, Some(&tagged_uuid), DmFlags::default())
+        .unwrap();
+    dm.device_create(&untagged, None, DmFlags::default())
+        .unwrap();
+
+    let removed = dm
+        .remove_all_matching(
+            |_, uuid| uuid == Some(&*tagged_uuid),
+            DmFlags::default(),
+        )
+        .unwrap();
+    assert_eq!(removed, vec![tagged]);
+
+    let remaining = list_test_devices(&dm).unwrap();
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].0, untagged);
+
+    dm.device_remove(&DevId::Name(&untagged), DmFlags::default())
+        .unwrap();
+}
+
+#[test]
+/// Verify that a message reply is returned untouched by
+/// target_msg_raw and as text by target_msg, including when the
+/// reply is empty.  "@stats_list" is handled by DM itself, so it
+/// does not need a table, and lists nothing on a new device.
+fn sudo_test_target_msg_empty_reply() {
+    let dm = DM::new().unwrap();
+    let name = test_name("example-dev").expect("is valid DM name");
+    dm.device_create(&name, None, DmFlags::default()).unwrap();
+    let id = DevId::Name(&name);
+
+    let (_, raw) = dm.target_msg_raw(&id, None, b"@stats_list").unwrap();
+    assert_eq!(raw, Some(vec![b'\0']));
+    let (_, text) = dm.target_msg(&id, None, "@stats_list").unwrap();
+    assert_eq!(text, Some(String::new()));
+
+    assert_matches!(
+        dm.target_msg_raw(&id, None, b"@stats_list\0junk"),
+        Err(DmError::InvalidArgument(_))
+    );
+
+    dm.device_remove(&id, DmFlags::default()).unwrap();
+}
+
+#[test]
+/// Verify that a statistics region can be created, listed, printed
+/// and deleted through the typed stats helpers.
+fn sudo_test_stats_round_trip() {
+    let dm = DM::new().unwrap();
+    let name = test_name("example-dev").expect("is valid DM name");
+    let table = vec![TargetLine::new(0, 1024, "zero", "").unwrap()];
+    dm.device_create_with_table(&name, None, &table, DmFlags::default())
+        .unwrap();
+    let id = DevId::Name(&name);
+
+    let region_id = dm
+        .stats_create(&id, Some((0, 512)), StatsStep::Areas(2), Some("test"))
+        .unwrap();
+    let regions = dm.stats_list(&id, Some("test")).unwrap();
+    assert_eq!(regions.len(), 1);
+    assert_eq!(regions[0].region_id, region_id);
+    assert_eq!((regions[0].start, regions[0].length), (0, 512));
+    assert_eq!(regions[0].step, 256);
+    assert_eq!(regions[0].program_id, "test");
+
+    let areas = dm.stats_print(&id, region_id).unwrap();
+    assert_eq!(
+        areas
+            .iter()
+            .map(|(bounds, _)| bounds.start)
+            .collect::<Vec<_>>(),
+        vec![0, 256]
+    );
+    assert!(areas.iter().all(|(bounds, _)| bounds.length == 256));
+    assert!(areas.iter().all(|(_, counters)| counters.in_flight == 0));
+
+    dm.stats_delete(&id, region_id).unwrap();
+    assert!(dm.stats_list(&id, None).unwrap().is_empty());
+
+    dm.device_remove(&id, DmFlags::default()).unwrap();
+}
+
+#[test]
+/// Verify that device_node_paths reports paths derived from the
+/// device's minor number and name.
+fn sudo_test_device_node_paths() {
+    let dm = DM::new().unwrap();
+    let name = test_name("example-dev").expect("is valid DM name");
+    let info = dm.device_create(&name, None, DmFlags::default()).unwrap();
+    let id = DevId::Name(&name);
+
+    let (dm_path, mapper_path) = dm.device_node_paths(&id).unwrap();
+    assert_eq!(
+        dm_path,
+        PathBuf::from(format!("/dev/dm-{}", info.device().minor))
+    );
+    assert_eq!(
+        mapper_path,
+        Some(PathBuf::from(format!("/dev/mapper/{}", &*name)))
+    );
+
+    dm.device_remove(&id, DmFlags::default()).unwrap();
+}
+
+#[test]
+/// Verify that table_status_raw reports the same targets as
+/// table_status, with the params left as NUL-terminated bytes.
+fn sudo_test_table_status_raw() {
+    let dm = DM::new().unwrap();
+    let name = test_name("example-dev").expect("is valid DM name");
+    let table = vec![TargetLine::new(0, 2048, "error", "").unwrap()];
+    dm.device_create_with_table(&name, None, &table, DmFlags::default())
+        .unwrap();
+    let id = DevId::Name(&name);
+
+    let (_, raw) = dm.table_status_raw(&id, DmFlags::DM_STATUS_TABLE).unwrap();
+    assert_eq!(raw.len(), 1);
+    assert_eq!(raw[0].sector_start, 0);
+    assert_eq!(raw[0].length, 2048);
+    assert_eq!(raw[0].target_type, "error");
+    assert_eq!(raw[0].params.first(), Some(&b'\0'));
+
+    dm.device_remove(&id, DmFlags::default()).unwrap();
+}
+
+#[test]
+/// Verify that on kernels that report name list flags, exactly one
+/// of the uuid flags is set for each device, consistent with its uuid.
+fn sudo_test_list_devices_extended() {
+    let dm = DM::new().unwrap();
+    let with_uuid = test_name("example-uuid").expect("is valid DM name");
+    let uuid = test_uuid("example-uuid").expect("is valid DM uuid");
+    let without_uuid = test_name("example-no-uuid").expect("is valid DM name");
+    dm.device_create(&with_uuid, Some(&uuid), DmFlags::default())
+        .unwrap();
+    dm.device_create(&without_uuid, None, DmFlags::default())
+        .unwrap();
+
+    for entry in dm.list_devices_extended().unwrap() {
+        let Some(flags) = entry.flags else {
+            continue;
+        };
+        if entry.name == with_uuid {
+            assert_eq!(flags, DmNameListFlags::HAS_UUID);
+            assert_eq!(entry.uuid, Some(uuid.clone()));
+        } else if entry.name == without_uuid {
+            assert_eq!(flags, DmNameListFlags::DOESNT_HAVE_UUID);
+            assert_eq!(entry.uuid, None);
+        }
+    }
+
+    dm.device_remove(&DevId::Name(&with_uuid), DmFlags::default())
+        .unwrap();
+    dm.device_remove(&DevId::Name(&without_uuid), DmFlags::default())
+        .unwrap();
+}
+
+#[test]
+/// Verify that DeviceInfo reports the number of targets in the
+/// active table and that an unused device is not open.
+fn sudo_test_device_info_counts() {
+    let dm = DM::new().unwrap();
+    let name = test_name("example-dev").expect("is valid DM name");
+    let table = vec![
+        TargetLine::new(0, 1024, "error", "").unwrap(),
+        TargetLine::new(1024, 1024, "zero", "").unwrap(),
+    ];
+    dm.device_create_with_table(&name, None, &table, DmFlags::default())
+        .unwrap();
+    let id = DevId::Name(&name);
+
+    let info = dm.device_info(&id).unwrap();
+    assert_eq!(info.target_count(), 2);
+    assert_eq!(info.open_count(), 0);
+
+    dm.device_remove(&id, DmFlags::default()).unwrap();
+}
+
+#[test]
+/// Verify that device_wait_for returns immediately when the device's
+/// event number already differs from the one given.
+fn sudo_test_device_wait_for_stale() {
+    let dm = DM::new().unwrap();
+    let name = test_name("example-dev").expect("is valid DM name");
+    let table = vec![TargetLine::new(0, 1024, "error", "").unwrap()];
+    dm.device_create_with_table(&name, None, &table, DmFlags::default())
+        .unwrap();
+    let id = DevId::Name(&name);
+
+    let event_nr = dm.device_info(&id).unwrap().event_nr();
+    let (info, status) = dm
+        .device_wait_for(&id, event_nr.wrapping_add(1), DmFlags::default())
+        .unwrap();
+    assert_eq!(info.event_nr(), event_nr);
+    assert_eq!(status.len(), 1);
+
+    dm.device_remove(&id, DmFlags::default()).unwrap();
+}
+
+#[test]
+/// Verify that open_with can create a missing control node, and
+/// its parent directory, and that the resulting context works.
+fn sudo_test_open_with_create_control() {
+    let tmp = std::env::temp_dir().join(test_string("example-dev-dir"));
+    let _ = fs::remove_dir_all(&tmp);
+    fs::create_dir(&tmp).unwrap();
+    let dir = File::open(&tmp).unwrap();
+
+    let mut options = DmOpenOptions::default();
+    options.dir(dir.as_fd()).path("mapper/control");
+    assert_matches!(
+        DM::open_with(&options).err(),
+        Some(DmError::ContextInit(_))
+    );
+
+    let dm = DM::open_with(options.create_control(true)).unwrap();
+    assert_matches!(dm.version(), Ok(_));
+    assert!(tmp.join("mapper/control").exists());
+
+    fs::remove_dir_all(&tmp).unwrap();
+}
+
+#[test]
+/// Verify the DeviceInfo flag accessors through a device's life
+/// cycle: created, given a read-only table in the inactive slot,
+/// activated, then removed with deferral.
+fn sudo_test_device_info_flag_accessors() {
+    let dm = DM::new().unwrap();
+    let name = test_name("example-dev").expect("is valid DM name");
+    let table = vec![TargetLine::new(0, 1024, "error", "").unwrap()];
+    let info = dm.device_create(&name, None, DmFlags::default()).unwrap();
+    assert!(info.is_suspended());
+    assert!(!info.is_active_table_present());
+
+    let id = DevId::Name(&name);
+    let info = dm.table_load(&id, &table, DmFlags::DM_READONLY).unwrap();
+    assert!(info.is_inactive_table_present());
+
+    let info = dm.resume(&id).unwrap();
+    assert!(!info.is_suspended());
+    assert!(info.is_read_only());
+    assert!(info.is_active_table_present());
+    assert!(!info.is_inactive_table_present());
+
+    let info = dm.device_remove(&id, DmFlags::DM_DEFERRED_REMOVE).unwrap();
+    assert!(!info.is_deferred_remove_scheduled());
+}
+
+#[test]
+/// Verify that a stack of two linear devices can be suspended and
+/// resumed together, and that a failure partway through is reported
+/// per device according to the batch mode.
+fn sudo_test_suspend_resume_all() {
+    let dm = DM::new().unwrap();
+    let loop_dev = LoopDevice::new("example-loop", 4096);
+    let bottom = test_name("example-bottom").expect("is valid DM name");
+    let top = test_name("example-top").expect("is valid DM name");
+    let missing = test_name("example-missing").expect("is valid DM name");
+
+    let table = vec![TargetLine::new(
+        0,
+        2048,
+        "linear",
+        &format!("{} 0", loop_dev.device()),
+    )
+    .unwrap()];
+    let info = dm
+        .device_create_with_table(&bottom, None, &table, DmFlags::default())
+        .unwrap();
+    let table = vec![TargetLine::new(
+        0,
+        2048,
+        "linear",
+        &format!("{} 0", info.device()),
+    )
+    .unwrap()];
+    dm.device_create_with_table(&top, None, &table, DmFlags::default())
+        .unwrap();
+
+    let down = [DevId::Name(&top), DevId::Name(&bottom)];
+    let up = [DevId::Name(&bottom), DevId::Name(&top)];
+    let infos = dm
+        .suspend_all(&down, SuspendOptions::default(), BatchMode::StopOnError)
+        .unwrap();
+    assert!(infos.iter().all(|info| info.is_suspended()));
+    let infos = dm.resume_all(&up, BatchMode::StopOnError).unwrap();
+    assert!(infos.iter().all(|info| !info.is_suspended()));
+
+    let ids = [
+        DevId::Name(&top),
+        DevId::Name(&missing),
+        DevId::Name(&bottom),
+    ];
+    let err = dm
+        .suspend_all(&ids, SuspendOptions::default(), BatchMode::StopOnError)
+        .unwrap_err();
+    assert_matches!(
+        &err.outcomes[..],
+        [
+            BatchOutcome::Succeeded(_),
+            BatchOutcome::Failed(err),
+            BatchOutcome::Skipped
+        ] if err.errno() == Some(nix::errno::Errno::ENXIO)
+    );
+    assert!(!dm
+        .device_info(&DevId::Name(&bottom))
+        .unwrap()
+        .is_suspended());
+    dm.resume_all(&up, BatchMode::StopOnError).unwrap();
+
+    let err = dm
+        .suspend_all(
+            &ids,
+            SuspendOptions::default(),
+            BatchMode::ContinueOnError,
+        )
+        .unwrap_err();
+    assert_matches!(
+        &err.outcomes[..],
+        [
+            BatchOutcome::Succeeded(_),
+            BatchOutcome::Failed(_),
+            BatchOutcome::Succeeded(_)
+        ]
+    );
+    dm.resume_all(&up, BatchMode::StopOnError).unwrap();
+
+    dm.device_remove(&DevId::Name(&top), DmFlags::default())
+        .unwrap();
+    dm.device_remove(&DevId::Name(&bottom), DmFlags::default())
+        .unwrap();
+}
+
+#[test]
+/// Verify that the dependencies of a stacked linear device are
+/// resolved to DM names where they are DM devices.
+fn sudo_test_table_deps_resolved() {
+    let dm = DM::new().unwrap();
+    let loop_dev = LoopDevice::new("example-loop", 4096);
+    let bottom = test_name("example-bottom").expect("is valid DM name");
+    let top = test_name("example-top").expect("is valid DM name");
+
+    let table = vec![TargetLine::new(
+        0,
+        2048,
+        "linear",
+        &format!("{} 0", loop_dev.device()),
+    )
+    .unwrap()];
+    let info = dm
+        .device_create_with_table(&bottom, None, &table, DmFlags::default())
+        .unwrap();
+    let table = vec![TargetLine::new(
+        0,
+        2048,
+        "linear",
+        &format!("{} 0", info.device()),
+    )
+    .unwrap()];
+    dm.device_create_with_table(&top, None, &table, DmFlags::default())
+        .unwrap();
+
+    assert_eq!(
+        dm.table_deps_resolved(&DevId::Name(&top), DmFlags::default())
+            .unwrap(),
+        vec![DepEntry {
+            device: info.device(),
+            name: Some(bottom.clone()),
+        }]
+    );
+    assert_eq!(
+        dm.table_deps_resolved(&DevId::Name(&bottom), DmFlags::default())
+            .unwrap(),
+        vec![DepEntry {
+            device: loop_dev.device(),
+            name: None,
+        }]
+    );
+
+    dm.device_remove(&DevId::Name(&top), DmFlags::default())
+        .unwrap();
+    dm.device_remove(&DevId::Name(&bottom), DmFlags::default())
+        .unwrap();
+}
+
+#[test]
+/// Verify that the dependency graph of a three-deep stack of linear
+/// devices over a loop device has the expected shape.
+fn sudo_test_dep_tree() {
+    let dm = DM::new().unwrap();
+    let loop_dev = LoopDevice::new("example-loop", 4096);
+    let names = ["example-bottom", "example-middle", "example-top"]
+        .map(|name| test_name(name).expect("is valid DM name"));
+
+    let mut under = loop_dev.device();
+    for name in &names {
+        let table =
+            vec![TargetLine::new(0, 2048, "linear", &format!("{under} 0"))
+                .unwrap()];
+        under = dm
+            .device_create_with_table(name, None, &table, DmFlags::default())
+            .unwrap()
+            .device();
+    }
+    let [bottom, middle, top] = &names;
+
+    let graph = dm.dep_tree().unwrap();
+    assert_eq!(graph.device(top), Some(under));
+    assert_eq!(graph.parents(bottom), vec![&**middle]);
+    assert_eq!(graph.parents(middle), vec![&**top]);
+    assert!(graph.roots().contains(&&**top));
+    assert!(!graph.roots().contains(&&**middle));
+    assert_eq!(
+        graph.children(bottom),
+        Some(
+            &[DepEntry {
+                device: loop_dev.device(),
+                name: None
+            }][..]
+        )
+    );
+    assert_eq!(graph.leaves(top), vec![loop_dev.device()]);
+
+    let order = graph.topological_order().unwrap();
+    let position = |name: &DmNameBuf| {
+        order.iter().position(|&dev| dev == &**name).unwrap()
+    };
+    assert!(position(top) < position(middle));
+    assert!(position(middle) < position(bottom));
+
+    for name in [top, middle, bottom] {
+        dm.device_remove(&DevId::Name(name), DmFlags::default())
+            .unwrap();
+    }
+}
+
+#[test]
+/// Verify that remove_devices removes stacked devices top first,
+/// whatever order they are given in, and reports a missing device
+/// without giving up on the others.
+fn sudo_test_remove_devices() {
+    let dm = DM::new().unwrap();
+    let names = ["example-bottom", "example-top", "example-missing"]
+        .map(|name| test_name(name).expect("is valid DM name"));
+    let [bottom, top, missing] = &names;
+
+    let table = vec![TargetLine::new(0, 2048, "zero", "").unwrap()];
+    let under = dm
+        .device_create_with_table(bottom, None, &table, DmFlags::default())
+        .unwrap()
+        .device();
+    let table = vec![
+        TargetLine::new(0, 2048, "linear", &format!("{under} 0")).unwrap()
+    ];
+    dm.device_create_with_table(top, None, &table, DmFlags::default())
+        .unwrap();
+
+    let results = dm
+        .remove_devices(&[&**bottom, &**missing, &**top], DmFlags::default())
+        .unwrap();
+    assert_eq!(results.len(), 3);
+    assert_eq!(&results[0].0, top);
+    assert_matches!(results[0].1, Ok(()));
+    assert_eq!(&results[1].0, bottom);
+    assert_matches!(results[1].1, Ok(()));
+    assert_eq!(&results[2].0, missing);
+    assert_eq!(
+        results[2].1.as_ref().unwrap_err().errno(),
+        Some(nix::errno::Errno::ENXIO)
+    );
+
+    assert!(list_test_devices(&dm).unwrap().is_empty());
+}
+
+#[test]
+/// Verify that remove_devices leaves a device used by another device
+/// that is not being removed alone, rather than fencing it.
+fn sudo_test_remove_devices_in_use() {
+    let dm = DM::new().unwrap();
+    let bottom = test_name("example-bottom").expect("is valid DM name");
+    let top = test_name("example-top").expect("is valid DM name");
+
+    let table = vec![TargetLine::new(0, 2048, "zero", "").unwrap()];
+    let under = dm
+        .device_create_with_table(&bottom, None, &table, DmFlags::default())
+        .unwrap()
+        .device();
+    let linear = vec![TargetLine::new(
+        0,
+        2048,
+        "linear",
+        &format!("{under} 0"),
+    )
+    .unwrap()];
+    dm.device_create_with_table(&top, None, &linear, DmFlags::default())
+        .unwrap();
+
+    let results = dm.remove_devices(&[&*bottom], DmFlags::default()).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].0, bottom);
+    assert_matches!(results[0].1, Err(DmError::InvalidArgument(_)));
+    let id = DevId::Name(&bottom);
+    assert_eq!(
+        dm.table_status(&id, DmFlags::DM_STATUS_TABLE).unwrap().1,
+        table
+    );
+
+    for name in [&top, &bottom] {
+        dm.device_remove(&DevId::Name(name), DmFlags::default())
+            .unwrap();
+    }
+}
+
+#[test]
+/// Verify that a device can be found by the device number of its
+/// node.
+fn sudo_test_devno_id() {
+    let dm = DM::new().unwrap();
+    let name = test_name("example-dev").expect("is valid DM name");
+    dm.device_create(&name, None, DmFlags::default()).unwrap();
+
+    let (dm_path, _) = dm.device_node_paths(&DevId::Name(&name)).unwrap();
+    let device = Device::from_devnode(&dm_path)
+        .unwrap()
+        .expect("is a block device");
+    let id = DevId::Devno(device);
+    assert_eq!(dm.device_info(&id).unwrap().name(), Some(&*name));
+    assert_eq!(dm.table_deps(&id, DmFlags::default()).unwrap(), vec![]);
+    assert!(dm
+        .suspend(&id, SuspendOptions::default())
+        .unwrap()
+        .is_suspended());
+    assert!(!dm.resume(&id).unwrap().is_suspended());
+
+    dm.device_remove(&id, DmFlags::default()).unwrap();
+    assert!(list_test_devices(&dm).unwrap().is_empty());
+}
+
+#[test]
+/// Verify that a device can be switched to an error table, and that
+/// the new table is active.
+fn sudo_test_device_set_error_table() {
+    let dm = DM::new().unwrap();
+    let loop_dev = LoopDevice::new("example-loop", 4096);
+    let name = test_name("example-dev").expect("is valid DM name");
+    let table = vec![TargetLine::new(
+        0,
+        2048,
+        "linear",
+        &format!("{} 0", loop_dev.device()),
+    )
+    .unwrap()];
+    dm.device_create_with_table(&name, None, &table, DmFlags::default())
+        .unwrap();
+
+    let id = DevId::Name(&name);
+    let info = dm
+        .device_set_error_table(&id, 2048, ReloadOptions::default())
+        .unwrap();
+    assert!(!info.is_suspended());
+    assert_eq!(
+        dm.table_status(&id, DmFlags::DM_STATUS_TABLE).unwrap().1,
+        vec![TargetLine::new(0, 2048, "error", "").unwrap()]
+    );
+    assert!(dm.table_deps(&id, DmFlags::default()).unwrap().is_empty());
+
+    dm.device_remove(&id, DmFlags::default()).unwrap();
+}
+
+#[test]
+/// Verify that a device can be created with a chosen minor number,
+/// and that a second device cannot be given the same one.
+fn sudo_test_create_persistent() {
+    let dm = DM::new().unwrap();
+    let name = test_name("example-dev").expect("is valid DM name");
+    let minor = 0xf_0000;
+    let info = dm
+        .device_create_persistent(&name, None, minor, DmFlags::default())
+        .unwrap();
+    assert_eq!(info.device().minor, minor);
+    assert!(info.flags().contains(DmFlags::DM_PERSISTENT_DEV));
+
+    let other = test_name("example-dev-2").expect("is valid DM name");
+    let err = dm
+        .device_create_persistent(&other, None, minor, DmFlags::default())
+        .unwrap_err();
+    assert_eq!(err.errno(), Some(nix::errno::Errno::EBUSY));
+
+    dm.device_remove(&DevId::Name(&name), DmFlags::default())
+        .unwrap();
+    assert!(list_test_devices(&dm).unwrap().is_empty());
+}
+
+#[test]
+/// Verify that /dev/mapper nodes of both kinds can be created for a
+/// device, and removed again.
+fn sudo_test_mknod_rmnod() {
+    let dm = DM::new().unwrap();
+    let name = test_name("example-dev").expect("is valid DM name");
+    let info = dm.device_create(&name, None, DmFlags::default()).unwrap();
+    let (dm_path, mapper_path) =
+        dm.device_node_paths(&DevId::Name(&name)).unwrap();
+    let mapper_path = mapper_path.expect("device has a name");
+
+    let path = dm.mknod(&info, MapperNode::BlockDevice).unwrap();
+    assert_eq!(path, mapper_path);
+    assert_eq!(Device::from_devnode(&path).unwrap(), Some(info.device()));
+
+    let path = dm.mknod(&info, MapperNode::Symlink).unwrap();
+    assert_eq!(
+        fs::canonicalize(&path).unwrap(),
+        fs::canonicalize(&dm_path).unwrap()
+    );
+
+    dm.rmnod(&name).unwrap();
+    assert!(fs::symlink_metadata(&path).is_err());
+
+    dm.device_remove(&DevId::Name(&name), DmFlags::default())
+        .unwrap();
+    assert!(list_test_devices(&dm).unwrap().is_empty());
+}
+
+#[test]
+/// Verify that DM devices, and only DM devices, are recognized by
+/// their major number.
+fn sudo_test_is_dm() {
+    let dm = DM::new().unwrap();
+    let loop_dev = LoopDevice::new("example-loop", 4096);
+    let name = test_name("example-dev").expect("is valid DM name");
+    let info = dm.device_create(&name, None, DmFlags::default()).unwrap();
+
+    assert_eq!(dm_major().unwrap(), Some(info.device().major));
+    assert!(info.device().is_dm().unwrap());
+    assert!(!loop_dev.device().is_dm().unwrap());
+
+    dm.device_remove(&DevId::Name(&name), DmFlags::default())
+        .unwrap();
+    assert!(list_test_devices(&dm).unwrap().is_empty());
+}
+
+#[test]
+/// Verify that ioctl_raw returns the same DeviceInfo as the typed
+/// method for the same command.
+fn sudo_test_ioctl_raw() {
+    let dm = DM::new().unwrap();
+    let name = test_name("example-dev").expect("is valid DM name");
+    dm.device_create(&name, None, DmFlags::default()).unwrap();
+
+    let id = DevId::Name(&name);
+    let (info, data) = dm
+        .ioctl_raw(DmIoctlCmd::DM_DEV_STATUS, Some(&id), DmFlags::empty(), None)
+        .unwrap();
+    assert_eq!(info.name(), Some(&*name));
+    assert_eq!(info.device(), dm.device_info(&id).unwrap().device());
+    assert!(data.is_empty());
+
+    let (_, data) = dm
+        .ioctl_raw(DmIoctlCmd::DM_LIST_VERSIONS, None, DmFlags::empty(), None)
+        .unwrap();
+    assert!(!data.is_empty());
+
+    dm.device_remove(&id, DmFlags::default()).unwrap();
+    assert!(list_test_devices(&dm).unwrap().is_empty());
+}
+
+#[test]
+/// Verify that wiping a device's table leaves a single error target
+/// of the same length, and that a device without a table is refused.
+fn sudo_test_wipe_table() {
+    let dm = DM::new().unwrap();
+    let loop_dev = LoopDevice::new("example-loop", 4096);
+    let name = test_name("example-dev").expect("is valid DM name");
+    let table = vec![
+        TargetLine::new(0, 1024, "linear", &format!("{} 0", loop_dev.device()))
+            .unwrap(),
+        TargetLine::new(1024, 512, "zero", "").unwrap(),
+    ];
+    dm.device_create_with_table(&name, None, &table, DmFlags::default())
+        .unwrap();
+
+    let id = DevId::Name(&name);
+    let opts = ReloadOptions {
+        suspend: true,
+        noflush: true,
+        ..Default::default()
+    };
+    assert!(!dm.wipe_table(&id, opts).unwrap().is_suspended());
+    assert_eq!(
+        dm.table_status(&id, DmFlags::DM_STATUS_TABLE).unwrap().1,
+        vec![TargetLine::new(0, 1536, "error", "").unwrap()]
+    );
+    dm.device_remove(&id, DmFlags::default()).unwrap();
+
+    dm.device_create(&name, None, DmFlags::default()).unwrap();
+    assert_matches!(
+        dm.wipe_table(&id, ReloadOptions::default()),
+        Err(DmError::InvalidArgument(_))
+    );
+    dm.device_remove(&id, DmFlags::default()).unwrap();
+    assert!(list_test_devices(&dm).unwrap().is_empty());
+}
+
+#[test]
+/// Verify that table presence is reported as tables are loaded and
+/// made active, and that querying an absent table gives no targets.
+fn sudo_test_table_presence() {
+    let dm = DM::new().unwrap();
+    let name = test_name("example-dev").expect("is valid DM name");
+    dm.device_create(&name, None, DmFlags::default()).unwrap();
+
+    let id = DevId::Name(&name);
+    assert!(!dm.has_active_table(&id).unwrap());
+    assert!(!dm.has_inactive_table(&id).unwrap());
+
+    let table = vec![TargetLine::new(0, 1024, "zero", "").unwrap()];
+    dm.table_load(&id, &table, DmFlags::default()).unwrap();
+    assert!(!dm.has_active_table(&id).unwrap());
+    assert!(dm.has_inactive_table(&id).unwrap());
+    assert!(dm
+        .table_status(&id, DmFlags::DM_STATUS_TABLE)
+        .unwrap()
+        .1
+        .is_empty());
+    assert_eq!(
+        dm.table_status(
+            &id,
+            DmFlags::DM_STATUS_TABLE | DmFlags::DM_QUERY_INACTIVE_TABLE
+        )
+        .unwrap()
+        .1,
+        table
+    );
+
+    dm.resume(&id).unwrap();
+    assert!(dm.has_active_table(&id).unwrap());
+    assert!(!dm.has_inactive_table(&id).unwrap());
+    assert!(dm
+        .table_status(&id, DmFlags::DM_QUERY_INACTIVE_TABLE)
+        .unwrap()
+        .1
+        .is_empty());
+
+    dm.device_remove(&id, DmFlags::default()).unwrap();
+    assert!(list_test_devices(&dm).unwrap().is_empty());
+}
+
+#[test]
+/// Verify that linear parameters built from a device path are loaded
+/// as given, and parse back from the table the kernel reports.
+fn sudo_test_linear_params_round_trip() {
+    let loop_dev = LoopDevice::new("example-loop", 4096);
+    let params = LinearTargetParams::from_path(loop_dev.path(), 1024).unwrap();
+    assert_eq!(params.dest, loop_dev.device());
+    with_target_device(
+        "example-dev",
+        2048,
+        &params,
+        DmFlags::default(),
+        |_, _, line| {
+            assert_eq!(line.params.parse::<LinearTargetParams>(), Ok(params));
+            assert_eq!(
+                TypedTargetLine::parse(line),
+                Ok(TypedTargetLine {
+                    start: 0,
+                    length: 2048,
+                    params,
+                })
+            );
+        },
+    );
+}
+
+#[test]
+/// Verify that a striped target spanning two devices reports both
+/// stripes alive, and that its parameters round-trip.
+fn sudo_test_striped_status() {
+    let first = LoopDevice::new("example-loop-1", 4096);
+    let second = LoopDevice::new("example-loop-2", 4096);
+
+    let params = StripedTargetParams::new(
+        128,
+        vec![(first.device(), 0), (second.device(), 0)],
+    )
+    .unwrap();
+    let length = params.aligned_length(4000);
+    assert_eq!(length, 3840);
+    with_target_device(
+        "example-dev",
+        length,
+        &params,
+        DmFlags::default(),
+        |dm, id, line| {
+            assert_eq!(
+                line.params.parse::<StripedTargetParams>().as_ref(),
+                Ok(&params)
+            );
+
+            let (_, lines) = dm.table_status(id, DmFlags::default()).unwrap();
+            let status: StripedTargetStatus = lines[0].params.parse().unwrap();
+            assert_eq!(
+                status.stripes,
+                vec![(first.device(), true), (second.device(), true)]
+            );
+            assert!(status.is_healthy());
+        },
+    );
+}
+
+#[test]
+/// Verify that unstriped parameters are reported back as given, for
+/// a target as long as unstriped_length gives.
+fn sudo_test_unstripe_params_round_trip() {
+    let dest = LoopDevice::new("example-loop", 4096);
+
+    let params =
+        UnstripeTargetParams::new(2, 128, 1, dest.device(), 0).unwrap();
+    let length = params.unstriped_length(4096);
+    assert_eq!(length, 2048);
+    assert_params_round_trip("example-unstriped", length, &params);
+}
+
+#[test]
+/// Verify that device_remove_retry keeps trying while the device is
+/// held open, and succeeds once it is closed.
+fn sudo_test_device_remove_retry() {
+    let dm = DM::new().unwrap();
+    let name = test_name("example-dev").expect("is valid DM name");
+    dm.device_create_with_table(
+        &name,
+        None,
+        &[TargetLine::new(0, 1024, "zero", "").unwrap()],
+        DmFlags::default(),
+    )
+    .unwrap();
+
+    let id = DevId::Name(&name);
+    let (dm_path, _) = dm.device_node_paths(&id).unwrap();
+    let holder = File::open(dm_path).unwrap();
+    assert_matches!(
+        dm.device_remove_retry(
+            &id,
+            DmFlags::default(),
+            2,
+            Duration::from_millis(10)
+        ),
+        Err(DmError::Ioctl(_, _, _, nix::errno::Errno::EBUSY))
+    );
+
+    let closer = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        drop(holder);
+    });
+    dm.device_remove_retry(
+        &id,
+        DmFlags::default(),
+        100,
+        Duration::from_millis(50),
+    )
+    .unwrap();
+    closer.join().unwrap();
+    assert!(list_test_devices(&dm).unwrap().is_empty());
+}
+
+#[test]
+/// Verify that delay parameters with separate read and write classes
+/// are reported back as given.
+fn sudo_test_delay_params_round_trip() {
+    let loop_dev = LoopDevice::new("example-loop", 4096);
+
+    let params = DelayTargetParams {
+        read: DelayClass::new(loop_dev.device(), 0, Duration::ZERO).unwrap(),
+        write: Some(
+            DelayClass::new(loop_dev.device(), 0, Duration::from_millis(10))
+                .unwrap(),
+        ),
+        flush: None,
+    };
+    assert_params_round_trip("example-dev", 4096, &params);
+}
+
+#[test]
+/// Verify that a flakey device passes reads through while up, and
+/// fails them with EIO while down.
+fn sudo_test_flakey_down_interval() {
+    let loop_dev = LoopDevice::new("example-loop", 16384);
+
+    let params =
+        FlakeyTargetParams::new(loop_dev.device(), 0, 1, 1, vec![]).unwrap();
+    with_target_device(
+        "example-dev",
+        16384,
+        &params,
+        DmFlags::default(),
+        |dm, id, line| {
+            assert_eq!(
+                line.params.parse::<FlakeyTargetParams>().as_ref(),
+                Ok(&params)
+            );
+
+            // Read a fresh part of the device each time, so that the
+            // page cache does not hide the errors, until a read has
+            // both succeeded and failed.
+            let (dm_path, _) = dm.device_node_paths(id).unwrap();
+            let file = File::open(dm_path).unwrap();
+            let mut buf = [0u8; 512];
+            let (mut succeeded, mut failed) = (false, false);
+            for block in 0..16u64 {
+                match file.read_at(&mut buf, block * 512 * 1024) {
+                    Ok(_) => succeeded = true,
+                    Err(err) => {
+                        assert_eq!(err.raw_os_error(), Some(nix::libc::EIO));
+                        failed = true;
+                    }
+                }
+                if succeeded && failed {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(250));
+            }
+            assert!(succeeded && failed);
+        },
+    );
+}
+
+#[test]
+/// Verify that the DeviceInfo returned by table_load reports the
+/// newly loaded inactive table, and no active table until resume.
+fn sudo_test_table_load_info() {
+    let dm = DM::new().unwrap();
+    let loop_dev = LoopDevice::new("example-loop", 4096);
+    let name = test_name("example-dev").expect("is valid DM name");
+    dm.device_create(&name, None, DmFlags::default()).unwrap();
+
+    let id = DevId::Name(&name);
+    let mut table = TargetTable::new();
+    table
+        .push_typed(
+            4096,
+            &LinearTargetParams {
+                dest: loop_dev.device(),
+                offset: 0,
+            },
+        )
+        .unwrap();
+    let info = dm
+        .table_load(&id, table.targets(), DmFlags::default())
+        .unwrap();
+    assert!(info.is_inactive_table_present());
+    assert!(!info.is_active_table_present());
+
+    let info = dm.resume(&id).unwrap();
+    assert!(!info.is_inactive_table_present());
+    assert!(info.is_active_table_present());
+
+    dm.device_remove(&id, DmFlags::default()).unwrap();
+    assert!(list_test_devices(&dm).unwrap().is_empty());
+}
+
+#[test]
+/// Verify that thin-pool parameters are reported back as given.
+fn sudo_test_thin_pool_params_round_trip() {
+    let metadata = LoopDevice::new("example-meta", 8192);
+    let data = LoopDevice::new("example-data", 32768);
+
+    let params = ThinPoolTargetParams::new(
+        metadata.device(),
+        data.device(),
+        128,
+        16,
+        ThinPoolFeatures::SKIP_BLOCK_ZEROING
+            | ThinPoolFeatures::ERROR_IF_NO_SPACE,
+    )
+    .unwrap();
+    with_target_device(
+        "example-pool",
+        32768,
+        &params,
+        DmFlags::default(),
+        |dm, id, line| {
+            assert_eq!(
+                line.params.parse::<ThinPoolTargetParams>().as_ref(),
+                Ok(&params)
+            );
+
+            let (_, status) = dm.table_status(id, DmFlags::default()).unwrap();
+            let status = match status[0].params.parse().unwrap() {
+                ThinPoolTargetStatus::Working(status) => status,
+                status => panic!("pool is not working: {status:?}"),
+            };
+            assert_eq!(status.transaction_id, 0);
+            assert_eq!(status.data_used, 0);
+            assert_eq!(status.data_total, 32768 / 128);
+            assert_eq!(status.mode, ThinPoolMode::ReadWrite);
+            assert!(status.error_if_no_space);
+        },
+    );
+}
+
+#[test]
+/// Verify that a table read back from a device can be modified and
+/// loaded again.
+fn sudo_test_table_read_modify_write() {
+    let dm = DM::new().unwrap();
+    let loop_dev = LoopDevice::new("example-loop", 4096);
+    let name = test_name("example-dev").expect("is valid DM name");
+    let mut table = TargetTable::new();
+    table.push(2048, "zero", "").unwrap();
+    table.push(2048, "zero", "").unwrap();
+    dm.device_create_with_table(
+        &name,
+        None,
+        table.targets(),
+        DmFlags::default(),
+    )
+    .unwrap();
+
+    let id = DevId::Name(&name);
+    let (_, lines) = dm.table_status(&id, DmFlags::DM_STATUS_TABLE).unwrap();
+    let mut table = TargetTable::from(lines);
+    table.targets_mut()[1] = TargetLine::new(
+        2048,
+        2048,
+        "linear",
+        &format!("{} 0", loop_dev.device()),
+    )
+    .unwrap();
+    table.check_layout().unwrap();
+    dm.table_reload(&id, table.targets(), ReloadOptions::default())
+        .unwrap();
+    let (_, lines) = dm.table_status(&id, DmFlags::DM_STATUS_TABLE).unwrap();
+    assert_eq!(lines, table.targets());
+
+    dm.device_remove(&id, DmFlags::default()).unwrap();
+    assert!(list_test_devices(&dm).unwrap().is_empty());
+}
+
+#[test]
+/// Verify that a thin device can be provisioned from a pool using
+/// only typed parameters, and that its parameters are reported back
+/// as given.
+fn sudo_test_thin_params_round_trip() {
+    let metadata = LoopDevice::new("example-meta", 8192);
+    let data = LoopDevice::new("example-data", 32768);
+
+    let pool_params = ThinPoolTargetParams::new(
+        metadata.device(),
+        data.device(),
+        128,
+        16,
+        ThinPoolFeatures::empty(),
+    )
+    .unwrap();
+    with_target_device(
+        "example-pool",
+        32768,
+        &pool_params,
+        DmFlags::default(),
+        |dm, pool_id, _| {
+            let pool = dm.device_info(pool_id).unwrap().device();
+            dm.target_msg(pool_id, None, "create_thin 1").unwrap();
+
+            let params = ThinTargetParams::new(pool, 1, None).unwrap();
+            assert_params_round_trip("example-thin", 65536, &params);
+
+            dm.target_msg(pool_id, None, "delete 1").unwrap();
+        },
+    );
+}
+
+#[test]
+/// Verify that a table loaded with DM_SECURE_DATA, through
+/// table_reload, can be read back with DM_SECURE_DATA.
+fn sudo_test_secure_data_round_trip() {
+    let dm = DM::new().unwrap();
+    let name = test_name("example-dev").expect("is valid DM name");
+    dm.device_create(&name, None, DmFlags::default()).unwrap();
+
+    let id = DevId::Name(&name);
+    let table = TargetTable::zero_table(1024).unwrap();
+    let opts = ReloadOptions {
+        secure_data: true,
+        ..Default::default()
+    };
+    dm.table_reload(&id, table.targets(), opts).unwrap();
+    let (_, lines) = dm
+        .table_status(&id, DmFlags::DM_STATUS_TABLE | DmFlags::DM_SECURE_DATA)
+        .unwrap();
+    assert_eq!(lines, table.targets());
+
+    dm.device_remove(&id, DmFlags::default()).unwrap();
+    assert!(list_test_devices(&dm).unwrap().is_empty());
+}
+
+#[test]
+/// Verify that a snapshot-origin and snapshot pair can be created
+/// with typed parameters, and that their tables read back as given.
+fn sudo_test_snapshot_params_round_trip() {
+    let origin_dev = LoopDevice::new("example-origin", 8192);
+    let cow_dev = LoopDevice::new("example-cow", 4096);
+
+    let origin = SnapshotOriginTargetParams {
+        origin: origin_dev.device(),
+    };
+    let snapshot = SnapshotTargetParams::new(
+        origin_dev.device(),
+        cow_dev.device(),
+        SnapshotPersistence::Persistent,
+        16,
+        SnapshotFeatures::empty(),
+    )
+    .unwrap();
+    with_target_device(
+        "example-origin",
+        8192,
+        &origin,
+        DmFlags::default(),
+        |_, _, line| {
+            assert_eq!(
+                line.params.parse::<SnapshotOriginTargetParams>(),
+                Ok(origin)
+            );
+            assert_params_round_trip("example-snap", 8192, &snapshot);
+        },
+    );
+}
+
+#[test]
+/// Verify that a crypt table loaded with table_load_typed reads back
+/// with the same key and options.
+fn sudo_test_crypt_params_round_trip() {
+    let loop_dev = LoopDevice::new("example-loop", 8192);
+
+    let params = CryptTargetParams {
+        cipher: "aes-xts-plain64".parse().unwrap(),
+        key: CryptKey::Bytes((0..64).collect()),
+        iv_offset: 0,
+        dest: loop_dev.device(),
+        offset: 0,
+        options: CryptOptions {
+            allow_discards: true,
+            sector_size: Some(4096),
+            ..Default::default()
+        },
+    };
+    with_target_device(
+        "example-dev",
+        8192,
+        &params,
+        DmFlags::default(),
+        |_, _, line| {
+            assert_eq!(
+                line.params.parse::<CryptTargetParams>().unwrap(),
+                params
+            );
+        },
+    );
+}
+
+#[test]
+/// Verify that cache parameters, including policy arguments, are
+/// reported back as given.
+fn sudo_test_cache_params_round_trip() {
+    let metadata = LoopDevice::new("example-meta", 8192);
+    let cache = LoopDevice::new("example-cache", 8192);
+    let origin = LoopDevice::new("example-origin", 32768);
+
+    let params = CacheTargetParams::new(
+        metadata.device(),
+        cache.device(),
+        origin.device(),
+        128,
+        vec![CacheFeature::Writethrough, CacheFeature::Metadata2],
+        "smq".to_string(),
+        vec![("migration_threshold".to_string(), "4096".to_string())],
+    )
+    .unwrap();
+    assert_params_round_trip("example-cache-dev", 32768, &params);
+}
+
+#[test]
+/// Verify that dust parameters are reported back as given.
+fn sudo_test_dust_params_round_trip() {
+    let dest = LoopDevice::new("example-dest", 4096);
+
+    let params = DustTargetParams::new(dest.device(), 0, 4096).unwrap();
+    assert_params_round_trip("example-dust", 4096, &params);
+}
+
+#[test]
+/// Verify that ebs parameters are reported back as given, with the
+/// underlying block size given so that the kernel reports it.
+fn sudo_test_ebs_params_round_trip() {
+    let dest = LoopDevice::new("example-dest", 4096);
+
+    let params = EbsTargetParams::new(dest.device(), 0, 8, Some(1)).unwrap();
+    assert_params_round_trip("example-ebs", 4096, &params);
+}
+
+#[test]
+/// Verify that era parameters are reported back as given, with both
+/// devices written as major:minor.
+fn sudo_test_era_params_round_trip() {
+    let metadata = LoopDevice::new("example-meta", 8192);
+    let origin = LoopDevice::new("example-origin", 32768);
+
+    let params =
+        EraTargetParams::new(metadata.device(), origin.device(), 128).unwrap();
+    assert_params_round_trip("example-era", 32768, &params);
+}
+
+#[test]
+/// Verify that clone parameters are reported back as given.  The
+/// kernel reports the hydration settings even when they were not
+/// given, so they are given here.
+fn sudo_test_clone_params_round_trip() {
+    let metadata = LoopDevice::new("example-meta", 8192);
+    let dest = LoopDevice::new("example-dest", 32768);
+    let source = LoopDevice::new("example-source", 32768);
+
+    let params = CloneTargetParams::new(
+        metadata.device(),
+        dest.device(),
+        source.device(),
+        64,
+        CloneFeatures::NO_HYDRATION,
+        Some(2),
+        Some(4),
+    )
+    .unwrap();
+    assert_params_round_trip("example-clone", 32768, &params);
+}
+
+#[test]
+/// Verify that log-writes parameters are reported back as given, and
+/// that a mark can be recorded.
+fn sudo_test_log_writes_params_round_trip() {
+    let dest = LoopDevice::new("example-dest", 4096);
+    let log = LoopDevice::new("example-log", 8192);
+
+    let params = LogWritesTargetParams {
+        dest: dest.device(),
+        log_dev: log.device(),
+    };
+    with_target_device(
+        "example-log-writes",
+        4096,
+        &params,
+        DmFlags::default(),
+        |dm, id, line| {
+            assert_eq!(
+                line.params.parse::<LogWritesTargetParams>(),
+                Ok(params)
+            );
+
+            let msg = LogWritesTargetParams::mark_message("test-mark").unwrap();
+            dm.target_msg(id, None, &msg).unwrap();
+        },
+    );
+}
+
+#[test]
+/// Verify that writecache settings are reported back as given.
+fn sudo_test_writecache_params_round_trip() {
+    let origin = LoopDevice::new("example-origin", 32768);
+    let cache = LoopDevice::new("example-cache", 8192);
+
+    let settings = WritecacheSettings {
+        high_watermark: Some(60),
+        low_watermark: Some(40),
+        writeback_jobs: Some(128),
+        cleaner: true,
+        ..Default::default()
+    };
+    let params = WritecacheTargetParams::new(
+        WritecacheMode::Ssd,
+        origin.device(),
+        cache.device(),
+        4096,
+        settings,
+    )
+    .unwrap();
+    assert_params_round_trip("example-writecache", 32768, &params);
+}
+
+#[test]
+/// Verify that integrity parameters are reported back with the
+/// kernel's defaults for the journal settings filled in.
+fn sudo_test_integrity_params_round_trip() {
+    let ld = LoopDevice::new("example-dev", 8192);
+
+    let params = IntegrityTargetParams {
+        dest: ld.device(),
+        offset: 0,
+        tag_size: 4,
+        mode: IntegrityMode::Journal,
+        options: IntegrityOptions {
+            internal_hash: Some(IntegrityAlgorithm {
+                name: "crc32c".to_string(),
+                key: None,
+            }),
+            ..Default::default()
+        },
+    };
+    with_target_device(
+        "example-integrity",
+        4096,
+        &params,
+        DmFlags::default(),
+        |_, _, line| {
+            let read_back =
+                line.params.parse::<IntegrityTargetParams>().unwrap();
+            assert_eq!(read_back.dest, params.dest);
+            assert_eq!(read_back.tag_size, 4);
+            assert_eq!(read_back.mode, IntegrityMode::Journal);
+            assert_eq!(
+                read_back.options.internal_hash,
+                params.options.internal_hash
+            );
+            assert!(read_back.options.journal_sectors.is_some());
+            assert_eq!(read_back.to_string(), line.params.trim());
+        },
+    );
+}
+
+#[test]
+/// Verify that mirror parameters are reported back as given.
+fn sudo_test_mirror_params_round_trip() {
+    let first = LoopDevice::new("example-leg-0", 4096);
+    let second = LoopDevice::new("example-leg-1", 4096);
+
+    let params = MirrorTargetParams::new(
+        MirrorLog::Core,
+        1024,
+        Some(false),
+        vec![(first.device(), 0), (second.device(), 0)],
+        MirrorFeatures::HANDLE_ERRORS,
+    )
+    .unwrap();
+    assert_params_round_trip("example-mirror", 4096, &params);
+}
+
+#[test]
+/// Verify that switch parameters are reported back as given, and
+/// that a region mapping message built for them is accepted.
+fn sudo_test_switch_params_round_trip() {
+    let first = LoopDevice::new("example-path-0", 4096);
+    let second = LoopDevice::new("example-path-1", 4096);
+
+    let params = SwitchTargetParams::new(
+        64,
+        vec![(first.device(), 0), (second.device(), 0)],
+    )
+    .unwrap();
+    with_target_device(
+        "example-switch",
+        4096,
+        &params,
+        DmFlags::default(),
+        |dm, id, line| {
+            assert_eq!(
+                line.params.parse::<SwitchTargetParams>().as_ref(),
+                Ok(&params)
+            );
+
+            let msg = params
+                .region_mappings_message(&[SwitchMapping::Range {
+                    first: 0,
+                    last: 63,
+                    path: 1,
+                }])
+                .unwrap();
+            dm.target_msg(id, None, &msg).unwrap();
+        },
+    );
+}
+
+#[test]
+/// Verify that raid parameters are reported back as given, with
+/// absent metadata devices written as `-`.
+fn sudo_test_raid_params_round_trip() {
+    let first = LoopDevice::new("example-raid-0", 4096);
+    let second = LoopDevice::new("example-raid-1", 4096);
+
+    let params = RaidTargetParams::new(
+        RaidType::Raid1,
+        0,
+        RaidOptions {
+            sync: Some(false),
+            ..Default::default()
+        },
+        [&first, &second]
+            .iter()
+            .map(|ld| RaidDevice {
+                metadata_dev: None,
+                data_dev: Some(ld.device()),
+            })
+            .collect(),
+    )
+    .unwrap();
+    with_target_device(
+        "example-raid",
+        4096,
+        &params,
+        DmFlags::default(),
+        |_, _, line| {
+            let read_back = line.params.parse::<RaidTargetParams>().unwrap();
+            assert_eq!(read_back.raid_type, RaidType::Raid1);
+            assert_eq!(read_back.options.sync, Some(false));
+            assert_eq!(read_back.devices, params.devices);
+        },
+    );
+}
+
+#[test]
+/// Verify that verity parameters are reported back as given.  The
+/// kernel only checks the hash tree when blocks are read, so the
+/// tree need not exist.
+fn sudo_test_verity_params_round_trip() {
+    let data = LoopDevice::new("example-data", 4096);
+    let hash = LoopDevice::new("example-hash", 2048);
+
+    let params = VerityTargetParams::new(
+        1,
+        data.device(),
+        hash.device(),
+        4096,
+        4096,
+        512,
+        0,
+        "sha256".to_string(),
+        vec![0x5a; 32],
+        vec![0x01, 0x02],
+        VerityOptions {
+            ignore_zero_blocks: true,
+            check_at_most_once: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    with_target_device(
+        "example-verity",
+        4096,
+        &params,
+        DmFlags::DM_READONLY,
+        |_, _, line| {
+            assert_eq!(
+                line.params.parse::<VerityTargetParams>().as_ref(),
+                Ok(&params)
+            );
+        },
+    );
+}
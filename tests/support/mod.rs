@@ -4,7 +4,17 @@
 
 //! Utility functions used solely by unit tests.
 
-use dm_ioctl::{Device, DmNameBuf, DmResult, DmUuidBuf, DM};
+use std::{
+    fmt::Debug,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use dm_ioctl::{
+    targets::TargetParams, DevId, Device, DmFlags, DmNameBuf, DmResult,
+    DmUuidBuf, TargetLine, TargetTable, DM,
+};
 
 /// String that is to be concatenated with test supplied name to identify
 /// devices and filesystems generated by tests.
@@ -35,6 +45,108 @@ pub fn list_test_devices(
     dm: &DM,
 ) -> DmResult<Vec<(DmNameBuf, Device, Option<u32>)>> {
     let mut test_devs = dm.list_devices()?;
-    test_devs.retain(|x| x.0.as_bytes().ends_with(DM_TEST_ID.as_bytes()));
+    test_devs.retain(|x| x.0.ends_with(DM_TEST_ID));
     Ok(test_devs)
 }
+
+/// Create the test device `name`, whose table is the single target
+/// `params`, `length` sectors long, loaded with `flags`; call `check`
+/// with the device's id and the target's line as the kernel reports
+/// it; then remove the device.
+pub fn with_target_device<T: TargetParams>(
+    name: &str,
+    length: u64,
+    params: &T,
+    flags: DmFlags,
+    check: impl FnOnce(&DM, &DevId<'_>, &TargetLine),
+) {
+    let dm = DM::new().unwrap();
+    let name = test_name(name).expect("is valid DM name");
+    let mut table = TargetTable::new();
+    table.push_typed(length, params).unwrap();
+    dm.device_create(&name, None, DmFlags::default()).unwrap();
+
+    let id = DevId::Name(&name);
+    dm.table_load_typed(&id, &table, flags).unwrap();
+    dm.resume(&id).unwrap();
+    let mut status_flags = DmFlags::DM_STATUS_TABLE;
+    if table.contains_sensitive_params() {
+        status_flags |= DmFlags::DM_SECURE_DATA;
+    }
+    let (_, lines) = dm.table_status(&id, status_flags).unwrap();
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0].length, length);
+    check(&dm, &id, &lines[0]);
+
+    dm.device_remove(&id, DmFlags::default()).unwrap();
+}
+
+/// Check that the kernel reports `params` back exactly as given, in
+/// the table of a test device `name` made as [`with_target_device`]
+/// makes it.
+pub fn assert_params_round_trip<T>(name: &str, length: u64, params: &T)
+where
+    T: TargetParams + PartialEq + Debug,
+{
+    with_target_device(
+        name,
+        length,
+        params,
+        DmFlags::default(),
+        |_, _, line| {
+            assert_eq!(&T::parse(&line.params).unwrap(), params);
+        },
+    );
+}
+
+/// A loop device backed by a sparse file in the temporary directory.
+/// The loop device is detached and the file deleted when this is
+/// dropped.
+pub struct LoopDevice {
+    path: PathBuf,
+    backing_file: PathBuf,
+}
+
+impl LoopDevice {
+    /// Set up a loop device `sectors` 512-byte sectors long, whose
+    /// backing file name is derived from `name`.
+    pub fn new(name: &str, sectors: u64) -> LoopDevice {
+        let backing_file = std::env::temp_dir().join(test_string(name));
+        File::create(&backing_file)
+            .and_then(|f| f.set_len(sectors * 512))
+            .unwrap();
+
+        let output = Command::new("losetup")
+            .arg("--find")
+            .arg("--show")
+            .arg(&backing_file)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "losetup failed: {output:?}");
+        let path = String::from_utf8(output.stdout).unwrap();
+
+        LoopDevice {
+            path: PathBuf::from(path.trim()),
+            backing_file,
+        }
+    }
+
+    /// The path of the loop device's node, e.g. `/dev/loop0`.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The device number of the loop device.
+    pub fn device(&self) -> Device {
+        Device::from_devnode(&self.path)
+            .unwrap()
+            .expect("loop device node is a block device")
+    }
+}
+
+impl Drop for LoopDevice {
+    fn drop(&mut self) {
+        let _ = Command::new("losetup").arg("-d").arg(&self.path).status();
+        let _ = fs::remove_file(&self.backing_file);
+    }
+}